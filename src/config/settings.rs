@@ -53,6 +53,19 @@ pub struct CliArgs {
     #[arg(long, value_enum, value_name = "ALGO")]
     pub verify: Option<HashAlgorithm>,
 
+    /// How verification re-reads copies (cache-bypass reads from storage)
+    #[arg(long, value_enum, default_value = "cached", value_name = "MODE")]
+    pub verify_mode: VerifyMode,
+
+    /// Threads for verification (0 = same as --threads)
+    #[arg(long, default_value = "0", value_name = "NUM")]
+    pub verify_threads: usize,
+
+    /// Write a sha256sum/b3sum/xxh128sum-compatible checksum file of the
+    /// destination (uses the --verify algorithm, SHA-256 by default)
+    #[arg(long, value_name = "FILE")]
+    pub write_checksums: Option<PathBuf>,
+
     /// Enable incremental/delta sync mode
     #[arg(short = 'i', long)]
     pub incremental: bool,
@@ -65,6 +78,11 @@ pub struct CliArgs {
     #[arg(long, default_value = "10M", value_name = "SIZE")]
     pub delta_threshold: String,
 
+    /// Update existing destination files of at least --delta-threshold in
+    /// place, without room for a second copy
+    #[arg(long, requires = "delta")]
+    pub inplace: bool,
+
     /// Enable LZ4 compression for transfers
     #[arg(short = 'c', long)]
     pub compress: bool,
@@ -97,6 +115,15 @@ pub struct CliArgs {
     #[arg(long, default_value = "9876", value_name = "PORT")]
     pub tcp_port: u16,
 
+    /// Pre-shared key file for authenticating with the direct TCP server
+    #[arg(long, value_name = "PATH")]
+    pub tcp_key_file: Option<PathBuf>,
+
+    /// Encrypt direct TCP sessions with ChaCha20-Poly1305 (requires
+    /// --tcp-key-file and the encryption feature)
+    #[arg(long, requires = "tcp_key_file")]
+    pub tcp_encrypt: bool,
+
     // === QUIC Transport Options ===
     /// Enable QUIC transport (HTTP/3-like, requires remote agent)
     #[arg(long)]
@@ -148,14 +175,75 @@ pub struct CliArgs {
     #[arg(long)]
     pub delete_extra: bool,
 
+    /// Move destination files that reappear under a new path instead of
+    /// deleting and copying them again
+    #[arg(long, requires_all = ["incremental", "delete_extra"])]
+    pub detect_renames: bool,
+
     /// Preserve file attributes (permissions, timestamps)
     #[arg(long, default_value = "true")]
     pub preserve: bool,
 
-    /// Follow symbolic links
-    #[arg(short = 'L', long)]
+    /// Follow symbolic links (copy the files they point to)
+    #[arg(short = 'L', long, alias = "copy-links")]
     pub follow_symlinks: bool,
 
+    /// Skip symlinks that point outside the source tree
+    #[arg(long, conflicts_with = "copy_unsafe_links")]
+    pub safe_links: bool,
+
+    /// Copy the referent of symlinks that point outside the source tree
+    #[arg(long)]
+    pub copy_unsafe_links: bool,
+
+    /// Munge symlink targets so they can't be followed at the destination
+    #[arg(long)]
+    pub munge_links: bool,
+
+    /// Preserve ACLs (POSIX access and default ACLs on Linux)
+    #[arg(long)]
+    pub acls: bool,
+
+    /// Preserve extended attributes
+    #[arg(long)]
+    pub xattrs: bool,
+
+    /// Extended attributes to copy (glob on full name, e.g. 'user.*')
+    #[arg(long, value_name = "PATTERN")]
+    pub xattr_include: Vec<String>,
+
+    /// Extended attributes to skip (e.g. 'security.selinux', 'trusted.*')
+    #[arg(long, value_name = "PATTERN")]
+    pub xattr_exclude: Vec<String>,
+
+    /// Recreate FIFOs and sockets
+    #[arg(long)]
+    pub specials: bool,
+
+    /// Recreate character and block device nodes (requires root)
+    #[arg(long)]
+    pub devices: bool,
+
+    /// Convert filenames to a Unicode normalization form
+    #[arg(long, value_enum, value_name = "FORM")]
+    pub normalize: Option<NormalizationForm>,
+
+    /// What to do with names that collide at the destination
+    #[arg(long, value_enum, default_value = "fail")]
+    pub on_collision: CollisionPolicy,
+
+    /// Treat the destination as case-insensitive even if not detected
+    #[arg(long)]
+    pub case_insensitive: bool,
+
+    /// Write identical files once and link the duplicates
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub dedupe: Option<DedupeMode>,
+
+    /// Skip the destination free space and inode preflight
+    #[arg(long)]
+    pub no_space_check: bool,
+
     /// Include hidden files
     #[arg(long)]
     pub include_hidden: bool,
@@ -192,10 +280,14 @@ pub struct CliArgs {
     #[arg(long)]
     pub continue_on_error: bool,
 
-    /// Path to manifest file for tracking
+    /// Path to manifest file for tracking (bincode if it ends in .bin, else JSON)
     #[arg(long, value_name = "PATH")]
     pub manifest: Option<PathBuf>,
 
+    /// Sign the manifest with this key (from `smartcopy keygen`)
+    #[arg(long, value_name = "KEY", requires = "manifest")]
+    pub sign_manifest: Option<PathBuf>,
+
     /// Output format for reports
     #[arg(long, value_enum, default_value = "text")]
     pub output_format: OutputFormat,
@@ -263,13 +355,145 @@ pub enum Commands {
     /// Verify integrity of a previous copy
     #[command(name = "verify")]
     Verify {
-        /// Source path
+        /// Source path (or the tree to check, with --manifest)
         source: String,
         /// Destination path
-        destination: String,
+        #[arg(required_unless_present_any = ["manifest", "checksum_file", "s3_listing"])]
+        destination: Option<String>,
+        /// Hash algorithm [default: xxhash3, or inferred from --checksum-file]
+        #[arg(long, value_enum)]
+        algorithm: Option<HashAlgorithm>,
+        /// Check against a stored hash manifest (JSON, bincode or Parquet) instead of a source
+        #[arg(long, value_name = "PATH", conflicts_with = "destination")]
+        manifest: Option<PathBuf>,
+        /// Require the manifest to be signed by this public key (from `smartcopy keygen`)
+        #[arg(long, value_name = "PATH", requires = "manifest")]
+        pubkey: Option<PathBuf>,
+        /// Check against a sha256sum/b3sum/xxh128sum checksum file instead of a source
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["destination", "manifest"])]
+        checksum_file: Option<PathBuf>,
+        /// Check against S3 ETags: s3://bucket/prefix (listed with the aws CLI) or a saved
+        /// `aws s3api list-objects-v2` JSON file
+        #[arg(long, value_name = "URL|PATH", conflicts_with_all = ["destination", "manifest", "checksum_file"])]
+        s3_listing: Option<String>,
+        /// Key prefix to strip from a saved listing [default: the s3:// URL's prefix]
+        #[arg(long, value_name = "PREFIX", requires = "s3_listing")]
+        s3_prefix: Option<String>,
+        /// Multipart upload part size, needed to check multipart ETags (e.g., 8M)
+        #[arg(long, value_name = "SIZE", requires = "s3_listing")]
+        part_size: Option<String>,
+    },
+
+    /// Re-hash a slice of an archive against its manifest to catch bit rot
+    #[command(name = "scrub")]
+    Scrub {
+        /// Archive root
+        path: PathBuf,
+        /// Hash manifest (JSON, bincode or Parquet); verification times are written back
+        #[arg(long, value_name = "PATH")]
+        manifest: PathBuf,
+        /// Fraction of files to re-read this run, least recently verified first
+        #[arg(long, default_value = "0.1", value_name = "FRACTION")]
+        fraction: f64,
+        /// Read rate limit (e.g., 100M, 1G)
+        #[arg(long, value_name = "RATE")]
+        bandwidth_limit: Option<String>,
+        /// Worker threads (0 = auto)
+        #[arg(short, long, default_value = "0")]
+        threads: usize,
+    },
+
+    /// Fix files that no longer match a manifest, re-copying only damaged chunks
+    #[command(name = "repair")]
+    Repair {
+        /// Source directory holding good copies
+        source: PathBuf,
+        /// Destination directory to repair
+        destination: PathBuf,
+        /// Hash manifest of the source (JSON, bincode or Parquet)
+        #[arg(long, value_name = "PATH")]
+        manifest: PathBuf,
+    },
+
+    /// Write a sha256sum/b3sum/xxh128sum-compatible checksum file for a tree
+    #[command(name = "checksum")]
+    Checksum {
+        /// Directory to checksum
+        path: PathBuf,
         /// Hash algorithm
-        #[arg(long, value_enum, default_value = "xxhash3")]
+        #[arg(long, value_enum, default_value = "sha256")]
         algorithm: HashAlgorithm,
+        /// Output file (default: stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// Also write a hash manifest for verify --manifest and scrub
+        /// (bincode with a .bin extension, JSON otherwise)
+        #[arg(long, value_name = "PATH")]
+        manifest: Option<PathBuf>,
+        /// Sign the manifest with this key (from `smartcopy keygen`)
+        #[arg(long, value_name = "KEY", requires = "manifest")]
+        sign_manifest: Option<PathBuf>,
+        /// Record content-defined (FastCDC) chunk hashes in the manifest, which
+        /// identify shared content across files and versions [default: 16K,64K,256K]
+        #[arg(
+            long,
+            value_name = "MIN,AVG,MAX",
+            num_args = 0..=1,
+            default_missing_value = "16K,64K,256K",
+            requires = "manifest"
+        )]
+        cdc: Option<crate::sync::CdcParams>,
+        /// Include hidden files
+        #[arg(long)]
+        include_hidden: bool,
+    },
+
+    /// Create an Ed25519 key pair for signing manifests (KEY and KEY.pub)
+    #[command(name = "keygen")]
+    Keygen {
+        /// Secret key file to create; the public key goes to KEY.pub
+        key: PathBuf,
+    },
+
+    /// Write the block signature of a basis file or directory tree
+    #[command(name = "signature")]
+    Signature {
+        /// Basis file or directory
+        basis: PathBuf,
+        /// Signature file to write
+        signature: PathBuf,
+        /// Fixed block size (e.g., 64K, 1M)
+        #[arg(long, default_value = "1M", value_name = "SIZE", conflicts_with = "cdc")]
+        block_size: String,
+        /// Use content-defined (FastCDC) chunks instead of fixed blocks
+        /// [default: 16K,64K,256K]
+        #[arg(long, value_name = "MIN,AVG,MAX", num_args = 0..=1, default_missing_value = "16K,64K,256K")]
+        cdc: Option<crate::sync::CdcParams>,
+    },
+
+    /// Write a delta from a signature to the new version of a file or tree
+    #[command(name = "delta")]
+    Delta {
+        /// Signature of the basis (from `smartcopy signature`)
+        signature: PathBuf,
+        /// New file or directory
+        new: PathBuf,
+        /// Delta file to write
+        delta: PathBuf,
+        /// Compression of literal data
+        #[arg(long, value_enum, default_value = "lz4")]
+        compress: crate::sync::LiteralCompression,
+    },
+
+    /// Rebuild the new version of a file or tree from its basis and a delta
+    #[command(name = "patch")]
+    Patch {
+        /// Basis file or directory the signature was made from
+        basis: PathBuf,
+        /// Delta file (from `smartcopy delta`)
+        delta: PathBuf,
+        /// Output file or directory
+        output: PathBuf,
     },
 
     /// Show manifest/sync status
@@ -277,6 +501,9 @@ pub enum Commands {
     Status {
         /// Manifest file path
         manifest: PathBuf,
+        /// Require the manifest to be signed by this public key
+        #[arg(long, value_name = "PATH")]
+        pubkey: Option<PathBuf>,
     },
 
     /// Run as TCP server for direct transfers
@@ -288,6 +515,21 @@ pub enum Commands {
         /// Bind address
         #[arg(short, long, default_value = "0.0.0.0")]
         bind: String,
+        /// Pre-shared key file clients must authenticate with
+        #[arg(long, value_name = "PATH")]
+        key_file: Option<PathBuf>,
+        /// Only serve downloads
+        #[arg(long, conflicts_with = "write_only")]
+        read_only: bool,
+        /// Only accept uploads
+        #[arg(long)]
+        write_only: bool,
+        /// Client address or network allowed to connect (repeatable, e.g. 10.0.0.0/8)
+        #[arg(long = "allow", value_name = "CIDR")]
+        allow: Vec<String>,
+        /// Refuse unencrypted sessions (requires --key-file)
+        #[arg(long, requires = "key_file")]
+        require_encryption: bool,
     },
 
     /// Run benchmarks
@@ -331,6 +573,16 @@ pub enum Commands {
         key: Option<PathBuf>,
     },
 
+    /// Report files with identical contents and the space they waste
+    #[command(name = "dedupe-report")]
+    DedupeReport {
+        /// Directory to examine
+        path: PathBuf,
+        /// Include hidden files
+        #[arg(long)]
+        include_hidden: bool,
+    },
+
     /// Show high-speed network tuning guide (10G/100G/200G/400G)
     #[command(name = "highspeed")]
     HighSpeed {
@@ -373,7 +625,8 @@ pub enum AgentProtocol {
 pub enum HashAlgorithm {
     /// XXHash3 - Ultra fast, non-cryptographic (128-bit)
     #[default]
-    #[value(name = "xxhash3")]
+    #[value(name = "xxhash3", alias = "xxh128")]
+    #[serde(alias = "xxh128")]
     XXHash3,
     /// XXHash64 - Fast, non-cryptographic (64-bit)
     #[value(name = "xxhash64")]
@@ -384,6 +637,15 @@ pub enum HashAlgorithm {
     /// SHA-256 - Standard cryptographic hash
     #[value(name = "sha256")]
     Sha256,
+    /// CRC32C - Hardware-accelerated checksum used by S3 and GCS (32-bit)
+    #[value(name = "crc32c")]
+    Crc32c,
+    /// MD5 - Legacy, matches S3 single-part ETags (128-bit)
+    #[value(name = "md5")]
+    Md5,
+    /// SHA-512 - Cryptographic hash for compliance requirements
+    #[value(name = "sha512")]
+    Sha512,
 }
 
 impl HashAlgorithm {
@@ -394,6 +656,9 @@ impl HashAlgorithm {
             Self::XXHash64 => 8,
             Self::Blake3 => 32,
             Self::Sha256 => 32,
+            Self::Crc32c => 4,
+            Self::Md5 => 16,
+            Self::Sha512 => 64,
         }
     }
 
@@ -404,6 +669,27 @@ impl HashAlgorithm {
             Self::XXHash64 => "XXHash64",
             Self::Blake3 => "BLAKE3",
             Self::Sha256 => "SHA-256",
+            Self::Crc32c => "CRC32C",
+            Self::Md5 => "MD5",
+            Self::Sha512 => "SHA-512",
+        }
+    }
+
+    /// Look up an algorithm by its CLI name or alias (case-insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        <Self as ValueEnum>::from_str(name, true).ok()
+    }
+
+    /// Standard tool that checks checksum files of this algorithm, if any
+    pub fn checksum_tool(&self) -> Option<&'static str> {
+        match self {
+            Self::XXHash3 => Some("xxh128sum"),
+            Self::XXHash64 => Some("xxh64sum"),
+            Self::Blake3 => Some("b3sum"),
+            Self::Sha256 => Some("sha256sum"),
+            Self::Crc32c => None,
+            Self::Md5 => Some("md5sum"),
+            Self::Sha512 => Some("sha512sum"),
         }
     }
 }
@@ -462,6 +748,50 @@ pub enum OrderingStrategy {
     None,
 }
 
+/// Unicode normalization form for filenames
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizationForm {
+    /// Composed form (Linux, Windows)
+    Nfc,
+    /// Decomposed form (HFS+)
+    Nfd,
+}
+
+/// Handling of names that collide under case folding or normalization
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Abort before copying anything
+    #[default]
+    Fail,
+    /// Copy later names under a new, unique name
+    Rename,
+    /// Copy only the first name, skip the rest
+    Skip,
+}
+
+/// How duplicate files are written at the destination
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupeMode {
+    /// Hard links (duplicates share one inode and its metadata)
+    Hardlink,
+    /// Reflinks (copy-on-write clones, Btrfs/XFS)
+    Reflink,
+}
+
+/// How copies are re-read for verification
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VerifyMode {
+    /// Ordinary reads (fast, but may be served from the page cache)
+    #[default]
+    Cached,
+    /// Re-read from storage with O_DIRECT, or flush and drop cached pages
+    CacheBypass,
+}
+
 /// Runtime configuration derived from CLI args
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CopyConfig {
@@ -477,12 +807,20 @@ pub struct CopyConfig {
     pub buffer_size: usize,
     /// Hash algorithm for verification
     pub verify: Option<HashAlgorithm>,
+    /// How verification re-reads copies
+    pub verify_mode: VerifyMode,
+    /// Verification thread count (0 = same as copy)
+    pub verify_threads: usize,
+    /// Checksum file to write after copying
+    pub write_checksums: Option<PathBuf>,
     /// Enable incremental sync
     pub incremental: bool,
     /// Enable delta transfer
     pub delta: bool,
     /// Delta threshold in bytes
     pub delta_threshold: u64,
+    /// Apply delta transfers to existing destination files in place
+    pub inplace: bool,
     /// Enable compression
     pub compress: bool,
     /// Compression level
@@ -491,6 +829,34 @@ pub struct CopyConfig {
     pub preserve: bool,
     /// Follow symlinks
     pub follow_symlinks: bool,
+    /// Skip symlinks pointing outside the source tree
+    pub safe_links: bool,
+    /// Dereference symlinks pointing outside the source tree
+    pub copy_unsafe_links: bool,
+    /// Munge created symlink targets
+    pub munge_links: bool,
+    /// Preserve ACLs
+    pub preserve_acls: bool,
+    /// Preserve extended attributes
+    pub xattrs: bool,
+    /// Extended attribute include patterns
+    pub xattr_include: Vec<String>,
+    /// Extended attribute exclude patterns
+    pub xattr_exclude: Vec<String>,
+    /// Recreate FIFOs and sockets
+    pub specials: bool,
+    /// Recreate device nodes
+    pub devices: bool,
+    /// Filename normalization form
+    pub normalize: Option<NormalizationForm>,
+    /// Name collision handling
+    pub on_collision: CollisionPolicy,
+    /// Force case-insensitive collision checks
+    pub case_insensitive: bool,
+    /// Link duplicate files at the destination
+    pub dedupe: Option<DedupeMode>,
+    /// Check destination free space and inodes before copying
+    pub space_check: bool,
     /// Include hidden files
     pub include_hidden: bool,
     /// Include patterns
@@ -513,8 +879,12 @@ pub struct CopyConfig {
     pub dry_run: bool,
     /// Delete extra files
     pub delete_extra: bool,
+    /// Turn deletions plus new copies of the same file into renames
+    pub detect_renames: bool,
     /// Manifest path
     pub manifest_path: Option<PathBuf>,
+    /// Key to sign the manifest with
+    pub manifest_signing_key: Option<PathBuf>,
     /// File ordering strategy
     pub ordering: OrderingStrategy,
 }
@@ -536,6 +906,10 @@ pub struct RemoteConfig {
     pub tcp_direct: bool,
     /// TCP port for direct mode
     pub tcp_port: u16,
+    /// Pre-shared key file for direct TCP
+    pub tcp_key_file: Option<PathBuf>,
+    /// Encrypt direct TCP sessions
+    pub tcp_encrypt: bool,
     /// Use QUIC transport
     pub quic: bool,
     /// QUIC port
@@ -644,13 +1018,31 @@ impl Default for CopyConfig {
             threads: 0, // Auto-detect
             buffer_size: 1024 * 1024, // 1MB
             verify: None,
+            verify_mode: VerifyMode::Cached,
+            verify_threads: 0,
+            write_checksums: None,
             incremental: false,
             delta: false,
             delta_threshold: 10 * 1024 * 1024, // 10MB
+            inplace: false,
             compress: false,
             compress_level: 1,
             preserve: true,
             follow_symlinks: false,
+            safe_links: false,
+            copy_unsafe_links: false,
+            munge_links: false,
+            preserve_acls: false,
+            xattrs: false,
+            xattr_include: Vec::new(),
+            xattr_exclude: Vec::new(),
+            specials: false,
+            devices: false,
+            normalize: None,
+            on_collision: CollisionPolicy::Fail,
+            case_insensitive: false,
+            dedupe: None,
+            space_check: true,
             include_hidden: false,
             include_patterns: Vec::new(),
             exclude_patterns: Vec::new(),
@@ -662,7 +1054,9 @@ impl Default for CopyConfig {
             continue_on_error: false,
             dry_run: false,
             delete_extra: false,
+            detect_renames: false,
             manifest_path: None,
+            manifest_signing_key: None,
             ordering: OrderingStrategy::SmallestFirst,
         }
     }
@@ -751,6 +1145,8 @@ impl CopyConfig {
                 streams: args.ssh_streams,
                 tcp_direct: args.tcp_direct,
                 tcp_port: args.tcp_port,
+                tcp_key_file: args.tcp_key_file.clone(),
+                tcp_encrypt: args.tcp_encrypt,
                 quic: args.quic,
                 quic_port: args.quic_port,
                 use_agent: args.agent,
@@ -772,6 +1168,8 @@ impl CopyConfig {
                     streams: args.ssh_streams,
                     tcp_direct: args.tcp_direct,
                     tcp_port: args.tcp_port,
+                    tcp_key_file: args.tcp_key_file.clone(),
+                    tcp_encrypt: args.tcp_encrypt,
                     quic: args.quic,
                     quic_port: args.quic_port,
                     use_agent: args.agent,
@@ -785,13 +1183,31 @@ impl CopyConfig {
         config.threads = args.threads;
         config.buffer_size = parse_size(&args.buffer_size).map_err(|e| format!("Invalid buffer size: {}", e))? as usize;
         config.verify = args.verify;
+        config.verify_mode = args.verify_mode;
+        config.verify_threads = args.verify_threads;
+        config.write_checksums = args.write_checksums.clone();
         config.incremental = args.incremental;
         config.delta = args.delta;
         config.delta_threshold = parse_size(&args.delta_threshold).map_err(|e| format!("Invalid delta threshold: {}", e))?;
+        config.inplace = args.inplace;
         config.compress = args.compress;
         config.compress_level = args.compress_level;
         config.preserve = args.preserve;
         config.follow_symlinks = args.follow_symlinks;
+        config.safe_links = args.safe_links;
+        config.copy_unsafe_links = args.copy_unsafe_links;
+        config.munge_links = args.munge_links;
+        config.preserve_acls = args.acls;
+        config.xattrs = args.xattrs;
+        config.xattr_include = args.xattr_include.clone();
+        config.xattr_exclude = args.xattr_exclude.clone();
+        config.specials = args.specials;
+        config.devices = args.devices;
+        config.normalize = args.normalize;
+        config.on_collision = args.on_collision;
+        config.case_insensitive = args.case_insensitive;
+        config.dedupe = args.dedupe;
+        config.space_check = !args.no_space_check;
         config.include_hidden = args.include_hidden;
         config.include_patterns = args.include.clone();
         config.exclude_patterns = args.exclude.clone();
@@ -803,7 +1219,9 @@ impl CopyConfig {
         config.continue_on_error = args.continue_on_error;
         config.dry_run = args.dry_run;
         config.delete_extra = args.delete_extra;
+        config.detect_renames = args.detect_renames;
         config.manifest_path = args.manifest.clone();
+        config.manifest_signing_key = args.sign_manifest.clone();
 
         Ok(config)
     }
@@ -840,5 +1258,8 @@ mod tests {
         assert_eq!(HashAlgorithm::XXHash3.output_size(), 16);
         assert_eq!(HashAlgorithm::Blake3.output_size(), 32);
         assert_eq!(HashAlgorithm::XXHash3.name(), "XXHash3");
+        assert_eq!(HashAlgorithm::from_name("XXH128"), Some(HashAlgorithm::XXHash3));
+        assert_eq!(HashAlgorithm::from_name("crc32c"), Some(HashAlgorithm::Crc32c));
+        assert_eq!(HashAlgorithm::from_name("md4"), None);
     }
 }
@@ -3,17 +3,27 @@
 //! Orchestrates multi-threaded file copying with intelligent scheduling,
 //! progress reporting, and integrity verification.
 
-use crate::config::{CopyConfig, HashAlgorithm, OrderingStrategy};
+use crate::config::{CollisionPolicy, CopyConfig, DedupeMode, HashAlgorithm, OrderingStrategy, VerifyMode};
 use crate::core::{TaskResult, TaskScheduler, TaskSuccess};
-use crate::error::{Result, SmartCopyError};
-use crate::fs::{create_directories, CopyOptions, FileEntry, FileCopier, FileSizeCategory, Scanner, ScanConfig, ScanResult};
-use crate::hash::{HashResult, StreamingHasher};
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use crate::fs::capacity::is_privileged;
+use crate::fs::symlinks::{create_symlink, remove_symlink, resolve_symlink};
+use crate::fs::{
+    apply_directory_metadata, copy_special, create_directories, CapacityPlanner, CopyOptions,
+    FileEntry, FileCopier,
+    detect_storage_type, escape_path, unescape_path, hard_link_file, reflink_file, FileSizeCategory, NamePlan, NameRules, Scanner, ScanConfig, ScanResult,
+    SymlinkAction, SymlinkPolicy, XattrFilter,
+};
+use crate::hash::{
+    composite_chunk_size, find_duplicates, load_signing_key, ChecksumFile, ChunkHashes, HashResult, StreamingHasher,
+};
 use crate::progress::ProgressReporter;
-use crate::sync::ChunkedCopier;
+use crate::sync::{apply_renames, in_place_journal_path, ChunkedCopier, IncrementalSync, ManifestEntry, SyncManifest};
 use rayon::prelude::*;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -26,8 +36,22 @@ pub struct CopyResult {
     pub bytes_copied: u64,
     /// Total directories created
     pub dirs_created: u64,
-    /// Failed operations
+    /// FIFOs and sockets recreated
+    pub specials_created: u64,
+    /// Device nodes recreated
+    pub devices_created: u64,
+    /// Duplicate files written as links instead of copies
+    pub files_deduplicated: u64,
+    /// Bytes not written thanks to deduplication
+    pub bytes_deduplicated: u64,
+    /// Destination files moved to a new path instead of copied again
+    pub files_renamed: u64,
+    /// Bytes not copied thanks to renames
+    pub bytes_renamed: u64,
+    /// Failed operations (paths in escaped form, see [`escape_path`])
     pub failures: Vec<(String, String)>,
+    /// Non-fatal problems (e.g. attributes that could not be preserved)
+    pub warnings: Vec<(String, String)>,
     /// Total duration
     pub duration: Duration,
     /// Average throughput in bytes/second
@@ -48,6 +72,26 @@ impl CopyResult {
         println!("Files copied:    {}", self.files_copied);
         println!("Bytes copied:    {}", humansize::format_size(self.bytes_copied, humansize::BINARY));
         println!("Directories:     {}", self.dirs_created);
+        if self.specials_created > 0 {
+            println!("Specials:        {}", self.specials_created);
+        }
+        if self.devices_created > 0 {
+            println!("Devices:         {}", self.devices_created);
+        }
+        if self.files_deduplicated > 0 {
+            println!(
+                "Deduplicated:    {} ({} saved)",
+                self.files_deduplicated,
+                humansize::format_size(self.bytes_deduplicated, humansize::BINARY)
+            );
+        }
+        if self.files_renamed > 0 {
+            println!(
+                "Renamed:         {} ({} saved)",
+                self.files_renamed,
+                humansize::format_size(self.bytes_renamed, humansize::BINARY)
+            );
+        }
         println!("Duration:        {:.2?}", self.duration);
         println!("Throughput:      {}/s", humansize::format_size(self.throughput as u64, humansize::BINARY));
 
@@ -58,11 +102,21 @@ impl CopyResult {
             }
         }
 
+        if !self.warnings.is_empty() {
+            println!("\nWarnings: {}", self.warnings.len());
+            for (path, warning) in &self.warnings {
+                println!("  {} - {}", path, warning);
+            }
+        }
+
         if let Some(verification) = &self.verification {
             println!("\nVerification:");
             println!("  Verified:  {}", verification.verified);
             println!("  Passed:    {}", verification.passed);
             println!("  Failed:    {}", verification.failed);
+            if verification.repaired > 0 {
+                println!("  Repaired:  {}", verification.repaired);
+            }
         }
     }
 }
@@ -76,6 +130,8 @@ pub struct VerificationSummary {
     pub passed: u64,
     /// Files that failed verification
     pub failed: u64,
+    /// Files that passed after their mismatched chunks were re-copied
+    pub repaired: u64,
     /// Mismatched files
     pub mismatches: Vec<(String, String, String)>,
 }
@@ -141,6 +197,23 @@ pub struct CopyEngine {
     cancelled: Arc<AtomicBool>,
     /// Maximum concurrent in-flight files (0 = auto: 2x CPU count)
     max_concurrent: usize,
+    /// Non-fatal per-path warnings collected during a run
+    warnings: Mutex<Vec<(String, String)>>,
+    /// Chunk hashes of huge source files, by relative path, for repair
+    chunk_hashes: Mutex<HashMap<PathBuf, (PathBuf, u64, ChunkHashes)>>,
+    /// Entries copied from behind dereferenced directory links
+    referents: Mutex<ReferentCopies>,
+}
+
+/// What the copies of dereferenced directory links add to a run
+#[derive(Default)]
+struct ReferentCopies {
+    /// Directory links dereferenced, each also counted as a file
+    links: u64,
+    /// Entries copied from behind them
+    files: u64,
+    hashes: Vec<(PathBuf, HashResult)>,
+    failures: Vec<(String, String)>,
 }
 
 impl CopyEngine {
@@ -159,6 +232,13 @@ impl CopyEngine {
             network_optimized: true,
             network_streams: 4,
             network_buffer_size: 4 * 1024 * 1024,
+            // Invalid patterns are reported by execute()
+            xattr_filter: if config.xattrs {
+                Some(XattrFilter::new(&config.xattr_include, &config.xattr_exclude).unwrap_or_default())
+            } else {
+                None
+            },
+            preserve_acls: config.preserve_acls,
         };
 
         Self {
@@ -167,6 +247,9 @@ impl CopyEngine {
             progress: None,
             cancelled: Arc::new(AtomicBool::new(false)),
             max_concurrent: 0,
+            warnings: Mutex::new(Vec::new()),
+            chunk_hashes: Mutex::new(HashMap::new()),
+            referents: Mutex::new(ReferentCopies::default()),
         }
     }
 
@@ -198,25 +281,117 @@ impl CopyEngine {
         self.cancelled.load(Ordering::SeqCst)
     }
 
+    /// Record a non-fatal warning for a path
+    fn warn(&self, path: &Path, message: impl Into<String>) {
+        let message = message.into();
+        tracing::warn!("{}: {}", path.display(), message);
+        if let Ok(mut warnings) = self.warnings.lock() {
+            warnings.push((escape_path(path), message));
+        }
+    }
+
+    /// Take all warnings collected so far
+    fn take_warnings(&self) -> Vec<(String, String)> {
+        self.warnings
+            .lock()
+            .map(|mut w| std::mem::take(&mut *w))
+            .unwrap_or_default()
+    }
+
+    /// Record attribute warnings reported by the file copier
+    fn warn_all(&self, path: &Path, warnings: Vec<String>) {
+        for warning in warnings {
+            self.warn(path, warning);
+        }
+    }
+
+    /// Apply default ACLs and extended attributes to the created directories
+    ///
+    /// Relative paths are taken from the destination root.
+    fn apply_directory_attributes(&self, directories: &[FileEntry]) {
+        let filter = self.xattr_filter();
+        if !self.config.preserve_acls && filter.is_none() {
+            return;
+        }
+
+        for dir in directories {
+            let dest_dir = self.config.destination.join(&dir.relative_path);
+            if let Some(filter) = filter {
+                self.warn_all(&dir.relative_path, crate::fs::copy_xattrs(&dir.path, &dest_dir, filter));
+            }
+            // Default ACLs go on before the files so children inherit them;
+            // the access ACL waits for the directory metadata pass, as it
+            // may leave the directory unwritable
+            if self.config.preserve_acls {
+                match self.copier.copy_default_acl(&dir.path, &dest_dir) {
+                    Ok(warnings) => self.warn_all(&dir.relative_path, warnings),
+                    Err(e) => self.warn(&dir.relative_path, format!("ACLs not preserved: {}", e)),
+                }
+            }
+        }
+    }
+
+    /// Extended attribute filter, if xattr preservation is enabled
+    fn xattr_filter(&self) -> Option<&XattrFilter> {
+        self.copier.options().xattr_filter.as_ref()
+    }
+
+    /// Copy ACLs, turning unresolvable principals and failures into warnings
+    fn apply_acls(&self, source: &Path, dest: &Path, relative: &Path) {
+        match self.copier.copy_acl(source, dest) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    self.warn(relative, warning);
+                }
+            }
+            Err(e) => self.warn(relative, format!("ACLs not preserved: {}", e)),
+        }
+    }
+
     /// Execute the copy operation
     pub fn execute(&self) -> Result<CopyResult> {
         let start_time = Instant::now();
 
+        if self.config.xattrs {
+            XattrFilter::new(&self.config.xattr_include, &self.config.xattr_exclude)?;
+        }
+        // A bad key should fail the run before anything is copied
+        let signing_key = match &self.config.manifest_signing_key {
+            Some(path) => Some(load_signing_key(path)?),
+            None => None,
+        };
+
         // Scan source directory
-        let scan_result = self.scan_source()?;
+        let mut scan_result = self.scan_source()?;
+        self.resolve_name_collisions(&mut scan_result)?;
 
-        if scan_result.files.is_empty() && scan_result.directories.is_empty() {
+        // Duplicates are linked to their first copy once it exists
+        let duplicates = self.take_duplicates(&mut scan_result);
+
+        if scan_result.files.is_empty()
+            && scan_result.directories.is_empty()
+            && scan_result.specials.is_empty()
+        {
             return Ok(CopyResult {
                 files_copied: 0,
                 bytes_copied: 0,
                 dirs_created: 0,
+                specials_created: 0,
+                devices_created: 0,
+                files_deduplicated: 0,
+                bytes_deduplicated: 0,
+                files_renamed: 0,
+                bytes_renamed: 0,
                 failures: Vec::new(),
+                warnings: Vec::new(),
                 duration: start_time.elapsed(),
                 throughput: 0.0,
                 verification: None,
             });
         }
 
+        self.check_capacity(&scan_result, &duplicates)?;
+
         // Initialize progress
         if let Some(progress) = &self.progress {
             progress.set_total_files(scan_result.file_count as u64);
@@ -226,12 +401,58 @@ impl CopyEngine {
         // Create directory structure first
         let dirs_created = create_directories(&scan_result.directories, &self.config.destination)?;
 
+        if !self.config.dry_run {
+            self.apply_directory_attributes(&scan_result.directories);
+        }
+
         if self.is_cancelled() {
             return Err(SmartCopyError::Cancelled);
         }
 
+        // Move renamed files into place so the copy below skips them
+        let (files_renamed, bytes_renamed) = self.apply_renames(&scan_result)?;
+
         // Execute parallel copy
-        let (files_copied, bytes_copied, failures, hashes) = self.copy_files_parallel(&scan_result)?;
+        let (mut files_copied, mut bytes_copied, mut failures, mut hashes) =
+            self.copy_files_parallel(&scan_result)?;
+
+        let (files_deduplicated, bytes_deduplicated) = match self.config.dedupe {
+            Some(mode) => {
+                let (linked, bytes_linked, copied, bytes) = self.link_duplicates(
+                    mode,
+                    &duplicates,
+                    &scan_result.root,
+                    &mut failures,
+                    &mut hashes,
+                )?;
+                files_copied += copied;
+                bytes_copied += bytes;
+                (linked, bytes_linked)
+            }
+            None => (0, 0),
+        };
+        let referents = std::mem::take(&mut *self.referents.lock().unwrap());
+        files_copied = files_copied + referents.files - referents.links;
+        hashes.extend(referents.hashes);
+        failures.extend(referents.failures);
+
+        // Entries the scan could not read (including symlink loops) were not copied
+        let source = escape_path(&self.config.source);
+        failures.extend(scan_result.errors.iter().map(|e| (source.clone(), e.clone())));
+
+        // Recreate FIFOs, sockets and device nodes
+        let (specials_created, devices_created) =
+            self.copy_specials(&scan_result.specials, &mut failures)?;
+
+        // Directory modes and times last, once nothing else is written below them
+        if !self.config.dry_run {
+            let options = self.copier.options();
+            for (path, warning) in
+                apply_directory_metadata(&scan_result.directories, &self.config.destination, options)
+            {
+                self.warn(&path, warning);
+            }
+        }
 
         // Verify if requested
         let verification = if self.config.verify.is_some() && !hashes.is_empty() {
@@ -240,6 +461,18 @@ impl CopyEngine {
             None
         };
 
+        if let Some(path) = &self.config.write_checksums {
+            if !self.config.dry_run {
+                self.write_checksums(path, &scan_result, &duplicates, &hashes)?;
+            }
+        }
+
+        if let Some(path) = &self.config.manifest_path {
+            if !self.config.dry_run {
+                self.write_manifest(path, &scan_result, &duplicates, &hashes, &failures, signing_key.as_ref())?;
+            }
+        }
+
         let duration = start_time.elapsed();
         let throughput = bytes_copied as f64 / duration.as_secs_f64();
 
@@ -247,16 +480,402 @@ impl CopyEngine {
             files_copied,
             bytes_copied,
             dirs_created: dirs_created as u64,
+            specials_created,
+            devices_created,
+            files_deduplicated,
+            bytes_deduplicated,
+            files_renamed,
+            bytes_renamed,
             failures,
+            warnings: self.take_warnings(),
             duration,
             throughput,
             verification,
         })
     }
 
+    /// Destination naming rules, detected from the destination filesystem
+    fn name_rules(&self) -> NameRules {
+        // The destination may not exist yet; probe its nearest existing ancestor
+        let probe = self
+            .config
+            .destination
+            .ancestors()
+            .find(|p| p.exists())
+            .unwrap_or(&self.config.destination);
+
+        let mut rules = NameRules::for_storage(detect_storage_type(probe));
+        rules.case_insensitive |= self.config.case_insensitive;
+        rules.normalize = self.config.normalize;
+        rules
+    }
+
+    /// Map source names to destination names, handling collisions per policy
+    fn resolve_name_collisions(&self, scan_result: &mut ScanResult) -> Result<()> {
+        let rules = self.name_rules();
+        if rules.is_identity() {
+            return Ok(());
+        }
+
+        let plan = NamePlan::build(scan_result, &rules, self.config.on_collision);
+        if let (CollisionPolicy::Fail, Some(first)) = (self.config.on_collision, plan.collisions.first()) {
+            for collision in &plan.collisions {
+                tracing::warn!(
+                    "{} collides with {} at destination",
+                    collision.path.display(),
+                    collision.other.display()
+                );
+            }
+            return Err(SmartCopyError::NameCollision {
+                path: first.path.clone(),
+                other: first.other.clone(),
+                count: plan.collisions.len(),
+            });
+        }
+
+        for collision in &plan.collisions {
+            let message = match &collision.renamed_to {
+                Some(renamed) => format!(
+                    "collides with '{}', copied as '{}'",
+                    collision.other.display(),
+                    renamed.display()
+                ),
+                None => format!("collides with '{}', skipped", collision.other.display()),
+            };
+            self.warn(&collision.path, message);
+        }
+
+        plan.apply(scan_result);
+        Ok(())
+    }
+
+    /// Refuse to start when the destination can't hold the copy
+    ///
+    /// In dry-run mode shortfalls are reported as warnings.
+    fn check_capacity(
+        &self,
+        scan_result: &ScanResult,
+        duplicates: &[(PathBuf, Vec<FileEntry>)],
+    ) -> Result<()> {
+        if !self.config.space_check {
+            return Ok(());
+        }
+
+        if let Some(progress) = &self.progress {
+            progress.set_status("Checking destination capacity...");
+        }
+
+        let dest = &self.config.destination;
+        let mut planner = CapacityPlanner::new(self.config.incremental);
+        let planned = planner.add_scan(scan_result, dest).and_then(|()| {
+            // Reflinks are new inodes sharing the original's blocks
+            if self.config.dedupe == Some(DedupeMode::Reflink) {
+                for entry in duplicates.iter().flat_map(|(_, entries)| entries) {
+                    planner.add_inode(&dest.join(&entry.relative_path), false)?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = planned {
+            self.warn(dest, format!("capacity not checked: {}", e));
+            return Ok(());
+        }
+
+        let privileged = is_privileged();
+        for fs in planner.finish() {
+            tracing::debug!(
+                "{}: {} bytes ({} freed), {} inodes needed",
+                fs.path.display(),
+                fs.bytes_needed,
+                fs.bytes_freed,
+                fs.inodes_needed
+            );
+            match fs.check(privileged) {
+                Ok(warnings) => {
+                    for warning in warnings {
+                        self.warn(&fs.path, warning);
+                    }
+                }
+                Err(e) if self.config.dry_run => self.warn(&fs.path, e.to_string()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove duplicate files from the scan when `--dedupe` is set
+    ///
+    /// Returns each kept file's destination path with the entries that
+    /// duplicate it.
+    fn take_duplicates(&self, scan_result: &mut ScanResult) -> Vec<(PathBuf, Vec<FileEntry>)> {
+        if self.config.dedupe.is_none() || self.config.dry_run {
+            return Vec::new();
+        }
+
+        if let Some(progress) = &self.progress {
+            progress.set_status("Finding duplicate files...");
+        }
+
+        let report = find_duplicates(&scan_result.files);
+        if report.groups.is_empty() {
+            return Vec::new();
+        }
+
+        let duplicate_paths: HashSet<&Path> = report
+            .groups
+            .iter()
+            .flat_map(|g| g.files[1..].iter().map(PathBuf::as_path))
+            .collect();
+        let originals: HashSet<&Path> = report.groups.iter().map(|g| g.files[0].as_path()).collect();
+
+        // Source path -> duplicate entry, and source path -> destination of originals
+        let mut entries: HashMap<PathBuf, FileEntry> = HashMap::new();
+        let mut kept: HashMap<PathBuf, PathBuf> = HashMap::new();
+        scan_result.files.retain(|entry| {
+            if duplicate_paths.contains(entry.path.as_path()) {
+                entries.insert(entry.path.clone(), entry.clone());
+                return false;
+            }
+            if originals.contains(entry.path.as_path()) {
+                kept.insert(entry.path.clone(), entry.relative_path.clone());
+            }
+            true
+        });
+
+        scan_result.file_count = scan_result.files.len();
+        scan_result.total_size = scan_result.files.iter().map(|f| f.size).sum();
+
+        report
+            .groups
+            .iter()
+            .filter_map(|group| {
+                let target = kept.get(&group.files[0])?.clone();
+                let duplicates = group.files[1..]
+                    .iter()
+                    .filter_map(|path| entries.remove(path))
+                    .collect();
+                Some((target, duplicates))
+            })
+            .collect()
+    }
+
+    /// Link duplicates to their copied original
+    ///
+    /// Duplicates that can't be linked (no reflink support, link count
+    /// limit, original failed to copy) are copied normally with a warning.
+    /// Returns files and bytes linked, then files and bytes copied.
+    fn link_duplicates(
+        &self,
+        mode: DedupeMode,
+        duplicates: &[(PathBuf, Vec<FileEntry>)],
+        source_root: &Path,
+        failures: &mut Vec<(String, String)>,
+        hashes: &mut Vec<(PathBuf, HashResult)>,
+    ) -> Result<(u64, u64, u64, u64)> {
+        let dest = &self.config.destination;
+        let mut totals = (0, 0, 0, 0);
+
+        for (target, entries) in duplicates {
+            let target = dest.join(target);
+            for entry in entries {
+                let dest_path = dest.join(&entry.relative_path);
+                if self.config.incremental && self.is_up_to_date(entry, &dest_path) {
+                    continue;
+                }
+
+                let linked = match mode {
+                    DedupeMode::Hardlink => hard_link_file(&target, &dest_path),
+                    DedupeMode::Reflink => reflink_file(&target, &dest_path).and_then(|()| {
+                        let warnings = self.copier.apply_attributes(&entry.path, &dest_path)?;
+                        self.warn_all(&entry.relative_path, warnings);
+                        Ok(())
+                    }),
+                };
+
+                match linked {
+                    Ok(()) => {
+                        totals.0 += 1;
+                        totals.1 += entry.size;
+                        continue;
+                    }
+                    Err(e) => self.warn(
+                        &entry.relative_path,
+                        format!("not deduplicated, copied instead: {}", e),
+                    ),
+                }
+
+                match self.copy_single_file(entry, source_root, dest, self.hash_algorithm()) {
+                    Ok((bytes, hash)) => {
+                        totals.2 += 1;
+                        totals.3 += bytes;
+                        if let Some(h) = hash {
+                            hashes.push((entry.relative_path.clone(), h));
+                        }
+                    }
+                    Err(e) if self.config.continue_on_error => {
+                        failures.push((escape_path(&entry.relative_path), e.to_string()));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        Ok(totals)
+    }
+
+    /// Algorithm to hash with while copying, if anything needs the hashes
+    fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        match (self.config.verify, &self.config.write_checksums) {
+            (Some(algo), _) => Some(algo),
+            // sha256sum is installed everywhere
+            (None, Some(_)) => Some(HashAlgorithm::Sha256),
+            (None, None) => None,
+        }
+    }
+
+    /// Write a checksum file covering every regular file copied
+    ///
+    /// Digests come from the copy itself. Files the copy didn't hash (up to
+    /// date in incremental mode) or hashed only as a composite (huge files)
+    /// are hashed from the source, so the file records what was read and a
+    /// bad copy fails `sha256sum -c` instead of being vouched for.
+    fn write_checksums(
+        &self,
+        path: &Path,
+        scan_result: &ScanResult,
+        duplicates: &[(PathBuf, Vec<FileEntry>)],
+        hashes: &[(PathBuf, HashResult)],
+    ) -> Result<()> {
+        let Some(algorithm) = self.hash_algorithm() else {
+            return Ok(());
+        };
+        if let Some(progress) = &self.progress {
+            progress.set_status("Writing checksums...");
+        }
+
+        let mut known: HashMap<&Path, String> = hashes
+            .iter()
+            .filter(|(_, hash)| composite_chunk_size(&hash.hash).is_none())
+            .map(|(path, hash)| (path.as_path(), hash.hash.clone()))
+            .collect();
+        // Linked duplicates share their original's contents
+        for (target, entries) in duplicates {
+            if let Some(hash) = known.get(target.as_path()).cloned() {
+                for entry in entries {
+                    known.entry(entry.relative_path.as_path()).or_insert_with(|| hash.clone());
+                }
+            }
+        }
+
+        let unhashed: Vec<&FileEntry> = scan_result
+            .files
+            .iter()
+            .chain(duplicates.iter().flat_map(|(_, entries)| entries))
+            .filter(|e| !e.is_symlink && !known.contains_key(e.relative_path.as_path()))
+            .collect();
+        let source_hashes: Vec<_> = unhashed
+            .par_iter()
+            .map(|entry| (entry.relative_path.as_path(), crate::hash::hash_file(&entry.path, algorithm)))
+            .collect();
+        for (path, hash) in source_hashes {
+            match hash {
+                Ok(hash) => {
+                    known.insert(path, hash.hash);
+                }
+                Err(e) => self.warn(path, format!("left out of checksum file: {}", e)),
+            }
+        }
+
+        let checksums = ChecksumFile::from_hashes(
+            algorithm,
+            known.into_iter().map(|(path, hash)| (path.to_path_buf(), hash)),
+        );
+        checksums.save(path)
+    }
+
+    /// Write the sync manifest of this run, signed when a key was given
+    ///
+    /// Files that failed to copy are left out. Digests from the copy are
+    /// recorded where there are any; the manifest is bincode when its name
+    /// ends in `.bin`, JSON otherwise.
+    fn write_manifest(
+        &self,
+        path: &Path,
+        scan_result: &ScanResult,
+        duplicates: &[(PathBuf, Vec<FileEntry>)],
+        hashes: &[(PathBuf, HashResult)],
+        failures: &[(String, String)],
+        signing_key: Option<&ed25519_dalek::SigningKey>,
+    ) -> Result<()> {
+        let failed: HashSet<&str> = failures.iter().map(|(path, _)| path.as_str()).collect();
+        let digests: HashMap<&Path, &HashResult> = hashes
+            .iter()
+            .filter(|(_, hash)| composite_chunk_size(&hash.hash).is_none())
+            .map(|(path, hash)| (path.as_path(), hash))
+            .collect();
+
+        let mut manifest = SyncManifest::new(
+            &scan_result.root.to_string_lossy(),
+            &self.config.destination.to_string_lossy(),
+        );
+        let entries = scan_result
+            .files
+            .iter()
+            .chain(duplicates.iter().flat_map(|(_, entries)| entries))
+            .chain(&scan_result.specials);
+        for entry in entries {
+            let mut record = ManifestEntry::from_file_entry(entry);
+            if failed.contains(record.path.as_str()) {
+                continue;
+            }
+            if let Some(hash) = digests.get(entry.relative_path.as_path()) {
+                record = record.with_hash(hash.hash.clone(), hash.algorithm);
+            }
+            manifest.add_entry(record);
+        }
+
+        if let Some(key) = signing_key {
+            manifest.sign(key)?;
+        }
+        if path.extension().is_some_and(|ext| ext == "bin") {
+            manifest.save_binary(path)
+        } else {
+            manifest.save(path)
+        }
+    }
+
+    /// Whether an incremental copy can leave the destination as it is
+    fn is_up_to_date(&self, entry: &FileEntry, dest_path: &Path) -> bool {
+        // An interrupted in-place update leaves a journal and a file that
+        // may already have the right size and a new mtime
+        if in_place_journal_path(dest_path).exists() {
+            return false;
+        }
+        match std::fs::metadata(dest_path) {
+            Ok(dest_meta) => {
+                let dest_mtime = dest_meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+                // Destination is same or newer
+                dest_meta.len() == entry.size && dest_mtime >= entry.modified
+            }
+            Err(_) => false,
+        }
+    }
+
     /// Scan source directory
     fn scan_source(&self) -> Result<ScanResult> {
-        let scan_config = ScanConfig {
+        let scanner = Scanner::new(self.scan_config())?;
+
+        if let Some(progress) = &self.progress {
+            progress.set_status("Scanning source directory...");
+        }
+
+        scanner.scan_sorted(&self.config.source, self.config.ordering)
+    }
+
+    /// Scan settings selected by the configuration
+    fn scan_config(&self) -> ScanConfig {
+        ScanConfig {
             follow_symlinks: self.config.follow_symlinks,
             include_hidden: self.config.include_hidden,
             max_depth: None,
@@ -265,22 +884,61 @@ impl CopyEngine {
             min_size: self.config.min_size,
             max_size: self.config.max_size,
             threads: self.config.threads,
-        };
+            include_specials: self.config.specials,
+            include_devices: self.config.devices,
+        }
+    }
 
-        let scanner = Scanner::new(scan_config)?;
+    /// Detect files that moved in the source and move them at the destination
+    ///
+    /// Only runs with `detect_renames`, which requires incremental mode and
+    /// `delete_extra`. Renamed files keep their times, so the incremental
+    /// check then skips them. Failed renames are reported as warnings and
+    /// those files are copied as usual.
+    fn apply_renames(&self, scan_result: &ScanResult) -> Result<(u64, u64)> {
+        if !self.config.detect_renames || self.config.dry_run || !self.config.destination.is_dir() {
+            return Ok((0, 0));
+        }
 
         if let Some(progress) = &self.progress {
-            progress.set_status("Scanning source directory...");
+            progress.set_status("Detecting renamed files...");
         }
 
-        scanner.scan_sorted(&self.config.source, self.config.ordering)
+        let scan_config = ScanConfig {
+            follow_symlinks: false,
+            include_hidden: self.config.include_hidden,
+            max_depth: None,
+            include_patterns: self.config.include_patterns.clone(),
+            exclude_patterns: self.config.exclude_patterns.clone(),
+            min_size: self.config.min_size,
+            max_size: self.config.max_size,
+            threads: self.config.threads,
+            include_specials: false,
+            include_devices: false,
+        };
+        let dest_scan = Scanner::new(scan_config)?.scan(&self.config.destination)?;
+
+        let analysis = IncrementalSync::new()
+            .delete_extra(true)
+            .detect_renames(true)
+            .compare_scans(scan_result, &dest_scan)?;
+        if analysis.to_rename.is_empty() {
+            return Ok((0, 0));
+        }
+
+        let result = apply_renames(&self.config.destination, &analysis.to_rename);
+        for (path, error) in &result.failed {
+            let path = unescape_path(path);
+            self.warn(&path, format!("rename not applied: {}", error));
+        }
+        Ok((result.renamed, result.bytes))
     }
 
     /// Copy files in parallel using rayon with backpressure control
     fn copy_files_parallel(
         &self,
         scan_result: &ScanResult,
-    ) -> Result<(u64, u64, Vec<(String, String)>, Vec<(String, HashResult)>)> {
+    ) -> Result<(u64, u64, Vec<(String, String)>, Vec<(PathBuf, HashResult)>)> {
         let threads = if self.config.threads == 0 {
             crate::system::numa::get_available_cpus()
         } else {
@@ -302,7 +960,7 @@ impl CopyEngine {
         let limiter = BackpressureLimiter::new(max_concurrent);
 
         let dest = &self.config.destination;
-        let verify_algo = self.config.verify;
+        let verify_algo = self.hash_algorithm();
         let cancelled = &self.cancelled;
         let progress = &self.progress;
 
@@ -318,7 +976,7 @@ impl CopyEngine {
                     // Acquire backpressure permit before starting file copy
                     let _permit = limiter.acquire();
 
-                    let result = self.copy_single_file(entry, dest, verify_algo);
+                    let result = self.copy_single_file(entry, &scan_result.root, dest, verify_algo);
 
                     if let Some(progress) = progress {
                         progress.increment_files(1);
@@ -329,7 +987,7 @@ impl CopyEngine {
                         progress.increment_bytes(bytes);
                     }
 
-                    Some((entry.relative_path.to_string_lossy().to_string(), result))
+                    Some((entry.relative_path.as_path(), result))
                 })
                 .collect()
         });
@@ -346,12 +1004,12 @@ impl CopyEngine {
                     files_copied += 1;
                     bytes_copied += bytes;
                     if let Some(h) = hash {
-                        hashes.push((path, h));
+                        hashes.push((path.to_path_buf(), h));
                     }
                 }
                 Err(e) => {
                     if self.config.continue_on_error {
-                        failures.push((path, e.to_string()));
+                        failures.push((escape_path(path), e.to_string()));
                     } else {
                         return Err(e);
                     }
@@ -362,116 +1020,387 @@ impl CopyEngine {
         Ok((files_copied, bytes_copied, failures, hashes))
     }
 
-    /// Copy a single file
-    fn copy_single_file(
+    /// Symlink policy selected by the configuration
+    fn symlink_policy(&self) -> SymlinkPolicy {
+        SymlinkPolicy {
+            safe_links: self.config.safe_links,
+            copy_unsafe_links: self.config.copy_unsafe_links,
+            munge_links: self.config.munge_links,
+        }
+    }
+
+    /// Copy a symlink according to the symlink policy
+    ///
+    /// `ancestors` are the directories being copied from behind links
+    /// that lead to this one.
+    fn copy_symlink(
         &self,
         entry: &FileEntry,
+        source_root: &Path,
         dest: &Path,
         verify_algo: Option<HashAlgorithm>,
+        ancestors: &[PathBuf],
     ) -> Result<(u64, Option<HashResult>)> {
         let dest_path = dest.join(&entry.relative_path);
+        let target = entry.symlink_target.as_ref().ok_or_else(|| SmartCopyError::SymlinkError {
+            path: entry.path.clone(),
+            message: "cannot read link target".to_string(),
+        })?;
+        let dest_root = std::path::absolute(dest).map_err(|e| SmartCopyError::io(dest, e))?;
+
+        match self.symlink_policy().plan(&entry.relative_path, target, source_root, &dest_root) {
+            SymlinkAction::Skip(reason) => {
+                tracing::info!("{}: {}", entry.relative_path.display(), reason);
+                Ok((0, None))
+            }
+            SymlinkAction::Create(target) => {
+                if !self.config.dry_run {
+                    if let Some(parent) = dest_path.parent() {
+                        std::fs::create_dir_all(parent).map_err(|e| SmartCopyError::io(parent, e))?;
+                    }
+                    create_symlink(&target, &dest_path)?;
+                }
+                Ok((0, None))
+            }
+            SymlinkAction::Dereference => {
+                let referent = resolve_symlink(&entry.path)?;
+                if !self.config.dry_run {
+                    remove_symlink(&dest_path)?;
+                }
+                if referent.is_dir() {
+                    // Copying a directory that holds the link would never end
+                    let mut copying = std::iter::once(source_root).chain(ancestors.iter().map(PathBuf::as_path));
+                    if copying.any(|dir| dir.starts_with(&referent)) {
+                        return Err(SmartCopyError::SymlinkError {
+                            path: entry.path.clone(),
+                            message: format!("symlink loop back to '{}'", referent.display()),
+                        });
+                    }
+                    self.copy_referent_tree(entry, &referent, source_root, dest, verify_algo, ancestors)
+                } else {
+                    let followed = FileEntry {
+                        relative_path: entry.relative_path.clone(),
+                        ..FileEntry::from_path_following(&entry.path, source_root)?
+                    };
+                    self.copy_single_file(&followed, source_root, dest, verify_algo)
+                }
+            }
+        }
+    }
 
-        // Ensure parent directory exists
-        if let Some(parent) = dest_path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| SmartCopyError::io(parent, e))?;
+    /// Copy the directory behind a dereferenced symlink
+    ///
+    /// Entries are rebased under the link's relative path and go through
+    /// the same filters, symlink policy and per-file handling as the rest
+    /// of the tree; their hashes are verified with the others. A link
+    /// leading back to a directory being copied is reported as a loop.
+    /// Special files inside the directory are left out.
+    fn copy_referent_tree(
+        &self,
+        link: &FileEntry,
+        referent: &Path,
+        source_root: &Path,
+        dest: &Path,
+        verify_algo: Option<HashAlgorithm>,
+        ancestors: &[PathBuf],
+    ) -> Result<(u64, Option<HashResult>)> {
+        // Patterns are matched against the rebased paths, as for the
+        // entries found by the source scan
+        let selection = Scanner::new(self.scan_config())?;
+        let scan_config = ScanConfig {
+            include_patterns: Vec::new(),
+            exclude_patterns: Vec::new(),
+            include_specials: false,
+            include_devices: false,
+            ..self.scan_config()
+        };
+        let scan = Scanner::new(scan_config)?.scan(referent)?;
+        let rebase = |entry: &FileEntry| FileEntry {
+            relative_path: link.relative_path.join(&entry.relative_path),
+            ..entry.clone()
+        };
+        let directories: Vec<FileEntry> = scan.directories.iter().map(rebase).collect();
+
+        if !self.config.dry_run {
+            create_directories(&directories, dest)?;
+            self.apply_directory_attributes(&directories);
         }
 
-        // Check if we should skip (incremental mode)
-        if self.config.incremental {
-            if let Ok(dest_meta) = std::fs::metadata(&dest_path) {
-                let dest_mtime = dest_meta.modified().unwrap_or(std::time::UNIX_EPOCH);
-                if dest_meta.len() == entry.size && dest_mtime >= entry.modified {
-                    // Skip - destination is same or newer
-                    return Ok((0, None));
+        let mut chain = ancestors.to_vec();
+        chain.push(referent.to_path_buf());
+        let mut copies = ReferentCopies { links: 1, ..Default::default() };
+        let mut bytes = 0;
+        for file in scan.files.iter().map(rebase).filter(|f| selection.is_selected(&f.relative_path)) {
+            if self.is_cancelled() {
+                return Err(SmartCopyError::Cancelled);
+            }
+            let result = if file.is_symlink {
+                self.copy_symlink(&file, source_root, dest, verify_algo, &chain)
+            } else {
+                self.copy_single_file(&file, source_root, dest, verify_algo)
+            };
+            match result {
+                Ok((copied, hash)) => {
+                    copies.files += 1;
+                    bytes += copied;
+                    if let Some(hash) = hash {
+                        copies.hashes.push((file.relative_path, hash));
+                    }
                 }
+                Err(e) if self.config.continue_on_error => {
+                    copies.failures.push((escape_path(&file.relative_path), e.to_string()));
+                }
+                Err(e) => return Err(e),
             }
         }
+        let source = escape_path(referent);
+        copies.failures.extend(scan.errors.iter().map(|e| (source.clone(), e.clone())));
 
-        // Dry run - just report
-        if self.config.dry_run {
-            return Ok((entry.size, None));
+        if !self.config.dry_run {
+            for (path, warning) in apply_directory_metadata(&directories, dest, self.copier.options()) {
+                self.warn(&path, warning);
+            }
         }
 
-        // Determine file size category for optimal copy strategy
-        let size_category = FileSizeCategory::from_size(entry.size);
-
-        // For HUGE files (>=1GB), use parallel chunked copy for maximum performance
-        if size_category.use_parallel_chunks() {
-            // Use parallel chunked copy - each chunk is copied by a separate thread
-            // Chunk size of 64MB with workers = CPU cores for optimal I/O parallelism
-            let chunk_size = 64 * 1024 * 1024; // 64MB chunks
-            let workers = num_cpus::get().max(4);
-            let chunked_copier = ChunkedCopier::new(chunk_size, workers);
+        let mut referents = self.referents.lock().unwrap();
+        referents.links += copies.links;
+        referents.files += copies.files;
+        referents.hashes.append(&mut copies.hashes);
+        referents.failures.append(&mut copies.failures);
+        Ok((bytes, None))
+    }
 
-            let result = chunked_copier.copy_parallel(&entry.path, &dest_path)?;
+    /// Recreate special files, returning (specials, devices) created
+    ///
+    /// Device nodes that can't be created for lack of privileges are
+    /// reported as warnings rather than failures.
+    fn copy_specials(
+        &self,
+        specials: &[FileEntry],
+        failures: &mut Vec<(String, String)>,
+    ) -> Result<(u64, u64)> {
+        let mut created = (0u64, 0u64);
+
+        for entry in specials {
+            if self.is_cancelled() {
+                return Err(SmartCopyError::Cancelled);
+            }
+            let Some(kind) = entry.special else { continue };
+
+            if !self.config.dry_run {
+                let dest_path = self.config.destination.join(&entry.relative_path);
+                match copy_special(entry, &dest_path, self.copier.options()) {
+                    Ok(()) => {}
+                    Err(e) if kind.is_device() && e.is_permission_error() => {
+                        self.warn(
+                            &entry.relative_path,
+                            format!("{} not created (requires root): {}", kind.as_str(), e),
+                        );
+                        continue;
+                    }
+                    Err(e) if self.config.continue_on_error => {
+                        failures.push((escape_path(&entry.relative_path), e.to_string()));
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
 
-            // Preserve attributes
-            self.copier.preserve_attributes(&entry.path, &dest_path)?;
-
-            // If verification is requested, compute a proper streaming hash of the
-            // source file. We can't use per-chunk composite hashes because they won't
-            // match the streaming hash that verify_copies() computes on the destination.
-            // XXHash3 runs at 30+ GB/s so the extra read is negligible.
-            let hash_result = if let Some(algo) = verify_algo {
-                Some(crate::hash::hash_file(&entry.path, algo)?)
+            if kind.is_device() {
+                created.1 += 1;
             } else {
-                None
-            };
-
-            return Ok((result.bytes_copied, hash_result));
+                created.0 += 1;
+            }
         }
 
-        // Copy with or without hashing
-        if let Some(algo) = verify_algo {
-            let mut hasher = StreamingHasher::new(algo);
-            self.copier.copy_with_hash(&entry.path, &dest_path, &mut hasher)?;
-            let hash = hasher.finalize();
-            Ok((entry.size, Some(hash)))
-        } else {
-            let stats = self.copier.copy(&entry.path, &dest_path)?;
-            Ok((stats.bytes_copied, None))
-        }
+        Ok(created)
+    }
+
+    /// Update an existing destination file in place with delta transfer
+    fn copy_in_place(
+        &self,
+        entry: &FileEntry,
+        dest_path: &Path,
+        verify_algo: Option<HashAlgorithm>,
+    ) -> Result<(u64, Option<HashResult>)> {
+        let chunked_copier = ChunkedCopier::new(1024 * 1024, num_cpus::get());
+        let result = chunked_copier.copy_delta_inplace(&entry.path, dest_path)?;
+
+        let warnings = self.copier.apply_attributes(&entry.path, dest_path)?;
+        self.warn_all(&entry.relative_path, warnings);
+        if self.config.preserve_acls {
+            self.apply_acls(&entry.path, dest_path, &entry.relative_path);
+        }
+
+        // Nothing streamed through a hasher, so the source is hashed for
+        // verification to compare against
+        let hash = verify_algo
+            .map(|algo| crate::hash::hash_file(&entry.path, algo))
+            .transpose()?;
+        Ok((result.bytes_transferred, hash))
+    }
+
+    /// Copy a single file
+    fn copy_single_file(
+        &self,
+        entry: &FileEntry,
+        source_root: &Path,
+        dest: &Path,
+        verify_algo: Option<HashAlgorithm>,
+    ) -> Result<(u64, Option<HashResult>)> {
+        if entry.is_symlink {
+            return self.copy_symlink(entry, source_root, dest, verify_algo, &[]);
+        }
+
+        let dest_path = dest.join(&entry.relative_path);
+
+        // Ensure parent directory exists
+        if let Some(parent) = dest_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| SmartCopyError::io(parent, e))?;
+        }
+
+        // Check if we should skip (incremental mode)
+        if self.config.incremental && self.is_up_to_date(entry, &dest_path) {
+            return Ok((0, None));
+        }
+
+        // Dry run - just report
+        if self.config.dry_run {
+            return Ok((entry.size, None));
+        }
+
+        // Large files that already exist are patched in place, writing only
+        // changed blocks and needing no room for a second copy
+        if self.config.delta && self.config.inplace && entry.size >= self.config.delta_threshold {
+            let existing = std::fs::symlink_metadata(&dest_path).map(|m| m.is_file()).unwrap_or(false);
+            if existing {
+                return self.copy_in_place(entry, &dest_path, verify_algo);
+            }
+        }
+
+        // A full copy replaces a file left half-updated in place, so its
+        // journal would only keep incremental runs from skipping it
+        if self.config.incremental {
+            let journal = in_place_journal_path(&dest_path);
+            if journal.exists() {
+                std::fs::remove_file(&journal).with_path(&journal)?;
+            }
+        }
+
+        // Determine file size category for optimal copy strategy
+        let size_category = FileSizeCategory::from_size(entry.size);
+
+        // For HUGE files (>=1GB), use parallel chunked copy for maximum performance
+        if size_category.use_parallel_chunks() {
+            // Use parallel chunked copy - each chunk is copied by a separate thread
+            // Chunk size of 64MB with workers = CPU cores for optimal I/O parallelism
+            let chunk_size = 64 * 1024 * 1024; // 64MB chunks
+            let workers = num_cpus::get().max(4);
+            let chunked_copier = ChunkedCopier::new(chunk_size, workers);
+
+            // With verification, each chunk is hashed by the worker that copies
+            // it. The composite of those hashes stands in for the file hash and
+            // the chunk hashes allow repairing a mismatch chunk by chunk.
+            let (result, hash_result) = if let Some(algo) = verify_algo {
+                let (result, chunks) =
+                    chunked_copier.copy_parallel_with_chunk_hashes(&entry.path, &dest_path, algo)?;
+                let hash = chunks.composite(entry.size);
+                self.chunk_hashes
+                    .lock()
+                    .unwrap()
+                    .insert(entry.relative_path.clone(), (entry.path.clone(), entry.size, chunks));
+                (result, Some(hash))
+            } else {
+                (chunked_copier.copy_parallel(&entry.path, &dest_path)?, None)
+            };
+
+            // Preserve attributes
+            let warnings = self.copier.apply_attributes(&entry.path, &dest_path)?;
+            self.warn_all(&entry.relative_path, warnings);
+            if self.config.preserve_acls {
+                self.apply_acls(&entry.path, &dest_path, &entry.relative_path);
+            }
+
+            return Ok((result.bytes_copied, hash_result));
+        }
+
+        // Copy with or without hashing
+        let result = if let Some(algo) = verify_algo {
+            let mut hasher = StreamingHasher::new(algo);
+            let stats = self.copier.copy_with_hash(&entry.path, &dest_path, &mut hasher)?;
+            self.warn_all(&entry.relative_path, stats.warnings);
+            let hash = hasher.finalize();
+            (entry.size, Some(hash))
+        } else {
+            let stats = self.copier.copy(&entry.path, &dest_path)?;
+            self.warn_all(&entry.relative_path, stats.warnings);
+            (stats.bytes_copied, None)
+        };
+
+        if self.config.preserve_acls {
+            self.apply_acls(&entry.path, &dest_path, &entry.relative_path);
+        }
+
+        Ok(result)
     }
 
     /// Verify copied files
-    fn verify_copies(&self, hashes: &[(String, HashResult)]) -> Result<VerificationSummary> {
+    fn verify_copies(&self, hashes: &[(PathBuf, HashResult)]) -> Result<VerificationSummary> {
         if let Some(progress) = &self.progress {
             progress.set_status("Verifying copies...");
         }
 
-        let algo = self.config.verify.unwrap_or(HashAlgorithm::XXHash3);
         let dest = &self.config.destination;
 
-        let results: Vec<_> = hashes
-            .par_iter()
-            .map(|(path, expected_hash)| {
-                let dest_path = dest.join(path);
-                let actual = crate::hash::hash_file(&dest_path, algo);
-
-                match actual {
-                    Ok(actual_hash) => {
-                        if actual_hash.verify(expected_hash) {
-                            Ok(true)
-                        } else {
-                            Ok(false)
+        // Verification can run with fewer threads than the copy
+        let threads = match (self.config.verify_threads, self.config.threads) {
+            (0, 0) => crate::system::numa::get_available_cpus(),
+            (0, threads) | (threads, _) => threads,
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| SmartCopyError::ThreadPoolError(e.to_string()))?;
+
+        let results: Vec<_> = pool.install(|| {
+            hashes
+                .par_iter()
+                .map(|(path, expected_hash)| {
+                    let dest_path = dest.join(path);
+                    let actual = self.hash_copy(&dest_path, expected_hash);
+
+                    match actual {
+                        Ok(actual_hash) => {
+                            if actual_hash.verify(expected_hash) {
+                                Ok(Verified::Passed)
+                            } else if self.repair_chunks(path, &dest_path, expected_hash) {
+                                Ok(Verified::Repaired)
+                            } else {
+                                Ok(Verified::Failed)
+                            }
                         }
+                        Err(e) => Err((escape_path(path), e.to_string())),
                     }
-                    Err(e) => Err((path.clone(), e.to_string())),
-                }
-            })
-            .collect();
+                })
+                .collect()
+        });
 
         let mut verified = 0u64;
         let mut passed = 0u64;
         let mut failed = 0u64;
+        let mut repaired = 0u64;
         let mut mismatches = Vec::new();
 
         for result in results {
             verified += 1;
             match result {
-                Ok(true) => passed += 1,
-                Ok(false) => {
+                Ok(Verified::Passed) => passed += 1,
+                Ok(Verified::Repaired) => {
+                    passed += 1;
+                    repaired += 1;
+                }
+                Ok(Verified::Failed) => {
                     failed += 1;
                 }
                 Err((path, error)) => {
@@ -485,9 +1414,63 @@ impl CopyEngine {
             verified,
             passed,
             failed,
+            repaired,
             mismatches,
         })
     }
+
+    /// Hash a copied file the same way its copy was hashed
+    ///
+    /// Composite hashes of huge files are recomputed chunk-parallel.
+    fn hash_copy(&self, path: &Path, expected: &HashResult) -> Result<HashResult> {
+        let algorithm = expected.algorithm;
+        match (composite_chunk_size(&expected.hash), self.config.verify_mode) {
+            (Some(chunk_size), VerifyMode::Cached) => {
+                Ok(crate::hash::hash_file_chunked(path, algorithm, chunk_size)?.composite(expected.size))
+            }
+            (Some(chunk_size), VerifyMode::CacheBypass) => {
+                let size = std::fs::metadata(path).with_path(path)?.len();
+                Ok(crate::hash::hash_file_chunks_uncached(path, algorithm, chunk_size)?.composite(size))
+            }
+            (None, VerifyMode::Cached) => crate::hash::hash_file(path, algorithm),
+            (None, VerifyMode::CacheBypass) => crate::hash::hash_file_uncached(path, algorithm),
+        }
+    }
+
+    /// Re-copy the mismatched chunks of a huge file and re-verify it
+    ///
+    /// Only files whose chunk hashes were recorded during the copy can be
+    /// repaired. Returns whether the destination now matches.
+    fn repair_chunks(&self, relative: &Path, dest_path: &Path, expected: &HashResult) -> bool {
+        let Some((source, size, chunks)) = self.chunk_hashes.lock().unwrap().get(relative).cloned() else {
+            return false;
+        };
+
+        let repaired = chunks.find_mismatches(dest_path, size).and_then(|bad| {
+            chunks.copy_chunks(&source, dest_path, &bad, size)?;
+            let actual = self.hash_copy(dest_path, expected)?;
+            Ok((bad.len(), actual.verify(expected)))
+        });
+
+        match repaired {
+            Ok((count, true)) => {
+                self.warn(relative, format!("verification mismatch, re-copied {} chunks", count));
+                true
+            }
+            Ok((_, false)) => false,
+            Err(e) => {
+                self.warn(relative, format!("chunk repair failed: {}", e));
+                false
+            }
+        }
+    }
+}
+
+/// Outcome of verifying one copied file
+enum Verified {
+    Passed,
+    Repaired,
+    Failed,
 }
 
 /// Simple synchronous copy for small operations
@@ -660,6 +1643,198 @@ mod tests {
         assert!(verification.failed == 0);
     }
 
+    #[test]
+    fn test_verify_repairs_mismatched_chunks() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(src.path().join("huge.bin"), &data).unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            verify: Some(HashAlgorithm::Blake3),
+            ..Default::default()
+        };
+        let engine = CopyEngine::new(config);
+
+        // Stand in for the huge-file path with small chunks and a bad copy
+        let source = src.path().join("huge.bin");
+        let chunks = crate::hash::hash_file_chunked(&source, HashAlgorithm::Sha256, 4096).unwrap();
+        let hash = chunks.composite(20_000);
+        engine
+            .chunk_hashes
+            .lock()
+            .unwrap()
+            .insert(PathBuf::from("huge.bin"), (source, 20_000, chunks));
+        let mut damaged = data.clone();
+        damaged[5000] ^= 0xff;
+        std::fs::write(dst.path().join("huge.bin"), &damaged).unwrap();
+
+        let summary = engine.verify_copies(&[(PathBuf::from("huge.bin"), hash)]).unwrap();
+        assert_eq!((summary.passed, summary.failed, summary.repaired), (1, 0, 1));
+        assert_eq!(std::fs::read(dst.path().join("huge.bin")).unwrap(), data);
+        assert_eq!(engine.take_warnings().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_dedupe() {
+        use std::os::unix::fs::MetadataExt;
+
+        let src = TempDir::new().unwrap();
+        let frame = vec![3u8; 8192];
+        for name in ["a.fits", "b.fits", "sub/c.fits"] {
+            let path = src.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, &frame).unwrap();
+        }
+        std::fs::write(src.path().join("unique.fits"), vec![4u8; 8192]).unwrap();
+
+        let dst = TempDir::new().unwrap();
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            dedupe: Some(DedupeMode::Hardlink),
+            ..Default::default()
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.files_copied, 2);
+        assert_eq!((result.files_deduplicated, result.bytes_deduplicated), (2, 2 * 8192));
+
+        let ino = |name: &str| std::fs::metadata(dst.path().join(name)).unwrap().ino();
+        assert_eq!(ino("a.fits"), ino("b.fits"));
+        assert_eq!(ino("a.fits"), ino("sub/c.fits"));
+        assert_ne!(ino("a.fits"), ino("unique.fits"));
+
+        // Without reflink support the duplicates are copied instead
+        let dst = TempDir::new().unwrap();
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            dedupe: Some(DedupeMode::Reflink),
+            verify: Some(HashAlgorithm::XXHash3),
+            ..Default::default()
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.files_copied + result.files_deduplicated, 4);
+        assert_eq!(result.warnings.len() as u64, 2 - result.files_deduplicated);
+        assert_eq!(std::fs::read(dst.path().join("sub/c.fits")).unwrap(), frame);
+    }
+
+    #[test]
+    fn test_copy_verify_cache_bypass() {
+        let src = TempDir::new().unwrap();
+        for i in 0..4 {
+            std::fs::write(src.path().join(format!("f{}.dat", i)), vec![i as u8; 10_000]).unwrap();
+        }
+
+        let dst = TempDir::new().unwrap();
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            verify: Some(HashAlgorithm::Blake3),
+            verify_mode: VerifyMode::CacheBypass,
+            verify_threads: 1,
+            ..Default::default()
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+        let verification = result.verification.unwrap();
+        assert_eq!((verification.verified, verification.passed), (4, 4));
+    }
+
+    #[test]
+    fn test_copy_write_checksums() {
+        use crate::hash::{hash_bytes, verify_checksums};
+
+        let src = TempDir::new().unwrap();
+        std::fs::create_dir(src.path().join("sub")).unwrap();
+        std::fs::write(src.path().join("a.txt"), b"alpha").unwrap();
+        std::fs::write(src.path().join("sub/b.txt"), b"beta").unwrap();
+
+        let dst = TempDir::new().unwrap();
+        let sums = dst.path().join("SHA256SUMS");
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().join("copy"),
+            write_checksums: Some(sums.clone()),
+            incremental: true,
+            ..Default::default()
+        };
+        CopyEngine::new(config.clone()).execute().unwrap();
+
+        // Second run copies one file; the up-to-date ones are hashed from the source
+        std::fs::write(src.path().join("c.txt"), b"gamma").unwrap();
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert_eq!(result.bytes_copied, 5);
+
+        let checksums = ChecksumFile::load(&sums, None).unwrap();
+        assert_eq!(checksums.algorithm, HashAlgorithm::Sha256);
+        let paths: Vec<_> = checksums.entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("a.txt"), PathBuf::from("c.txt"), PathBuf::from("sub/b.txt")]);
+        assert_eq!(checksums.entries[0].hash, hash_bytes(b"alpha", HashAlgorithm::Sha256).hash);
+        assert!(verify_checksums(&checksums, &dst.path().join("copy")).unwrap().is_success());
+    }
+
+    #[test]
+    fn test_copy_write_checksums_hashes_source() {
+        use crate::hash::{hash_bytes, verify_checksums};
+
+        let src = TempDir::new().unwrap();
+        std::fs::write(src.path().join("a.txt"), b"alpha").unwrap();
+        let dst = TempDir::new().unwrap();
+        let sums = dst.path().join("SHA256SUMS");
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().join("copy"),
+            write_checksums: Some(sums.clone()),
+            incremental: true,
+            ..Default::default()
+        };
+        CopyEngine::new(config.clone()).execute().unwrap();
+
+        // A damaged copy that still looks up to date is not vouched for
+        std::fs::write(dst.path().join("copy/a.txt"), b"ALPHA").unwrap();
+        CopyEngine::new(config).execute().unwrap();
+
+        let checksums = ChecksumFile::load(&sums, None).unwrap();
+        assert_eq!(checksums.entries[0].hash, hash_bytes(b"alpha", HashAlgorithm::Sha256).hash);
+        assert!(!verify_checksums(&checksums, &dst.path().join("copy")).unwrap().is_success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_non_utf8_names() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+
+        // Latin-1 names that are identical after lossy conversion
+        let names = [
+            crate::fs::path_from_bytes(b"caf\xe9.dat"),
+            crate::fs::path_from_bytes(b"caf\xe8.dat"),
+        ];
+        for (i, name) in names.iter().enumerate() {
+            std::fs::write(src.path().join(name), vec![b'x'; i + 1]).unwrap();
+        }
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            verify: Some(HashAlgorithm::XXHash3),
+            ..Default::default()
+        };
+
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert!(result.is_success());
+        let verification = result.verification.unwrap();
+        assert_eq!((verification.passed, verification.failed), (2, 0));
+        for (i, name) in names.iter().enumerate() {
+            assert_eq!(std::fs::read(dst.path().join(name)).unwrap().len(), i + 1);
+        }
+    }
+
     #[test]
     fn test_incremental_copy() {
         let src = TempDir::new().unwrap();
@@ -692,6 +1867,358 @@ mod tests {
         assert!(result2.bytes_copied < result1.bytes_copied);
     }
 
+    #[test]
+    fn test_incremental_copy_detects_renames() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+
+        create_test_structure(src.path());
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            ..Default::default()
+        };
+        CopyEngine::new(config).execute().unwrap();
+
+        std::fs::create_dir(src.path().join("moved")).unwrap();
+        std::fs::rename(src.path().join("small.bin"), src.path().join("moved/small.bin")).unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            incremental: true,
+            delete_extra: true,
+            detect_renames: true,
+            ..Default::default()
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+
+        assert_eq!(result.files_renamed, 1);
+        assert_eq!(result.bytes_copied, 0);
+        assert!(!dst.path().join("small.bin").exists());
+        assert_eq!(
+            std::fs::read(dst.path().join("moved/small.bin")).unwrap(),
+            std::fs::read(src.path().join("moved/small.bin")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_copy_writes_signed_manifest() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        let state = TempDir::new().unwrap();
+        create_test_structure(src.path());
+
+        let key_path = state.path().join("sync.key");
+        let public = crate::hash::generate_signing_key(&key_path).unwrap();
+        let manifest_path = state.path().join("sync.bin");
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            manifest_path: Some(manifest_path.clone()),
+            manifest_signing_key: Some(key_path),
+            ..Default::default()
+        };
+        CopyEngine::new(config).execute().unwrap();
+
+        let manifest = SyncManifest::load_any(&manifest_path).unwrap();
+        manifest.verify_signature(&public).unwrap();
+        assert_eq!(manifest.total_files, 4);
+        assert_eq!(manifest.find("subdir1/medium.bin").unwrap().size, 100 * 1024);
+        assert_eq!(manifest.dest_root, dst.path().to_string_lossy());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_preserves_filtered_xattrs() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+
+        create_test_structure(src.path());
+        if xattr::set(src.path().join("tiny.txt"), "user.keep", b"file").is_err() {
+            // Filesystem without user xattr support
+            return;
+        }
+        xattr::set(src.path().join("tiny.txt"), "user.drop", b"x").unwrap();
+        xattr::set(src.path().join("subdir1"), "user.keep", b"dir").unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            xattrs: true,
+            xattr_exclude: vec!["user.drop".to_string()],
+            verify: Some(HashAlgorithm::XXHash3),
+            ..Default::default()
+        };
+
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert!(result.is_success());
+        assert!(result.warnings.is_empty());
+
+        let file = dst.path().join("tiny.txt");
+        assert_eq!(xattr::get(&file, "user.keep").unwrap(), Some(b"file".to_vec()));
+        assert_eq!(xattr::get(&file, "user.drop").unwrap(), None);
+        assert_eq!(
+            xattr::get(dst.path().join("subdir1"), "user.keep").unwrap(),
+            Some(b"dir".to_vec())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_metadata_applied_after_contents() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        create_test_structure(src.path());
+
+        let nested = src.path().join("subdir2/nested");
+        let old = filetime::FileTime::from_unix_time(1_500_000_000, 0);
+        filetime::set_file_mtime(src.path().join("subdir1"), old).unwrap();
+        filetime::set_file_mtime(&nested, old).unwrap();
+        std::fs::set_permissions(&nested, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        // Second run must cope with the read-only directory left by the first
+        for _ in 0..2 {
+            let result = CopyEngine::new(config.clone()).execute().unwrap();
+            assert!(result.is_success());
+            assert!(result.warnings.is_empty());
+        }
+
+        let dest_nested = dst.path().join("subdir2/nested");
+        assert!(dest_nested.join("deep.txt").exists());
+        let meta = std::fs::metadata(&dest_nested).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o555);
+        assert_eq!(filetime::FileTime::from_last_modification_time(&meta), old);
+
+        let meta = std::fs::metadata(dst.path().join("subdir1")).unwrap();
+        assert_eq!(filetime::FileTime::from_last_modification_time(&meta), old);
+
+        // Allow TempDir cleanup
+        for dir in [&nested, &dest_nested] {
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_specials() {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        create_test_structure(src.path());
+
+        let fifo = src.path().join("subdir1/pipe");
+        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        std::fs::set_permissions(&fifo, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            specials: true,
+            ..Default::default()
+        };
+
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert!(result.is_success());
+        assert_eq!(result.files_copied, 4);
+        assert_eq!(result.specials_created, 1);
+        assert_eq!(result.devices_created, 0);
+
+        let meta = std::fs::symlink_metadata(dst.path().join("subdir1/pipe")).unwrap();
+        assert!(meta.file_type().is_fifo());
+        assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policies() {
+        use std::os::unix::fs::symlink;
+
+        let src = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        create_test_structure(src.path());
+        std::fs::write(outside.path().join("secret.txt"), b"outside").unwrap();
+
+        let src_root = src.path().canonicalize().unwrap();
+        symlink("../tiny.txt", src.path().join("subdir1/rel")).unwrap();
+        symlink(src_root.join("subdir1/medium.bin"), src.path().join("abs")).unwrap();
+        symlink(outside.path().join("secret.txt"), src.path().join("out")).unwrap();
+
+        let run = |safe_links: bool, copy_unsafe_links: bool| {
+            let dst = TempDir::new().unwrap();
+            let config = CopyConfig {
+                source: src.path().to_path_buf(),
+                destination: dst.path().to_path_buf(),
+                safe_links,
+                copy_unsafe_links,
+                ..Default::default()
+            };
+            let result = CopyEngine::new(config).execute().unwrap();
+            assert!(result.is_success(), "{:?}", result.failures);
+            dst
+        };
+
+        // Default: links recreated, absolute in-tree link rewritten to the new root
+        let dst = run(false, false);
+        assert_eq!(std::fs::read_link(dst.path().join("subdir1/rel")).unwrap(), Path::new("../tiny.txt"));
+        assert_eq!(
+            std::fs::read_link(dst.path().join("abs")).unwrap(),
+            dst.path().join("subdir1/medium.bin")
+        );
+        assert!(std::fs::symlink_metadata(dst.path().join("out")).unwrap().file_type().is_symlink());
+
+        let dst = run(true, false);
+        assert!(std::fs::symlink_metadata(dst.path().join("out")).is_err());
+        assert!(std::fs::symlink_metadata(dst.path().join("subdir1/rel")).is_ok());
+
+        let dst = run(false, true);
+        let meta = std::fs::symlink_metadata(dst.path().join("out")).unwrap();
+        assert!(meta.is_file());
+        assert_eq!(std::fs::read(dst.path().join("out")).unwrap(), b"outside");
+        assert!(std::fs::symlink_metadata(dst.path().join("subdir1/rel")).unwrap().file_type().is_symlink());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dereferenced_directory_verified_with_metadata() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        create_test_structure(src.path());
+        std::fs::create_dir(outside.path().join("inner")).unwrap();
+        std::fs::write(outside.path().join("inner/data.txt"), b"behind the link").unwrap();
+        std::fs::set_permissions(outside.path().join("inner"), std::fs::Permissions::from_mode(0o750)).unwrap();
+        symlink(outside.path(), src.path().join("ext")).unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            copy_unsafe_links: true,
+            preserve: true,
+            verify: Some(HashAlgorithm::Blake3),
+            ..Default::default()
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert!(result.is_success(), "{:?}", result.failures);
+
+        let copied = dst.path().join("ext/inner/data.txt");
+        assert_eq!(std::fs::read(&copied).unwrap(), b"behind the link");
+        let mode = std::fs::metadata(dst.path().join("ext/inner")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+
+        // The referent's file is verified alongside the in-tree files
+        let verification = result.verification.unwrap();
+        assert_eq!(verification.verified, 5);
+        assert_eq!(verification.failed, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dereferenced_directory_follows_copy_settings() {
+        use std::os::unix::fs::symlink;
+
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        create_test_structure(src.path());
+        std::fs::write(outside.path().join("data.txt"), b"behind the link").unwrap();
+        std::fs::write(outside.path().join(".hidden"), b"hidden").unwrap();
+        std::fs::write(outside.path().join("skip.log"), b"excluded").unwrap();
+        std::fs::write(other.path().join("far.txt"), b"further out").unwrap();
+        symlink(other.path().join("far.txt"), outside.path().join("far")).unwrap();
+        symlink(outside.path(), outside.path().join("again")).unwrap();
+        symlink(outside.path(), src.path().join("ext")).unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            copy_unsafe_links: true,
+            continue_on_error: true,
+            exclude_patterns: vec!["ext/*.log".to_string()],
+            ..Default::default()
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+
+        let ext = dst.path().join("ext");
+        assert_eq!(std::fs::read(ext.join("data.txt")).unwrap(), b"behind the link");
+        assert!(!ext.join(".hidden").exists());
+        assert!(!ext.join("skip.log").exists());
+        // Unsafe links inside the referent are dereferenced too
+        assert!(std::fs::symlink_metadata(ext.join("far")).unwrap().is_file());
+        assert_eq!(std::fs::read(ext.join("far")).unwrap(), b"further out");
+
+        // The link back to the referent fails on its own
+        assert_eq!(result.failures.len(), 1, "{:?}", result.failures);
+        assert_eq!(result.failures[0].0, "ext/again");
+        assert!(result.failures[0].1.contains("loop"));
+        assert_eq!(result.files_copied, 6);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop_reported() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        create_test_structure(src.path());
+        std::os::unix::fs::symlink("..", src.path().join("subdir1/up")).unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            follow_symlinks: true,
+            continue_on_error: true,
+            ..Default::default()
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+
+        assert!(result.failures.iter().any(|(_, e)| e.contains("Symbolic link error") && e.contains("loop")));
+        assert!(dst.path().join("subdir2/nested/deep.txt").exists());
+    }
+
+    #[test]
+    fn test_case_collision_preflight() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        std::fs::write(src.path().join("Makefile"), b"upper").unwrap();
+        std::fs::write(src.path().join("makefile"), b"lower").unwrap();
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            case_insensitive: true,
+            ..Default::default()
+        };
+        match CopyEngine::new(config.clone()).execute() {
+            Err(SmartCopyError::NameCollision { count, .. }) => assert_eq!(count, 1),
+            other => panic!("Expected NameCollision, got {:?}", other),
+        }
+        assert!(!dst.path().join("Makefile").exists());
+
+        let config = CopyConfig {
+            on_collision: CollisionPolicy::Rename,
+            ..config
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert_eq!(result.files_copied, 2);
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(std::fs::read(dst.path().join("Makefile")).unwrap(), b"upper");
+        assert_eq!(std::fs::read(dst.path().join("makefile~2")).unwrap(), b"lower");
+    }
+
     #[test]
     fn test_parallel_copy() {
         let src = TempDir::new().unwrap();
@@ -711,4 +2238,50 @@ mod tests {
 
         assert!(result.is_success());
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_only_directory_acl_applied_after_contents() {
+        use crate::fs::{PosixAcl, PosixAclEntry, PosixAclTag};
+
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        create_test_structure(src.path());
+
+        // user::r-x leaves the directory unwritable for its owner
+        let acl = PosixAcl {
+            entries: vec![
+                PosixAclEntry::new(PosixAclTag::UserObj, 0o5),
+                PosixAclEntry::named(PosixAclTag::User, 0, 0o5),
+                PosixAclEntry::new(PosixAclTag::GroupObj, 0o5),
+                PosixAclEntry::new(PosixAclTag::Mask, 0o5),
+                PosixAclEntry::new(PosixAclTag::Other, 0),
+            ],
+        };
+        let ro_dir = src.path().join("subdir2/nested");
+        if xattr::set(&ro_dir, crate::fs::posix_acl::ACL_ACCESS_XATTR, &acl.to_xattr_bytes()).is_err() {
+            // Filesystem or sandbox without ACL support
+            return;
+        }
+
+        let config = CopyConfig {
+            source: src.path().to_path_buf(),
+            destination: dst.path().to_path_buf(),
+            preserve: true,
+            preserve_acls: true,
+            ..Default::default()
+        };
+        let result = CopyEngine::new(config).execute().unwrap();
+        assert!(result.is_success(), "{:?}", result.failures);
+
+        let dest_dir = dst.path().join("subdir2/nested");
+        assert_eq!(std::fs::read(dest_dir.join("deep.txt")).unwrap(), b"deep file content");
+        let copied = crate::fs::PosixAclOps::get_acls(&dest_dir).unwrap().access.unwrap();
+        assert!(copied.entries.iter().any(|e| e.tag == PosixAclTag::UserObj && e.perm == 0o5));
+        assert_eq!(copied.named_entries().count(), 1);
+
+        // Let the temporary directory be removed
+        crate::fs::PosixAclOps::set_acls(&dest_dir, &Default::default()).unwrap();
+        std::fs::set_permissions(&dest_dir, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    }
 }
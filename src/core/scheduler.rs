@@ -453,6 +453,9 @@ mod tests {
             is_symlink: false,
             symlink_target: None,
             permissions: 0o644,
+            special: None,
+            rdev: 0,
+            inode: 0,
         }
     }
 
@@ -2,7 +2,13 @@
 //!
 //! Provides encryption at rest for secure file transfers.
 //! Supports multiple encryption algorithms and key management options.
+//! With the `encryption` feature, also provides authenticated stream
+//! encryption for network transports.
 
 mod encryption;
+#[cfg(feature = "encryption")]
+mod stream;
 
 pub use encryption::*;
+#[cfg(feature = "encryption")]
+pub use stream::*;
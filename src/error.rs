@@ -73,6 +73,10 @@ pub enum SmartCopyError {
     #[error("Manifest error: {0}")]
     ManifestError(String),
 
+    /// Manifest signature missing, malformed or invalid
+    #[error("Manifest signature error: {0}")]
+    SignatureError(String),
+
     /// Delta sync error
     #[error("Delta sync error: {0}")]
     DeltaSyncError(String),
@@ -101,6 +105,14 @@ pub enum SmartCopyError {
         available: u64,
     },
 
+    /// Out of inodes
+    #[error("Insufficient inodes at '{path}': need {required}, have {available}")]
+    InsufficientInodes {
+        path: PathBuf,
+        required: u64,
+        available: u64,
+    },
+
     /// File too large
     #[error("File too large: {path} ({size} bytes exceeds limit of {limit} bytes)")]
     FileTooLarge {
@@ -117,6 +129,14 @@ pub enum SmartCopyError {
     #[error("Unsupported file type at '{path}': {file_type}")]
     UnsupportedFileType { path: PathBuf, file_type: String },
 
+    /// Source names that would overwrite each other at the destination
+    #[error("{count} name collision(s) at destination, e.g. '{path}' collides with '{other}'")]
+    NameCollision {
+        path: PathBuf,
+        other: PathBuf,
+        count: usize,
+    },
+
     /// Unsupported operation on this platform
     #[error("Unsupported operation: {0}")]
     UnsupportedOperation(String),
@@ -226,8 +246,10 @@ impl SmartCopyError {
             | Self::SameSourceAndDestination(path)
             | Self::IntegrityMismatch { path, .. }
             | Self::InsufficientSpace { path, .. }
+            | Self::InsufficientInodes { path, .. }
             | Self::FileTooLarge { path, .. }
             | Self::SymlinkError { path, .. }
+            | Self::NameCollision { path, .. }
             | Self::UnsupportedFileType { path, .. } => Some(path),
             _ => None,
         }
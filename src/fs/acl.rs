@@ -2,6 +2,7 @@
 //!
 //! Provides support for Windows Access Control Lists (ACLs).
 //! Preserves security descriptors, DACLs, and SACLs during file copies.
+//! POSIX ACLs on Linux are handled by [`crate::fs::posix_acl`].
 
 use serde::{Deserialize, Serialize};
 use std::io;
@@ -429,7 +430,7 @@ pub fn is_windows() -> bool {
     cfg!(windows)
 }
 
-/// Check if Windows ACL support is available
+/// Check if ACL support (Windows DACLs or Linux POSIX ACLs) is available
 pub fn acl_available() -> bool {
     #[cfg(windows)]
     {
@@ -437,7 +438,7 @@ pub fn acl_available() -> bool {
     }
     #[cfg(not(windows))]
     {
-        false
+        super::posix_acl::posix_acl_available()
     }
 }
 
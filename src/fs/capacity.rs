@@ -0,0 +1,417 @@
+//! Destination capacity preflight
+//!
+//! Works out what a copy will really consume on each destination
+//! filesystem before anything is written: bytes rounded up to whole
+//! blocks, minus the blocks of files that get overwritten, and one inode
+//! per new file, directory, symlink or special file. Files an incremental
+//! copy will skip cost nothing. The totals are checked against `statvfs`,
+//! honouring the blocks and inodes reserved for root.
+
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use crate::fs::{FileEntry, ScanResult};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Free space and inodes of a filesystem, from `statvfs`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsStats {
+    /// Fundamental block size in bytes
+    pub block_size: u64,
+    /// Free blocks, including those reserved for root
+    pub free_blocks: u64,
+    /// Free blocks available to unprivileged users
+    pub avail_blocks: u64,
+    /// Total inodes (0 when the filesystem has no fixed inode table)
+    pub total_inodes: u64,
+    /// Free inodes, including those reserved for root
+    pub free_inodes: u64,
+    /// Free inodes available to unprivileged users
+    pub avail_inodes: u64,
+}
+
+impl FsStats {
+    /// Query the filesystem holding `path`
+    #[cfg(unix)]
+    pub fn for_path(path: &Path) -> Result<Self> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| SmartCopyError::InvalidPath(path.display().to_string()))?;
+
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return Err(SmartCopyError::io(path, std::io::Error::last_os_error()));
+        }
+
+        let block_size = if stat.f_frsize > 0 { stat.f_frsize } else { stat.f_bsize };
+        Ok(Self {
+            block_size: block_size as u64,
+            free_blocks: stat.f_bfree as u64,
+            avail_blocks: stat.f_bavail as u64,
+            total_inodes: stat.f_files as u64,
+            free_inodes: stat.f_ffree as u64,
+            avail_inodes: stat.f_favail as u64,
+        })
+    }
+
+    /// Query the filesystem holding `path` (free bytes only)
+    #[cfg(not(unix))]
+    pub fn for_path(path: &Path) -> Result<Self> {
+        let available = crate::fs::available_space(path)?;
+        Ok(Self {
+            block_size: 4096,
+            free_blocks: available / 4096,
+            avail_blocks: available / 4096,
+            ..Default::default()
+        })
+    }
+
+    /// Round a file size up to whole blocks
+    pub fn allocated(&self, size: u64) -> u64 {
+        let block = self.block_size.max(1);
+        size.div_ceil(block) * block
+    }
+}
+
+/// What a copy needs on one destination filesystem
+#[derive(Debug, Clone)]
+pub struct FsRequirement {
+    /// Existing destination path on this filesystem used for `statvfs`
+    pub path: PathBuf,
+    /// Filesystem free space and inodes
+    pub stats: FsStats,
+    /// Bytes of new data, rounded to blocks
+    pub bytes_needed: u64,
+    /// Bytes released by files that are overwritten
+    pub bytes_freed: u64,
+    /// New inodes
+    pub inodes_needed: u64,
+}
+
+impl FsRequirement {
+    /// Net bytes the copy consumes
+    pub fn net_bytes(&self) -> u64 {
+        self.bytes_needed.saturating_sub(self.bytes_freed)
+    }
+
+    /// Check the requirement against free space and inodes
+    ///
+    /// Returns warnings when only root's reserve is left (and `privileged`
+    /// is set), or an error when the copy cannot fit.
+    pub fn check(&self, privileged: bool) -> Result<Vec<String>> {
+        let mut warnings = Vec::new();
+        let stats = &self.stats;
+        let bytes = self.net_bytes();
+        let free = stats.free_blocks * stats.block_size;
+        let avail = stats.avail_blocks * stats.block_size;
+
+        let usable = if privileged { free } else { avail };
+        if bytes > usable {
+            return Err(SmartCopyError::InsufficientSpace {
+                path: self.path.clone(),
+                required: bytes,
+                available: usable,
+            });
+        }
+        if bytes > avail {
+            warnings.push(format!(
+                "copy needs {} and will use blocks reserved for root",
+                humansize::format_size(bytes, humansize::BINARY)
+            ));
+        }
+
+        // Filesystems without a fixed inode table report zero inodes
+        if stats.total_inodes > 0 {
+            let usable = if privileged { stats.free_inodes } else { stats.avail_inodes };
+            if self.inodes_needed > usable {
+                return Err(SmartCopyError::InsufficientInodes {
+                    path: self.path.clone(),
+                    required: self.inodes_needed,
+                    available: usable,
+                });
+            }
+            if self.inodes_needed > stats.avail_inodes {
+                warnings.push(format!(
+                    "copy needs {} inodes and will use inodes reserved for root",
+                    self.inodes_needed
+                ));
+            }
+        }
+
+        Ok(warnings)
+    }
+}
+
+/// Nearest existing ancestor of a destination directory
+#[derive(Debug, Clone, Copy)]
+struct DirInfo {
+    /// Filesystem device
+    dev: u64,
+    /// Whether the directory itself exists
+    exists: bool,
+}
+
+/// Accumulates per-filesystem requirements for a copy
+#[derive(Debug, Default)]
+pub struct CapacityPlanner {
+    incremental: bool,
+    filesystems: HashMap<u64, FsRequirement>,
+    dirs: HashMap<PathBuf, DirInfo>,
+}
+
+impl CapacityPlanner {
+    /// Create a planner; with `incremental` unchanged files cost nothing
+    pub fn new(incremental: bool) -> Self {
+        Self {
+            incremental,
+            ..Default::default()
+        }
+    }
+
+    /// Add every entry of a scan copied to `dest_root`
+    pub fn add_scan(&mut self, scan: &ScanResult, dest_root: &Path) -> Result<()> {
+        for dir in &scan.directories {
+            self.add_inode(&dest_root.join(&dir.relative_path), true)?;
+        }
+        for entry in &scan.files {
+            let dest = dest_root.join(&entry.relative_path);
+            if entry.is_symlink {
+                self.add_inode(&dest, false)?;
+            } else {
+                self.add_file(entry, &dest)?;
+            }
+        }
+        for special in &scan.specials {
+            self.add_inode(&dest_root.join(&special.relative_path), false)?;
+        }
+        Ok(())
+    }
+
+    /// Add a regular file written to `dest`
+    pub fn add_file(&mut self, entry: &FileEntry, dest: &Path) -> Result<()> {
+        let (dev, existing) = self.lookup(dest)?;
+        let requirement = self.filesystems.get_mut(&dev).expect("filesystem registered by lookup");
+
+        match existing {
+            Some(meta) if meta.is_file() => {
+                let modified = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+                if self.incremental && meta.len() == entry.size && modified >= entry.modified {
+                    return Ok(());
+                }
+                requirement.bytes_needed += requirement.stats.allocated(entry.size);
+                requirement.bytes_freed += allocated_bytes(&meta);
+            }
+            Some(_) => {
+                requirement.bytes_needed += requirement.stats.allocated(entry.size);
+            }
+            None => {
+                requirement.bytes_needed += requirement.stats.allocated(entry.size);
+                requirement.inodes_needed += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add an entry that needs an inode but no data blocks
+    ///
+    /// Directories also get one block for their entries.
+    pub fn add_inode(&mut self, dest: &Path, is_dir: bool) -> Result<()> {
+        let (dev, existing) = self.lookup(dest)?;
+        if existing.is_some() {
+            return Ok(());
+        }
+        let requirement = self.filesystems.get_mut(&dev).expect("filesystem registered by lookup");
+        requirement.inodes_needed += 1;
+        if is_dir {
+            requirement.bytes_needed += requirement.stats.block_size;
+        }
+        Ok(())
+    }
+
+    /// Requirements per destination filesystem
+    pub fn finish(self) -> Vec<FsRequirement> {
+        let mut filesystems: Vec<_> = self.filesystems.into_values().collect();
+        filesystems.sort_by(|a, b| a.path.cmp(&b.path));
+        filesystems
+    }
+
+    /// Filesystem of `dest` and its current metadata, if it exists
+    fn lookup(&mut self, dest: &Path) -> Result<(u64, Option<std::fs::Metadata>)> {
+        let parent = dest.parent().unwrap_or(Path::new(""));
+        let info = self.dir_info(parent)?;
+
+        // Nothing exists below a directory that doesn't
+        let existing = if info.exists {
+            std::fs::symlink_metadata(dest).ok()
+        } else {
+            None
+        };
+
+        // A mount point below the destination root is a different filesystem
+        let dev = match &existing {
+            Some(meta) if meta.is_dir() => self.register(dest, meta)?,
+            _ => info.dev,
+        };
+        Ok((dev, existing))
+    }
+
+    /// Device and existence of a directory, cached
+    fn dir_info(&mut self, dir: &Path) -> Result<DirInfo> {
+        if let Some(info) = self.dirs.get(dir) {
+            return Ok(*info);
+        }
+
+        let info = match std::fs::metadata(dir) {
+            Ok(meta) => DirInfo {
+                dev: self.register(dir, &meta)?,
+                exists: true,
+            },
+            Err(_) => match dir.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => DirInfo {
+                    exists: false,
+                    ..self.dir_info(parent)?
+                },
+                _ => {
+                    let cwd = std::env::current_dir().with_path(dir)?;
+                    let meta = std::fs::metadata(&cwd).with_path(&cwd)?;
+                    DirInfo {
+                        dev: self.register(&cwd, &meta)?,
+                        exists: false,
+                    }
+                }
+            },
+        };
+
+        self.dirs.insert(dir.to_path_buf(), info);
+        Ok(info)
+    }
+
+    /// Record the filesystem of an existing path, querying it once
+    fn register(&mut self, path: &Path, meta: &std::fs::Metadata) -> Result<u64> {
+        let dev = device_id(meta);
+        if let Entry::Vacant(slot) = self.filesystems.entry(dev) {
+            slot.insert(FsRequirement {
+                path: path.to_path_buf(),
+                stats: FsStats::for_path(path)?,
+                bytes_needed: 0,
+                bytes_freed: 0,
+                inodes_needed: 0,
+            });
+        }
+        Ok(dev)
+    }
+}
+
+/// Whether the current process may use root's reserved blocks and inodes
+#[cfg(unix)]
+pub fn is_privileged() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Whether the current process may use root's reserved blocks and inodes
+#[cfg(not(unix))]
+pub fn is_privileged() -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn device_id(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.dev()
+}
+
+#[cfg(not(unix))]
+fn device_id(_meta: &std::fs::Metadata) -> u64 {
+    0
+}
+
+#[cfg(unix)]
+fn allocated_bytes(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_bytes(meta: &std::fs::Metadata) -> u64 {
+    meta.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{ScanConfig, Scanner};
+    use tempfile::TempDir;
+
+    fn requirement(bytes: u64, inodes: u64, stats: FsStats) -> FsRequirement {
+        FsRequirement {
+            path: PathBuf::from("/dest"),
+            stats,
+            bytes_needed: bytes,
+            bytes_freed: 0,
+            inodes_needed: inodes,
+        }
+    }
+
+    #[test]
+    fn test_plan_counts_blocks_and_inodes() {
+        let src = TempDir::new().unwrap();
+        for i in 0..10 {
+            std::fs::write(src.path().join(format!("small_{}", i)), b"x").unwrap();
+        }
+        std::fs::create_dir(src.path().join("sub")).unwrap();
+        std::fs::write(src.path().join("sub/kept"), vec![0u8; 100]).unwrap();
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(src.path()).unwrap();
+
+        let dst = TempDir::new().unwrap();
+        let mut planner = CapacityPlanner::new(false);
+        planner.add_scan(&scan, dst.path()).unwrap();
+        let plan = planner.finish();
+        assert_eq!(plan.len(), 1);
+        let block = plan[0].stats.block_size;
+        // Every 1-byte file takes a whole block
+        assert_eq!(plan[0].bytes_needed, 11 * block + block);
+        assert_eq!(plan[0].inodes_needed, 12);
+
+        // An incremental re-run over an up-to-date copy needs nothing new
+        std::fs::create_dir(dst.path().join("sub")).unwrap();
+        for entry in &scan.files {
+            let dest = dst.path().join(&entry.relative_path);
+            std::fs::copy(&entry.path, &dest).unwrap();
+            filetime::set_file_mtime(&dest, filetime::FileTime::from_system_time(entry.modified))
+                .unwrap();
+        }
+        let mut planner = CapacityPlanner::new(true);
+        planner.add_scan(&scan, dst.path()).unwrap();
+        let plan = planner.finish();
+        assert_eq!((plan[0].net_bytes(), plan[0].inodes_needed), (0, 0));
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let stats = FsStats {
+            block_size: 4096,
+            free_blocks: 100,
+            avail_blocks: 90,
+            total_inodes: 1000,
+            free_inodes: 50,
+            avail_inodes: 40,
+        };
+
+        assert!(requirement(80 * 4096, 10, stats).check(false).unwrap().is_empty());
+        assert!(matches!(
+            requirement(95 * 4096, 10, stats).check(false),
+            Err(SmartCopyError::InsufficientSpace { .. })
+        ));
+        assert_eq!(requirement(95 * 4096, 10, stats).check(true).unwrap().len(), 1);
+        assert!(matches!(
+            requirement(4096, 45, stats).check(false),
+            Err(SmartCopyError::InsufficientInodes { required: 45, available: 40, .. })
+        ));
+        assert_eq!(requirement(4096, 45, stats).check(true).unwrap().len(), 1);
+
+        // No inode limit
+        let btrfs = FsStats { total_inodes: 0, free_inodes: 0, avail_inodes: 0, ..stats };
+        assert!(requirement(4096, 1_000_000, btrfs).check(false).unwrap().is_empty());
+    }
+}
@@ -11,7 +11,13 @@ pub mod compress;
 pub mod sparse;
 pub mod scheduler;
 pub mod acl;
+pub mod posix_acl;
 pub mod patricia;
+pub mod xattrs;
+pub mod symlinks;
+pub mod names;
+pub mod path_encoding;
+pub mod capacity;
 
 pub use scanner::*;
 pub use operations::*;
@@ -21,3 +27,9 @@ pub use compress::{Lz4Compressor, CompressionStats};
 pub use sparse::{SparseCopier, SparseInfo, SparseCopyResult, is_sparse};
 pub use scheduler::{BandwidthSchedule, ScheduleRule, ScheduledLimiter, ScheduleStatus};
 pub use acl::{WindowsAcl, SecurityInfo, Acl, AclEntry, AccessMask};
+pub use posix_acl::{PosixAcl, PosixAclEntry, PosixAclOps, PosixAclTag, PosixSecurityInfo};
+pub use path_encoding::{escape_path, path_from_bytes, path_to_bytes, unescape_path};
+pub use capacity::{CapacityPlanner, FsRequirement, FsStats};
+pub use names::{NameCollision, NamePlan, NameRules};
+pub use symlinks::{SymlinkAction, SymlinkPolicy};
+pub use xattrs::{copy_xattrs, read_xattrs, write_xattrs, XattrFilter, XattrList};
@@ -0,0 +1,299 @@
+//! Destination name collision preflight
+//!
+//! Case-insensitive targets (SMB shares, macOS, Windows) and filesystems
+//! that normalize Unicode treat `Makefile`/`makefile` or NFC/NFD spellings
+//! of `café` as the same name, so one silently overwrites the other. The
+//! preflight maps every scanned path to its destination name, optionally
+//! converting to NFC or NFD, and reports names that would collide.
+
+use crate::config::{CollisionPolicy, NormalizationForm};
+use crate::fs::{DetectedStorageType, ScanResult};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// How the destination compares and spells names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NameRules {
+    /// Names differing only in case are the same file
+    pub case_insensitive: bool,
+    /// Names differing only in Unicode normalization are the same file
+    pub normalization_insensitive: bool,
+    /// Convert names to this form when copying
+    pub normalize: Option<NormalizationForm>,
+}
+
+impl NameRules {
+    /// Rules for a destination of the given storage type on this platform
+    pub fn for_storage(storage: DetectedStorageType) -> Self {
+        Self {
+            case_insensitive: storage == DetectedStorageType::SMB
+                || cfg!(any(target_os = "macos", windows)),
+            normalization_insensitive: cfg!(target_os = "macos"),
+            normalize: None,
+        }
+    }
+
+    /// Whether distinct source names can never collide
+    pub fn is_identity(&self) -> bool {
+        !self.case_insensitive && !self.normalization_insensitive && self.normalize.is_none()
+    }
+
+    /// Destination spelling of a name
+    ///
+    /// Names that are not valid UTF-8 are never converted.
+    pub fn convert(&self, name: &OsStr) -> OsString {
+        match (self.normalize, name.to_str()) {
+            (Some(NormalizationForm::Nfc), Some(s)) => s.nfc().collect::<String>().into(),
+            (Some(NormalizationForm::Nfd), Some(s)) => s.nfd().collect::<String>().into(),
+            _ => name.to_os_string(),
+        }
+    }
+
+    /// Key under which the destination considers two names equal
+    fn key(&self, name: &OsStr) -> OsString {
+        let Some(s) = name.to_str() else {
+            return name.to_os_string();
+        };
+        let folded = if self.case_insensitive {
+            s.to_lowercase()
+        } else {
+            s.to_string()
+        };
+        if self.normalization_insensitive {
+            folded.nfd().collect::<String>().into()
+        } else {
+            folded.into()
+        }
+    }
+}
+
+/// A source path whose destination name is already taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameCollision {
+    /// Source relative path that lost the name
+    pub path: PathBuf,
+    /// Destination relative path it collides with
+    pub other: PathBuf,
+    /// New destination path, when renamed
+    pub renamed_to: Option<PathBuf>,
+}
+
+/// Destination path for every scanned entry
+#[derive(Debug, Clone, Default)]
+pub struct NamePlan {
+    /// Collisions found, in scan order
+    pub collisions: Vec<NameCollision>,
+    /// Source relative path to destination relative path (`None` = skipped)
+    destinations: HashMap<PathBuf, Option<PathBuf>>,
+}
+
+impl NamePlan {
+    /// Build the plan for a scan
+    ///
+    /// Entries are visited parents first in byte order, so the first
+    /// spelling of a name keeps it and later ones are renamed or skipped.
+    /// With [`CollisionPolicy::Fail`] collisions are only recorded.
+    pub fn build(scan: &ScanResult, rules: &NameRules, policy: CollisionPolicy) -> Self {
+        let mut plan = Self::default();
+
+        let mut paths: Vec<&Path> = scan
+            .directories
+            .iter()
+            .chain(&scan.files)
+            .chain(&scan.specials)
+            .map(|e| e.relative_path.as_path())
+            .collect();
+        paths.sort_by(|a, b| {
+            a.components()
+                .count()
+                .cmp(&b.components().count())
+                .then_with(|| a.as_os_str().cmp(b.as_os_str()))
+        });
+
+        // (destination parent, key) -> destination path holding the name
+        let mut taken: HashMap<(PathBuf, OsString), PathBuf> = HashMap::new();
+
+        for path in paths {
+            let Some(name) = path.file_name() else {
+                plan.destinations.insert(path.to_path_buf(), Some(path.to_path_buf()));
+                continue;
+            };
+
+            let parent = path.parent().unwrap_or(Path::new(""));
+            let dest_parent = match plan.destinations.get(parent) {
+                Some(Some(dest)) => dest.clone(),
+                Some(None) => {
+                    plan.destinations.insert(path.to_path_buf(), None);
+                    continue;
+                }
+                None => parent.to_path_buf(),
+            };
+
+            let converted = rules.convert(name);
+            let key = (dest_parent.clone(), rules.key(&converted));
+            let Some(other) = taken.get(&key).cloned() else {
+                let dest = dest_parent.join(&converted);
+                taken.insert(key, dest.clone());
+                plan.destinations.insert(path.to_path_buf(), Some(dest));
+                continue;
+            };
+
+            let renamed_to = match policy {
+                CollisionPolicy::Rename => {
+                    let dest = (2..)
+                        .map(|n| numbered_name(&converted, n))
+                        .find_map(|candidate| {
+                            let key = (dest_parent.clone(), rules.key(&candidate));
+                            if taken.contains_key(&key) {
+                                return None;
+                            }
+                            let dest = dest_parent.join(&candidate);
+                            taken.insert(key, dest.clone());
+                            Some(dest)
+                        })
+                        .expect("unbounded candidate range");
+                    Some(dest)
+                }
+                CollisionPolicy::Skip | CollisionPolicy::Fail => None,
+            };
+
+            plan.destinations.insert(path.to_path_buf(), renamed_to.clone());
+            plan.collisions.push(NameCollision {
+                path: path.to_path_buf(),
+                other,
+                renamed_to,
+            });
+        }
+
+        plan
+    }
+
+    /// Rewrite destination paths in a scan and drop skipped entries
+    pub fn apply(&self, scan: &mut ScanResult) {
+        let destinations = &self.destinations;
+        let remap = |entries: &mut Vec<crate::fs::FileEntry>| {
+            entries.retain_mut(|entry| match destinations.get(&entry.relative_path) {
+                Some(Some(dest)) => {
+                    entry.relative_path = dest.clone();
+                    true
+                }
+                Some(None) => false,
+                None => true,
+            });
+        };
+
+        remap(&mut scan.files);
+        remap(&mut scan.directories);
+        remap(&mut scan.specials);
+
+        scan.file_count = scan.files.len();
+        scan.dir_count = scan.directories.len();
+        scan.total_size = scan.files.iter().map(|f| f.size).sum();
+    }
+}
+
+/// `name~N.ext`, keeping the extension so the file type stays recognisable
+fn numbered_name(name: &OsStr, n: usize) -> OsString {
+    let path = Path::new(name);
+    let mut numbered = path
+        .file_stem()
+        .map(OsStr::to_os_string)
+        .unwrap_or_else(|| name.to_os_string());
+    numbered.push(format!("~{}", n));
+    if let Some(ext) = path.extension() {
+        numbered.push(".");
+        numbered.push(ext);
+    }
+    numbered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{ScanConfig, Scanner};
+    use tempfile::TempDir;
+
+    fn scan_tree(names: &[&str]) -> (TempDir, ScanResult) {
+        let dir = TempDir::new().unwrap();
+        for name in names {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, name.as_bytes()).unwrap();
+        }
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(dir.path()).unwrap();
+        (dir, scan)
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let (_dir, scan) = scan_tree(&["Makefile", "makefile", "src/a.c", "SRC/b.c"]);
+        let rules = NameRules {
+            case_insensitive: true,
+            ..Default::default()
+        };
+
+        let plan = NamePlan::build(&scan, &rules, CollisionPolicy::Fail);
+        let mut collided: Vec<_> = plan.collisions.iter().map(|c| c.path.clone()).collect();
+        collided.sort();
+        assert_eq!(collided, vec![PathBuf::from("makefile"), PathBuf::from("src")]);
+
+        // Case-sensitive destinations see no collisions
+        let plan = NamePlan::build(&scan, &NameRules::default(), CollisionPolicy::Fail);
+        assert!(plan.collisions.is_empty());
+    }
+
+    #[test]
+    fn test_rename_and_skip() {
+        let (_dir, scan) = scan_tree(&["Notes.txt", "notes.txt", "DIR/x", "dir/y"]);
+        let rules = NameRules {
+            case_insensitive: true,
+            ..Default::default()
+        };
+
+        let mut renamed = scan.clone();
+        NamePlan::build(&scan, &rules, CollisionPolicy::Rename).apply(&mut renamed);
+        let mut paths: Vec<_> = renamed.files.iter().map(|f| f.relative_path.clone()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("DIR/x"),
+                PathBuf::from("Notes.txt"),
+                PathBuf::from("dir~2/y"),
+                PathBuf::from("notes~2.txt"),
+            ]
+        );
+
+        let mut skipped = scan.clone();
+        NamePlan::build(&scan, &rules, CollisionPolicy::Skip).apply(&mut skipped);
+        assert_eq!(skipped.file_count, 2);
+        assert!(skipped.files.iter().all(|f| !f.relative_path.starts_with("dir")));
+    }
+
+    #[test]
+    fn test_normalization() {
+        let nfc = "caf\u{e9}";
+        let nfd = "cafe\u{301}";
+        let (_dir, scan) = scan_tree(&[nfc, nfd]);
+
+        let rules = NameRules {
+            normalization_insensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(NamePlan::build(&scan, &rules, CollisionPolicy::Fail).collisions.len(), 1);
+
+        // Converting to one form also makes them collide
+        let rules = NameRules {
+            normalize: Some(NormalizationForm::Nfc),
+            ..Default::default()
+        };
+        let mut converted = scan.clone();
+        let plan = NamePlan::build(&scan, &rules, CollisionPolicy::Rename);
+        assert_eq!(plan.collisions.len(), 1);
+        plan.apply(&mut converted);
+        assert!(converted.files.iter().any(|f| f.relative_path == Path::new(nfc)));
+        assert!(converted.files.iter().all(|f| f.relative_path != Path::new(nfd)));
+    }
+}
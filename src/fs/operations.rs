@@ -4,10 +4,10 @@
 //! zero-copy techniques, and platform-specific optimizations.
 
 use crate::error::{IoResultExt, Result, SmartCopyError};
-use crate::fs::FileEntry;
+use crate::fs::{FileEntry, XattrFilter};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write, BufReader, BufWriter};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 
 
@@ -22,6 +22,8 @@ pub struct CopyStats {
     pub throughput: f64,
     /// Method used for copy
     pub method: CopyMethod,
+    /// Non-fatal problems while preserving attributes
+    pub warnings: Vec<String>,
 }
 
 impl CopyStats {
@@ -95,6 +97,10 @@ pub struct CopyOptions {
     pub network_streams: usize,
     /// Network buffer size (larger for high-latency networks)
     pub network_buffer_size: usize,
+    /// Extended attributes to preserve (None = don't copy xattrs)
+    pub xattr_filter: Option<XattrFilter>,
+    /// Copy directory access ACLs in [`apply_directory_metadata`]
+    pub preserve_acls: bool,
 }
 
 impl Default for CopyOptions {
@@ -112,6 +118,8 @@ impl Default for CopyOptions {
             network_optimized: true,
             network_streams: 4, // SMB multichannel typically uses 4-8 channels
             network_buffer_size: 4 * 1024 * 1024, // 4MB for network transfers
+            xattr_filter: None,
+            preserve_acls: false,
         }
     }
 }
@@ -132,6 +140,8 @@ impl CopyOptions {
             network_optimized: true,
             network_streams: 8, // SMB3 multichannel can use up to 8 channels
             network_buffer_size: 4 * 1024 * 1024, // 4MB optimal for SMB
+            xattr_filter: None,
+            preserve_acls: false,
         }
     }
 
@@ -150,6 +160,8 @@ impl CopyOptions {
             network_optimized: true,
             network_streams: 4, // NFS pNFS can use parallel streams
             network_buffer_size: 1024 * 1024,
+            xattr_filter: None,
+            preserve_acls: false,
         }
     }
 
@@ -168,6 +180,8 @@ impl CopyOptions {
             network_optimized: false,
             network_streams: 1,
             network_buffer_size: 1024 * 1024,
+            xattr_filter: None,
+            preserve_acls: false,
         }
     }
 }
@@ -270,6 +284,11 @@ impl FileCopier {
         Self { options }
     }
 
+    /// Options this copier was created with
+    pub fn options(&self) -> &CopyOptions {
+        &self.options
+    }
+
     /// Create with default options
     pub fn default_copier() -> Self {
         Self::new(CopyOptions::default())
@@ -322,13 +341,7 @@ impl FileCopier {
         };
 
         // Preserve attributes
-        if self.options.preserve_permissions {
-            self.copy_permissions(source, dest)?;
-        }
-
-        if self.options.preserve_mtime {
-            self.copy_mtime(source, dest)?;
-        }
+        let warnings = self.apply_attributes(source, dest)?;
 
         // Sync if requested
         if self.options.sync {
@@ -342,6 +355,7 @@ impl FileCopier {
             duration,
             throughput: 0.0,
             method,
+            warnings,
         };
         stats.calculate_throughput();
 
@@ -487,30 +501,15 @@ impl FileCopier {
         Ok(())
     }
 
-    /// Copy extended attributes (xattr) - Unix only
-    #[cfg(unix)]
+    /// Copy extended attributes (xattr) using the configured filter
+    ///
+    /// Falls back to copying every attribute when no filter is set.
+    /// Failures are logged, never returned.
     pub fn copy_xattr(&self, source: &Path, dest: &Path) -> Result<()> {
-        use xattr::FileExt;
-
-        let src_file = File::open(source).with_path(source)?;
-        let dst_file = File::open(dest).with_path(dest)?;
-
-        // List all extended attributes from source
-        if let Ok(xattrs) = src_file.list_xattr() {
-            for attr_name in xattrs {
-                if let Ok(Some(value)) = src_file.get_xattr(&attr_name) {
-                    // Set the attribute on destination
-                    let _ = dst_file.set_xattr(&attr_name, &value);
-                }
-            }
+        let filter = self.options.xattr_filter.clone().unwrap_or_default();
+        for warning in crate::fs::copy_xattrs(source, dest, &filter) {
+            tracing::warn!("{}: {}", dest.display(), warning);
         }
-
-        Ok(())
-    }
-
-    #[cfg(not(unix))]
-    pub fn copy_xattr(&self, _source: &Path, _dest: &Path) -> Result<()> {
-        // Extended attributes not supported on this platform
         Ok(())
     }
 
@@ -518,12 +517,9 @@ impl FileCopier {
     /// This prevents cache pollution and can improve performance for sequential large file copies
     #[cfg(target_os = "linux")]
     pub fn copy_direct_io(&self, source: &Path, dest: &Path, size: u64) -> Result<u64> {
+        use std::os::unix::fs::OpenOptionsExt;
         use std::os::unix::io::AsRawFd;
 
-        // O_DIRECT requires aligned buffers and aligned file offsets
-        const ALIGNMENT: usize = 4096; // 4KB alignment for most filesystems
-        const BUFFER_SIZE: usize = 4 * 1024 * 1024; // 4MB buffer
-
         // Open source with O_DIRECT
         let src_file = OpenOptions::new()
             .read(true)
@@ -548,39 +544,8 @@ impl FileCopier {
         let src_fd = src_file.as_raw_fd();
         let dst_fd = dst_file.as_raw_fd();
 
-        // RAII wrapper for aligned buffer to prevent memory leaks on panic
-        struct AlignedBuffer {
-            ptr: *mut u8,
-            layout: std::alloc::Layout,
-        }
-
-        impl Drop for AlignedBuffer {
-            fn drop(&mut self) {
-                unsafe { std::alloc::dealloc(self.ptr, self.layout); }
-            }
-        }
-
-        // Allocate aligned buffer
-        let layout = std::alloc::Layout::from_size_align(BUFFER_SIZE, ALIGNMENT)
-            .map_err(|_| SmartCopyError::IoError {
-                path: source.to_path_buf(),
-                message: "Failed to create aligned buffer layout".to_string(),
-            })?;
-
-        let aligned_buf = unsafe {
-            let ptr = std::alloc::alloc(layout);
-            if ptr.is_null() {
-                return Err(SmartCopyError::IoError {
-                    path: source.to_path_buf(),
-                    message: "Failed to allocate aligned buffer".to_string(),
-                });
-            }
-            AlignedBuffer { ptr, layout }
-        };
-
-        let buffer = unsafe {
-            std::slice::from_raw_parts_mut(aligned_buf.ptr, BUFFER_SIZE)
-        };
+        let mut aligned_buf = AlignedBuffer::new(DIRECT_IO_BUFFER_SIZE, DIRECT_IO_ALIGNMENT, source)?;
+        let buffer = aligned_buf.as_mut_slice();
 
         let mut total_copied = 0u64;
         let mut offset = 0i64;
@@ -588,7 +553,7 @@ impl FileCopier {
         loop {
             // Read with pread for thread safety
             let bytes_read = unsafe {
-                libc::pread(src_fd, buffer.as_mut_ptr() as *mut libc::c_void, BUFFER_SIZE, offset)
+                libc::pread(src_fd, buffer.as_mut_ptr() as *mut libc::c_void, DIRECT_IO_BUFFER_SIZE, offset)
             };
 
             if bytes_read < 0 {
@@ -615,7 +580,7 @@ impl FileCopier {
                 return Err(SmartCopyError::io(dest, err));
             }
 
-            offset += bytes_read;
+            offset += bytes_read as i64;
             total_copied += bytes_read as u64;
         }
 
@@ -649,13 +614,40 @@ impl FileCopier {
         Ok(())
     }
 
+    /// Apply the attributes selected by the copy options
+    ///
+    /// Extended attributes go first, while the destination is still
+    /// writable, then permissions and times. Returns xattr warnings.
+    pub fn apply_attributes(&self, source: &Path, dest: &Path) -> Result<Vec<String>> {
+        let warnings = match &self.options.xattr_filter {
+            Some(filter) => crate::fs::copy_xattrs(source, dest, filter),
+            None => Vec::new(),
+        };
+
+        self.preserve_attributes(source, dest)?;
+
+        Ok(warnings)
+    }
+
     /// Preserve all attributes including xattr
     pub fn preserve_all_attributes(&self, source: &Path, dest: &Path) -> Result<()> {
-        self.preserve_attributes(source, dest)?;
         self.copy_xattr(source, dest)?;
+        self.preserve_attributes(source, dest)?;
         Ok(())
     }
 
+    /// Copy POSIX access and default ACLs
+    ///
+    /// Returns warnings for principals that could not be resolved on this host.
+    pub fn copy_acl(&self, source: &Path, dest: &Path) -> Result<Vec<String>> {
+        crate::fs::PosixAclOps::copy_acls(source, dest).with_path(dest)
+    }
+
+    /// Copy only the default ACL of a directory
+    pub fn copy_default_acl(&self, source: &Path, dest: &Path) -> Result<Vec<String>> {
+        crate::fs::PosixAclOps::copy_default_acl(source, dest).with_path(dest)
+    }
+
     /// Copy a file with streaming hash computation
     pub fn copy_with_hash<H: HashWriter>(
         &self,
@@ -699,15 +691,10 @@ impl FileCopier {
         }
 
         writer.flush().with_path(dest)?;
+        drop(writer);
 
         // Preserve attributes
-        if self.options.preserve_permissions {
-            self.copy_permissions(source, dest)?;
-        }
-
-        if self.options.preserve_mtime {
-            self.copy_mtime(source, dest)?;
-        }
+        let warnings = self.apply_attributes(source, dest)?;
 
         let duration = start.elapsed();
         let mut stats = CopyStats {
@@ -715,6 +702,7 @@ impl FileCopier {
             duration,
             throughput: 0.0,
             method: CopyMethod::Buffered,
+            warnings,
         };
         stats.calculate_throughput();
 
@@ -728,6 +716,125 @@ pub trait HashWriter {
     fn update(&mut self, data: &[u8]);
 }
 
+/// O_DIRECT requires aligned buffers and aligned file offsets
+#[cfg(target_os = "linux")]
+const DIRECT_IO_ALIGNMENT: usize = 4096; // 4KB alignment for most filesystems
+#[cfg(target_os = "linux")]
+const DIRECT_IO_BUFFER_SIZE: usize = 4 * 1024 * 1024; // 4MB buffer
+
+/// RAII wrapper for aligned buffer to prevent memory leaks on panic
+#[cfg(target_os = "linux")]
+struct AlignedBuffer {
+    ptr: *mut u8,
+    layout: std::alloc::Layout,
+}
+
+#[cfg(target_os = "linux")]
+impl AlignedBuffer {
+    /// Allocate `size` bytes aligned to `align` (`path` is for errors)
+    fn new(size: usize, align: usize, path: &Path) -> Result<Self> {
+        let layout = std::alloc::Layout::from_size_align(size, align)
+            .map_err(|_| SmartCopyError::IoError {
+                path: path.to_path_buf(),
+                message: "Failed to create aligned buffer layout".to_string(),
+            })?;
+
+        let ptr = unsafe { std::alloc::alloc(layout) };
+        if ptr.is_null() {
+            return Err(SmartCopyError::IoError {
+                path: path.to_path_buf(),
+                message: "Failed to allocate aligned buffer".to_string(),
+            });
+        }
+        Ok(Self { ptr, layout })
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.layout.size()) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr, self.layout); }
+    }
+}
+
+/// Read a file from storage rather than from the page cache
+///
+/// Uses `O_DIRECT` where the filesystem supports it. Otherwise the file is
+/// flushed and its cached pages dropped with `posix_fadvise(DONTNEED)`
+/// before an ordinary read. Calls `consume` with each chunk and returns the
+/// bytes read.
+#[cfg(target_os = "linux")]
+pub fn read_uncached(path: &Path, mut consume: impl FnMut(&[u8])) -> Result<u64> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let direct = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path);
+
+    let Ok(file) = direct else {
+        // Filesystems such as tmpfs reject O_DIRECT
+        let mut file = File::open(path).with_path(path)?;
+        file.sync_all().with_path(path)?;
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+        }
+        return read_chunks(&mut file, path, consume);
+    };
+
+    let mut aligned_buf = AlignedBuffer::new(DIRECT_IO_BUFFER_SIZE, DIRECT_IO_ALIGNMENT, path)?;
+    let buffer = aligned_buf.as_mut_slice();
+    let fd = file.as_raw_fd();
+    let mut offset = 0u64;
+
+    loop {
+        let read = unsafe {
+            libc::pread(fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), offset as i64)
+        };
+        if read < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(SmartCopyError::io(path, err));
+        }
+        if read == 0 {
+            return Ok(offset);
+        }
+        consume(&buffer[..read as usize]);
+        offset += read as u64;
+    }
+}
+
+/// Read a file from storage rather than from the page cache
+///
+/// Without `O_DIRECT` or `posix_fadvise` the file is only flushed before
+/// being read, so the data may still come from the cache.
+#[cfg(not(target_os = "linux"))]
+pub fn read_uncached(path: &Path, consume: impl FnMut(&[u8])) -> Result<u64> {
+    let mut file = File::open(path).with_path(path)?;
+    file.sync_all().with_path(path)?;
+    read_chunks(&mut file, path, consume)
+}
+
+fn read_chunks(file: &mut File, path: &Path, mut consume: impl FnMut(&[u8])) -> Result<u64> {
+    let mut buffer = vec![0u8; 4 * 1024 * 1024];
+    let mut total = 0u64;
+    loop {
+        let read = file.read(&mut buffer).with_path(path)?;
+        if read == 0 {
+            return Ok(total);
+        }
+        consume(&buffer[..read]);
+        total += read as u64;
+    }
+}
+
 /// Copy a file entry to a new location
 pub fn copy_entry(entry: &FileEntry, dest_root: &Path, options: &CopyOptions) -> Result<CopyStats> {
     let dest_path = dest_root.join(&entry.relative_path);
@@ -750,10 +857,145 @@ pub fn copy_entry(entry: &FileEntry, dest_root: &Path, options: &CopyOptions) ->
         return Ok(CopyStats::default());
     }
 
+    if entry.is_special() {
+        copy_special(entry, &dest_path, options)?;
+        return Ok(CopyStats::default());
+    }
+
     let copier = FileCopier::new(options.clone());
     copier.copy(&entry.path, &dest_path)
 }
 
+/// Recreate a FIFO, socket or device node and apply its metadata
+///
+/// FIFOs are made with `mkfifo`, sockets and devices with `mknod`. Device
+/// nodes need privileges; without them the error is a permission error
+/// (see [`SmartCopyError::is_permission_error`]). An existing non-directory
+/// entry at the destination is replaced.
+#[cfg(unix)]
+pub fn copy_special(entry: &FileEntry, dest: &Path, options: &CopyOptions) -> Result<()> {
+    use crate::fs::SpecialKind;
+    use std::os::unix::ffi::OsStrExt;
+
+    let kind = entry.special.ok_or_else(|| SmartCopyError::UnsupportedFileType {
+        path: entry.path.clone(),
+        file_type: "regular file".to_string(),
+    })?;
+
+    let c_path = std::ffi::CString::new(dest.as_os_str().as_bytes())
+        .map_err(|_| SmartCopyError::InvalidPath(dest.display().to_string()))?;
+
+    match std::fs::symlink_metadata(dest) {
+        Ok(meta) if !meta.is_dir() => std::fs::remove_file(dest).with_path(dest)?,
+        _ => {}
+    }
+
+    let perm = (entry.permissions & 0o7777) as libc::mode_t;
+    let rc = unsafe {
+        match kind {
+            SpecialKind::Fifo => libc::mkfifo(c_path.as_ptr(), perm),
+            SpecialKind::Socket => libc::mknod(c_path.as_ptr(), libc::S_IFSOCK | perm, 0),
+            SpecialKind::CharDevice => {
+                libc::mknod(c_path.as_ptr(), libc::S_IFCHR | perm, entry.rdev as libc::dev_t)
+            }
+            SpecialKind::BlockDevice => {
+                libc::mknod(c_path.as_ptr(), libc::S_IFBLK | perm, entry.rdev as libc::dev_t)
+            }
+        }
+    };
+    if rc != 0 {
+        return Err(SmartCopyError::io(dest, std::io::Error::last_os_error()));
+    }
+
+    // mkfifo/mknod honour the umask, so set the mode explicitly
+    if options.preserve_permissions {
+        copy_ownership(&entry.path, dest).with_path(dest)?;
+        set_mode(dest, entry.permissions).with_path(dest)?;
+    }
+    if options.preserve_mtime {
+        let mtime = filetime::FileTime::from_system_time(entry.modified);
+        filetime::set_file_mtime(dest, mtime).with_path(dest)?;
+    }
+
+    Ok(())
+}
+
+/// Recreate a special file (unsupported platform)
+#[cfg(not(unix))]
+pub fn copy_special(entry: &FileEntry, _dest: &Path, _options: &CopyOptions) -> Result<()> {
+    Err(SmartCopyError::UnsupportedFileType {
+        path: entry.path.clone(),
+        file_type: entry.special.map(|k| k.as_str()).unwrap_or("unknown").to_string(),
+    })
+}
+
+/// Hard-link `dest` to the already copied file `target`
+///
+/// An existing non-directory entry at `dest` is replaced unless it is
+/// already a link to `target`.
+pub fn hard_link_file(target: &Path, dest: &Path) -> Result<()> {
+    if let Ok(meta) = std::fs::symlink_metadata(dest) {
+        if meta.is_dir() {
+            return Err(SmartCopyError::io(dest, std::io::ErrorKind::AlreadyExists.into()));
+        }
+        if is_same_file(target, dest) {
+            return Ok(());
+        }
+        std::fs::remove_file(dest).with_path(dest)?;
+    }
+    std::fs::hard_link(target, dest).with_path(dest)
+}
+
+/// Clone `source` into `dest` sharing its data blocks (`FICLONE`)
+///
+/// Only filesystems with reflink support (Btrfs, XFS, bcachefs) accept
+/// this; elsewhere the error is `EOPNOTSUPP`, `EXDEV` or `EINVAL` and
+/// `dest` is left absent.
+#[cfg(target_os = "linux")]
+pub fn reflink_file(source: &Path, dest: &Path) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if let Ok(meta) = std::fs::symlink_metadata(dest) {
+        if !meta.is_dir() {
+            std::fs::remove_file(dest).with_path(dest)?;
+        }
+    }
+
+    let src_file = File::open(source).with_path(source)?;
+    let dst_file = File::create(dest).with_path(dest)?;
+
+    let rc = unsafe { libc::ioctl(dst_file.as_raw_fd(), libc::FICLONE, src_file.as_raw_fd()) };
+    if rc != 0 {
+        let err = std::io::Error::last_os_error();
+        drop(dst_file);
+        let _ = std::fs::remove_file(dest);
+        return Err(SmartCopyError::io(dest, err));
+    }
+
+    Ok(())
+}
+
+/// Clone a file (unsupported platform)
+#[cfg(not(target_os = "linux"))]
+pub fn reflink_file(_source: &Path, dest: &Path) -> Result<()> {
+    Err(SmartCopyError::io(dest, std::io::ErrorKind::Unsupported.into()))
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
 /// Create all directories from a list of entries
 pub fn create_directories(entries: &[FileEntry], dest_root: &Path) -> Result<usize> {
     let mut created = 0;
@@ -767,12 +1009,135 @@ pub fn create_directories(entries: &[FileEntry], dest_root: &Path) -> Result<usi
         if !dest_path.exists() {
             std::fs::create_dir_all(&dest_path).with_path(&dest_path)?;
             created += 1;
+        } else {
+            // A previous run may have left it read-only
+            ensure_dir_writable(&dest_path)?;
         }
     }
 
     Ok(created)
 }
 
+/// Make an existing destination directory writable by its owner
+#[cfg(unix)]
+fn ensure_dir_writable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = std::fs::metadata(path).with_path(path)?;
+    let mode = metadata.permissions().mode();
+    if metadata.is_dir() && mode & 0o200 == 0 {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode | 0o700)).with_path(path)?;
+    }
+    Ok(())
+}
+
+/// Make an existing destination directory writable (no-op on this platform)
+#[cfg(not(unix))]
+fn ensure_dir_writable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Apply directory permissions, ownership, access ACLs and times once
+/// contents are written
+///
+/// Runs deepest-first so finishing a child never bumps its parent's mtime,
+/// and read-only source modes and ACLs are applied only after nothing else
+/// needs to be created below them. Failures are returned as per-directory
+/// warnings.
+pub fn apply_directory_metadata(
+    entries: &[FileEntry],
+    dest_root: &Path,
+    options: &CopyOptions,
+) -> Vec<(PathBuf, String)> {
+    let mut warnings = Vec::new();
+    if !options.preserve_permissions && !options.preserve_mtime && !options.preserve_acls {
+        return warnings;
+    }
+
+    let mut dirs: Vec<_> = entries.iter().filter(|e| e.is_dir).collect();
+    dirs.sort_by_key(|e| std::cmp::Reverse(e.relative_path.components().count()));
+
+    for entry in dirs {
+        let dest_path = dest_root.join(&entry.relative_path);
+        if !dest_path.is_dir() {
+            continue;
+        }
+
+        if options.preserve_permissions {
+            if let Err(e) = copy_ownership(&entry.path, &dest_path) {
+                warnings.push((entry.relative_path.clone(), format!("ownership not preserved: {}", e)));
+            }
+            if let Err(e) = set_mode(&dest_path, entry.permissions) {
+                warnings.push((entry.relative_path.clone(), format!("permissions not preserved: {}", e)));
+            }
+        }
+
+        // After the mode, which would otherwise rewrite the ACL mask
+        if options.preserve_acls {
+            match crate::fs::PosixAclOps::copy_access_acl(&entry.path, &dest_path) {
+                Ok(acl_warnings) => {
+                    warnings.extend(acl_warnings.into_iter().map(|w| (entry.relative_path.clone(), w)));
+                }
+                Err(e) => warnings.push((entry.relative_path.clone(), format!("ACLs not preserved: {}", e))),
+            }
+        }
+
+        if options.preserve_mtime {
+            let mtime = filetime::FileTime::from_system_time(entry.modified);
+            if let Err(e) = filetime::set_file_mtime(&dest_path, mtime) {
+                warnings.push((entry.relative_path.clone(), format!("mtime not preserved: {}", e)));
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Copy owner and group, silently skipping when not permitted
+///
+/// Unprivileged users can't give files away, so `EPERM` is expected and
+/// not worth a warning.
+#[cfg(unix)]
+fn copy_ownership(source: &Path, dest: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let src = std::fs::symlink_metadata(source)?;
+    let dst = std::fs::symlink_metadata(dest)?;
+    if src.uid() == dst.uid() && src.gid() == dst.gid() {
+        return Ok(());
+    }
+
+    match std::os::unix::fs::lchown(dest, Some(src.uid()), Some(src.gid())) {
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) => {
+            // Still try the group, which the owner may change to one of theirs
+            match std::os::unix::fs::lchown(dest, None, Some(src.gid())) {
+                Err(e) if e.raw_os_error() == Some(libc::EPERM) => Ok(()),
+                other => other,
+            }
+        }
+        other => other,
+    }
+}
+
+/// Copy owner and group (no-op on this platform)
+#[cfg(not(unix))]
+fn copy_ownership(_source: &Path, _dest: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    std::fs::set_permissions(path, permissions)
+}
+
 /// Verify that a file was copied correctly by size comparison
 pub fn verify_copy_size(source: &Path, dest: &Path) -> Result<bool> {
     let src_meta = std::fs::metadata(source).with_path(source)?;
@@ -878,6 +1243,20 @@ mod tests {
         assert!(verify_copy_size(&src, &dst).unwrap());
     }
 
+    #[test]
+    fn test_read_uncached() {
+        let dir = TempDir::new().unwrap();
+        // Not a multiple of the O_DIRECT alignment
+        let data: Vec<u8> = (0..5 * 1024 * 1024 + 123).map(|i| (i % 251) as u8).collect();
+        let path = dir.path().join("data.bin");
+        std::fs::write(&path, &data).unwrap();
+
+        let mut read = Vec::new();
+        let size = read_uncached(&path, |chunk| read.extend_from_slice(chunk)).unwrap();
+        assert_eq!(size, data.len() as u64);
+        assert!(read == data);
+    }
+
     #[test]
     fn test_copy_empty_file() {
         let src_dir = TempDir::new().unwrap();
@@ -0,0 +1,218 @@
+//! Lossless path encoding
+//!
+//! Unix paths are arbitrary bytes, but manifests, reports and hash keys
+//! are strings. `to_string_lossy` replaces invalid UTF-8 with U+FFFD, so
+//! two Latin-1 names can end up with the same key and neither can be
+//! found again. The escaped form used here is reversible: bytes that are
+//! not valid UTF-8 become `\xNN` and every other character is kept, so
+//! any UTF-8 name (Windows paths included) keeps the key it had before.
+//! The only backslash that needs escaping is one that is followed by
+//! text reading as `xNN`; it becomes `\x5c`.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// Raw bytes of a path
+///
+/// On Windows paths are UTF-16 and are converted lossily.
+pub fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Cow::Borrowed(path.as_os_str().as_bytes())
+    }
+
+    #[cfg(not(unix))]
+    {
+        match path.to_string_lossy() {
+            Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+            Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+        }
+    }
+}
+
+/// Build a path from raw bytes
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Reversible display form of a path (see module docs)
+pub fn escape_path(path: &Path) -> String {
+    let bytes = path_to_bytes(path);
+    let mut escaped = String::with_capacity(bytes.len());
+
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        for (index, c) in valid.char_indices() {
+            if c == '\\' && is_hex_escape(&valid.as_bytes()[index..]) {
+                escaped.push_str("\\x5c");
+            } else {
+                escaped.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+
+    escaped
+}
+
+/// Whether `text` starts with a `\xNN` escape
+fn is_hex_escape(text: &[u8]) -> bool {
+    matches!(text, [b'\\', b'x', hi, lo, ..] if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit())
+}
+
+/// Reverse [`escape_path`]
+///
+/// A backslash that does not start a `\xNN` escape is kept as is, so
+/// plain strings from older manifests decode to the same path.
+pub fn unescape_path(escaped: &str) -> PathBuf {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if is_hex_escape(rest) {
+            let hex = |d: u8| (d as char).to_digit(16).unwrap_or(0) as u8;
+            bytes.push(hex(rest[2]) << 4 | hex(rest[3]));
+            rest = &rest[4..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    path_from_bytes(&bytes)
+}
+
+/// Serde adapter storing a `PathBuf` as raw bytes
+///
+/// For length-prefixed formats such as bincode the encoding of a valid
+/// UTF-8 path is identical to serde's string form, so peers using the
+/// plain `PathBuf` encoding interoperate for ordinary names.
+pub mod raw_bytes {
+    use super::{path_from_bytes, path_to_bytes};
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+    use std::path::{Path, PathBuf};
+
+    /// Serialize a path as its raw bytes
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&path_to_bytes(path))
+    }
+
+    /// Deserialize a path from raw bytes (or a string, from older peers)
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        deserializer.deserialize_byte_buf(PathBytesVisitor)
+    }
+
+    struct PathBytesVisitor;
+
+    impl<'de> Visitor<'de> for PathBytesVisitor {
+        type Value = PathBuf;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("path bytes")
+        }
+
+        fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(path_from_bytes(v))
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+            Ok(PathBuf::from(v))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element::<u8>()? {
+                bytes.push(byte);
+            }
+            Ok(path_from_bytes(&bytes))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_unchanged() {
+        assert_eq!(escape_path(Path::new("data/café/run 1.dat")), "data/café/run 1.dat");
+        assert_eq!(escape_path(Path::new("a\\b")), "a\\b");
+        assert_eq!(unescape_path("a\\b"), Path::new("a\\b"));
+
+        // Windows paths keep the key they had before escaping existed
+        let windows = Path::new("C:\\Users\\x\\\\server\\share\\");
+        assert_eq!(escape_path(windows), windows.to_string_lossy());
+        assert_eq!(unescape_path(&escape_path(windows)), windows);
+    }
+
+    #[test]
+    fn test_backslash_before_hex_text() {
+        // A literal "\x41" must not decode to "A"
+        let path = Path::new("dir\\x41\\x4");
+        let escaped = escape_path(path);
+        assert_eq!(escaped, "dir\\x5cx41\\x4");
+        assert_eq!(unescape_path(&escaped), path);
+        assert_eq!(unescape_path("dir\\x41"), Path::new("dirA"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_invalid_utf8_roundtrip() {
+        // "caf\xe9" is Latin-1 for "café"
+        let latin1 = path_from_bytes(b"instrument/caf\xe9\\x.dat");
+        let escaped = escape_path(&latin1);
+        assert_eq!(escaped, "instrument/caf\\xe9\\x.dat");
+        assert_eq!(unescape_path(&escaped), latin1);
+
+        // A backslash right before an escaped byte stays literal
+        let before = path_from_bytes(b"a\\\xff");
+        assert_eq!(escape_path(&before), "a\\\\xff");
+        assert_eq!(unescape_path(&escape_path(&before)), before);
+
+        // Distinct names keep distinct keys
+        let other = path_from_bytes(b"instrument/caf\xe8\\x.dat");
+        assert_ne!(escape_path(&other), escaped);
+    }
+
+    #[test]
+    fn test_incomplete_escape_is_literal() {
+        assert_eq!(unescape_path("bad\\q"), Path::new("bad\\q"));
+        assert_eq!(unescape_path("bad\\x4"), Path::new("bad\\x4"));
+        assert_eq!(unescape_path("bad\\xzz"), Path::new("bad\\xzz"));
+        assert_eq!(unescape_path("bad\\x+f"), Path::new("bad\\x+f"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_raw_bytes_serde() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Wrapper {
+            #[serde(with = "raw_bytes")]
+            path: PathBuf,
+        }
+
+        let path = path_from_bytes(b"dir/\xff\xfe.bin");
+        let encoded = bincode::serialize(&Wrapper { path: path.clone() }).unwrap();
+        let decoded: Wrapper = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(decoded.path, path);
+
+        // Matches the plain PathBuf encoding for UTF-8 names
+        let plain = bincode::serialize(&PathBuf::from("dir/file")).unwrap();
+        let raw = bincode::serialize(&Wrapper { path: PathBuf::from("dir/file") }).unwrap();
+        assert_eq!(plain, raw);
+    }
+}
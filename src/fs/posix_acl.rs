@@ -0,0 +1,617 @@
+//! POSIX ACL Support
+//!
+//! Reads and writes Linux POSIX access and default ACLs, which the kernel
+//! exposes as the `system.posix_acl_access` and `system.posix_acl_default`
+//! extended attributes. Named user and group entries are carried with both
+//! their numeric id and resolved name so they can be remapped by name on
+//! the destination host.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Extended attribute holding the access ACL
+pub const ACL_ACCESS_XATTR: &str = "system.posix_acl_access";
+
+/// Extended attribute holding the default ACL (directories only)
+pub const ACL_DEFAULT_XATTR: &str = "system.posix_acl_default";
+
+/// On-disk xattr format version used by Linux
+const ACL_EA_VERSION: u32 = 2;
+
+/// Id stored for entries that carry no qualifier
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+/// Size of the xattr header and of each entry
+const ACL_EA_HEADER_SIZE: usize = 4;
+const ACL_EA_ENTRY_SIZE: usize = 8;
+
+/// POSIX ACL entry tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PosixAclTag {
+    /// Owning user (`user::`)
+    UserObj,
+    /// Named user (`user:name:`)
+    User,
+    /// Owning group (`group::`)
+    GroupObj,
+    /// Named group (`group:name:`)
+    Group,
+    /// Maximum rights for named entries and the owning group (`mask::`)
+    Mask,
+    /// Everyone else (`other::`)
+    Other,
+}
+
+impl PosixAclTag {
+    /// Decode the tag value used in the xattr format
+    pub fn from_raw(value: u16) -> Option<Self> {
+        match value {
+            0x01 => Some(Self::UserObj),
+            0x02 => Some(Self::User),
+            0x04 => Some(Self::GroupObj),
+            0x08 => Some(Self::Group),
+            0x10 => Some(Self::Mask),
+            0x20 => Some(Self::Other),
+            _ => None,
+        }
+    }
+
+    /// Encode the tag value used in the xattr format
+    pub fn as_raw(&self) -> u16 {
+        match self {
+            Self::UserObj => 0x01,
+            Self::User => 0x02,
+            Self::GroupObj => 0x04,
+            Self::Group => 0x08,
+            Self::Mask => 0x10,
+            Self::Other => 0x20,
+        }
+    }
+
+    /// Whether entries with this tag carry a user or group id
+    pub fn has_qualifier(&self) -> bool {
+        matches!(self, Self::User | Self::Group)
+    }
+}
+
+/// Single POSIX ACL entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PosixAclEntry {
+    /// Entry tag
+    pub tag: PosixAclTag,
+    /// Permission bits (r = 4, w = 2, x = 1)
+    pub perm: u16,
+    /// Numeric uid/gid for named entries
+    pub id: Option<u32>,
+    /// User or group name for named entries, if resolvable on the source
+    pub name: Option<String>,
+}
+
+impl PosixAclEntry {
+    /// Read permission bit
+    pub const READ: u16 = 0x04;
+    /// Write permission bit
+    pub const WRITE: u16 = 0x02;
+    /// Execute permission bit
+    pub const EXECUTE: u16 = 0x01;
+
+    /// Create an entry without a qualifier
+    pub fn new(tag: PosixAclTag, perm: u16) -> Self {
+        Self { tag, perm, id: None, name: None }
+    }
+
+    /// Create a named user or group entry
+    pub fn named(tag: PosixAclTag, id: u32, perm: u16) -> Self {
+        Self { tag, perm, id: Some(id), name: None }
+    }
+
+    /// Permissions in `rwx` form
+    pub fn perm_string(&self) -> String {
+        let mut s = String::with_capacity(3);
+        s.push(if self.perm & Self::READ != 0 { 'r' } else { '-' });
+        s.push(if self.perm & Self::WRITE != 0 { 'w' } else { '-' });
+        s.push(if self.perm & Self::EXECUTE != 0 { 'x' } else { '-' });
+        s
+    }
+
+    /// Principal as shown in text form (name if known, otherwise id)
+    fn qualifier_string(&self) -> String {
+        match (&self.name, self.id) {
+            (Some(name), _) => name.clone(),
+            (None, Some(id)) => id.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// POSIX access or default ACL
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PosixAcl {
+    /// ACL entries in canonical order
+    pub entries: Vec<PosixAclEntry>,
+}
+
+impl PosixAcl {
+    /// Parse the binary xattr representation
+    pub fn from_xattr_bytes(data: &[u8]) -> io::Result<Self> {
+        if data.len() < ACL_EA_HEADER_SIZE
+            || !(data.len() - ACL_EA_HEADER_SIZE).is_multiple_of(ACL_EA_ENTRY_SIZE)
+        {
+            return Err(invalid_data("truncated POSIX ACL"));
+        }
+
+        let version = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        if version != ACL_EA_VERSION {
+            return Err(invalid_data(format!("unsupported POSIX ACL version {}", version)));
+        }
+
+        let mut entries = Vec::with_capacity((data.len() - ACL_EA_HEADER_SIZE) / ACL_EA_ENTRY_SIZE);
+        for raw in data[ACL_EA_HEADER_SIZE..].chunks_exact(ACL_EA_ENTRY_SIZE) {
+            let tag_value = u16::from_le_bytes([raw[0], raw[1]]);
+            let perm = u16::from_le_bytes([raw[2], raw[3]]);
+            let id = u32::from_le_bytes([raw[4], raw[5], raw[6], raw[7]]);
+
+            let tag = PosixAclTag::from_raw(tag_value)
+                .ok_or_else(|| invalid_data(format!("unknown POSIX ACL tag 0x{:x}", tag_value)))?;
+
+            entries.push(PosixAclEntry {
+                tag,
+                perm: perm & 0x07,
+                id: if tag.has_qualifier() { Some(id) } else { None },
+                name: None,
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Encode to the binary xattr representation
+    pub fn to_xattr_bytes(&self) -> Vec<u8> {
+        let mut entries = self.entries.clone();
+        entries.sort_by_key(|e| (e.tag, e.id));
+
+        let mut data = Vec::with_capacity(ACL_EA_HEADER_SIZE + entries.len() * ACL_EA_ENTRY_SIZE);
+        data.extend_from_slice(&ACL_EA_VERSION.to_le_bytes());
+        for entry in &entries {
+            data.extend_from_slice(&entry.tag.as_raw().to_le_bytes());
+            data.extend_from_slice(&entry.perm.to_le_bytes());
+            data.extend_from_slice(&entry.id.unwrap_or(ACL_UNDEFINED_ID).to_le_bytes());
+        }
+        data
+    }
+
+    /// True if the ACL only mirrors the mode bits (no named entries or mask)
+    pub fn is_minimal(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|e| matches!(e.tag, PosixAclTag::UserObj | PosixAclTag::GroupObj | PosixAclTag::Other))
+    }
+
+    /// Named user and group entries
+    pub fn named_entries(&self) -> impl Iterator<Item = &PosixAclEntry> {
+        self.entries.iter().filter(|e| e.tag.has_qualifier())
+    }
+
+    /// Fill in user and group names for named entries
+    pub fn resolve_names(&mut self) {
+        for entry in &mut self.entries {
+            if let Some(id) = entry.id {
+                entry.name = match entry.tag {
+                    PosixAclTag::User => user_name(id),
+                    PosixAclTag::Group => group_name(id),
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    /// Map named entries to local ids by name
+    ///
+    /// Entries whose principal cannot be resolved keep their numeric id and
+    /// produce a warning.
+    pub fn remap_ids(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        for entry in &mut self.entries {
+            if !entry.tag.has_qualifier() {
+                continue;
+            }
+            let kind = if entry.tag == PosixAclTag::User { "user" } else { "group" };
+            let id = entry.id.unwrap_or(ACL_UNDEFINED_ID);
+
+            match &entry.name {
+                Some(name) => {
+                    let local = match entry.tag {
+                        PosixAclTag::User => user_id(name),
+                        _ => group_id(name),
+                    };
+                    match local {
+                        Some(local_id) => entry.id = Some(local_id),
+                        None => warnings.push(format!(
+                            "unresolvable ACL {} '{}', keeping numeric id {}",
+                            kind, name, id
+                        )),
+                    }
+                }
+                None => warnings.push(format!(
+                    "ACL {} id {} has no name on source, keeping numeric id",
+                    kind, id
+                )),
+            }
+        }
+
+        warnings
+    }
+}
+
+impl fmt::Display for PosixAcl {
+    /// Short text form as used by `getfacl -c`/`setfacl`, comma separated
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .entries
+            .iter()
+            .map(|e| {
+                let kind = match e.tag {
+                    PosixAclTag::UserObj | PosixAclTag::User => "user",
+                    PosixAclTag::GroupObj | PosixAclTag::Group => "group",
+                    PosixAclTag::Mask => "mask",
+                    PosixAclTag::Other => "other",
+                };
+                format!("{}:{}:{}", kind, e.qualifier_string(), e.perm_string())
+            })
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+/// Access and default ACLs of a single path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PosixSecurityInfo {
+    /// Access ACL (`system.posix_acl_access`)
+    pub access: Option<PosixAcl>,
+    /// Default ACL inherited by new children (`system.posix_acl_default`)
+    pub default: Option<PosixAcl>,
+}
+
+impl PosixSecurityInfo {
+    /// True if there is nothing beyond the mode bits to preserve
+    pub fn is_empty(&self) -> bool {
+        self.access.as_ref().is_none_or(|a| a.is_minimal()) && self.default.is_none()
+    }
+}
+
+/// POSIX ACL operations
+pub struct PosixAclOps;
+
+impl PosixAclOps {
+    /// Read access and default ACLs of a path
+    #[cfg(target_os = "linux")]
+    pub fn get_acls<P: AsRef<Path>>(path: P) -> io::Result<PosixSecurityInfo> {
+        let path = path.as_ref();
+
+        let access = read_acl_xattr(path, ACL_ACCESS_XATTR)?;
+        let default = if path.is_dir() {
+            read_acl_xattr(path, ACL_DEFAULT_XATTR)?
+        } else {
+            None
+        };
+
+        Ok(PosixSecurityInfo { access, default })
+    }
+
+    /// Read ACLs (non-Linux stub)
+    #[cfg(not(target_os = "linux"))]
+    pub fn get_acls<P: AsRef<Path>>(_path: P) -> io::Result<PosixSecurityInfo> {
+        Ok(PosixSecurityInfo::default())
+    }
+
+    /// Apply access and default ACLs to a path
+    ///
+    /// Named entries are remapped by name; returns warnings for principals
+    /// that could not be resolved.
+    pub fn set_acls<P: AsRef<Path>>(path: P, info: &PosixSecurityInfo) -> io::Result<Vec<String>> {
+        let path = path.as_ref();
+        let mut warnings = Self::set_access_acl(path, info.access.as_ref())?;
+        warnings.extend(Self::set_default_acl(path, info.default.as_ref())?);
+        Ok(warnings)
+    }
+
+    /// Apply an access ACL, `None` leaving only the mode bits
+    #[cfg(target_os = "linux")]
+    pub fn set_access_acl<P: AsRef<Path>>(path: P, access: Option<&PosixAcl>) -> io::Result<Vec<String>> {
+        let path = path.as_ref();
+        match access {
+            // A minimal ACL is fully represented by the mode bits
+            Some(access) if !access.is_minimal() => {
+                let mut acl = access.clone();
+                let warnings = acl.remap_ids();
+                write_acl_xattr(path, ACL_ACCESS_XATTR, &acl)?;
+                Ok(warnings)
+            }
+            _ => remove_acl_xattr(path, ACL_ACCESS_XATTR).map(|()| Vec::new()),
+        }
+    }
+
+    /// Apply an access ACL (non-Linux stub)
+    #[cfg(not(target_os = "linux"))]
+    pub fn set_access_acl<P: AsRef<Path>>(_path: P, _access: Option<&PosixAcl>) -> io::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Apply a default ACL to a directory; other paths are left alone
+    #[cfg(target_os = "linux")]
+    pub fn set_default_acl<P: AsRef<Path>>(path: P, default: Option<&PosixAcl>) -> io::Result<Vec<String>> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Ok(Vec::new());
+        }
+        match default {
+            Some(default) => {
+                let mut acl = default.clone();
+                let warnings = acl.remap_ids();
+                write_acl_xattr(path, ACL_DEFAULT_XATTR, &acl)?;
+                Ok(warnings)
+            }
+            // Drop any default ACL inherited from the destination parent
+            None => remove_acl_xattr(path, ACL_DEFAULT_XATTR).map(|()| Vec::new()),
+        }
+    }
+
+    /// Apply a default ACL (non-Linux stub)
+    #[cfg(not(target_os = "linux"))]
+    pub fn set_default_acl<P: AsRef<Path>>(_path: P, _default: Option<&PosixAcl>) -> io::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Copy ACLs from one path to another, returning warnings
+    pub fn copy_acls<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<Vec<String>> {
+        Self::set_acls(dst, &Self::get_resolved_acls(src)?)
+    }
+
+    /// Copy only the access ACL
+    pub fn copy_access_acl<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<Vec<String>> {
+        Self::set_access_acl(dst, Self::get_resolved_acls(src)?.access.as_ref())
+    }
+
+    /// Copy only the default ACL
+    ///
+    /// Lets a directory's children inherit it while the directory itself
+    /// stays writable until its access ACL is copied.
+    pub fn copy_default_acl<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<Vec<String>> {
+        Self::set_default_acl(dst, Self::get_resolved_acls(src)?.default.as_ref())
+    }
+
+    /// Read ACLs with the names of their principals resolved
+    fn get_resolved_acls<P: AsRef<Path>>(src: P) -> io::Result<PosixSecurityInfo> {
+        let mut info = Self::get_acls(src)?;
+        if let Some(acl) = info.access.as_mut() {
+            acl.resolve_names();
+        }
+        if let Some(acl) = info.default.as_mut() {
+            acl.resolve_names();
+        }
+        Ok(info)
+    }
+}
+
+/// Check if POSIX ACL support is compiled in for this platform
+pub fn posix_acl_available() -> bool {
+    cfg!(target_os = "linux")
+}
+
+#[cfg(target_os = "linux")]
+fn read_acl_xattr(path: &Path, name: &str) -> io::Result<Option<PosixAcl>> {
+    match xattr::get(path, name) {
+        Ok(Some(data)) => PosixAcl::from_xattr_bytes(&data).map(Some),
+        Ok(None) => Ok(None),
+        Err(e) if is_absent_error(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn write_acl_xattr(path: &Path, name: &str, acl: &PosixAcl) -> io::Result<()> {
+    xattr::set(path, name, &acl.to_xattr_bytes()).map_err(|e| {
+        if e.raw_os_error() == Some(libc::EOPNOTSUPP) {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("destination filesystem does not support POSIX ACLs ({})", name),
+            )
+        } else {
+            e
+        }
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn remove_acl_xattr(path: &Path, name: &str) -> io::Result<()> {
+    match xattr::remove(path, name) {
+        Ok(()) => Ok(()),
+        Err(e) if is_absent_error(&e) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Missing attribute or filesystem without ACL support
+#[cfg(target_os = "linux")]
+fn is_absent_error(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::ENODATA) | Some(libc::EOPNOTSUPP))
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Look up a user name by uid
+#[cfg(unix)]
+pub fn user_name(uid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+    let rc = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Look up a group name by gid
+#[cfg(unix)]
+pub fn group_name(gid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+
+    let rc = unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(grp.gr_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Look up a uid by user name
+#[cfg(unix)]
+pub fn user_id(name: &str) -> Option<u32> {
+    let c_name = std::ffi::CString::new(name).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+    let rc = unsafe { libc::getpwnam_r(c_name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return None;
+    }
+    Some(pwd.pw_uid)
+}
+
+/// Look up a gid by group name
+#[cfg(unix)]
+pub fn group_id(name: &str) -> Option<u32> {
+    let c_name = std::ffi::CString::new(name).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+
+    let rc = unsafe { libc::getgrnam_r(c_name.as_ptr(), &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return None;
+    }
+    Some(grp.gr_gid)
+}
+
+/// Look up a user name by uid (unsupported platform)
+#[cfg(not(unix))]
+pub fn user_name(_uid: u32) -> Option<String> {
+    None
+}
+
+/// Look up a group name by gid (unsupported platform)
+#[cfg(not(unix))]
+pub fn group_name(_gid: u32) -> Option<String> {
+    None
+}
+
+/// Look up a uid by user name (unsupported platform)
+#[cfg(not(unix))]
+pub fn user_id(_name: &str) -> Option<u32> {
+    None
+}
+
+/// Look up a gid by group name (unsupported platform)
+#[cfg(not(unix))]
+pub fn group_id(_name: &str) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_acl() -> PosixAcl {
+        PosixAcl {
+            entries: vec![
+                PosixAclEntry::new(PosixAclTag::UserObj, 0o7),
+                PosixAclEntry::named(PosixAclTag::User, 0, 0o5),
+                PosixAclEntry::new(PosixAclTag::GroupObj, 0o5),
+                PosixAclEntry::named(PosixAclTag::Group, 4242, 0o4),
+                PosixAclEntry::new(PosixAclTag::Mask, 0o5),
+                PosixAclEntry::new(PosixAclTag::Other, 0),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_xattr_roundtrip() {
+        let acl = sample_acl();
+        let bytes = acl.to_xattr_bytes();
+        assert_eq!(bytes.len(), 4 + 6 * 8);
+
+        let parsed = PosixAcl::from_xattr_bytes(&bytes).unwrap();
+        assert_eq!(parsed, acl);
+        assert!(!parsed.is_minimal());
+        assert_eq!(parsed.named_entries().count(), 2);
+    }
+
+    #[test]
+    fn test_invalid_xattr() {
+        assert!(PosixAcl::from_xattr_bytes(&[2, 0, 0]).is_err());
+        assert!(PosixAcl::from_xattr_bytes(&[1, 0, 0, 0]).is_err());
+
+        let mut bytes = sample_acl().to_xattr_bytes();
+        bytes[4] = 0x40;
+        assert!(PosixAcl::from_xattr_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_text_form_and_names() {
+        let mut acl = sample_acl();
+        acl.resolve_names();
+
+        // uid 0 always resolves, the made-up gid normally does not
+        assert_eq!(acl.entries[1].name.as_deref(), Some("root"));
+        assert_eq!(
+            acl.to_string(),
+            format!(
+                "user::rwx,user:root:r-x,group::r-x,group:{}:r--,mask::r-x,other::---",
+                acl.entries[3].name.clone().unwrap_or_else(|| "4242".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn test_remap_warns_on_unresolvable() {
+        let mut acl = sample_acl();
+        acl.entries[1].name = Some("root".to_string());
+        acl.entries[3].name = Some("smartcopy-no-such-group".to_string());
+
+        let warnings = acl.remap_ids();
+        assert_eq!(acl.entries[1].id, Some(0));
+        assert_eq!(acl.entries[3].id, Some(4242));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("smartcopy-no-such-group"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_copy_acls_on_disk() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let src = dir.path().join("src.txt");
+        let dst = dir.path().join("dst.txt");
+        std::fs::write(&src, b"acl").unwrap();
+        std::fs::write(&dst, b"acl").unwrap();
+
+        if xattr::set(&src, ACL_ACCESS_XATTR, &sample_acl().to_xattr_bytes()).is_err() {
+            // Filesystem or sandbox without ACL support
+            return;
+        }
+
+        PosixAclOps::copy_acls(&src, &dst).unwrap();
+        let copied = PosixAclOps::get_acls(&dst).unwrap();
+        assert_eq!(copied.access.unwrap().named_entries().count(), 2);
+    }
+}
@@ -38,6 +38,70 @@ pub struct FileEntry {
     /// File mode placeholder for non-Unix
     #[cfg(not(unix))]
     pub permissions: u32,
+    /// Special file type (FIFO, socket or device node)
+    #[serde(default)]
+    pub special: Option<SpecialKind>,
+    /// Device number for character and block devices
+    #[serde(default)]
+    pub rdev: u64,
+    /// Inode number (0 where not available)
+    #[serde(default)]
+    pub inode: u64,
+}
+
+/// Kind of special (non-regular) file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecialKind {
+    /// Named pipe
+    Fifo,
+    /// Unix domain socket
+    Socket,
+    /// Character device node
+    CharDevice,
+    /// Block device node
+    BlockDevice,
+}
+
+impl SpecialKind {
+    /// Classify a file type, returning `None` for regular files, directories and symlinks
+    #[cfg(unix)]
+    pub fn from_file_type(file_type: &std::fs::FileType) -> Option<Self> {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_fifo() {
+            Some(Self::Fifo)
+        } else if file_type.is_socket() {
+            Some(Self::Socket)
+        } else if file_type.is_char_device() {
+            Some(Self::CharDevice)
+        } else if file_type.is_block_device() {
+            Some(Self::BlockDevice)
+        } else {
+            None
+        }
+    }
+
+    /// Classify a file type (no special files on this platform)
+    #[cfg(not(unix))]
+    pub fn from_file_type(_file_type: &std::fs::FileType) -> Option<Self> {
+        None
+    }
+
+    /// Whether this is a device node (selected by `--devices` rather than `--specials`)
+    pub fn is_device(self) -> bool {
+        matches!(self, Self::CharDevice | Self::BlockDevice)
+    }
+
+    /// Short name for display
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fifo => "fifo",
+            Self::Socket => "socket",
+            Self::CharDevice => "char device",
+            Self::BlockDevice => "block device",
+        }
+    }
 }
 
 impl FileEntry {
@@ -45,6 +109,17 @@ impl FileEntry {
     pub fn from_path(path: &Path, source_root: &Path) -> Result<Self> {
         let metadata = std::fs::symlink_metadata(path)
             .map_err(|e| SmartCopyError::io(path, e))?;
+        Self::from_metadata(path, source_root, metadata)
+    }
+
+    /// Create a FileEntry describing what a symlink points to
+    pub fn from_path_following(path: &Path, source_root: &Path) -> Result<Self> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| SmartCopyError::io(path, e))?;
+        Self::from_metadata(path, source_root, metadata)
+    }
+
+    fn from_metadata(path: &Path, source_root: &Path, metadata: std::fs::Metadata) -> Result<Self> {
 
         let relative_path = path
             .strip_prefix(source_root)
@@ -67,6 +142,25 @@ impl FileEntry {
         #[cfg(not(unix))]
         let permissions = 0o644;
 
+        let special = SpecialKind::from_file_type(&metadata.file_type());
+
+        #[cfg(unix)]
+        let rdev = if special.is_some_and(SpecialKind::is_device) {
+            use std::os::unix::fs::MetadataExt;
+            metadata.rdev()
+        } else {
+            0
+        };
+
+        #[cfg(not(unix))]
+        let rdev = 0;
+
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(&metadata);
+
+        #[cfg(not(unix))]
+        let inode = 0;
+
         Ok(FileEntry {
             path: path.to_path_buf(),
             relative_path,
@@ -77,9 +171,17 @@ impl FileEntry {
             is_symlink,
             symlink_target,
             permissions,
+            special,
+            rdev,
+            inode,
         })
     }
 
+    /// Check if this is a FIFO, socket or device node
+    pub fn is_special(&self) -> bool {
+        self.special.is_some()
+    }
+
     /// Get file extension
     pub fn extension(&self) -> Option<&str> {
         self.path.extension().and_then(|e| e.to_str())
@@ -104,6 +206,9 @@ pub struct ScanResult {
     pub files: Vec<FileEntry>,
     /// All directory entries
     pub directories: Vec<FileEntry>,
+    /// FIFOs, sockets and device nodes selected by the scan config
+    #[serde(default)]
+    pub specials: Vec<FileEntry>,
     /// Total size of all files
     pub total_size: u64,
     /// Total number of files
@@ -174,6 +279,10 @@ pub struct ScanConfig {
     pub max_size: Option<u64>,
     /// Number of threads for parallel scanning
     pub threads: usize,
+    /// Include FIFOs and sockets
+    pub include_specials: bool,
+    /// Include character and block device nodes
+    pub include_devices: bool,
 }
 
 impl Default for ScanConfig {
@@ -187,6 +296,8 @@ impl Default for ScanConfig {
             min_size: None,
             max_size: None,
             threads: num_cpus::get(),
+            include_specials: false,
+            include_devices: false,
         }
     }
 }
@@ -253,7 +364,7 @@ impl Scanner {
                 match entry {
                     Ok(e) => self.process_entry(&e, &root),
                     Err(err) => {
-                        Some(Err(err.to_string()))
+                        Some(Err(Self::describe_walk_error(&err)))
                     }
                 }
             })
@@ -262,6 +373,7 @@ impl Scanner {
         // Separate successes and errors
         let mut files = Vec::new();
         let mut directories = Vec::new();
+        let mut specials = Vec::new();
         let mut errors = Vec::new();
 
         for result in results {
@@ -269,6 +381,8 @@ impl Scanner {
                 Ok(entry) => {
                     if entry.is_dir {
                         directories.push(entry);
+                    } else if entry.is_special() {
+                        specials.push(entry);
                     } else {
                         files.push(entry);
                     }
@@ -286,6 +400,7 @@ impl Scanner {
             root,
             files,
             directories,
+            specials,
             total_size,
             file_count,
             dir_count,
@@ -303,8 +418,13 @@ impl Scanner {
             return None;
         }
 
-        // Create file entry
-        let file_entry = match FileEntry::from_path(path, root) {
+        // Create file entry, describing the referent when following links
+        let file_entry = if self.config.follow_symlinks && entry.path_is_symlink() {
+            FileEntry::from_path_following(path, root)
+        } else {
+            FileEntry::from_path(path, root)
+        };
+        let file_entry = match file_entry {
             Ok(e) => e,
             Err(err) => return Some(Err(err.to_string())),
         };
@@ -314,32 +434,63 @@ impl Scanner {
             return Some(Ok(file_entry));
         }
 
-        // Apply size filters
-        if let Some(min_size) = self.config.min_size {
-            if file_entry.size < min_size {
+        // Special files are only included when explicitly requested
+        if let Some(kind) = file_entry.special {
+            let wanted = if kind.is_device() {
+                self.config.include_devices
+            } else {
+                self.config.include_specials
+            };
+            if !wanted {
                 return None;
             }
         }
-        if let Some(max_size) = self.config.max_size {
-            if file_entry.size > max_size {
-                return None;
+
+        // Apply size filters (special files have no content to size)
+        if !file_entry.is_special() {
+            if let Some(min_size) = self.config.min_size {
+                if file_entry.size < min_size {
+                    return None;
+                }
+            }
+            if let Some(max_size) = self.config.max_size {
+                if file_entry.size > max_size {
+                    return None;
+                }
             }
         }
 
         // Apply pattern filters
-        let path_str = file_entry.relative_path.to_string_lossy();
+        if !self.is_selected(&file_entry.relative_path) {
+            return None;
+        }
+
+        Some(Ok(file_entry))
+    }
+
+    /// Check a relative path against the include and exclude patterns
+    pub fn is_selected(&self, relative: &Path) -> bool {
+        let path_str = relative.to_string_lossy();
 
         // Check include patterns (if any)
         if !self.include_matcher.is_empty() && !self.include_matcher.is_match(&*path_str) {
-            return None;
+            return false;
         }
 
         // Check exclude patterns
-        if self.exclude_matcher.is_match(&*path_str) {
-            return None;
-        }
+        !self.exclude_matcher.is_match(&*path_str)
+    }
 
-        Some(Ok(file_entry))
+    /// Turn a walk error into a message, reporting loops as `SymlinkError`
+    fn describe_walk_error(err: &walkdir::Error) -> String {
+        match (err.path(), err.loop_ancestor()) {
+            (Some(path), Some(ancestor)) => SmartCopyError::SymlinkError {
+                path: path.to_path_buf(),
+                message: format!("symlink loop back to '{}'", ancestor.display()),
+            }
+            .to_string(),
+            _ => err.to_string(),
+        }
     }
 
     /// Check if an entry is hidden
@@ -531,6 +682,32 @@ mod tests {
         assert!(!result.files.iter().any(|f| f.extension() == Some("bin")));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_scanner_specials() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = create_test_dir();
+        let fifo = dir.path().join("pipe");
+        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o640) }, 0);
+
+        // Skipped unless requested
+        let result = Scanner::new(ScanConfig::default()).unwrap().scan(dir.path()).unwrap();
+        assert!(result.specials.is_empty());
+        assert!(!result.files.iter().any(|f| f.is_special()));
+
+        let config = ScanConfig {
+            include_specials: true,
+            min_size: Some(1),
+            ..Default::default()
+        };
+        let result = Scanner::new(config).unwrap().scan(dir.path()).unwrap();
+        assert_eq!(result.specials.len(), 1);
+        assert_eq!(result.specials[0].special, Some(SpecialKind::Fifo));
+        assert!(!result.files.iter().any(|f| f.is_special()));
+    }
+
     #[test]
     fn test_file_size_category() {
         assert_eq!(FileSizeCategory::from_size(100), FileSizeCategory::Tiny);
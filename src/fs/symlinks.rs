@@ -0,0 +1,248 @@
+//! Symbolic link policies
+//!
+//! Decides what happens to each symlink found in the source tree:
+//! recreate it, rewrite it for the new root, dereference it or skip it.
+//! Safety is judged lexically, like rsync: a link is unsafe when its
+//! target escapes the source tree, without resolving intermediate links.
+
+use crate::error::{Result, SmartCopyError};
+use std::path::{Component, Path, PathBuf};
+
+/// Prefix added to link targets by `--munge-links` (compatible with rsync)
+pub const MUNGE_PREFIX: &str = "/rsyncd-munged/";
+
+/// How symlinks that point outside the source tree are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SymlinkPolicy {
+    /// Skip symlinks that point outside the source tree
+    pub safe_links: bool,
+    /// Copy the referent of symlinks that point outside the source tree
+    pub copy_unsafe_links: bool,
+    /// Prefix created link targets with [`MUNGE_PREFIX`] so they can't be followed
+    pub munge_links: bool,
+}
+
+/// What to do with a single symlink
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymlinkAction {
+    /// Create a symlink at the destination with this target
+    Create(PathBuf),
+    /// Copy the file or directory the link points to
+    Dereference,
+    /// Leave the link out of the copy
+    Skip(String),
+}
+
+impl SymlinkPolicy {
+    /// Decide how to copy a symlink
+    ///
+    /// `relative` is the link's path below `source_root`, `target` its
+    /// contents as returned by `read_link`. Absolute targets inside the
+    /// source tree are rewritten to point into `dest_root`.
+    pub fn plan(
+        &self,
+        relative: &Path,
+        target: &Path,
+        source_root: &Path,
+        dest_root: &Path,
+    ) -> SymlinkAction {
+        let (target, safe) = if target.is_absolute() {
+            match normalize_lexically(target).strip_prefix(source_root) {
+                Ok(inside) => (dest_root.join(inside), true),
+                Err(_) => (target.to_path_buf(), false),
+            }
+        } else {
+            (target.to_path_buf(), is_within_tree(relative, target))
+        };
+
+        if !safe {
+            if self.copy_unsafe_links {
+                return SymlinkAction::Dereference;
+            }
+            if self.safe_links {
+                return SymlinkAction::Skip(format!(
+                    "ignoring unsafe symlink to '{}'",
+                    target.display()
+                ));
+            }
+        }
+
+        SymlinkAction::Create(self.finalize_target(target))
+    }
+
+    /// Apply `--munge-links` to a target about to be written
+    pub fn finalize_target(&self, target: PathBuf) -> PathBuf {
+        if self.munge_links {
+            let mut munged = std::ffi::OsString::from(MUNGE_PREFIX);
+            munged.push(target.as_os_str());
+            PathBuf::from(munged)
+        } else {
+            target
+        }
+    }
+}
+
+/// Check whether a relative target stays inside the tree
+///
+/// The link's own directory is the starting point; walking above the
+/// tree root at any step makes the link unsafe.
+pub fn is_within_tree(relative: &Path, target: &Path) -> bool {
+    let mut depth: usize = relative
+        .parent()
+        .map(|p| p.components().filter(|c| matches!(c, Component::Normal(_))).count())
+        .unwrap_or(0);
+
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::ParentDir => {
+                if depth == 0 {
+                    return false;
+                }
+                depth -= 1;
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+
+    true
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+pub fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Create a symlink, replacing any existing non-directory entry
+#[cfg(unix)]
+pub fn create_symlink(target: &Path, dest: &Path) -> Result<()> {
+    if let Ok(meta) = std::fs::symlink_metadata(dest) {
+        if !meta.is_dir() {
+            if meta.file_type().is_symlink() && std::fs::read_link(dest).ok().as_deref() == Some(target) {
+                return Ok(());
+            }
+            std::fs::remove_file(dest).map_err(|e| SmartCopyError::io(dest, e))?;
+        }
+    }
+
+    std::os::unix::fs::symlink(target, dest).map_err(|e| SmartCopyError::SymlinkError {
+        path: dest.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+/// Create a symlink (unsupported platform)
+#[cfg(not(unix))]
+pub fn create_symlink(_target: &Path, dest: &Path) -> Result<()> {
+    Err(SmartCopyError::SymlinkError {
+        path: dest.to_path_buf(),
+        message: "symlinks are not supported on this platform".to_string(),
+    })
+}
+
+/// Remove a symlink at `dest` so the referent can be written in its place
+pub fn remove_symlink(dest: &Path) -> Result<()> {
+    match std::fs::symlink_metadata(dest) {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            std::fs::remove_file(dest).map_err(|e| SmartCopyError::io(dest, e))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Resolve a symlink for dereferencing, reporting loops as `SymlinkError`
+pub fn resolve_symlink(path: &Path) -> Result<PathBuf> {
+    std::fs::canonicalize(path).map_err(|e| {
+        if is_loop_error(&e) {
+            SmartCopyError::SymlinkError {
+                path: path.to_path_buf(),
+                message: "symlink loop detected".to_string(),
+            }
+        } else {
+            SmartCopyError::SymlinkError {
+                path: path.to_path_buf(),
+                message: format!("cannot resolve target: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(unix)]
+fn is_loop_error(e: &std::io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ELOOP)
+}
+
+#[cfg(not(unix))]
+fn is_loop_error(_e: &std::io::Error) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_tree() {
+        assert!(is_within_tree(Path::new("a/link"), Path::new("file")));
+        assert!(is_within_tree(Path::new("a/link"), Path::new("../b/file")));
+        assert!(!is_within_tree(Path::new("a/link"), Path::new("../../file")));
+        assert!(!is_within_tree(Path::new("link"), Path::new("../file")));
+        // Escaping and coming back is still unsafe
+        assert!(!is_within_tree(Path::new("a/link"), Path::new("../../src/a/file")));
+    }
+
+    #[test]
+    fn test_plan_policies() {
+        let src = Path::new("/data/src");
+        let dst = Path::new("/backup/dst");
+        let rel = Path::new("dir/link");
+
+        let default = SymlinkPolicy::default();
+        assert_eq!(
+            default.plan(rel, Path::new("../etc/passwd"), src, dst),
+            SymlinkAction::Create(PathBuf::from("../etc/passwd"))
+        );
+        assert_eq!(
+            default.plan(rel, Path::new("/data/src/dir/file"), src, dst),
+            SymlinkAction::Create(PathBuf::from("/backup/dst/dir/file"))
+        );
+
+        let safe = SymlinkPolicy { safe_links: true, ..Default::default() };
+        assert!(matches!(safe.plan(rel, Path::new("/etc/passwd"), src, dst), SymlinkAction::Skip(_)));
+        assert!(matches!(safe.plan(rel, Path::new("../../x"), src, dst), SymlinkAction::Skip(_)));
+        assert!(matches!(safe.plan(rel, Path::new("file"), src, dst), SymlinkAction::Create(_)));
+
+        let copy_unsafe = SymlinkPolicy { copy_unsafe_links: true, ..Default::default() };
+        assert_eq!(copy_unsafe.plan(rel, Path::new("/etc/passwd"), src, dst), SymlinkAction::Dereference);
+
+        let munge = SymlinkPolicy { munge_links: true, ..Default::default() };
+        assert_eq!(
+            munge.plan(rel, Path::new("file"), src, dst),
+            SymlinkAction::Create(PathBuf::from("/rsyncd-munged/file"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_loop() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::os::unix::fs::symlink("b", dir.path().join("a")).unwrap();
+        std::os::unix::fs::symlink("a", dir.path().join("b")).unwrap();
+
+        match resolve_symlink(&dir.path().join("a")) {
+            Err(SmartCopyError::SymlinkError { message, .. }) => assert!(message.contains("loop")),
+            other => panic!("Expected SymlinkError, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Extended attribute preservation
+//!
+//! Copies extended attributes between files and directories with
+//! include/exclude filtering by namespace and name, e.g. copy `user.*`
+//! but skip `security.selinux` and `trusted.*`. Failures are returned as
+//! warnings so a single unsupported attribute never aborts a copy.
+
+use crate::error::{Result, SmartCopyError};
+use crate::fs::posix_acl::{ACL_ACCESS_XATTR, ACL_DEFAULT_XATTR};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Extended attribute name and value pairs
+pub type XattrList = Vec<(String, Vec<u8>)>;
+
+/// Include/exclude filter for extended attribute names
+///
+/// Patterns are globs matched against the full attribute name including
+/// its namespace (`user.checksum`, `security.selinux`). An empty include
+/// list selects every attribute. POSIX ACL attributes are always skipped;
+/// they are handled by `--acls`.
+#[derive(Debug, Clone)]
+pub struct XattrFilter {
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl XattrFilter {
+    /// Create a filter from include and exclude glob patterns
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: Self::build_globset(include)?,
+            exclude: Self::build_globset(exclude)?,
+        })
+    }
+
+    /// Filter that selects every attribute
+    pub fn allow_all() -> Self {
+        Self {
+            include: GlobSet::empty(),
+            exclude: GlobSet::empty(),
+        }
+    }
+
+    fn build_globset(patterns: &[String]) -> Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern).map_err(|e| {
+                SmartCopyError::ConfigError(format!("Invalid xattr pattern '{}': {}", pattern, e))
+            })?;
+            builder.add(glob);
+        }
+        builder
+            .build()
+            .map_err(|e| SmartCopyError::ConfigError(format!("Failed to build xattr filter: {}", e)))
+    }
+
+    /// Check whether an attribute should be copied
+    pub fn matches(&self, name: &str) -> bool {
+        if name == ACL_ACCESS_XATTR || name == ACL_DEFAULT_XATTR {
+            return false;
+        }
+        let included = self.include.is_empty() || self.include.is_match(name);
+        included && !self.exclude.is_match(name)
+    }
+}
+
+impl Default for XattrFilter {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+/// Read the extended attributes of a path that pass the filter
+///
+/// Returns the attributes plus warnings for any that could not be read.
+/// Filesystems without xattr support yield an empty list.
+#[cfg(unix)]
+pub fn read_xattrs(path: &Path, filter: &XattrFilter) -> (XattrList, Vec<String>) {
+    let mut attrs = Vec::new();
+    let mut warnings = Vec::new();
+
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(e) if is_unsupported(&e) => return (attrs, warnings),
+        Err(e) => {
+            warnings.push(format!("cannot list xattrs: {}", e));
+            return (attrs, warnings);
+        }
+    };
+
+    for name in names {
+        let name = match name.into_string() {
+            Ok(name) => name,
+            Err(raw) => {
+                warnings.push(format!("skipping xattr with non-UTF-8 name {:?}", raw));
+                continue;
+            }
+        };
+        if !filter.matches(&name) {
+            continue;
+        }
+        match xattr::get(path, &name) {
+            Ok(Some(value)) => attrs.push((name, value)),
+            Ok(None) => {}
+            Err(e) => warnings.push(format!("cannot read xattr '{}': {}", name, e)),
+        }
+    }
+
+    (attrs, warnings)
+}
+
+/// Read extended attributes (unsupported platform)
+#[cfg(not(unix))]
+pub fn read_xattrs(_path: &Path, _filter: &XattrFilter) -> (XattrList, Vec<String>) {
+    (Vec::new(), Vec::new())
+}
+
+/// Set extended attributes on a path, returning a warning per failure
+#[cfg(unix)]
+pub fn write_xattrs(path: &Path, attrs: &[(String, Vec<u8>)]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter_map(|(name, value)| {
+            xattr::set(path, name, value)
+                .err()
+                .map(|e| format!("cannot set xattr '{}': {}", name, e))
+        })
+        .collect()
+}
+
+/// Set extended attributes (unsupported platform)
+#[cfg(not(unix))]
+pub fn write_xattrs(_path: &Path, _attrs: &[(String, Vec<u8>)]) -> Vec<String> {
+    Vec::new()
+}
+
+/// Copy filtered extended attributes from one path to another
+///
+/// Never fails; every problem is reported as a warning.
+pub fn copy_xattrs(source: &Path, dest: &Path, filter: &XattrFilter) -> Vec<String> {
+    let (attrs, mut warnings) = read_xattrs(source, filter);
+    if !attrs.is_empty() {
+        warnings.extend(write_xattrs(dest, &attrs));
+    }
+    warnings
+}
+
+#[cfg(unix)]
+fn is_unsupported(e: &std::io::Error) -> bool {
+    e.raw_os_error() == Some(libc::EOPNOTSUPP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_filter_namespaces() {
+        let filter = XattrFilter::new(
+            &["user.*".to_string(), "security.*".to_string()],
+            &["security.selinux".to_string(), "trusted.*".to_string()],
+        )
+        .unwrap();
+
+        assert!(filter.matches("user.checksum"));
+        assert!(filter.matches("security.capability"));
+        assert!(!filter.matches("security.selinux"));
+        assert!(!filter.matches("trusted.overlay"));
+        assert!(!filter.matches("system.posix_acl_access"));
+    }
+
+    #[test]
+    fn test_filter_allow_all() {
+        let filter = XattrFilter::default();
+        assert!(filter.matches("user.a"));
+        assert!(filter.matches("trusted.b"));
+        assert!(!filter.matches(ACL_DEFAULT_XATTR));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(XattrFilter::new(&["user.[".to_string()], &[]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_xattrs_filtered() {
+        let dir = TempDir::new().unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        std::fs::create_dir(&src).unwrap();
+        std::fs::create_dir(&dst).unwrap();
+
+        if xattr::set(&src, "user.keep", b"1").is_err() {
+            // Filesystem without user xattr support
+            return;
+        }
+        xattr::set(&src, "user.skip", b"2").unwrap();
+
+        let filter = XattrFilter::new(&["user.*".to_string()], &["user.skip".to_string()]).unwrap();
+        let warnings = copy_xattrs(&src, &dst, &filter);
+
+        assert!(warnings.is_empty());
+        assert_eq!(xattr::get(&dst, "user.keep").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(xattr::get(&dst, "user.skip").unwrap(), None);
+    }
+}
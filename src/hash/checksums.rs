@@ -0,0 +1,251 @@
+//! Checksum files compatible with `sha256sum`, `b3sum` and `xxhsum`
+//!
+//! Each line holds the hex digest, two spaces and the path relative to the
+//! tree root, so an archive can be checked from its root directory with
+//! the standard tool (`sha256sum -c`, `b3sum -c`, `xxh128sum -c`). Names
+//! are written as raw bytes; a name containing a backslash or line break
+//! is escaped and the line prefixed with `\`, as coreutils does.
+
+use crate::config::HashAlgorithm;
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use crate::fs::{path_from_bytes, path_to_bytes};
+use crate::hash::HashManifest;
+use std::path::{Path, PathBuf};
+
+/// One line of a checksum file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumEntry {
+    /// Path relative to the tree root
+    pub path: PathBuf,
+    /// Lowercase hex digest
+    pub hash: String,
+}
+
+/// Contents of a checksum file
+#[derive(Debug, Clone)]
+pub struct ChecksumFile {
+    /// Algorithm of every digest in the file
+    pub algorithm: HashAlgorithm,
+    /// Entries in path order
+    pub entries: Vec<ChecksumEntry>,
+}
+
+impl ChecksumFile {
+    /// Build from (relative path, hex digest) pairs
+    pub fn from_hashes(
+        algorithm: HashAlgorithm,
+        hashes: impl IntoIterator<Item = (PathBuf, String)>,
+    ) -> Self {
+        let mut entries: Vec<ChecksumEntry> = hashes
+            .into_iter()
+            .map(|(path, hash)| ChecksumEntry {
+                path,
+                hash: hash.to_ascii_lowercase(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.path.as_os_str().cmp(b.path.as_os_str()));
+        Self { algorithm, entries }
+    }
+
+    /// Build from a hash manifest
+    pub fn from_manifest(manifest: &HashManifest) -> Self {
+        Self::from_hashes(
+            manifest.algorithm,
+            manifest.entries.iter().map(|e| (e.to_path(), e.hash.clone())),
+        )
+    }
+
+    /// Encode in checksum file format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for entry in &self.entries {
+            let name = path_to_bytes(&entry.path);
+            let escape = name.iter().any(|b| matches!(b, b'\\' | b'\n' | b'\r'));
+            if escape {
+                out.push(b'\\');
+            }
+            out.extend_from_slice(entry.hash.as_bytes());
+            out.extend_from_slice(b"  ");
+            if escape {
+                for &b in name.iter() {
+                    match b {
+                        b'\\' => out.extend_from_slice(b"\\\\"),
+                        b'\n' => out.extend_from_slice(b"\\n"),
+                        b'\r' => out.extend_from_slice(b"\\r"),
+                        _ => out.push(b),
+                    }
+                }
+            } else {
+                out.extend_from_slice(&name);
+            }
+            out.push(b'\n');
+        }
+        out
+    }
+
+    /// Write to a file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_bytes()).with_path(path)
+    }
+
+    /// Parse checksum file contents
+    ///
+    /// Without an explicit algorithm it is inferred from the digest length;
+    /// 256-bit digests are taken as BLAKE3 when `name` mentions `b3` or
+    /// `blake3` and as SHA-256 otherwise, 128-bit digests as MD5 when it
+    /// mentions `md5` and as XXH128 otherwise.
+    pub fn parse(data: &[u8], name: &str, algorithm: Option<HashAlgorithm>) -> Result<Self> {
+        let mut entries = Vec::new();
+
+        for (number, line) in data.split(|&b| b == b'\n').enumerate() {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() || line.starts_with(b"#") {
+                continue;
+            }
+            let entry = parse_line(line).ok_or_else(|| {
+                SmartCopyError::ManifestError(format!(
+                    "{} line {}: not a checksum line",
+                    name,
+                    number + 1
+                ))
+            })?;
+            entries.push(entry);
+        }
+
+        let algorithm = match (algorithm, entries.first()) {
+            (Some(algorithm), _) => algorithm,
+            (None, Some(first)) => infer_algorithm(first.hash.len(), name)?,
+            (None, None) => HashAlgorithm::Sha256,
+        };
+        let digest_len = algorithm.output_size() * 2;
+        if let Some(entry) = entries.iter().find(|e| e.hash.len() != digest_len) {
+            return Err(SmartCopyError::ManifestError(format!(
+                "{}: {} digest for {} has {} hex digits, expected {}",
+                name,
+                algorithm.name(),
+                entry.path.display(),
+                entry.hash.len(),
+                digest_len
+            )));
+        }
+
+        Ok(Self { algorithm, entries })
+    }
+
+    /// Read and parse a checksum file
+    pub fn load(path: &Path, algorithm: Option<HashAlgorithm>) -> Result<Self> {
+        let data = std::fs::read(path).with_path(path)?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::parse(&data, &name, algorithm)
+    }
+}
+
+/// Parse `<hex>  <name>`, `<hex> *<name>` or the escaped form
+fn parse_line(line: &[u8]) -> Option<ChecksumEntry> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let split = line.iter().position(|&b| b == b' ')?;
+    let (hash, rest) = line.split_at(split);
+    // Second separator is ' ' (text mode) or '*' (binary mode)
+    let name = match rest.get(1)? {
+        b' ' | b'*' => &rest[2..],
+        _ => return None,
+    };
+    if hash.is_empty() || !hash.iter().all(u8::is_ascii_hexdigit) || name.is_empty() {
+        return None;
+    }
+
+    let name = if escaped { unescape_name(name)? } else { name.to_vec() };
+    Some(ChecksumEntry {
+        path: path_from_bytes(&name),
+        hash: String::from_utf8(hash.to_ascii_lowercase()).ok()?,
+    })
+}
+
+fn unescape_name(name: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(name.len());
+    let mut bytes = name.iter();
+    while let Some(&b) = bytes.next() {
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        match bytes.next()? {
+            b'\\' => out.push(b'\\'),
+            b'n' => out.push(b'\n'),
+            b'r' => out.push(b'\r'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+fn infer_algorithm(hex_len: usize, name: &str) -> Result<HashAlgorithm> {
+    let name = name.to_ascii_lowercase();
+    match hex_len {
+        8 => Ok(HashAlgorithm::Crc32c),
+        16 => Ok(HashAlgorithm::XXHash64),
+        32 if name.contains("md5") => Ok(HashAlgorithm::Md5),
+        32 => Ok(HashAlgorithm::XXHash3),
+        64 if name.contains("b3") || name.contains("blake3") => Ok(HashAlgorithm::Blake3),
+        64 => Ok(HashAlgorithm::Sha256),
+        128 => Ok(HashAlgorithm::Sha512),
+        _ => Err(SmartCopyError::UnsupportedHashAlgorithm(format!(
+            "{}-bit digests in {}",
+            hex_len * 4,
+            name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_and_escaping() {
+        let digest = "ab".repeat(32);
+        let file = ChecksumFile::from_hashes(
+            HashAlgorithm::Sha256,
+            vec![
+                (PathBuf::from("dir/b.dat"), digest.to_uppercase()),
+                (PathBuf::from("a\\b\nc"), digest.clone()),
+            ],
+        );
+        let bytes = file.to_bytes();
+        let expected = format!("\\{0}  a\\\\b\\nc\n{0}  dir/b.dat\n", digest);
+        assert_eq!(String::from_utf8(bytes.clone()).unwrap(), expected);
+
+        let parsed = ChecksumFile::parse(&bytes, "SHA256SUMS", None).unwrap();
+        assert_eq!(parsed.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(parsed.entries, file.entries);
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        let digest = "0f".repeat(32);
+        let data = format!("# comment\r\n{0} *binary.dat\r\n\n{0}  text.dat\n", digest);
+        let parsed = ChecksumFile::parse(data.as_bytes(), "B3SUMS", None).unwrap();
+        assert_eq!(parsed.algorithm, HashAlgorithm::Blake3);
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0].path, Path::new("binary.dat"));
+
+        let xxh = "0123456789abcdef".repeat(2);
+        let parsed = ChecksumFile::parse(format!("{}  x\n", xxh).as_bytes(), "sums", None).unwrap();
+        assert_eq!(parsed.algorithm, HashAlgorithm::XXHash3);
+        let parsed = ChecksumFile::parse(format!("{}  x\n", xxh).as_bytes(), "MD5SUMS", None).unwrap();
+        assert_eq!(parsed.algorithm, HashAlgorithm::Md5);
+
+        // Malformed lines and digests of the wrong length are rejected
+        assert!(ChecksumFile::parse(b"nothex  x\n", "sums", None).is_err());
+        assert!(ChecksumFile::parse(format!("{} x\n", xxh).as_bytes(), "sums", None).is_err());
+        let mixed = format!("{}  a\n{}  b\n", xxh, digest);
+        assert!(ChecksumFile::parse(mixed.as_bytes(), "sums", None).is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Per-chunk hashes for locating and repairing damage in large files
+//!
+//! A whole-file hash only says that a file is wrong. Recording the hash of
+//! every fixed-size chunk as well narrows a mismatch down to byte ranges,
+//! so a multi-hundred-GB file can be fixed by re-copying just the chunks
+//! that differ.
+//!
+//! Chunks can also be hashed in parallel. Their hashes combine into a
+//! composite digest, written `composite:<chunk size>:<hex>`, which lets
+//! algorithms without a parallel mode use every core on a single huge
+//! file. A composite digest only equals another composite digest of the
+//! same chunk size, never a plain whole-file hash.
+
+use crate::config::HashAlgorithm;
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use crate::hash::{hash_bytes, hash_file, HashResult, Hasher};
+use crate::sync::CdcParams;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Chunk size for chunk hashes (the engine's parallel chunk size)
+pub const HASH_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Hashes of consecutive chunks of a file
+///
+/// Chunks are either fixed-size or content-defined (see
+/// [`crate::sync::CdcParams`]); content-defined chunks also record their
+/// lengths, and the same content gives the same chunk hash in any file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkHashes {
+    /// Chunk size in bytes (the last chunk may be shorter); the average
+    /// size for content-defined chunks
+    pub chunk_size: u64,
+    /// Algorithm of every chunk hash
+    pub algorithm: HashAlgorithm,
+    /// Hash of each chunk, in file order
+    pub hashes: Vec<String>,
+    /// Length of each content-defined chunk (empty for fixed-size chunks)
+    #[serde(default)]
+    pub lengths: Vec<u64>,
+    /// Content-defined chunking parameters, `None` for fixed-size chunks
+    #[serde(default)]
+    pub cdc: Option<CdcParams>,
+}
+
+impl ChunkHashes {
+    /// Hashes of fixed-size chunks
+    pub fn fixed(chunk_size: u64, algorithm: HashAlgorithm, hashes: Vec<String>) -> Self {
+        Self {
+            chunk_size,
+            algorithm,
+            hashes,
+            lengths: Vec::new(),
+            cdc: None,
+        }
+    }
+
+    /// Offset and length of a chunk in a file of `file_size` bytes
+    ///
+    /// Content-defined chunks are summed up to `index`; use
+    /// [`ChunkHashes::spans`] to walk every chunk.
+    pub fn range(&self, index: usize, file_size: u64) -> (u64, u64) {
+        if self.lengths.is_empty() {
+            let offset = index as u64 * self.chunk_size;
+            (offset, self.chunk_size.min(file_size.saturating_sub(offset)))
+        } else {
+            (self.lengths[..index].iter().sum(), self.lengths[index])
+        }
+    }
+
+    /// Offset and length of every chunk, in order
+    pub fn spans(&self, file_size: u64) -> Vec<(u64, u64)> {
+        if self.lengths.is_empty() {
+            return (0..self.hashes.len()).map(|index| self.range(index, file_size)).collect();
+        }
+        let mut offset = 0;
+        self.lengths
+            .iter()
+            .map(|&len| {
+                offset += len;
+                (offset - len, len)
+            })
+            .collect()
+    }
+
+    /// Composite digest of fixed-size chunks, for a file of `file_size` bytes
+    ///
+    /// The digest is the chunk algorithm applied to the chunks' hex digests
+    /// concatenated in file order.
+    pub fn composite(&self, file_size: u64) -> HashResult {
+        let mut hasher = Hasher::new(self.algorithm);
+        for hash in &self.hashes {
+            hasher.update(hash.as_bytes());
+        }
+        let hash = format!("{}{}:{}", COMPOSITE_PREFIX, self.chunk_size, hasher.finalize());
+        HashResult::new(self.algorithm, hash, file_size)
+    }
+
+    /// Chunks of `path` that don't match, in order
+    ///
+    /// `file_size` is the size the file should have; chunks missing from a
+    /// short file count as mismatched.
+    pub fn find_mismatches(&self, path: &Path, file_size: u64) -> Result<Vec<usize>> {
+        let spans = self.spans(file_size);
+        let results: Vec<Result<Option<usize>>> = (0..self.hashes.len())
+            .into_par_iter()
+            .map(|index| {
+                let (offset, len) = spans[index];
+                let hash = match hash_range(path, offset, len, self.algorithm) {
+                    Ok(hash) => hash,
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(Some(index)),
+                    Err(e) => return Err(SmartCopyError::io(path, e)),
+                };
+                Ok((!hash.eq_ignore_ascii_case(&self.hashes[index])).then_some(index))
+            })
+            .collect();
+
+        let mut mismatched = Vec::new();
+        for result in results {
+            if let Some(index) = result? {
+                mismatched.push(index);
+            }
+        }
+        Ok(mismatched)
+    }
+
+    /// Byte ranges (offset, length) of the given chunks, adjacent ones merged
+    pub fn ranges(&self, indexes: &[usize], file_size: u64) -> Vec<(u64, u64)> {
+        let spans = self.spans(file_size);
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for &index in indexes {
+            let (offset, len) = spans[index];
+            match ranges.last_mut() {
+                Some(last) if last.0 + last.1 == offset => last.1 += len,
+                _ => ranges.push((offset, len)),
+            }
+        }
+        ranges
+    }
+
+    /// Copy chunks from `source` into `dest` at the same offsets
+    ///
+    /// Each source chunk is checked against its recorded hash first, so a
+    /// source that changed since the hashes were taken is never mixed into
+    /// the destination. `dest` is also cut or extended to `file_size`.
+    /// Returns the bytes written.
+    pub fn copy_chunks(&self, source: &Path, dest: &Path, indexes: &[usize], file_size: u64) -> Result<u64> {
+        let spans = self.spans(file_size);
+        let written: Vec<Result<u64>> = indexes
+            .par_iter()
+            .map(|&index| {
+                let (offset, len) = spans[index];
+                let data = read_range(source, offset, len).with_path(source)?;
+                let hash = hash_bytes(&data, self.algorithm).hash;
+                if !hash.eq_ignore_ascii_case(&self.hashes[index]) {
+                    return Err(SmartCopyError::IntegrityMismatch {
+                        path: source.to_path_buf(),
+                        expected: format!("chunk {} hash {}", index, self.hashes[index]),
+                        actual: hash,
+                    });
+                }
+
+                let mut file = OpenOptions::new().write(true).open(dest).with_path(dest)?;
+                file.seek(SeekFrom::Start(offset)).with_path(dest)?;
+                file.write_all(&data).with_path(dest)?;
+                Ok(len)
+            })
+            .collect();
+
+        let total = written.into_iter().sum::<Result<u64>>()?;
+        let file = OpenOptions::new().write(true).open(dest).with_path(dest)?;
+        file.set_len(file_size).with_path(dest)?;
+        file.sync_all().with_path(dest)?;
+        Ok(total)
+    }
+}
+
+/// Prefix of composite digests (see [`ChunkHashes::composite`])
+pub const COMPOSITE_PREFIX: &str = "composite:";
+
+/// Chunk size of a composite digest, or `None` for a plain hash
+pub fn composite_chunk_size(hash: &str) -> Option<u64> {
+    let rest = hash.strip_prefix(COMPOSITE_PREFIX)?;
+    rest.split_once(':')?.0.parse().ok()
+}
+
+fn read_range(path: &Path, offset: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = vec![0u8; len as usize];
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Hash `len` bytes at `offset` without holding them all in memory
+fn hash_range(path: &Path, offset: u64, len: u64, algorithm: HashAlgorithm) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0u8; (1024 * 1024).min(len as usize)];
+    let mut remaining = len;
+    while remaining > 0 {
+        let want = (buffer.len() as u64).min(remaining) as usize;
+        file.read_exact(&mut buffer[..want])?;
+        hasher.update(&buffer[..want]);
+        remaining -= want as u64;
+    }
+    Ok(hasher.finalize())
+}
+
+/// Splits a stream into chunks and hashes each one
+struct ChunkHasher {
+    algorithm: HashAlgorithm,
+    chunk_size: u64,
+    chunk: Hasher,
+    filled: u64,
+    hashes: Vec<String>,
+}
+
+impl ChunkHasher {
+    fn new(algorithm: HashAlgorithm, chunk_size: u64) -> Self {
+        Self {
+            algorithm,
+            chunk_size,
+            chunk: Hasher::new(algorithm),
+            filled: 0,
+            hashes: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = ((self.chunk_size - self.filled) as usize).min(data.len());
+            self.chunk.update(&data[..take]);
+            self.filled += take as u64;
+            data = &data[take..];
+            if self.filled == self.chunk_size {
+                let done = std::mem::replace(&mut self.chunk, Hasher::new(self.algorithm));
+                self.hashes.push(done.finalize());
+                self.filled = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> ChunkHashes {
+        if self.filled > 0 {
+            self.hashes.push(self.chunk.finalize());
+        }
+        ChunkHashes::fixed(self.chunk_size, self.algorithm, self.hashes)
+    }
+}
+
+/// Hash a file and each of its chunks in a single sequential read
+pub fn hash_file_with_chunks(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    chunk_size: u64,
+) -> Result<(HashResult, ChunkHashes)> {
+    let mut file = File::open(path).with_path(path)?;
+    let mut whole = Hasher::new(algorithm);
+    let mut chunks = ChunkHasher::new(algorithm, chunk_size);
+    let mut buffer = vec![0u8; 1024 * 1024];
+    let mut size = 0u64;
+
+    loop {
+        let read = file.read(&mut buffer).with_path(path)?;
+        if read == 0 {
+            break;
+        }
+        whole.update(&buffer[..read]);
+        chunks.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((HashResult::new(algorithm, whole.finalize(), size), chunks.finish()))
+}
+
+/// Hash a file and each of its content-defined chunks in a single read
+pub fn hash_file_cdc(path: &Path, algorithm: HashAlgorithm, params: CdcParams) -> Result<(HashResult, ChunkHashes)> {
+    let mut whole = Hasher::new(algorithm);
+    let mut hashes = Vec::new();
+    let mut lengths = Vec::new();
+    let size = params.chunk_file(path, |_, data| {
+        whole.update(data);
+        hashes.push(hash_bytes(data, algorithm).hash);
+        lengths.push(data.len() as u64);
+        Ok(())
+    })?;
+
+    Ok((
+        HashResult::new(algorithm, whole.finalize(), size),
+        ChunkHashes {
+            chunk_size: params.avg_size as u64,
+            algorithm,
+            hashes,
+            lengths,
+            cdc: Some(params),
+        },
+    ))
+}
+
+/// Hash the chunks of a file in parallel, one chunk per task
+pub fn hash_file_chunked(path: &Path, algorithm: HashAlgorithm, chunk_size: u64) -> Result<ChunkHashes> {
+    let size = std::fs::metadata(path).with_path(path)?.len();
+    let chunk_size = chunk_size.max(1);
+    let count = size.div_ceil(chunk_size) as usize;
+    let hashes = (0..count)
+        .into_par_iter()
+        .map(|index| {
+            let offset = index as u64 * chunk_size;
+            hash_range(path, offset, chunk_size.min(size - offset), algorithm).with_path(path)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ChunkHashes::fixed(chunk_size, algorithm, hashes))
+}
+
+/// Hash the chunks of a file as stored, bypassing the page cache
+///
+/// Reads are sequential (see [`crate::fs::read_uncached`]).
+pub fn hash_file_chunks_uncached(path: &Path, algorithm: HashAlgorithm, chunk_size: u64) -> Result<ChunkHashes> {
+    let mut chunks = ChunkHasher::new(algorithm, chunk_size.max(1));
+    crate::fs::read_uncached(path, |data| chunks.update(data))?;
+    Ok(chunks.finish())
+}
+
+/// Hash a file using every core
+///
+/// BLAKE3 hashes its tree in parallel and gives the standard digest.
+/// Other algorithms give a composite digest over [`HASH_CHUNK_SIZE`]
+/// chunks for files larger than one chunk, and the plain digest otherwise.
+pub fn hash_file_parallel(path: &Path, algorithm: HashAlgorithm) -> Result<HashResult> {
+    let size = std::fs::metadata(path).with_path(path)?.len();
+    if algorithm == HashAlgorithm::Blake3 || size <= HASH_CHUNK_SIZE {
+        return hash_file(path, algorithm);
+    }
+    Ok(hash_file_chunked(path, algorithm, HASH_CHUNK_SIZE)?.composite(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_file;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_locate_and_repair_chunks() {
+        let dir = TempDir::new().unwrap();
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 253) as u8).collect();
+        let source = dir.path().join("source.bin");
+        std::fs::write(&source, &data).unwrap();
+
+        let (whole, chunks) = hash_file_with_chunks(&source, HashAlgorithm::Blake3, 1024).unwrap();
+        assert_eq!(whole, hash_file(&source, HashAlgorithm::Blake3).unwrap());
+        assert_eq!(chunks.hashes.len(), 10);
+        assert_eq!(chunks.range(9, 10_000), (9216, 784));
+
+        // Flip bytes in chunks 2, 3 and 7, then truncate the final chunk
+        let mut damaged = data.clone();
+        damaged[2100] ^= 0xff;
+        damaged[3500] ^= 0xff;
+        damaged[7200] ^= 0xff;
+        damaged.truncate(9500);
+        let dest = dir.path().join("dest.bin");
+        std::fs::write(&dest, &damaged).unwrap();
+
+        let bad = chunks.find_mismatches(&dest, 10_000).unwrap();
+        assert_eq!(bad, vec![2, 3, 7, 9]);
+        assert_eq!(chunks.ranges(&bad, 10_000), vec![(2048, 2048), (7168, 1024), (9216, 784)]);
+
+        let written = chunks.copy_chunks(&source, &dest, &bad, 10_000).unwrap();
+        assert_eq!(written, 2048 + 1024 + 784);
+        assert_eq!(std::fs::read(&dest).unwrap(), data);
+
+        // A source that changed since hashing is refused
+        std::fs::write(&source, vec![0u8; 10_000]).unwrap();
+        assert!(chunks.copy_chunks(&source, &dest, &[0], 10_000).is_err());
+    }
+
+    #[test]
+    fn test_composite_digest() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 241) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        // Parallel, sequential and uncached chunking agree
+        let parallel = hash_file_chunked(&path, HashAlgorithm::Sha256, 1024).unwrap();
+        let (whole, sequential) = hash_file_with_chunks(&path, HashAlgorithm::Sha256, 1024).unwrap();
+        assert_eq!(parallel, sequential);
+        assert_eq!(hash_file_chunks_uncached(&path, HashAlgorithm::Sha256, 1024).unwrap(), parallel);
+
+        let composite = parallel.composite(10_000);
+        assert!(composite.hash.starts_with("composite:1024:"));
+        assert_eq!(composite_chunk_size(&composite.hash), Some(1024));
+        assert_eq!(composite_chunk_size(&whole.hash), None);
+        assert!(!composite.verify(&whole));
+
+        // Parallel BLAKE3 is the standard digest
+        assert_eq!(
+            hash_file_parallel(&path, HashAlgorithm::Blake3).unwrap(),
+            hash_bytes(&data, HashAlgorithm::Blake3)
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Duplicate file detection
+//!
+//! Files are narrowed down in three passes: group by size, then by XXHash3
+//! of the contents ([`quick_hash`]), then confirm with BLAKE3
+//! ([`hash_files_parallel`]). A file with a unique size is never read.
+//! Every other file is read in full by the quick hash, and those whose
+//! quick hash matches another's are read in full again to confirm.
+//! Empty files are ignored; they take no space and linking them gains
+//! nothing.
+
+use crate::config::HashAlgorithm;
+use crate::fs::{escape_path, FileEntry};
+use crate::hash::{hash_files_parallel, quick_hash};
+use rayon::prelude::*;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Files with identical contents
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    /// Size of each file in bytes
+    pub size: u64,
+    /// BLAKE3 hash of the contents
+    pub hash: String,
+    /// Paths in byte order; the first is kept, the rest are duplicates
+    #[serde(serialize_with = "serialize_escaped")]
+    pub files: Vec<PathBuf>,
+}
+
+/// Serialize paths in escaped form so non-UTF-8 names survive JSON
+fn serialize_escaped<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(paths.iter().map(|p| escape_path(p)))
+}
+
+impl DuplicateGroup {
+    /// Bytes taken by the copies beyond the first
+    pub fn wasted_bytes(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+/// Result of a duplicate search
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DedupeReport {
+    /// Files examined
+    pub files_scanned: usize,
+    /// Total size of the files examined
+    pub bytes_scanned: u64,
+    /// Duplicate groups, most wasted space first
+    pub groups: Vec<DuplicateGroup>,
+    /// Files that could not be read (escaped path, error)
+    pub errors: Vec<(String, String)>,
+}
+
+impl DedupeReport {
+    /// Total bytes that linking the duplicates would save
+    pub fn wasted_bytes(&self) -> u64 {
+        self.groups.iter().map(DuplicateGroup::wasted_bytes).sum()
+    }
+
+    /// Number of files that duplicate another
+    pub fn duplicate_files(&self) -> usize {
+        self.groups.iter().map(|g| g.files.len() - 1).sum()
+    }
+
+    /// Print report to console
+    pub fn print_summary(&self) {
+        println!("=== Duplicate Report ===");
+        println!("Files scanned:   {}", self.files_scanned);
+        println!("Bytes scanned:   {}", humansize::format_size(self.bytes_scanned, humansize::BINARY));
+        println!("Groups:          {}", self.groups.len());
+        println!("Duplicates:      {}", self.duplicate_files());
+        println!("Wasted space:    {}", humansize::format_size(self.wasted_bytes(), humansize::BINARY));
+
+        for group in &self.groups {
+            println!(
+                "\n{} x {} ({} wasted)",
+                group.files.len(),
+                humansize::format_size(group.size, humansize::BINARY),
+                humansize::format_size(group.wasted_bytes(), humansize::BINARY)
+            );
+            for path in &group.files {
+                println!("  {}", escape_path(path));
+            }
+        }
+
+        if !self.errors.is_empty() {
+            println!("\nErrors: {}", self.errors.len());
+            for (path, error) in &self.errors {
+                println!("  {} - {}", path, error);
+            }
+        }
+    }
+}
+
+/// Find files with identical contents among scanned entries
+///
+/// Symlinks and special files are skipped.
+pub fn find_duplicates(files: &[FileEntry]) -> DedupeReport {
+    let mut report = DedupeReport::default();
+
+    let mut by_size: HashMap<u64, Vec<&Path>> = HashMap::new();
+    for entry in files.iter().filter(|e| !e.is_symlink && !e.is_special()) {
+        report.files_scanned += 1;
+        report.bytes_scanned += entry.size;
+        if entry.size > 0 {
+            by_size.entry(entry.size).or_default().push(&entry.path);
+        }
+    }
+
+    // Pass 2: quick hash of every file sharing its size with another
+    let candidates: Vec<(u64, &Path)> = by_size
+        .iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(&size, paths)| paths.iter().map(move |&p| (size, p)))
+        .collect();
+    let quick: Vec<_> = candidates
+        .par_iter()
+        .map(|&(size, path)| (size, path, quick_hash(path)))
+        .collect();
+
+    let mut by_quick: HashMap<(u64, u64), Vec<&Path>> = HashMap::new();
+    for (size, path, hash) in quick {
+        match hash {
+            Ok(hash) => by_quick.entry((size, hash)).or_default().push(path),
+            Err(e) => report.errors.push((escape_path(path), e.to_string())),
+        }
+    }
+
+    // Pass 3: confirm with BLAKE3
+    let candidates: Vec<(u64, &Path)> = by_quick
+        .iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(&(size, _), paths)| paths.iter().map(move |&p| (size, p)))
+        .collect();
+    let paths: Vec<&Path> = candidates.iter().map(|&(_, p)| p).collect();
+    let hashes = hash_files_parallel(&paths, HashAlgorithm::Blake3);
+
+    let mut by_hash: HashMap<(u64, String), Vec<PathBuf>> = HashMap::new();
+    for ((size, path), hash) in candidates.into_iter().zip(hashes) {
+        match hash {
+            Ok(hash) => by_hash.entry((size, hash.hash)).or_default().push(path.to_path_buf()),
+            Err(e) => report.errors.push((escape_path(path), e.to_string())),
+        }
+    }
+
+    report.groups = by_hash
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|((size, hash), mut files)| {
+            files.sort();
+            DuplicateGroup { size, hash, files }
+        })
+        .collect();
+    report.groups.sort_by(|a, b| {
+        b.wasted_bytes()
+            .cmp(&a.wasted_bytes())
+            .then_with(|| a.files[0].cmp(&b.files[0]))
+    });
+    report.errors.sort();
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{ScanConfig, Scanner};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_duplicates() {
+        let dir = TempDir::new().unwrap();
+        let frame = vec![7u8; 4096];
+        std::fs::write(dir.path().join("cal_1.fits"), &frame).unwrap();
+        std::fs::write(dir.path().join("cal_2.fits"), &frame).unwrap();
+        std::fs::create_dir(dir.path().join("run")).unwrap();
+        std::fs::write(dir.path().join("run/cal.fits"), &frame).unwrap();
+        // Same size, different contents
+        std::fs::write(dir.path().join("science.fits"), vec![8u8; 4096]).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"one").unwrap();
+        std::fs::write(dir.path().join("other.txt"), b"one").unwrap();
+        std::fs::write(dir.path().join("empty_1"), b"").unwrap();
+        std::fs::write(dir.path().join("empty_2"), b"").unwrap();
+
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(dir.path()).unwrap();
+        let report = find_duplicates(&scan.files);
+
+        assert_eq!(report.files_scanned, 8);
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.groups[0].size, 4096);
+        assert_eq!(
+            report.groups[0].files,
+            vec![
+                dir.path().join("cal_1.fits"),
+                dir.path().join("cal_2.fits"),
+                dir.path().join("run/cal.fits"),
+            ]
+        );
+        assert_eq!(report.groups[1].files.len(), 2);
+        assert_eq!(report.duplicate_files(), 3);
+        assert_eq!(report.wasted_bytes(), 2 * 4096 + 3);
+        assert!(report.errors.is_empty());
+    }
+}
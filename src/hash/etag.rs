@@ -0,0 +1,209 @@
+//! S3 ETag computation for local files
+//!
+//! A single-part upload's ETag is the MD5 of the object. A multipart
+//! upload's ETag is the MD5 of the concatenated binary MD5s of its parts,
+//! followed by `-` and the part count, so it can only be reproduced when
+//! the part size used for the upload is known. ETags of SSE-KMS/SSE-C
+//! objects are not content digests and never match.
+
+use crate::error::{IoResultExt, Result};
+use crate::fs::HashWriter;
+use md5::{Digest, Md5};
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Streaming hasher that reproduces a multipart upload ETag
+pub struct S3ETagHasher {
+    part_size: u64,
+    part: Md5,
+    part_filled: u64,
+    digests: Vec<u8>,
+    parts: u64,
+}
+
+impl S3ETagHasher {
+    /// Create a hasher for uploads split into `part_size`-byte parts
+    pub fn new(part_size: u64) -> Self {
+        Self {
+            part_size: part_size.max(1),
+            part: Md5::new(),
+            part_filled: 0,
+            digests: Vec::new(),
+            parts: 0,
+        }
+    }
+
+    /// Process more data
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = ((self.part_size - self.part_filled) as usize).min(data.len());
+            self.part.update(&data[..take]);
+            self.part_filled += take as u64;
+            data = &data[take..];
+            if self.part_filled == self.part_size {
+                self.finish_part();
+            }
+        }
+    }
+
+    fn finish_part(&mut self) {
+        let part = std::mem::replace(&mut self.part, Md5::new());
+        self.digests.extend_from_slice(&part.finalize());
+        self.part_filled = 0;
+        self.parts += 1;
+    }
+
+    /// Finalize as `<hex>-<parts>`
+    pub fn finalize(mut self) -> String {
+        if self.part_filled > 0 || self.parts == 0 {
+            self.finish_part();
+        }
+        format!("{}-{}", hex::encode(Md5::digest(&self.digests)), self.parts)
+    }
+}
+
+impl HashWriter for S3ETagHasher {
+    fn update(&mut self, data: &[u8]) {
+        S3ETagHasher::update(self, data);
+    }
+}
+
+/// Compute the ETag S3 assigns to `path` uploaded in `part_size`-byte parts
+pub fn s3_multipart_etag(path: &Path, part_size: u64) -> Result<String> {
+    let mut file = File::open(path).with_path(path)?;
+    let mut hasher = S3ETagHasher::new(part_size);
+    let mut buffer = vec![0u8; 1024 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).with_path(path)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// An ETag as listed by S3, without surrounding quotes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3ETag {
+    /// Lowercase hex MD5 (of the object, or of the part digests)
+    pub digest: String,
+    /// Part count of a multipart ETag
+    pub parts: Option<u64>,
+}
+
+impl S3ETag {
+    /// Parse `"<md5>"` or `"<md5>-<parts>"`; other forms return `None`
+    pub fn parse(etag: &str) -> Option<Self> {
+        let etag = etag.trim().trim_matches('"');
+        let (digest, parts) = match etag.split_once('-') {
+            Some((digest, parts)) => (digest, Some(parts.parse().ok().filter(|&n| n > 0)?)),
+            None => (etag, None),
+        };
+        if digest.len() != 32 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(Self {
+            digest: digest.to_ascii_lowercase(),
+            parts,
+        })
+    }
+}
+
+/// Outcome of comparing a local file with an S3 ETag
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ETagCheck {
+    /// The file produces the listed ETag
+    Match,
+    /// The file produces a different ETag
+    Mismatch {
+        /// Listed ETag
+        expected: String,
+        /// ETag computed from the local file
+        actual: String,
+    },
+    /// The ETag can't be reproduced (reason)
+    Unverifiable(String),
+}
+
+/// Compare a local file with an S3 ETag
+///
+/// Multipart ETags need the upload's part size; without it, or when it
+/// doesn't give the listed part count, the result is
+/// [`ETagCheck::Unverifiable`].
+pub fn verify_etag(path: &Path, etag: &str, part_size: Option<u64>) -> Result<ETagCheck> {
+    let Some(parsed) = S3ETag::parse(etag) else {
+        return Ok(ETagCheck::Unverifiable(format!("{} is not an MD5-based ETag", etag)));
+    };
+
+    let actual = match parsed.parts {
+        None => crate::hash::hash_file(path, crate::config::HashAlgorithm::Md5)?.hash,
+        Some(parts) => {
+            let Some(part_size) = part_size else {
+                return Ok(ETagCheck::Unverifiable(format!(
+                    "multipart ETag with {} parts; part size unknown",
+                    parts
+                )));
+            };
+            let size = std::fs::metadata(path).with_path(path)?.len();
+            let expected_parts = size.div_ceil(part_size).max(1);
+            if expected_parts != parts {
+                return Ok(ETagCheck::Unverifiable(format!(
+                    "multipart ETag with {} parts; {}-byte parts give {}",
+                    parts, part_size, expected_parts
+                )));
+            }
+            s3_multipart_etag(path, part_size)?
+        }
+    };
+
+    let expected = match parsed.parts {
+        Some(parts) => format!("{}-{}", parsed.digest, parts),
+        None => parsed.digest,
+    };
+    if actual == expected {
+        Ok(ETagCheck::Match)
+    } else {
+        Ok(ETagCheck::Mismatch { expected, actual })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_multipart_etag() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("object.bin");
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 256) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        // MD5 of the part MD5s, computed by hand
+        let mut digests = Vec::new();
+        for part in data.chunks(1000) {
+            digests.extend_from_slice(&Md5::digest(part));
+        }
+        let expected = format!("{}-3", hex::encode(Md5::digest(&digests)));
+        assert_eq!(s3_multipart_etag(&path, 1000).unwrap(), expected);
+
+        let quoted = format!("\"{}\"", expected);
+        assert_eq!(verify_etag(&path, &quoted, Some(1000)).unwrap(), ETagCheck::Match);
+        assert!(matches!(verify_etag(&path, &quoted, None).unwrap(), ETagCheck::Unverifiable(_)));
+        assert!(matches!(verify_etag(&path, &quoted, Some(500)).unwrap(), ETagCheck::Unverifiable(_)));
+
+        // Single-part ETags are the plain MD5
+        let md5 = hex::encode(Md5::digest(&data));
+        assert_eq!(verify_etag(&path, &md5, None).unwrap(), ETagCheck::Match);
+        std::fs::write(&path, b"changed").unwrap();
+        assert!(matches!(verify_etag(&path, &md5, None).unwrap(), ETagCheck::Mismatch { .. }));
+
+        assert_eq!(S3ETag::parse("\"ABCDEF0123456789ABCDEF0123456789-12\"").unwrap().parts, Some(12));
+        assert!(S3ETag::parse("not-an-etag").is_none());
+    }
+}
@@ -5,13 +5,17 @@
 
 use crate::config::HashAlgorithm;
 use crate::error::{IoResultExt, Result, SmartCopyError};
-use crate::fs::HashWriter;
+use crate::fs::{escape_path, unescape_path, HashWriter, ScanResult};
+use crate::hash::{hash_file_cdc, hash_file_with_chunks, ChunkHashes, ManifestSignature, HASH_CHUNK_SIZE};
+use crate::sync::CdcParams;
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use rayon::prelude::*;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::hash::Hasher as StdHasher;
 use std::io::{BufReader, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Hash result as hex string
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -52,6 +56,12 @@ pub enum Hasher {
     Blake3(blake3::Hasher),
     /// SHA-256
     Sha256(sha2::Sha256),
+    /// CRC32C (running value)
+    Crc32c(u32),
+    /// MD5
+    Md5(md5::Md5),
+    /// SHA-512
+    Sha512(sha2::Sha512),
 }
 
 impl Hasher {
@@ -65,6 +75,15 @@ impl Hasher {
                 use sha2::Digest;
                 Self::Sha256(sha2::Sha256::new())
             }
+            HashAlgorithm::Crc32c => Self::Crc32c(0),
+            HashAlgorithm::Md5 => {
+                use md5::Digest;
+                Self::Md5(md5::Md5::new())
+            }
+            HashAlgorithm::Sha512 => {
+                use sha2::Digest;
+                Self::Sha512(sha2::Sha512::new())
+            }
         }
     }
 
@@ -75,6 +94,9 @@ impl Hasher {
             Self::XXHash64(_) => HashAlgorithm::XXHash64,
             Self::Blake3(_) => HashAlgorithm::Blake3,
             Self::Sha256(_) => HashAlgorithm::Sha256,
+            Self::Crc32c(_) => HashAlgorithm::Crc32c,
+            Self::Md5(_) => HashAlgorithm::Md5,
+            Self::Sha512(_) => HashAlgorithm::Sha512,
         }
     }
 
@@ -83,11 +105,21 @@ impl Hasher {
         match self {
             Self::XXHash3(h) => h.update(data),
             Self::XXHash64(h) => h.update(data),
+            Self::Blake3(h) if data.len() >= BLAKE3_PARALLEL_MIN => { h.update_rayon(data); }
             Self::Blake3(h) => { h.update(data); }
             Self::Sha256(h) => {
                 use sha2::Digest;
                 h.update(data);
             }
+            Self::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, data),
+            Self::Md5(h) => {
+                use md5::Digest;
+                h.update(data);
+            }
+            Self::Sha512(h) => {
+                use sha2::Digest;
+                h.update(data);
+            }
         }
     }
 
@@ -102,6 +134,15 @@ impl Hasher {
                 let result = h.finalize();
                 hex::encode(result)
             }
+            Self::Crc32c(crc) => format!("{:08x}", crc),
+            Self::Md5(h) => {
+                use md5::Digest;
+                hex::encode(h.finalize())
+            }
+            Self::Sha512(h) => {
+                use sha2::Digest;
+                hex::encode(h.finalize())
+            }
         }
     }
 
@@ -115,6 +156,9 @@ impl Hasher {
                 
                 sha2::Digest::reset(h);
             }
+            Self::Crc32c(crc) => *crc = 0,
+            Self::Md5(h) => md5::Digest::reset(h),
+            Self::Sha512(h) => sha2::Digest::reset(h),
         }
     }
 }
@@ -125,7 +169,14 @@ impl HashWriter for Hasher {
     }
 }
 
+/// Inputs at least this large are hashed with BLAKE3's multithreaded mode
+///
+/// Below this the cost of splitting the work exceeds the gain.
+const BLAKE3_PARALLEL_MIN: usize = 128 * 1024;
+
 /// Compute hash of a file
+///
+/// BLAKE3 hashes large files on every core; the digest is unchanged.
 pub fn hash_file(path: &Path, algorithm: HashAlgorithm) -> Result<HashResult> {
     hash_file_with_buffer(path, algorithm, 1024 * 1024) // 1MB buffer
 }
@@ -138,6 +189,11 @@ pub fn hash_file_with_buffer(
 ) -> Result<HashResult> {
     let file = File::open(path).with_path(path)?;
     let size = file.metadata().with_path(path)?.len();
+    if algorithm == HashAlgorithm::Blake3 && size >= BLAKE3_PARALLEL_MIN as u64 {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_mmap_rayon(path).with_path(path)?;
+        return Ok(HashResult::new(algorithm, hasher.finalize().to_hex().to_string(), size));
+    }
     let mut reader = BufReader::with_capacity(buffer_size, file);
     let mut hasher = Hasher::new(algorithm);
     let mut buffer = vec![0u8; buffer_size];
@@ -156,6 +212,15 @@ pub fn hash_file_with_buffer(
     Ok(HashResult::new(algorithm, hasher.finalize(), size))
 }
 
+/// Compute hash of a file as stored, bypassing the page cache
+///
+/// See [`crate::fs::read_uncached`].
+pub fn hash_file_uncached(path: &Path, algorithm: HashAlgorithm) -> Result<HashResult> {
+    let mut hasher = Hasher::new(algorithm);
+    let size = crate::fs::read_uncached(path, |chunk| hasher.update(chunk))?;
+    Ok(HashResult::new(algorithm, hasher.finalize(), size))
+}
+
 /// Compute hash of data in memory
 pub fn hash_bytes(data: &[u8], algorithm: HashAlgorithm) -> HashResult {
     let mut hasher = Hasher::new(algorithm);
@@ -248,10 +313,22 @@ impl HashWriter for StreamingHasher {
     }
 }
 
+/// Magic bytes of a binary hash manifest
+pub const HASH_MANIFEST_MAGIC: &[u8; 8] = b"SCPYHMF\0";
+
+/// Layout version written to binary hash manifests
+///
+/// bincode is not self-describing, so a binary manifest starts with
+/// [`HASH_MANIFEST_MAGIC`] and this version; bump it whenever a field is
+/// added to [`HashManifest`], [`FileHashEntry`] or [`ChunkHashes`].
+/// Manifests from before the header have the original layout, which is
+/// still read.
+pub const HASH_MANIFEST_BINARY_VERSION: u32 = 1;
+
 /// Hash entry for manifest files
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileHashEntry {
-    /// Relative path
+    /// Relative path (escaped form, see [`escape_path`])
     pub path: String,
     /// File size
     pub size: u64,
@@ -261,6 +338,19 @@ pub struct FileHashEntry {
     pub algorithm: HashAlgorithm,
     /// Modification time (Unix timestamp)
     pub mtime: u64,
+    /// When the contents last matched on re-read (Unix timestamp)
+    #[serde(default)]
+    pub last_verified: Option<u64>,
+    /// Per-chunk hashes, recorded for files larger than one chunk
+    #[serde(default)]
+    pub chunks: Option<ChunkHashes>,
+}
+
+impl FileHashEntry {
+    /// Get the relative path, including non-UTF-8 names
+    pub fn to_path(&self) -> PathBuf {
+        unescape_path(&self.path)
+    }
 }
 
 /// Collection of file hashes (manifest)
@@ -274,9 +364,39 @@ pub struct HashManifest {
     pub root: String,
     /// File entries
     pub entries: Vec<FileHashEntry>,
+    /// Ed25519 signature over root, algorithm and entries
+    #[serde(default)]
+    pub signature: Option<ManifestSignature>,
+}
+
+/// Chunk statistics of a manifest (see [`HashManifest::chunk_stats`])
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ChunkStats {
+    /// Chunks across all files
+    pub chunks: u64,
+    /// Distinct chunks
+    pub unique_chunks: u64,
+    /// Bytes covered by chunks
+    pub bytes: u64,
+    /// Bytes in distinct chunks
+    pub unique_bytes: u64,
+}
+
+/// Signed fields of a [`FileHashEntry`] (everything but `last_verified`)
+#[derive(Serialize)]
+struct SignedHashEntry<'a> {
+    path: &'a str,
+    size: u64,
+    hash: &'a str,
+    algorithm: HashAlgorithm,
+    mtime: u64,
+    chunks: &'a Option<ChunkHashes>,
 }
 
 impl HashManifest {
+    /// Domain tag of hash manifest signatures
+    const SIGNATURE_DOMAIN: &'static str = "smartcopy-hash-manifest-v1";
+
     /// Create a new empty manifest
     pub fn new(algorithm: HashAlgorithm, root: &str) -> Self {
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -289,9 +409,132 @@ impl HashManifest {
                 .unwrap_or(0),
             root: root.to_string(),
             entries: Vec::new(),
+            signature: None,
         }
     }
 
+    fn signed_payload(&self) -> Result<Vec<u8>> {
+        let entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|e| SignedHashEntry {
+                path: &e.path,
+                size: e.size,
+                hash: &e.hash,
+                algorithm: e.algorithm,
+                mtime: e.mtime,
+                chunks: &e.chunks,
+            })
+            .collect();
+        crate::hash::canonical_json(&(&self.root, self.algorithm, entries))
+    }
+
+    /// Sign root, algorithm and entries, replacing any previous signature
+    pub fn sign(&mut self, key: &SigningKey) -> Result<()> {
+        let payload = self.signed_payload()?;
+        self.signature = Some(ManifestSignature::sign(key, Self::SIGNATURE_DOMAIN, &payload));
+        Ok(())
+    }
+
+    /// Check that the manifest is signed by `key` and unchanged since
+    pub fn verify_signature(&self, key: &VerifyingKey) -> Result<()> {
+        let signature = self.signature.as_ref().ok_or_else(crate::hash::unsigned_manifest)?;
+        signature.verify(key, Self::SIGNATURE_DOMAIN, &self.signed_payload()?)
+    }
+
+    /// Hash every file of a scan in parallel
+    ///
+    /// Symlinks and special files are left out. Files larger than
+    /// [`HASH_CHUNK_SIZE`] also get per-chunk hashes.
+    pub fn from_scan(scan: &ScanResult, algorithm: HashAlgorithm) -> Result<Self> {
+        Self::from_scan_with_chunks(scan, algorithm, HASH_CHUNK_SIZE)
+    }
+
+    /// Like [`Self::from_scan`], with per-chunk hashes for files larger
+    /// than `chunk_size`
+    pub fn from_scan_with_chunks(
+        scan: &ScanResult,
+        algorithm: HashAlgorithm,
+        chunk_size: u64,
+    ) -> Result<Self> {
+        Self::from_scan_by(scan, algorithm, |path, size| {
+            if size > chunk_size {
+                let (hash, chunks) = hash_file_with_chunks(path, algorithm, chunk_size)?;
+                Ok((hash, Some(chunks)))
+            } else {
+                Ok((hash_file(path, algorithm)?, None))
+            }
+        })
+    }
+
+    /// Like [`Self::from_scan`], with content-defined chunk hashes for
+    /// every file
+    ///
+    /// Chunk hashes then identify content across files and versions (see
+    /// [`Self::chunk_stats`]).
+    pub fn from_scan_with_cdc(scan: &ScanResult, algorithm: HashAlgorithm, params: CdcParams) -> Result<Self> {
+        params.validate()?;
+        Self::from_scan_by(scan, algorithm, |path, _| {
+            let (hash, chunks) = hash_file_cdc(path, algorithm, params)?;
+            Ok((hash, Some(chunks)))
+        })
+    }
+
+    fn from_scan_by<F>(scan: &ScanResult, algorithm: HashAlgorithm, hash: F) -> Result<Self>
+    where
+        F: Fn(&Path, u64) -> Result<(HashResult, Option<ChunkHashes>)> + Sync,
+    {
+        let mut manifest = Self::new(algorithm, &escape_path(&scan.root));
+
+        manifest.entries = scan
+            .files
+            .par_iter()
+            .filter(|f| !f.is_symlink && !f.is_special())
+            .map(|f| {
+                let (hash, chunks) = hash(&f.path, f.size)?;
+                Ok(FileHashEntry {
+                    path: escape_path(&f.relative_path),
+                    size: hash.size,
+                    hash: hash.hash,
+                    algorithm,
+                    mtime: f
+                        .modified
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0),
+                    last_verified: None,
+                    chunks,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        manifest.entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(manifest)
+    }
+
+    /// Chunk counts and sizes, counting repeated chunks once
+    ///
+    /// Chunks are identified by hash and length, so with content-defined
+    /// chunks this measures how much content files and versions share.
+    pub fn chunk_stats(&self) -> ChunkStats {
+        let mut stats = ChunkStats::default();
+        let mut seen = std::collections::HashSet::new();
+        for entry in &self.entries {
+            let Some(chunks) = &entry.chunks else {
+                continue;
+            };
+            for (hash, (_, len)) in chunks.hashes.iter().zip(chunks.spans(entry.size)) {
+                stats.chunks += 1;
+                stats.bytes += len;
+                if seen.insert((hash.as_str(), len)) {
+                    stats.unique_chunks += 1;
+                    stats.unique_bytes += len;
+                }
+            }
+        }
+        stats
+    }
+
     /// Add a file entry
     pub fn add_entry(&mut self, entry: FileHashEntry) {
         self.entries.push(entry);
@@ -302,6 +545,11 @@ impl HashManifest {
         self.entries.iter().find(|e| e.path == path)
     }
 
+    /// Find entry by filesystem path (exact, including non-UTF-8 names)
+    pub fn find_path(&self, path: &Path) -> Option<&FileHashEntry> {
+        self.find_entry(&escape_path(path))
+    }
+
     /// Save manifest to JSON file
     pub fn save(&self, path: &Path) -> Result<()> {
         let json = serde_json::to_string_pretty(self)
@@ -316,6 +564,196 @@ impl HashManifest {
         serde_json::from_str(&json)
             .map_err(|e| SmartCopyError::ManifestError(e.to_string()))
     }
+
+    /// Save manifest to file (binary/bincode - more compact)
+    pub fn save_binary(&self, path: &Path) -> Result<()> {
+        let data = encode_binary_manifest(HASH_MANIFEST_MAGIC, HASH_MANIFEST_BINARY_VERSION, self)?;
+        std::fs::write(path, data).with_path(path)?;
+        Ok(())
+    }
+
+    /// Load manifest from binary file, including manifests written before
+    /// binary manifests had a version header
+    pub fn load_binary(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_path(path)?;
+        match binary_manifest_payload(&data, HASH_MANIFEST_MAGIC, HASH_MANIFEST_BINARY_VERSION)? {
+            Some(payload) => bincode::deserialize(payload)
+                .map_err(|e| SmartCopyError::ManifestError(e.to_string())),
+            None => decode_exact::<UnversionedHashManifest>(&data)
+                .map(UnversionedHashManifest::into_manifest)
+                .ok_or_else(|| {
+                    SmartCopyError::ManifestError(format!("{}: not a readable hash manifest", path.display()))
+                }),
+        }
+    }
+
+    /// Load a JSON, bincode or Parquet manifest, detected from its contents
+    ///
+    /// Parquet manifests need the `parquet_manifest` feature.
+    pub fn load_any(path: &Path) -> Result<Self> {
+        match HashManifestFormat::detect(path)? {
+            HashManifestFormat::Json => Self::load(path),
+            HashManifestFormat::Binary => Self::load_binary(path),
+            HashManifestFormat::Parquet => Self::load_parquet(path),
+        }
+    }
+
+    /// Write the manifest back in the given format, replacing the file
+    /// atomically
+    ///
+    /// A Parquet manifest carries sync metadata this type doesn't hold, so
+    /// only its `last_verified` column is updated.
+    pub fn store(&self, path: &Path, format: HashManifestFormat) -> Result<()> {
+        if format == HashManifestFormat::Parquet {
+            return self.store_parquet(path);
+        }
+
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        match format {
+            HashManifestFormat::Json => self.save(&tmp)?,
+            _ => self.save_binary(&tmp)?,
+        }
+        std::fs::rename(&tmp, path).with_path(path)
+    }
+
+    #[cfg(feature = "parquet_manifest")]
+    fn store_parquet(&self, path: &Path) -> Result<()> {
+        let verified = self
+            .entries
+            .iter()
+            .filter_map(|e| e.last_verified.map(|t| (e.path.clone(), t)))
+            .collect();
+        crate::sync::parquet_manifest::ParquetManifestReader::update_last_verified(path, &verified)
+            .map(|_| ())
+            .map_err(|e| SmartCopyError::ManifestError(e.to_string()))
+    }
+
+    #[cfg(not(feature = "parquet_manifest"))]
+    fn store_parquet(&self, path: &Path) -> Result<()> {
+        Self::load_parquet(path).map(|_| ())
+    }
+
+    #[cfg(feature = "parquet_manifest")]
+    fn load_parquet(path: &Path) -> Result<Self> {
+        crate::sync::parquet_manifest::ParquetManifestReader::read_hash_manifest(path)
+            .map_err(|e| SmartCopyError::ManifestError(e.to_string()))
+    }
+
+    #[cfg(not(feature = "parquet_manifest"))]
+    fn load_parquet(_path: &Path) -> Result<Self> {
+        Err(SmartCopyError::ManifestError(
+            "Parquet manifests require the parquet_manifest feature".to_string(),
+        ))
+    }
+}
+
+/// On-disk encoding of a [`HashManifest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashManifestFormat {
+    /// Pretty-printed JSON
+    Json,
+    /// bincode
+    Binary,
+    /// Parquet sync manifest (feature `parquet_manifest`)
+    Parquet,
+}
+
+impl HashManifestFormat {
+    /// Detect the format of a manifest file from its first bytes
+    pub fn detect(path: &Path) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        let read = File::open(path)
+            .and_then(|mut f| f.read(&mut magic))
+            .with_path(path)?;
+        let magic = &magic[..read];
+
+        if magic == b"PAR1" {
+            return Ok(Self::Parquet);
+        }
+        match magic.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => Ok(Self::Json),
+            _ => Ok(Self::Binary),
+        }
+    }
+}
+
+/// Encode a binary manifest: `magic`, `version` (little-endian `u32`) and
+/// the bincode payload
+pub(crate) fn encode_binary_manifest<T: Serialize>(magic: &[u8; 8], version: u32, value: &T) -> Result<Vec<u8>> {
+    let mut data = magic.to_vec();
+    data.extend_from_slice(&version.to_le_bytes());
+    bincode::serialize_into(&mut data, value)
+        .map_err(|e| SmartCopyError::ManifestError(e.to_string()))?;
+    Ok(data)
+}
+
+/// Payload of a binary manifest written by [`encode_binary_manifest`],
+/// or `None` if `data` has no header
+pub(crate) fn binary_manifest_payload<'a>(data: &'a [u8], magic: &[u8; 8], current: u32) -> Result<Option<&'a [u8]>> {
+    if data.len() < 12 || &data[..8] != magic {
+        return Ok(None);
+    }
+    let version = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
+    if version > current {
+        return Err(SmartCopyError::ManifestError(format!(
+            "manifest layout version {} is newer than this build reads ({})",
+            version, current
+        )));
+    }
+    Ok(Some(&data[12..]))
+}
+
+/// Decode bincode that must use up all of `data`, for reading manifests
+/// without a header
+pub(crate) fn decode_exact<T: DeserializeOwned>(data: &[u8]) -> Option<T> {
+    use bincode::Options;
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(data)
+        .ok()
+}
+
+/// Entry of a binary hash manifest written before the version header
+#[derive(Deserialize)]
+struct UnversionedHashEntry {
+    path: String,
+    size: u64,
+    hash: String,
+    algorithm: HashAlgorithm,
+    mtime: u64,
+}
+
+/// Binary hash manifest written before the version header
+#[derive(Deserialize)]
+struct UnversionedHashManifest {
+    algorithm: HashAlgorithm,
+    created: u64,
+    root: String,
+    entries: Vec<UnversionedHashEntry>,
+}
+
+impl UnversionedHashManifest {
+    fn into_manifest(self) -> HashManifest {
+        let entries = self.entries.into_iter().map(|e| FileHashEntry {
+            path: e.path,
+            size: e.size,
+            hash: e.hash,
+            algorithm: e.algorithm,
+            mtime: e.mtime,
+            last_verified: None,
+            chunks: None,
+        });
+        HashManifest {
+            algorithm: self.algorithm,
+            created: self.created,
+            root: self.root,
+            entries: entries.collect(),
+            signature: None,
+        }
+    }
 }
 
 /// Quick hash for change detection (uses XXHash3 by default)
@@ -349,6 +787,9 @@ pub fn benchmark_algorithms(data_size: usize) -> Vec<(HashAlgorithm, std::time::
         HashAlgorithm::XXHash64,
         HashAlgorithm::Blake3,
         HashAlgorithm::Sha256,
+        HashAlgorithm::Crc32c,
+        HashAlgorithm::Md5,
+        HashAlgorithm::Sha512,
     ] {
         let start = std::time::Instant::now();
         let iterations = 10;
@@ -400,6 +841,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_known_digests() {
+        // Reference values: RFC 3720 check value, md5sum and sha512sum of "abc"
+        assert_eq!(hash_bytes(b"123456789", HashAlgorithm::Crc32c).hash, "e3069283");
+        assert_eq!(hash_bytes(b"abc", HashAlgorithm::Md5).hash, "900150983cd24fb0d6963f7d28e17f72");
+        assert!(hash_bytes(b"abc", HashAlgorithm::Sha512).hash.starts_with("ddaf35a193617aba"));
+
+        // Streaming in pieces matches one-shot hashing
+        let mut hasher = Hasher::new(HashAlgorithm::Crc32c);
+        hasher.update(b"1234");
+        hasher.update(b"56789");
+        assert_eq!(hasher.finalize(), "e3069283");
+    }
+
     #[test]
     fn test_hash_file() {
         let dir = TempDir::new().unwrap();
@@ -445,6 +900,33 @@ mod tests {
         assert_eq!(result.hash, direct.hash);
     }
 
+    #[test]
+    fn test_load_unversioned_binary_manifest() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("old.bin");
+
+        // Written before binary manifests had a header
+        let entry = ("a.txt", 5u64, "abc123", HashAlgorithm::Blake3, 7u64);
+        let original = (HashAlgorithm::Blake3, 42u64, "/root", vec![entry]);
+        std::fs::write(&path, bincode::serialize(&original).unwrap()).unwrap();
+        let loaded = HashManifest::load_any(&path).unwrap();
+        assert_eq!((loaded.created, loaded.root.as_str()), (42, "/root"));
+        assert_eq!(loaded.entries[0].hash, "abc123");
+        assert_eq!(loaded.entries[0].mtime, 7);
+        assert_eq!(loaded.entries[0].last_verified, None);
+
+        // New files carry the header and round-trip
+        let mut manifest = loaded;
+        manifest.entries[0].last_verified = Some(99);
+        manifest.save_binary(&path).unwrap();
+        assert!(std::fs::read(&path).unwrap().starts_with(HASH_MANIFEST_MAGIC));
+        assert_eq!(HashManifestFormat::detect(&path).unwrap(), HashManifestFormat::Binary);
+        assert_eq!(HashManifest::load_any(&path).unwrap().entries[0].last_verified, Some(99));
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert!(HashManifest::load_binary(&path).is_err());
+    }
+
     #[test]
     fn test_manifest() {
         let dir = TempDir::new().unwrap();
@@ -456,6 +938,8 @@ mod tests {
             hash: "abc123".to_string(),
             algorithm: HashAlgorithm::Blake3,
             mtime: 1234567890,
+            last_verified: None,
+            chunks: None,
         });
 
         let manifest_path = dir.path().join("manifest.json");
@@ -466,6 +950,114 @@ mod tests {
         assert_eq!(loaded.entries[0].path, "file1.txt");
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_manifest_non_utf8_path() {
+        let dir = TempDir::new().unwrap();
+        let name = crate::fs::path_from_bytes(b"raw/\xff\xfe.bin");
+        let mut manifest = HashManifest::new(HashAlgorithm::Blake3, "/test/root");
+
+        manifest.add_entry(FileHashEntry {
+            path: escape_path(&name),
+            size: 1,
+            hash: "00".to_string(),
+            algorithm: HashAlgorithm::Blake3,
+            mtime: 0,
+            last_verified: None,
+            chunks: None,
+        });
+
+        let manifest_path = dir.path().join("manifest.json");
+        manifest.save(&manifest_path).unwrap();
+
+        let loaded = HashManifest::load(&manifest_path).unwrap();
+        assert_eq!(loaded.find_path(&name).unwrap().to_path(), name);
+    }
+
+    #[test]
+    fn test_signed_manifest() {
+        let dir = TempDir::new().unwrap();
+        let mut manifest = HashManifest::new(HashAlgorithm::Sha256, "/test/root");
+        for (path, hash) in [("a.dat", "aa"), ("b.dat", "bb")] {
+            manifest.add_entry(FileHashEntry {
+                path: path.to_string(),
+                size: 2,
+                hash: hash.to_string(),
+                algorithm: HashAlgorithm::Sha256,
+                mtime: 0,
+                last_verified: None,
+                chunks: None,
+            });
+        }
+        let key = SigningKey::from_bytes(&[1u8; 32]);
+        let public = key.verifying_key();
+        assert!(manifest.verify_signature(&public).is_err());
+        manifest.sign(&key).unwrap();
+
+        let manifest_path = dir.path().join("manifest.json");
+        manifest.save(&manifest_path).unwrap();
+        let mut loaded = HashManifest::load(&manifest_path).unwrap();
+        loaded.verify_signature(&public).unwrap();
+
+        // Scrub bookkeeping is not covered
+        loaded.entries[0].last_verified = Some(1);
+        loaded.verify_signature(&public).unwrap();
+
+        let mut tampered = loaded.clone();
+        tampered.entries[1].hash = "cc".to_string();
+        assert!(tampered.verify_signature(&public).is_err());
+        let mut tampered = loaded.clone();
+        tampered.entries.pop();
+        assert!(tampered.verify_signature(&public).is_err());
+        let mut tampered = loaded.clone();
+        tampered.entries.swap(0, 1);
+        assert!(tampered.verify_signature(&public).is_err());
+        let mut tampered = loaded.clone();
+        tampered.root = "/other".to_string();
+        assert!(tampered.verify_signature(&public).is_err());
+        let mut tampered = loaded;
+        tampered.algorithm = HashAlgorithm::Md5;
+        assert!(tampered.verify_signature(&public).is_err());
+    }
+
+    #[test]
+    fn test_cdc_manifest_shares_chunks() {
+        use crate::fs::{ScanConfig, Scanner};
+
+        let dir = TempDir::new().unwrap();
+        let mut state = 3u64;
+        let v1: Vec<u8> = (0..300_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 56) as u8
+            })
+            .collect();
+        let mut v2 = v1.clone();
+        v2.splice(150_000..150_000, b"inserted".iter().copied());
+        std::fs::write(dir.path().join("v1.dat"), &v1).unwrap();
+        std::fs::write(dir.path().join("v2.dat"), &v2).unwrap();
+
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(dir.path()).unwrap();
+        let params = CdcParams::new(1024, 4096, 16384).unwrap();
+        let manifest = HashManifest::from_scan_with_cdc(&scan, HashAlgorithm::Blake3, params).unwrap();
+        assert_eq!(manifest.entries[0].hash, hash_bytes(&v1, HashAlgorithm::Blake3).hash);
+
+        // The second version only adds the chunks around the insertion
+        let stats = manifest.chunk_stats();
+        assert_eq!(stats.bytes, (v1.len() + v2.len()) as u64);
+        assert!(stats.unique_bytes < v1.len() as u64 + 2 * 16384 + 8, "{:?}", stats);
+
+        // Variable-length chunks still locate damage
+        let chunks = manifest.entries[1].chunks.as_ref().unwrap();
+        let mut damaged = v2.clone();
+        damaged[250_000] ^= 1;
+        std::fs::write(dir.path().join("v2.dat"), &damaged).unwrap();
+        let bad = chunks.find_mismatches(&dir.path().join("v2.dat"), v2.len() as u64).unwrap();
+        assert_eq!(bad.len(), 1);
+        let (offset, len) = chunks.range(bad[0], v2.len() as u64);
+        assert!(offset <= 250_000 && 250_000 < offset + len);
+    }
+
     #[test]
     fn test_quick_hash() {
         let dir = TempDir::new().unwrap();
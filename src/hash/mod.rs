@@ -4,5 +4,21 @@
 //! with streaming support for single-pass copy-and-hash operations.
 
 mod integrity;
+mod dedupe;
+mod verify;
+mod checksums;
+mod scrub;
+mod chunks;
+mod repair;
+mod etag;
+mod signing;
 
 pub use integrity::*;
+pub use dedupe::*;
+pub use verify::*;
+pub use checksums::*;
+pub use scrub::*;
+pub use chunks::*;
+pub use repair::*;
+pub use etag::*;
+pub use signing::*;
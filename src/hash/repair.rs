@@ -0,0 +1,206 @@
+//! Repair of a destination tree from its source using a hash manifest
+//!
+//! Files that no longer match the manifest are fixed from the source.
+//! When the manifest holds chunk hashes for a file, only the chunks that
+//! differ are re-copied; other files are copied whole. Every source read
+//! is checked against the manifest, so a source that changed since the
+//! manifest was written is reported instead of copied, and every repaired
+//! file is re-verified.
+
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use crate::hash::{hash_file, FileHashEntry, HashManifest};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Outcome of a repair run
+///
+/// Paths are in escaped form (see [`crate::fs::escape_path`]).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepairReport {
+    /// Files that already matched
+    pub ok: u64,
+    /// Files fixed by re-copying chunks (path, chunks re-copied)
+    pub repaired: Vec<(String, usize)>,
+    /// Files copied whole
+    pub recopied: Vec<String>,
+    /// Bytes written to the destination
+    pub bytes_copied: u64,
+    /// Files that could not be repaired (path, error)
+    pub failures: Vec<(String, String)>,
+}
+
+impl RepairReport {
+    /// Whether every listed file now matches the manifest
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Print report to console
+    pub fn print_summary(&self) {
+        println!("=== Repair Report ===");
+        println!("OK:              {}", self.ok);
+        println!("Chunk repaired:  {}", self.repaired.len());
+        println!("Re-copied:       {}", self.recopied.len());
+        println!("Bytes written:   {}", humansize::format_size(self.bytes_copied, humansize::BINARY));
+        println!("Failures:        {}", self.failures.len());
+
+        if !self.repaired.is_empty() {
+            println!("\nRepaired files:");
+            for (path, chunks) in &self.repaired {
+                println!("  {} ({} chunks)", path, chunks);
+            }
+        }
+        if !self.recopied.is_empty() {
+            println!("\nRe-copied files:");
+            for path in &self.recopied {
+                println!("  {}", path);
+            }
+        }
+        if !self.failures.is_empty() {
+            println!("\nFailures:");
+            for (path, error) in &self.failures {
+                println!("  {} - {}", path, error);
+            }
+        }
+    }
+}
+
+enum RepairOutcome {
+    Ok,
+    Repaired { chunks: usize, bytes: u64 },
+    Recopied { bytes: u64 },
+}
+
+/// Bring the files of `manifest` under `dest_root` back in line from
+/// `source_root`
+pub fn repair_from_manifest(
+    manifest: &HashManifest,
+    source_root: &Path,
+    dest_root: &Path,
+) -> Result<RepairReport> {
+    let outcomes: Vec<Result<RepairOutcome>> = manifest
+        .entries
+        .par_iter()
+        .map(|entry| repair_entry(entry, source_root, dest_root))
+        .collect();
+
+    let mut report = RepairReport::default();
+    for (entry, outcome) in manifest.entries.iter().zip(outcomes) {
+        match outcome {
+            Ok(RepairOutcome::Ok) => report.ok += 1,
+            Ok(RepairOutcome::Repaired { chunks, bytes }) => {
+                report.repaired.push((entry.path.clone(), chunks));
+                report.bytes_copied += bytes;
+            }
+            Ok(RepairOutcome::Recopied { bytes }) => {
+                report.recopied.push(entry.path.clone());
+                report.bytes_copied += bytes;
+            }
+            Err(e) => report.failures.push((entry.path.clone(), e.to_string())),
+        }
+    }
+
+    Ok(report)
+}
+
+fn repair_entry(entry: &FileHashEntry, source_root: &Path, dest_root: &Path) -> Result<RepairOutcome> {
+    let relative = entry.to_path();
+    let source = source_root.join(&relative);
+    let dest = dest_root.join(&relative);
+    let dest_size = std::fs::metadata(&dest).map(|m| m.len()).ok();
+
+    let outcome = match (&entry.chunks, dest_size) {
+        (Some(chunks), Some(size)) => {
+            let bad = chunks.find_mismatches(&dest, entry.size)?;
+            if bad.is_empty() && size == entry.size {
+                return Ok(RepairOutcome::Ok);
+            }
+            let bytes = chunks.copy_chunks(&source, &dest, &bad, entry.size)?;
+            RepairOutcome::Repaired {
+                chunks: bad.len(),
+                bytes,
+            }
+        }
+        _ => {
+            let intact = dest_size == Some(entry.size)
+                && hash_file(&dest, entry.algorithm)?.hash.eq_ignore_ascii_case(&entry.hash);
+            if intact {
+                return Ok(RepairOutcome::Ok);
+            }
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent).with_path(parent)?;
+            }
+            // Copy beside the destination and only replace it once verified
+            let mut tmp = dest.as_os_str().to_os_string();
+            tmp.push(".repair");
+            let tmp = PathBuf::from(tmp);
+            let bytes = std::fs::copy(&source, &tmp).with_path(&source)?;
+            let actual = hash_file(&tmp, entry.algorithm)?;
+            if !actual.hash.eq_ignore_ascii_case(&entry.hash) {
+                let _ = std::fs::remove_file(&tmp);
+                return Err(SmartCopyError::IntegrityMismatch {
+                    path: source,
+                    expected: entry.hash.clone(),
+                    actual: actual.hash,
+                });
+            }
+            std::fs::rename(&tmp, &dest).with_path(&dest)?;
+            return Ok(RepairOutcome::Recopied { bytes });
+        }
+    };
+
+    let actual = hash_file(&dest, entry.algorithm)?;
+    if !actual.hash.eq_ignore_ascii_case(&entry.hash) {
+        return Err(SmartCopyError::IntegrityMismatch {
+            path: dest,
+            expected: entry.hash.clone(),
+            actual: actual.hash,
+        });
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HashAlgorithm;
+    use crate::fs::{ScanConfig, Scanner};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_repair_from_manifest() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+        let big: Vec<u8> = (0..10_000u32).map(|i| (i % 249) as u8).collect();
+        for dir in [src.path(), dst.path()] {
+            std::fs::create_dir(dir.join("sub")).unwrap();
+            std::fs::write(dir.join("big.dat"), &big).unwrap();
+            std::fs::write(dir.join("sub/small.dat"), b"small file").unwrap();
+            std::fs::write(dir.join("ok.dat"), b"fine").unwrap();
+        }
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(src.path()).unwrap();
+        let manifest = HashManifest::from_scan_with_chunks(&scan, HashAlgorithm::Blake3, 4096).unwrap();
+
+        let mut damaged = big.clone();
+        damaged[100] ^= 1;
+        std::fs::write(dst.path().join("big.dat"), &damaged).unwrap();
+        std::fs::remove_file(dst.path().join("sub/small.dat")).unwrap();
+
+        let report = repair_from_manifest(&manifest, src.path(), dst.path()).unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.ok, 1);
+        assert_eq!(report.repaired, vec![("big.dat".to_string(), 1)]);
+        assert_eq!(report.recopied, vec!["sub/small.dat".to_string()]);
+        assert_eq!(report.bytes_copied, 4096 + 10);
+        assert_eq!(std::fs::read(dst.path().join("big.dat")).unwrap(), big);
+
+        // A source that drifted from the manifest is not copied
+        std::fs::write(src.path().join("ok.dat"), b"changed").unwrap();
+        std::fs::remove_file(dst.path().join("ok.dat")).unwrap();
+        let report = repair_from_manifest(&manifest, src.path(), dst.path()).unwrap();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, "ok.dat");
+        assert!(!dst.path().join("ok.dat").exists());
+    }
+}
@@ -0,0 +1,271 @@
+//! Bit-rot scrubbing of archived trees
+//!
+//! Each run re-reads a slice of the files listed in a [`HashManifest`],
+//! least recently verified first (never-verified files before all others),
+//! and stamps `last_verified` on the ones that still match. Running it
+//! from cron with a small fraction and a bandwidth limit walks the whole
+//! archive over time without saturating the filesystem. Files that fail
+//! keep their old timestamp, so they are re-checked first next time.
+
+use crate::config::HashAlgorithm;
+use crate::error::{IoResultExt, Result};
+use crate::fs::throttle::ThrottledReader;
+use crate::fs::BandwidthLimiter;
+use crate::hash::{hash_file, HashManifest, HashResult, Hasher};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Scrub run settings
+#[derive(Debug, Clone)]
+pub struct ScrubOptions {
+    /// Fraction of the manifest to re-read per run (0.0-1.0)
+    pub fraction: f64,
+    /// Combined read rate limit in bytes per second
+    pub bandwidth_limit: Option<u64>,
+    /// Worker threads (0 = rayon default)
+    pub threads: usize,
+}
+
+impl Default for ScrubOptions {
+    fn default() -> Self {
+        Self {
+            fraction: 0.1,
+            bandwidth_limit: None,
+            threads: 0,
+        }
+    }
+}
+
+/// Outcome of a scrub run
+///
+/// Paths are in escaped form (see [`crate::fs::escape_path`]).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScrubReport {
+    /// Files listed in the manifest
+    pub files_total: usize,
+    /// Files re-read this run
+    pub files_scrubbed: usize,
+    /// Bytes re-read this run
+    pub bytes_scrubbed: u64,
+    /// Files that still match
+    pub ok: usize,
+    /// Corrupted files (path, expected, actual)
+    pub corrupted: Vec<(String, String, String)>,
+    /// Files no longer present
+    pub missing: Vec<String>,
+    /// Files that could not be read (path, error)
+    pub errors: Vec<(String, String)>,
+    /// Files never verified, after this run
+    pub never_verified: usize,
+    /// Oldest verification time left in the manifest (Unix timestamp)
+    pub oldest_verified: Option<u64>,
+}
+
+impl ScrubReport {
+    /// Whether every scrubbed file was present and intact
+    pub fn is_clean(&self) -> bool {
+        self.corrupted.is_empty() && self.missing.is_empty() && self.errors.is_empty()
+    }
+
+    /// Print report to console
+    pub fn print_summary(&self) {
+        println!("=== Scrub Report ===");
+        println!("Scrubbed:        {} of {} files", self.files_scrubbed, self.files_total);
+        println!("Bytes read:      {}", humansize::format_size(self.bytes_scrubbed, humansize::BINARY));
+        println!("OK:              {}", self.ok);
+        println!("Corrupted:       {}", self.corrupted.len());
+        println!("Missing:         {}", self.missing.len());
+        println!("Errors:          {}", self.errors.len());
+        println!("Never verified:  {}", self.never_verified);
+        if let Some(oldest) = self.oldest_verified {
+            let age = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs().saturating_sub(oldest))
+                .unwrap_or(0);
+            println!("Oldest check:    {} days ago", age / 86_400);
+        }
+
+        if !self.corrupted.is_empty() {
+            println!("\nCorrupted files:");
+            for (path, expected, actual) in &self.corrupted {
+                println!("  {} (expected {}, got {})", path, expected, actual);
+            }
+        }
+        if !self.missing.is_empty() {
+            println!("\nMissing files:");
+            for path in &self.missing {
+                println!("  {}", path);
+            }
+        }
+        if !self.errors.is_empty() {
+            println!("\nErrors:");
+            for (path, error) in &self.errors {
+                println!("  {} - {}", path, error);
+            }
+        }
+    }
+}
+
+enum ScrubCheck {
+    Ok,
+    Corrupted(String),
+    Missing,
+    Error(String),
+}
+
+/// Re-hash the stalest slice of `manifest` under `root`
+///
+/// Updates `last_verified` in place for files that match; the caller
+/// persists the manifest.
+pub fn scrub(manifest: &mut HashManifest, root: &Path, options: &ScrubOptions) -> Result<ScrubReport> {
+    let total = manifest.entries.len();
+    let fraction = options.fraction.clamp(0.0, 1.0);
+    let count = ((total as f64 * fraction).ceil() as usize).min(total);
+
+    // Never verified first, then oldest, then path order for stable slices
+    let mut order: Vec<usize> = (0..total).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&manifest.entries[a], &manifest.entries[b]);
+        a.last_verified
+            .cmp(&b.last_verified)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    order.truncate(count);
+
+    let limiter = options.bandwidth_limit.map(BandwidthLimiter::new);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads)
+        .build()
+        .map_err(|e| crate::error::SmartCopyError::ThreadPoolError(e.to_string()))?;
+
+    let entries = &manifest.entries;
+    let checks: Vec<(usize, u64, ScrubCheck)> = pool.install(|| {
+        order
+            .par_iter()
+            .map(|&i| {
+                let entry = &entries[i];
+                let path = root.join(entry.to_path());
+                match hash_throttled(&path, entry.algorithm, limiter.as_ref()) {
+                    Ok(actual) if actual.hash.eq_ignore_ascii_case(&entry.hash) => {
+                        (i, actual.size, ScrubCheck::Ok)
+                    }
+                    Ok(actual) => (i, actual.size, ScrubCheck::Corrupted(actual.hash)),
+                    Err(_) if !path.exists() => (i, 0, ScrubCheck::Missing),
+                    Err(e) => (i, 0, ScrubCheck::Error(e.to_string())),
+                }
+            })
+            .collect()
+    });
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut report = ScrubReport {
+        files_total: total,
+        files_scrubbed: checks.len(),
+        ..Default::default()
+    };
+
+    for (i, bytes, check) in checks {
+        let entry = &mut manifest.entries[i];
+        report.bytes_scrubbed += bytes;
+        match check {
+            ScrubCheck::Ok => {
+                report.ok += 1;
+                entry.last_verified = Some(now);
+            }
+            ScrubCheck::Corrupted(actual) => {
+                report.corrupted.push((entry.path.clone(), entry.hash.clone(), actual))
+            }
+            ScrubCheck::Missing => report.missing.push(entry.path.clone()),
+            ScrubCheck::Error(e) => report.errors.push((entry.path.clone(), e)),
+        }
+    }
+
+    report.never_verified = manifest.entries.iter().filter(|e| e.last_verified.is_none()).count();
+    report.oldest_verified = manifest.entries.iter().filter_map(|e| e.last_verified).min();
+    report.corrupted.sort();
+    report.missing.sort();
+    report.errors.sort();
+
+    Ok(report)
+}
+
+/// Hash a file, reading no faster than the limiter allows
+fn hash_throttled(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    limiter: Option<&BandwidthLimiter>,
+) -> Result<HashResult> {
+    let Some(limiter) = limiter else {
+        return hash_file(path, algorithm);
+    };
+
+    let file = File::open(path).with_path(path)?;
+    let mut reader = ThrottledReader::new(file, limiter.clone());
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0u8; 1024 * 1024];
+    let mut size = 0u64;
+
+    loop {
+        let read = reader.read(&mut buffer).with_path(path)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok(HashResult::new(algorithm, hasher.finalize(), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{ScanConfig, Scanner};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scrub_rotates_through_manifest() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..10 {
+            std::fs::write(dir.path().join(format!("f{}.dat", i)), vec![i as u8; 100]).unwrap();
+        }
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(dir.path()).unwrap();
+        let mut manifest = HashManifest::from_scan(&scan, HashAlgorithm::Blake3).unwrap();
+
+        let options = ScrubOptions {
+            fraction: 0.3,
+            ..Default::default()
+        };
+        let report = scrub(&mut manifest, dir.path(), &options).unwrap();
+        assert_eq!(report.files_scrubbed, 3);
+        assert_eq!(report.never_verified, 7);
+        assert!(report.is_clean());
+
+        // The next runs pick the files not yet verified
+        scrub(&mut manifest, dir.path(), &options).unwrap();
+        let report = scrub(&mut manifest, dir.path(), &options).unwrap();
+        assert_eq!(report.never_verified, 1);
+
+        // Damage is reported and keeps the file first in line
+        std::fs::write(dir.path().join("f9.dat"), vec![0u8; 100]).unwrap();
+        std::fs::remove_file(dir.path().join("f0.dat")).unwrap();
+        let options = ScrubOptions {
+            fraction: 1.0,
+            ..Default::default()
+        };
+        let report = scrub(&mut manifest, dir.path(), &options).unwrap();
+        assert_eq!(report.ok, 8);
+        assert_eq!(report.corrupted.len(), 1);
+        assert_eq!(report.corrupted[0].0, "f9.dat");
+        assert_eq!(report.missing, vec!["f0.dat".to_string()]);
+        assert!(!report.is_clean());
+        assert!(manifest.find_entry("f9.dat").unwrap().last_verified.is_none());
+    }
+}
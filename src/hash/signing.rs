@@ -0,0 +1,164 @@
+//! Ed25519 signatures over hash and sync manifests
+//!
+//! A signature covers a canonical JSON encoding of the manifest's roots,
+//! algorithm and entries, prefixed with a per-manifest-type domain tag, so
+//! changing, adding, removing or reordering any entry invalidates it.
+//! Bookkeeping that changes after signing, such as scrub verification
+//! times, is left out.
+//!
+//! Keys are stored as one line of hex: the 32-byte secret seed in the key
+//! file and the 32-byte public key in `<key>.pub`.
+
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Signature algorithm name recorded in manifests
+pub const SIGNATURE_ALGORITHM: &str = "ed25519";
+
+/// Detached signature stored alongside a manifest's entries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestSignature {
+    /// Signature algorithm (always `ed25519`)
+    pub algorithm: String,
+    /// Signer's public key (hex)
+    pub public_key: String,
+    /// Signature over the canonical manifest encoding (hex)
+    pub signature: String,
+}
+
+impl ManifestSignature {
+    /// Sign `payload` under `domain`
+    pub fn sign(key: &SigningKey, domain: &str, payload: &[u8]) -> Self {
+        let signature = key.sign(&signed_message(domain, payload));
+        Self {
+            algorithm: SIGNATURE_ALGORITHM.to_string(),
+            public_key: hex::encode(key.verifying_key().as_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+
+    /// Check that this signature by `key` covers `payload` under `domain`
+    pub fn verify(&self, key: &VerifyingKey, domain: &str, payload: &[u8]) -> Result<()> {
+        if self.algorithm != SIGNATURE_ALGORITHM {
+            return Err(SmartCopyError::SignatureError(format!(
+                "unsupported signature algorithm '{}'",
+                self.algorithm
+            )));
+        }
+        if !self.public_key.eq_ignore_ascii_case(&hex::encode(key.as_bytes())) {
+            return Err(SmartCopyError::SignatureError(format!(
+                "signed by a different key ({})",
+                self.public_key
+            )));
+        }
+        let bytes: [u8; 64] = hex::decode(&self.signature)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| SmartCopyError::SignatureError("malformed signature".to_string()))?;
+        key.verify(&signed_message(domain, payload), &Signature::from_bytes(&bytes))
+            .map_err(|_| SmartCopyError::SignatureError("signature does not match the manifest".to_string()))
+    }
+}
+
+fn signed_message(domain: &str, payload: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(domain.len() + 1 + payload.len());
+    message.extend_from_slice(domain.as_bytes());
+    message.push(0);
+    message.extend_from_slice(payload);
+    message
+}
+
+/// Path of the public key written next to a signing key
+pub fn public_key_path(key_path: &Path) -> PathBuf {
+    let mut path = key_path.as_os_str().to_os_string();
+    path.push(".pub");
+    PathBuf::from(path)
+}
+
+/// Create a signing key at `path` and its public key at `<path>.pub`
+///
+/// The secret key file is only readable by its owner. Existing files are
+/// not overwritten.
+pub fn generate_signing_key(path: &Path) -> Result<VerifyingKey> {
+    let mut seed = [0u8; 32];
+    getrandom::getrandom(&mut seed)
+        .map_err(|e| SmartCopyError::SignatureError(format!("no randomness available: {}", e)))?;
+    let key = SigningKey::from_bytes(&seed);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).with_path(path)?;
+    std::io::Write::write_all(&mut file, format!("{}\n", hex::encode(seed)).as_bytes()).with_path(path)?;
+
+    let public_path = public_key_path(path);
+    let public = key.verifying_key();
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&public_path)
+        .with_path(&public_path)?;
+    std::io::Write::write_all(&mut file, format!("{}\n", hex::encode(public.as_bytes())).as_bytes())
+        .with_path(&public_path)?;
+
+    Ok(public)
+}
+
+fn read_key_bytes(path: &Path, what: &str) -> Result<[u8; 32]> {
+    let text = std::fs::read_to_string(path).with_path(path)?;
+    hex::decode(text.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| {
+            SmartCopyError::SignatureError(format!("{} is not a hex-encoded Ed25519 {}", path.display(), what))
+        })
+}
+
+/// Load a signing key written by [`generate_signing_key`]
+pub fn load_signing_key(path: &Path) -> Result<SigningKey> {
+    Ok(SigningKey::from_bytes(&read_key_bytes(path, "secret key")?))
+}
+
+/// Load a public key written by [`generate_signing_key`]
+pub fn load_verifying_key(path: &Path) -> Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&read_key_bytes(path, "public key")?)
+        .map_err(|e| SmartCopyError::SignatureError(format!("{}: {}", path.display(), e)))
+}
+
+/// Canonical encoding of a manifest's signed fields
+pub(crate) fn canonical_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec(value).map_err(|e| SmartCopyError::ManifestError(e.to_string()))
+}
+
+/// Error for a manifest checked against a key but carrying no signature
+pub(crate) fn unsigned_manifest() -> SmartCopyError {
+    SmartCopyError::SignatureError("manifest is not signed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_key_files_and_signature() {
+        let dir = TempDir::new().unwrap();
+        let key_path = dir.path().join("release.key");
+        let public = generate_signing_key(&key_path).unwrap();
+        assert!(generate_signing_key(&key_path).is_err());
+
+        let key = load_signing_key(&key_path).unwrap();
+        assert_eq!(load_verifying_key(&public_key_path(&key_path)).unwrap(), public);
+
+        let signature = ManifestSignature::sign(&key, "test", b"payload");
+        signature.verify(&public, "test", b"payload").unwrap();
+        assert!(signature.verify(&public, "test", b"payloaD").is_err());
+        assert!(signature.verify(&public, "other", b"payload").is_err());
+
+        let other = SigningKey::from_bytes(&[7u8; 32]).verifying_key();
+        assert!(signature.verify(&other, "test", b"payload").is_err());
+    }
+}
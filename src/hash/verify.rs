@@ -0,0 +1,448 @@
+//! Destination-only verification against stored hashes
+//!
+//! Proves an archive still matches the hashes recorded when it was
+//! written, without access to the original source. Every entry of a hash
+//! manifest, checksum file or S3 listing is re-hashed in parallel; files
+//! on disk that it doesn't list are reported as extra.
+
+use crate::config::HashAlgorithm;
+use crate::error::Result;
+use crate::fs::{escape_path, ScanConfig, Scanner};
+use crate::hash::{hash_file, verify_etag, ChecksumFile, ChunkHashes, ETagCheck, HashManifest};
+use crate::storage::S3Object;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Outcome for one manifest entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum EntryCheck {
+    Ok,
+    Mismatch { expected: String, actual: String },
+    Missing,
+    Unverifiable(String),
+    Error(String),
+}
+
+/// Result of verifying a tree against a [`HashManifest`] or [`ChecksumFile`]
+///
+/// Paths are in escaped form (see [`escape_path`]).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ManifestVerification {
+    /// Files whose size and hash match
+    pub ok: u64,
+    /// Files whose contents differ (path, expected, actual)
+    pub mismatched: Vec<(String, String, String)>,
+    /// Files listed in the manifest but not present
+    pub missing: Vec<String>,
+    /// Files present but not listed in the manifest
+    pub extra: Vec<String>,
+    /// Files that could not be read (path, error)
+    pub errors: Vec<(String, String)>,
+    /// Damaged byte ranges (offset, length) of mismatched files that have
+    /// chunk hashes
+    pub corrupt_ranges: Vec<(String, Vec<(u64, u64)>)>,
+    /// Files whose stored hash can't be reproduced (path, reason)
+    pub unverifiable: Vec<(String, String)>,
+}
+
+impl ManifestVerification {
+    /// Whether every listed file is present and intact
+    ///
+    /// Extra and unverifiable files are reported but don't fail
+    /// verification.
+    pub fn is_success(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.errors.is_empty()
+    }
+
+    /// Print report to console
+    pub fn print_summary(&self) {
+        println!("OK:          {}", self.ok);
+        println!("Mismatched:  {}", self.mismatched.len());
+        println!("Missing:     {}", self.missing.len());
+        println!("Extra:       {}", self.extra.len());
+        println!("Errors:      {}", self.errors.len());
+        if !self.unverifiable.is_empty() {
+            println!("Unverifiable: {}", self.unverifiable.len());
+        }
+
+        if !self.mismatched.is_empty() {
+            println!("\nMismatched files:");
+            for (path, expected, actual) in &self.mismatched {
+                println!("  {} (expected {}, got {})", path, expected, actual);
+            }
+        }
+        if !self.corrupt_ranges.is_empty() {
+            println!("\nCorrupted ranges:");
+            for (path, ranges) in &self.corrupt_ranges {
+                let ranges: Vec<String> = ranges
+                    .iter()
+                    .map(|(offset, len)| format!("{}..{}", offset, offset + len))
+                    .collect();
+                println!("  {} bytes {}", path, ranges.join(", "));
+            }
+        }
+        if !self.missing.is_empty() {
+            println!("\nMissing files:");
+            for path in &self.missing {
+                println!("  {}", path);
+            }
+        }
+        if !self.extra.is_empty() {
+            println!("\nExtra files:");
+            for path in &self.extra {
+                println!("  {}", path);
+            }
+        }
+        if !self.unverifiable.is_empty() {
+            println!("\nUnverifiable files:");
+            for (path, reason) in &self.unverifiable {
+                println!("  {} - {}", path, reason);
+            }
+        }
+        if !self.errors.is_empty() {
+            println!("\nErrors:");
+            for (path, error) in &self.errors {
+                println!("  {} - {}", path, error);
+            }
+        }
+    }
+}
+
+impl ManifestVerification {
+    fn record(&mut self, key: String, check: EntryCheck) {
+        match check {
+            EntryCheck::Ok => self.ok += 1,
+            EntryCheck::Mismatch { expected, actual } => self.mismatched.push((key, expected, actual)),
+            EntryCheck::Missing => self.missing.push(key),
+            EntryCheck::Unverifiable(reason) => self.unverifiable.push((key, reason)),
+            EntryCheck::Error(e) => self.errors.push((key, e)),
+        }
+    }
+}
+
+/// Re-hash the files under `root` and compare them with `manifest`
+///
+/// Sizes are compared first so truncated files are caught without
+/// reading them. Hidden files are included when looking for extras.
+pub fn verify_manifest(manifest: &HashManifest, root: &Path) -> Result<ManifestVerification> {
+    let expected: Vec<Expected> = manifest
+        .entries
+        .iter()
+        .map(|e| Expected {
+            key: e.path.clone(),
+            path: e.to_path(),
+            algorithm: e.algorithm,
+            hash: &e.hash,
+            size: Some(e.size),
+            chunks: e.chunks.as_ref(),
+        })
+        .collect();
+    verify_expected(&expected, root)
+}
+
+/// Re-hash the files under `root` and compare them with a checksum file
+///
+/// Checksum files carry no sizes, so every listed file is read.
+pub fn verify_checksums(checksums: &ChecksumFile, root: &Path) -> Result<ManifestVerification> {
+    let expected: Vec<Expected> = checksums
+        .entries
+        .iter()
+        .map(|e| Expected {
+            key: escape_path(&e.path),
+            path: e.path.clone(),
+            algorithm: checksums.algorithm,
+            hash: &e.hash,
+            size: None,
+            chunks: None,
+        })
+        .collect();
+    verify_expected(&expected, root)
+}
+
+/// Compare the files under `root` with the ETags of an S3 listing
+///
+/// `prefix` is stripped from object keys to get local paths. Nothing is
+/// downloaded: single-part ETags are checked against the local MD5 and
+/// multipart ETags need the upload's `part_size` (see [`verify_etag`]).
+pub fn verify_s3_listing(
+    objects: &[S3Object],
+    prefix: &str,
+    root: &Path,
+    part_size: Option<u64>,
+) -> Result<ManifestVerification> {
+    // Zero-byte keys ending in '/' are folder markers, not files
+    let objects: Vec<(&S3Object, &str)> = objects
+        .iter()
+        .filter(|o| !o.key.ends_with('/'))
+        .map(|o| {
+            let relative = o.key.strip_prefix(prefix).unwrap_or(&o.key);
+            (o, relative.trim_start_matches('/'))
+        })
+        .collect();
+
+    let checks: Vec<EntryCheck> = objects
+        .par_iter()
+        .map(|(object, relative)| {
+            let path = root.join(relative);
+            let meta = match std::fs::metadata(&path) {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return EntryCheck::Missing,
+                Err(e) => return EntryCheck::Error(e.to_string()),
+            };
+            if meta.len() != object.size {
+                return EntryCheck::Mismatch {
+                    expected: format!("{} bytes", object.size),
+                    actual: format!("{} bytes", meta.len()),
+                };
+            }
+            let Some(etag) = &object.etag else {
+                return EntryCheck::Unverifiable("no ETag listed".to_string());
+            };
+            match verify_etag(&path, etag, part_size) {
+                Ok(ETagCheck::Match) => EntryCheck::Ok,
+                Ok(ETagCheck::Mismatch { expected, actual }) => EntryCheck::Mismatch { expected, actual },
+                Ok(ETagCheck::Unverifiable(reason)) => EntryCheck::Unverifiable(reason),
+                Err(e) => EntryCheck::Error(e.to_string()),
+            }
+        })
+        .collect();
+
+    let mut result = ManifestVerification::default();
+    for ((_, relative), check) in objects.iter().zip(checks) {
+        result.record(relative.to_string(), check);
+    }
+
+    let listed: HashSet<&str> = objects.iter().map(|(_, relative)| *relative).collect();
+    result.extra = extra_files(root, &listed)?;
+    Ok(result)
+}
+
+/// A file and the hash it should have
+struct Expected<'a> {
+    key: String,
+    path: PathBuf,
+    algorithm: HashAlgorithm,
+    hash: &'a str,
+    size: Option<u64>,
+    chunks: Option<&'a ChunkHashes>,
+}
+
+fn verify_expected(expected: &[Expected], root: &Path) -> Result<ManifestVerification> {
+    let checks: Vec<EntryCheck> = expected.par_iter().map(|e| check_file(e, root)).collect();
+
+    let mut result = ManifestVerification::default();
+    for (entry, check) in expected.iter().zip(checks) {
+        if matches!(check, EntryCheck::Mismatch { .. }) {
+            if let Some(ranges) = corrupt_ranges(entry, root) {
+                result.corrupt_ranges.push((entry.key.clone(), ranges));
+            }
+        }
+        result.record(entry.key.clone(), check);
+    }
+
+    let listed: HashSet<&str> = expected.iter().map(|e| e.key.as_str()).collect();
+    result.extra = extra_files(root, &listed)?;
+
+    Ok(result)
+}
+
+/// Files under `root` (escaped, sorted) whose escaped paths aren't listed
+fn extra_files(root: &Path, listed: &HashSet<&str>) -> Result<Vec<String>> {
+    let config = ScanConfig {
+        include_hidden: true,
+        ..Default::default()
+    };
+    let scan = Scanner::new(config)?.scan(root)?;
+    let mut extra: Vec<String> = scan
+        .files
+        .iter()
+        .map(|f| escape_path(&f.relative_path))
+        .filter(|path| !listed.contains(path.as_str()))
+        .collect();
+    extra.sort();
+    Ok(extra)
+}
+
+/// Locate the damaged ranges of a mismatched file from its chunk hashes
+fn corrupt_ranges(entry: &Expected, root: &Path) -> Option<Vec<(u64, u64)>> {
+    let (chunks, size) = (entry.chunks?, entry.size?);
+    let bad = chunks.find_mismatches(&root.join(&entry.path), size).ok()?;
+    Some(chunks.ranges(&bad, size))
+}
+
+fn check_file(entry: &Expected, root: &Path) -> EntryCheck {
+    let path = root.join(&entry.path);
+
+    let meta = match std::fs::metadata(&path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return EntryCheck::Missing,
+        Err(e) => return EntryCheck::Error(e.to_string()),
+    };
+    if let Some(size) = entry.size.filter(|&size| size != meta.len()) {
+        return EntryCheck::Mismatch {
+            expected: format!("{} bytes", size),
+            actual: format!("{} bytes", meta.len()),
+        };
+    }
+
+    match hash_file(&path, entry.algorithm) {
+        Ok(actual) if actual.hash.eq_ignore_ascii_case(entry.hash) => EntryCheck::Ok,
+        Ok(actual) => EntryCheck::Mismatch {
+            expected: entry.hash.to_string(),
+            actual: actual.hash,
+        },
+        Err(e) => EntryCheck::Error(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify_manifest() {
+        let dir = TempDir::new().unwrap();
+        for name in ["ok.dat", "corrupt.dat", "truncated.dat", "gone.dat"] {
+            std::fs::write(dir.path().join(name), name.repeat(100)).unwrap();
+        }
+
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(dir.path()).unwrap();
+        let manifest = HashManifest::from_scan(&scan, HashAlgorithm::Blake3).unwrap();
+
+        // Round-trip through every on-disk format
+        let json = dir.path().join("hashes.json");
+        manifest.save(&json).unwrap();
+        let bin = dir.path().join("hashes.bin");
+        manifest.save_binary(&bin).unwrap();
+        for path in [&json, &bin] {
+            assert_eq!(HashManifest::load_any(path).unwrap().entries.len(), 4);
+        }
+        std::fs::remove_file(&json).unwrap();
+        std::fs::remove_file(&bin).unwrap();
+
+        let mut corrupt = "corrupt.dat".repeat(100).into_bytes();
+        corrupt[5] ^= 1;
+        std::fs::write(dir.path().join("corrupt.dat"), corrupt).unwrap();
+        std::fs::write(dir.path().join("truncated.dat"), b"short").unwrap();
+        std::fs::remove_file(dir.path().join("gone.dat")).unwrap();
+        std::fs::write(dir.path().join(".new"), b"x").unwrap();
+
+        let result = verify_manifest(&manifest, dir.path()).unwrap();
+        assert_eq!(result.ok, 1);
+        let mut mismatched: Vec<_> = result.mismatched.iter().map(|m| m.0.as_str()).collect();
+        mismatched.sort();
+        assert_eq!(mismatched, vec!["corrupt.dat", "truncated.dat"]);
+        assert_eq!(result.missing, vec!["gone.dat".to_string()]);
+        assert_eq!(result.extra, vec![".new".to_string()]);
+        assert!(!result.is_success());
+    }
+
+    #[test]
+    fn test_verify_s3_listing() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("single.dat"), b"single part").unwrap();
+        std::fs::write(dir.path().join("multi.dat"), vec![1u8; 3000]).unwrap();
+        std::fs::write(dir.path().join("local-only.dat"), b"x").unwrap();
+
+        let object = |key: &str, size: u64, etag: String| S3Object {
+            key: format!("backup/{}", key),
+            size,
+            last_modified: 0,
+            etag: Some(format!("\"{}\"", etag)),
+            storage_class: None,
+            content_type: None,
+            metadata: Default::default(),
+        };
+        let single = hash_file(&dir.path().join("single.dat"), HashAlgorithm::Md5).unwrap().hash;
+        let multi = crate::hash::s3_multipart_etag(&dir.path().join("multi.dat"), 1024).unwrap();
+        let objects = vec![
+            object("single.dat", 11, single),
+            object("multi.dat", 3000, multi),
+            object("gone.dat", 5, "0".repeat(32)),
+        ];
+
+        let result = verify_s3_listing(&objects, "backup/", dir.path(), Some(1024)).unwrap();
+        assert_eq!(result.ok, 2);
+        assert_eq!(result.missing, vec!["gone.dat".to_string()]);
+        assert_eq!(result.extra, vec!["local-only.dat".to_string()]);
+
+        // Without the part size the multipart object is reported, not failed
+        let result = verify_s3_listing(&objects[..2], "backup", dir.path(), None).unwrap();
+        assert_eq!(result.ok, 1);
+        assert_eq!(result.unverifiable.len(), 1);
+        assert_eq!(result.unverifiable[0].0, "multi.dat");
+        assert!(result.is_success());
+    }
+
+    #[test]
+    fn test_verify_manifest_locates_corrupt_ranges() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("big.dat"), vec![7u8; 10_000]).unwrap();
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(dir.path()).unwrap();
+        let manifest = HashManifest::from_scan_with_chunks(&scan, HashAlgorithm::Blake3, 4096).unwrap();
+        assert_eq!(manifest.entries[0].chunks.as_ref().unwrap().hashes.len(), 3);
+
+        let mut data = vec![7u8; 10_000];
+        data[9000] = 0;
+        std::fs::write(dir.path().join("big.dat"), data).unwrap();
+
+        let result = verify_manifest(&manifest, dir.path()).unwrap();
+        assert_eq!(result.corrupt_ranges, vec![("big.dat".to_string(), vec![(8192, 1808)])]);
+    }
+
+    #[cfg(feature = "parquet_manifest")]
+    #[test]
+    fn test_verify_parquet_manifest() {
+        use crate::sync::parquet_manifest::{ManifestEntry, ParquetManifestWriter};
+
+        let dir = TempDir::new().unwrap();
+        let out = TempDir::new().unwrap();
+        for name in ["ok.dat", "corrupt.dat"] {
+            std::fs::write(dir.path().join(name), name.repeat(100)).unwrap();
+        }
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(dir.path()).unwrap();
+        let hashes = HashManifest::from_scan(&scan, HashAlgorithm::Blake3).unwrap();
+
+        let parquet = out.path().join("manifest.parquet");
+        let mut writer = ParquetManifestWriter::new(&parquet).unwrap();
+        for entry in &hashes.entries {
+            let (path, path_bytes) = ManifestEntry::encode_path(Path::new(&entry.path));
+            writer
+                .add_entry(ManifestEntry {
+                    path,
+                    size: entry.size,
+                    mtime_secs: entry.mtime as i64,
+                    mtime_nsecs: None,
+                    permissions: None,
+                    uid: None,
+                    gid: None,
+                    setuid: None,
+                    setgid: None,
+                    sticky: None,
+                    file_type: Some("file".to_string()),
+                    symlink_target: None,
+                    xxhash3: None,
+                    blake3: Some(entry.hash.clone()),
+                    path_bytes,
+                    last_verified: None,
+                })
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        // What `verify --manifest` does with the file
+        let manifest = HashManifest::load_any(&parquet).unwrap();
+        assert_eq!(manifest.algorithm, HashAlgorithm::Blake3);
+        let result = verify_manifest(&manifest, dir.path()).unwrap();
+        assert_eq!(result.ok, 2);
+        assert!(result.is_success());
+
+        std::fs::write(dir.path().join("corrupt.dat"), "CORRUPT.dat".repeat(100)).unwrap();
+        let result = verify_manifest(&manifest, dir.path()).unwrap();
+        assert_eq!(result.ok, 1);
+        assert_eq!(result.mismatched[0].0, "corrupt.dat");
+    }
+}
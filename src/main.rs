@@ -8,10 +8,13 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 use clap::Parser;
-use smartcopy::config::{AgentProtocol, CliArgs, Commands, CopyConfig, HighSpeedTier, WorkloadType};
+use smartcopy::config::{AgentProtocol, CliArgs, Commands, CopyConfig, HighSpeedTier, OutputFormat, WorkloadType};
 use smartcopy::core::CopyEngine;
 use smartcopy::error::Result;
-use smartcopy::hash::{benchmark_algorithms, verify_files_match};
+use smartcopy::hash::{
+    benchmark_algorithms, find_duplicates, load_verifying_key, verify_checksums, verify_files_match, verify_manifest,
+    ChecksumFile, HashManifest, ManifestVerification,
+};
 use smartcopy::network::{AgentServer, CertificateManager, QuicServer, SshTuningRecommendations};
 use smartcopy::progress::ProgressReporter;
 use smartcopy::sync::{IncrementalSync, SyncManifest};
@@ -68,6 +71,14 @@ fn run(args: CliArgs) -> Result<()> {
         let _tui_handle = dashboard.spawn();
     }
 
+    // Push to a `smartcopy server` over direct TCP
+    if let Some(remote) = config.remote.as_ref().filter(|r| r.tcp_direct) {
+        let destination = args.destination.as_deref().unwrap_or_default();
+        if smartcopy::config::parse_remote_path(destination).is_some() {
+            return cmd_tcp_upload(&config, remote, args.quiet);
+        }
+    }
+
     // Create progress reporter
     let progress = if args.quiet {
         ProgressReporter::disabled()
@@ -100,7 +111,7 @@ fn run(args: CliArgs) -> Result<()> {
     Ok(())
 }
 
-fn handle_command(command: &Commands, _args: &CliArgs) -> Result<()> {
+fn handle_command(command: &Commands, args: &CliArgs) -> Result<()> {
     match command {
         Commands::AnalyzeSystem { detailed } => {
             cmd_analyze_system(*detailed)
@@ -108,14 +119,67 @@ fn handle_command(command: &Commands, _args: &CliArgs) -> Result<()> {
         Commands::Tuning { workload } => {
             cmd_tuning(*workload)
         }
-        Commands::Verify { source, destination, algorithm } => {
-            cmd_verify(source, destination, *algorithm)
+        Commands::Verify {
+            source,
+            destination,
+            algorithm,
+            manifest,
+            pubkey,
+            checksum_file,
+            s3_listing,
+            s3_prefix,
+            part_size,
+        } => match (manifest, checksum_file, s3_listing, destination) {
+            (Some(manifest), _, _, _) => {
+                cmd_verify_manifest(manifest, pubkey.as_deref(), Path::new(source), args.output_format)
+            }
+            (None, Some(checksum_file), _, _) => {
+                cmd_verify_checksums(checksum_file, Path::new(source), *algorithm, args.output_format)
+            }
+            (None, None, Some(listing), _) => cmd_verify_s3(
+                listing,
+                s3_prefix.as_deref(),
+                part_size.as_deref(),
+                Path::new(source),
+                args.output_format,
+            ),
+            (None, None, None, Some(destination)) => {
+                cmd_verify(source, destination, algorithm.unwrap_or_default())
+            }
+            (None, None, None, None) => unreachable!("clap requires a destination without --manifest"),
+        },
+        Commands::Scrub { path, manifest, fraction, bandwidth_limit, threads } => {
+            cmd_scrub(path, manifest, *fraction, bandwidth_limit.as_deref(), *threads, args.output_format)
+        }
+        Commands::Repair { source, destination, manifest } => {
+            cmd_repair(source, destination, manifest, args.output_format)
+        }
+        Commands::Checksum { path, algorithm, output, manifest, sign_manifest, cdc, include_hidden } => cmd_checksum(
+            path,
+            *algorithm,
+            output.as_deref(),
+            manifest.as_deref(),
+            sign_manifest.as_deref(),
+            *cdc,
+            *include_hidden,
+        ),
+        Commands::Keygen { key } => {
+            cmd_keygen(key)
         }
-        Commands::Status { manifest } => {
-            cmd_status(manifest)
+        Commands::Signature { basis, signature, block_size, cdc } => {
+            cmd_signature(basis, signature, block_size, *cdc)
         }
-        Commands::Server { port, bind } => {
-            cmd_server(*port, bind)
+        Commands::Delta { signature, new, delta, compress } => {
+            cmd_delta(signature, new, delta, *compress)
+        }
+        Commands::Patch { basis, delta, output } => {
+            cmd_patch(basis, delta, output)
+        }
+        Commands::Status { manifest, pubkey } => {
+            cmd_status(manifest, pubkey.as_deref())
+        }
+        Commands::Server { port, bind, key_file, read_only, write_only, allow, require_encryption } => {
+            cmd_server(*port, bind, key_file.as_deref(), *read_only, *write_only, allow, *require_encryption)
         }
         Commands::Benchmark { path, size } => {
             cmd_benchmark(path, size)
@@ -126,6 +190,9 @@ fn handle_command(command: &Commands, _args: &CliArgs) -> Result<()> {
         Commands::QuicServer { port, bind, cert, key } => {
             cmd_quic_server(*port, bind, cert.as_deref(), key.as_deref())
         }
+        Commands::DedupeReport { path, include_hidden } => {
+            cmd_dedupe_report(path, *include_hidden, args.output_format)
+        }
         Commands::HighSpeed { speed } => {
             cmd_highspeed(*speed)
         }
@@ -225,9 +292,13 @@ fn cmd_verify(source: &str, destination: &str, algorithm: smartcopy::config::Has
     Ok(())
 }
 
-fn cmd_status(manifest_path: &Path) -> Result<()> {
-    let manifest = SyncManifest::load(manifest_path)?;
+fn cmd_status(manifest_path: &Path, pubkey: Option<&Path>) -> Result<()> {
+    let manifest = SyncManifest::load_any(manifest_path)?;
     manifest.print_summary();
+    if let Some(pubkey) = pubkey {
+        manifest.verify_signature(&load_verifying_key(pubkey)?)?;
+        println!("Signature: valid");
+    }
 
     println!("\nRecent entries:");
     for entry in manifest.entries.iter().take(10) {
@@ -241,23 +312,445 @@ fn cmd_status(manifest_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn cmd_server(port: u16, bind: &str) -> Result<()> {
-    use smartcopy::network::TcpServer;
+fn cmd_keygen(key: &Path) -> Result<()> {
+    let public = smartcopy::hash::generate_signing_key(key)?;
+    println!("Secret key: {}", key.display());
+    println!("Public key: {}", smartcopy::hash::public_key_path(key).display());
+    println!("Key:        {}", hex::encode(public.as_bytes()));
+    Ok(())
+}
+
+fn cmd_signature(basis: &Path, signature: &Path, block_size: &str, cdc: Option<smartcopy::sync::CdcParams>) -> Result<()> {
+    use smartcopy::sync::SignatureChunking;
+
+    let chunking = match cdc {
+        Some(params) => SignatureChunking::Cdc(params),
+        None => SignatureChunking::Fixed(
+            smartcopy::config::parse_size(block_size)
+                .map_err(|e| smartcopy::error::SmartCopyError::ConfigError(format!("Invalid block size: {}", e)))?
+                as usize,
+        ),
+    };
+    let summary = smartcopy::sync::write_signature(basis, signature, chunking)?;
+    println!(
+        "Signed {} files ({}, {} blocks) -> {} ({})",
+        summary.files,
+        humansize::format_size(summary.bytes, humansize::BINARY),
+        summary.blocks,
+        signature.display(),
+        humansize::format_size(summary.signature_size, humansize::BINARY)
+    );
+    Ok(())
+}
+
+fn cmd_delta(signature: &Path, new: &Path, delta: &Path, compress: smartcopy::sync::LiteralCompression) -> Result<()> {
+    let summary = smartcopy::sync::write_delta(signature, new, delta, compress)?;
+    println!(
+        "Files: {} changed, {} added, {} deleted, {} unchanged",
+        summary.patched, summary.added, summary.deleted, summary.unchanged
+    );
+    println!(
+        "Data:  {} literal, {} from basis",
+        humansize::format_size(summary.literal_bytes, humansize::BINARY),
+        humansize::format_size(summary.copied_bytes, humansize::BINARY)
+    );
+    println!(
+        "Delta: {} ({})",
+        delta.display(),
+        humansize::format_size(summary.delta_size, humansize::BINARY)
+    );
+    Ok(())
+}
+
+fn cmd_patch(basis: &Path, delta: &Path, output: &Path) -> Result<()> {
+    let summary = smartcopy::sync::apply_delta(basis, delta, output)?;
+    println!(
+        "Patched {} files ({}) into {}, {} deleted",
+        summary.files,
+        humansize::format_size(summary.bytes, humansize::BINARY),
+        output.display(),
+        summary.deleted
+    );
+    Ok(())
+}
+
+fn cmd_verify_manifest(manifest: &Path, pubkey: Option<&Path>, root: &Path, format: OutputFormat) -> Result<()> {
+    let manifest = HashManifest::load_any(manifest)?;
+    // A manifest that fails its signature check is not used at all
+    if let Some(pubkey) = pubkey {
+        manifest.verify_signature(&load_verifying_key(pubkey)?)?;
+        if format == OutputFormat::Text {
+            println!("Manifest signature valid ({})", pubkey.display());
+        }
+    }
+    if format == OutputFormat::Text {
+        println!("Verifying {} files in {}...", manifest.entries.len(), root.display());
+    }
+
+    let result = verify_manifest(&manifest, root)?;
+    print_manifest_verification(&result, format)
+}
+
+fn cmd_verify_checksums(
+    checksum_file: &Path,
+    root: &Path,
+    algorithm: Option<smartcopy::config::HashAlgorithm>,
+    format: OutputFormat,
+) -> Result<()> {
+    let checksums = ChecksumFile::load(checksum_file, algorithm)?;
+    if format == OutputFormat::Text {
+        println!(
+            "Verifying {} {} checksums in {}...",
+            checksums.entries.len(),
+            checksums.algorithm.name(),
+            root.display()
+        );
+    }
+
+    let result = verify_checksums(&checksums, root)?;
+    print_manifest_verification(&result, format)
+}
+
+fn cmd_verify_s3(
+    listing: &str,
+    prefix: Option<&str>,
+    part_size: Option<&str>,
+    root: &Path,
+    format: OutputFormat,
+) -> Result<()> {
+    use smartcopy::config::parse_size;
+    use smartcopy::error::SmartCopyError;
+    use smartcopy::hash::verify_s3_listing;
+    use smartcopy::storage::{parse_list_objects_json, parse_s3_url, S3Client, S3Config};
+
+    let part_size = part_size
+        .map(parse_size)
+        .transpose()
+        .map_err(|e| SmartCopyError::ConfigError(format!("Invalid part size: {}", e)))?;
+
+    let (objects, url_prefix) = match parse_s3_url(listing) {
+        Some((bucket, prefix)) => {
+            let config = S3Config {
+                bucket,
+                prefix: None,
+                ..S3Config::from_env()
+            };
+            let objects = S3Client::new(config)
+                .and_then(|client| client.list_objects(Some(prefix.as_str()).filter(|p| !p.is_empty())))
+                .map_err(|e| SmartCopyError::RemoteTransferError(format!("S3 listing failed: {}", e)))?;
+            (objects, prefix)
+        }
+        None => {
+            let path = Path::new(listing);
+            let data = std::fs::read(path).map_err(|e| SmartCopyError::io(path, e))?;
+            let objects = parse_list_objects_json(&data)
+                .map_err(|e| SmartCopyError::ManifestError(format!("{}: {}", listing, e)))?;
+            (objects, String::new())
+        }
+    };
+
+    if format == OutputFormat::Text {
+        println!("Verifying {} S3 objects against {}...", objects.len(), root.display());
+    }
+    let result = verify_s3_listing(&objects, prefix.unwrap_or(&url_prefix), root, part_size)?;
+    print_manifest_verification(&result, format)
+}
+
+fn print_manifest_verification(result: &ManifestVerification, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&result)
+                .map_err(|e| smartcopy::error::SmartCopyError::ConfigError(e.to_string()))?;
+            println!("{}", json);
+        }
+        OutputFormat::Text | OutputFormat::Csv => result.print_summary(),
+    }
+
+    if !result.is_success() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn cmd_scrub(
+    path: &Path,
+    manifest_path: &Path,
+    fraction: f64,
+    bandwidth_limit: Option<&str>,
+    threads: usize,
+    format: OutputFormat,
+) -> Result<()> {
+    use smartcopy::config::parse_size;
+    use smartcopy::error::SmartCopyError;
+    use smartcopy::hash::{scrub, HashManifestFormat, ScrubOptions};
+
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(SmartCopyError::ConfigError(format!(
+            "--fraction must be in (0, 1], got {}",
+            fraction
+        )));
+    }
+    let bandwidth_limit = bandwidth_limit
+        .map(parse_size)
+        .transpose()
+        .map_err(|e| SmartCopyError::ConfigError(format!("Invalid bandwidth limit: {}", e)))?;
+
+    let manifest_format = HashManifestFormat::detect(manifest_path)?;
+    let mut manifest = HashManifest::load_any(manifest_path)?;
+    let options = ScrubOptions {
+        fraction,
+        bandwidth_limit,
+        threads,
+    };
+    let report = scrub(&mut manifest, path, &options)?;
+    manifest.store(manifest_path, manifest_format)?;
+
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&report)
+                .map_err(|e| SmartCopyError::ConfigError(e.to_string()))?;
+            println!("{}", json);
+        }
+        OutputFormat::Text | OutputFormat::Csv => report.print_summary(),
+    }
+
+    if !report.is_clean() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn cmd_repair(source: &Path, destination: &Path, manifest_path: &Path, format: OutputFormat) -> Result<()> {
+    use smartcopy::error::SmartCopyError;
+    use smartcopy::hash::repair_from_manifest;
+
+    let manifest = HashManifest::load_any(manifest_path)?;
+    let report = repair_from_manifest(&manifest, source, destination)?;
+
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&report)
+                .map_err(|e| SmartCopyError::ConfigError(e.to_string()))?;
+            println!("{}", json);
+        }
+        OutputFormat::Text | OutputFormat::Csv => report.print_summary(),
+    }
+
+    if !report.is_success() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn cmd_checksum(
+    path: &Path,
+    algorithm: smartcopy::config::HashAlgorithm,
+    output: Option<&Path>,
+    manifest_path: Option<&Path>,
+    signing_key: Option<&Path>,
+    cdc: Option<smartcopy::sync::CdcParams>,
+    include_hidden: bool,
+) -> Result<()> {
+    use smartcopy::fs::{ScanConfig, Scanner};
+    use std::io::Write;
+
+    let config = ScanConfig {
+        include_hidden,
+        ..Default::default()
+    };
+    let scan = Scanner::new(config)?.scan(path)?;
+    let mut manifest = match cdc {
+        Some(params) => {
+            let manifest = HashManifest::from_scan_with_cdc(&scan, algorithm, params)?;
+            let stats = manifest.chunk_stats();
+            eprintln!(
+                "{} chunks, {} unique; {} of {} in unique chunks",
+                stats.chunks,
+                stats.unique_chunks,
+                humansize::format_size(stats.unique_bytes, humansize::BINARY),
+                humansize::format_size(stats.bytes, humansize::BINARY)
+            );
+            manifest
+        }
+        None => HashManifest::from_scan(&scan, algorithm)?,
+    };
+    if let Some(signing_key) = signing_key {
+        manifest.sign(&smartcopy::hash::load_signing_key(signing_key)?)?;
+    }
+    let checksums = ChecksumFile::from_manifest(&manifest);
+
+    if let Some(manifest_path) = manifest_path {
+        if manifest_path.extension().is_some_and(|ext| ext == "bin") {
+            manifest.save_binary(manifest_path)?;
+        } else {
+            manifest.save(manifest_path)?;
+        }
+    }
+
+    match output {
+        Some(output) => {
+            checksums.save(output)?;
+            let absolute = std::path::absolute(output).unwrap_or_else(|_| output.to_path_buf());
+            let check = match algorithm.checksum_tool() {
+                Some(tool) => format!("cd {} && {} -c {}", path.display(), tool, absolute.display()),
+                None => format!(
+                    "smartcopy verify --checksum-file {} {}",
+                    absolute.display(),
+                    path.display()
+                ),
+            };
+            eprintln!(
+                "Wrote {} checksums to {} (check with: {})",
+                checksums.entries.len(),
+                output.display(),
+                check
+            );
+        }
+        None => std::io::stdout()
+            .write_all(&checksums.to_bytes())
+            .map_err(|e| smartcopy::error::SmartCopyError::io(Path::new("<stdout>"), e))?,
+    }
+
+    Ok(())
+}
+
+fn cmd_dedupe_report(path: &Path, include_hidden: bool, format: OutputFormat) -> Result<()> {
+    use smartcopy::fs::{ScanConfig, Scanner};
+
+    let config = ScanConfig {
+        include_hidden,
+        ..Default::default()
+    };
+    let scan = Scanner::new(config)?.scan(path)?;
+    let report = find_duplicates(&scan.files);
+
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&report)
+                .map_err(|e| smartcopy::error::SmartCopyError::ConfigError(e.to_string()))?;
+            println!("{}", json);
+        }
+        OutputFormat::Text | OutputFormat::Csv => report.print_summary(),
+    }
+
+    Ok(())
+}
+
+fn cmd_server(
+    port: u16,
+    bind: &str,
+    key_file: Option<&Path>,
+    read_only: bool,
+    write_only: bool,
+    allow: &[String],
+    require_encryption: bool,
+) -> Result<()> {
+    use smartcopy::error::SmartCopyError;
+    use smartcopy::network::{load_tcp_key, AllowedNetwork, TcpAccess, TcpServer, TcpServerOptions};
 
     let addr = format!("{}:{}", bind, port);
     let cwd = std::env::current_dir().unwrap_or_default();
 
+    let options = TcpServerOptions {
+        psk: key_file.map(load_tcp_key).transpose()?,
+        access: match (read_only, write_only) {
+            (true, _) => TcpAccess::ReadOnly,
+            (_, true) => TcpAccess::WriteOnly,
+            _ => TcpAccess::ReadWrite,
+        },
+        allow: allow
+            .iter()
+            .map(|net| net.parse::<AllowedNetwork>())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(SmartCopyError::ConfigError)?,
+        require_encryption,
+    };
+
     println!("Starting SmartCopy TCP server...");
     println!("Listening on: {}", addr);
     println!("Serving from: {:?}", cwd);
+    println!("Access:       {:?}", options.access);
+    println!("Auth:         {}", if options.psk.is_some() { "pre-shared key" } else { "none" });
+    if !options.allow.is_empty() {
+        println!("Allowed:      {}", allow.join(", "));
+    }
     println!("Press Ctrl+C to stop.");
 
-    let server = TcpServer::bind(&addr, &cwd)?;
+    let server = TcpServer::bind(&addr, &cwd)?.with_options(options);
     server.run()?;
 
     Ok(())
 }
 
+fn cmd_tcp_upload(config: &CopyConfig, remote: &smartcopy::config::RemoteConfig, quiet: bool) -> Result<()> {
+    use smartcopy::fs::{ScanConfig, Scanner};
+    use smartcopy::network::{load_tcp_key, TcpClient, TcpClientOptions};
+
+    let addr = format!("{}:{}", remote.host, remote.tcp_port);
+    // The server confines pushes to its root, so remote paths are relative to it
+    let remote_root: std::path::PathBuf = config
+        .destination
+        .components()
+        .filter(|c| matches!(c, std::path::Component::Normal(_)))
+        .collect();
+
+    let scanner = Scanner::new(ScanConfig {
+        follow_symlinks: config.follow_symlinks,
+        include_hidden: config.include_hidden,
+        include_patterns: config.include_patterns.clone(),
+        exclude_patterns: config.exclude_patterns.clone(),
+        min_size: config.min_size,
+        max_size: config.max_size,
+        threads: config.threads,
+        ..ScanConfig::default()
+    })?;
+    let scan = scanner.scan(&config.source)?;
+
+    if config.dry_run {
+        println!("Would upload {} files ({}) to {}:{}",
+            scan.file_count,
+            humansize::format_size(scan.total_size, humansize::BINARY),
+            addr,
+            remote_root.display()
+        );
+        return Ok(());
+    }
+
+    let start = std::time::Instant::now();
+    let options = TcpClientOptions {
+        psk: remote.tcp_key_file.as_deref().map(load_tcp_key).transpose()?,
+        encrypt: remote.tcp_encrypt,
+    };
+    let summary = TcpClient::upload_tree(&addr, &options, &scan, &remote_root, remote.streams)?;
+
+    if !quiet {
+        let duration = start.elapsed();
+        println!("\n=== Upload Summary ===");
+        println!("Files uploaded:  {}", summary.files_uploaded);
+        println!("Bytes uploaded:  {}", humansize::format_size(summary.bytes_uploaded, humansize::BINARY));
+        println!("Directories:     {}", summary.dirs_created);
+        println!("Duration:        {:.2?}", duration);
+        println!("Throughput:      {}/s", humansize::format_size(
+            (summary.bytes_uploaded as f64 / duration.as_secs_f64()) as u64,
+            humansize::BINARY
+        ));
+        if !summary.failures.is_empty() {
+            println!("\nFailures: {}", summary.failures.len());
+            for (path, error) in &summary.failures {
+                println!("  {} - {}", path, error);
+            }
+        }
+    }
+
+    if !summary.failures.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 fn cmd_benchmark(path: &Path, size: &str) -> Result<()> {
     use smartcopy::config::parse_size;
     use smartcopy::fs::FileCopier;
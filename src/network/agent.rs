@@ -22,9 +22,10 @@
 //! smartcopy agent --protocol tcp --port 9878
 //! ```
 
-use crate::config::AgentProtocol;
+use crate::config::{AgentProtocol, HashAlgorithm};
 use crate::error::{Result, SmartCopyError};
-use crate::sync::FileSignature;
+use crate::fs::unescape_path;
+use crate::sync::{apply_in_place, CdcParams, ChunkSignature, FileSignature, InPlaceOp, RenameResult, SyncAction, SyncChange};
 use serde::{Deserialize, Serialize};
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::net::{TcpListener, TcpStream};
@@ -52,23 +53,27 @@ pub enum AgentRequest {
 
     /// Request file signature for delta transfer
     GetSignature {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         chunk_size: usize,
     },
 
     /// Get file metadata
     GetMetadata {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
     },
 
     /// List directory contents
     ListDirectory {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         recursive: bool,
     },
 
     /// Read file chunk
     ReadChunk {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         offset: u64,
         size: usize,
@@ -76,6 +81,7 @@ pub enum AgentRequest {
 
     /// Write file chunk
     WriteChunk {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         offset: u64,
         data: Vec<u8>,
@@ -84,25 +90,30 @@ pub enum AgentRequest {
 
     /// Create file with preallocated size
     CreateFile {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         size: u64,
     },
 
     /// Apply delta to existing file
     ApplyDelta {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         source_path: PathBuf,
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         dest_path: PathBuf,
         delta_ops: Vec<DeltaOp>,
     },
 
     /// Calculate hash of file
     HashFile {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         algorithm: String,
     },
 
     /// Sync file times/permissions
     SetAttributes {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         mtime: Option<u64>,
         permissions: Option<u32>,
@@ -110,12 +121,14 @@ pub enum AgentRequest {
 
     /// Create directory
     CreateDirectory {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         recursive: bool,
     },
 
     /// Remove file or directory
     Remove {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
         recursive: bool,
     },
@@ -125,6 +138,45 @@ pub enum AgentRequest {
 
     /// Shutdown the agent
     Shutdown,
+
+    /// Set extended attributes (requires the "xattrs" feature)
+    SetXattrs {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
+        path: PathBuf,
+        xattrs: Vec<(String, Vec<u8>)>,
+    },
+
+    /// Request a content-defined chunk signature (requires the "cdc" feature)
+    GetCdcSignature {
+        /// File to chunk
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
+        path: PathBuf,
+        /// Chunk size limits
+        params: CdcParams,
+    },
+
+    /// Apply delta operations to an existing file in place, without a
+    /// second copy (requires the "inplace" feature)
+    ApplyDeltaInPlace {
+        /// File to update
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
+        path: PathBuf,
+        /// Operations; copy sources are offsets in the file before the update
+        delta_ops: Vec<DeltaOp>,
+        /// Size of the updated file
+        target_size: u64,
+    },
+
+    /// Move a file to a new path, creating parent directories; never
+    /// replaces an existing file (requires the "rename" feature)
+    Rename {
+        /// Existing file
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
+        from: PathBuf,
+        /// New path, which must not exist
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
+        to: PathBuf,
+    },
 }
 
 /// Delta operation for remote application
@@ -185,6 +237,7 @@ pub enum AgentResponse {
 
     /// File created
     FileCreated {
+        #[serde(with = "crate::fs::path_encoding::raw_bytes")]
         path: PathBuf,
     },
 
@@ -220,6 +273,14 @@ pub enum AgentResponse {
 
     /// Shutdown acknowledgment
     ShutdownAck,
+
+    /// Extended attributes set, with per-attribute failures
+    XattrsSet {
+        warnings: Vec<String>,
+    },
+
+    /// File moved
+    Renamed,
 }
 
 /// Remote file signature (serializable version)
@@ -245,6 +306,7 @@ pub struct RemoteChunkSignature {
 /// Remote directory entry for agent protocol
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentRemoteEntry {
+    #[serde(with = "crate::fs::path_encoding::raw_bytes")]
     pub path: PathBuf,
     pub is_file: bool,
     pub is_dir: bool,
@@ -271,6 +333,29 @@ impl From<FileSignature> for RemoteFileSignature {
     }
 }
 
+impl RemoteFileSignature {
+    /// Convert back into a [`FileSignature`] for delta calculation
+    ///
+    /// `cdc` must be the parameters the signature was requested with, or
+    /// `None` for a fixed-size block signature.
+    pub fn into_file_signature(self, cdc: Option<CdcParams>) -> FileSignature {
+        FileSignature {
+            path: self.path,
+            file_size: self.file_size,
+            chunk_size: self.chunk_size,
+            num_chunks: self.num_chunks,
+            chunks: self.chunks.into_iter().map(|c| ChunkSignature {
+                index: c.index,
+                offset: c.offset,
+                size: c.size,
+                weak_checksum: c.weak_checksum,
+                strong_hash: c.strong_hash,
+            }).collect(),
+            cdc,
+        }
+    }
+}
+
 /// Agent server for handling remote requests
 pub struct AgentServer {
     /// Protocol type
@@ -444,6 +529,10 @@ impl AgentServer {
                         "delta".to_string(),
                         "parallel".to_string(),
                         "compression".to_string(),
+                        "xattrs".to_string(),
+                        "cdc".to_string(),
+                        "inplace".to_string(),
+                        "rename".to_string(),
                     ],
                 })
             }
@@ -597,6 +686,66 @@ impl AgentServer {
                 self.shutdown.store(true, Ordering::SeqCst);
                 Ok(AgentResponse::ShutdownAck)
             }
+
+            AgentRequest::SetXattrs { path, xattrs } => {
+                if !path.exists() {
+                    return Ok(AgentResponse::Error {
+                        code: 11,
+                        message: format!("Path not found: {}", path.display()),
+                    });
+                }
+                Ok(AgentResponse::XattrsSet {
+                    warnings: crate::fs::write_xattrs(&path, &xattrs),
+                })
+            }
+
+            AgentRequest::GetCdcSignature { path, params } => {
+                match FileSignature::generate_cdc(&path, params) {
+                    Ok(sig) => Ok(AgentResponse::Signature {
+                        signature: RemoteFileSignature::from(sig),
+                    }),
+                    Err(e) => Ok(AgentResponse::Error {
+                        code: 2,
+                        message: format!("{}", e),
+                    }),
+                }
+            }
+
+            AgentRequest::ApplyDeltaInPlace { path, delta_ops, target_size } => {
+                match Self::apply_delta_in_place(&path, delta_ops, target_size) {
+                    Ok((copied, written)) => Ok(AgentResponse::DeltaApplied {
+                        bytes_copied: copied,
+                        bytes_written: written,
+                    }),
+                    Err(e) => Ok(AgentResponse::Error {
+                        code: 6,
+                        message: e.to_string(),
+                    }),
+                }
+            }
+
+            AgentRequest::Rename { from, to } => {
+                if to.symlink_metadata().is_ok() {
+                    return Ok(AgentResponse::Error {
+                        code: 12,
+                        message: format!("{} already exists", to.display()),
+                    });
+                }
+
+                let result = match to.parent() {
+                    Some(parent) => std::fs::create_dir_all(parent),
+                    None => Ok(()),
+                }
+                .and_then(|_| std::fs::rename(&from, &to));
+
+                match result {
+                    Ok(_) => Ok(AgentResponse::Renamed),
+                    Err(e) => Ok(AgentResponse::Error {
+                        code: 12,
+                        message: e.to_string(),
+                    }),
+                }
+            }
         }
     }
 
@@ -772,54 +921,38 @@ impl AgentServer {
         Ok((bytes_copied, bytes_written))
     }
 
+    /// Apply delta operations to a file in place
+    fn apply_delta_in_place(path: &Path, delta_ops: Vec<DeltaOp>, target_size: u64) -> Result<(u64, u64)> {
+        let ops = delta_ops
+            .into_iter()
+            .map(|op| match op {
+                DeltaOp::CopyChunk { source_offset, dest_offset, size } => InPlaceOp::Copy {
+                    src: source_offset,
+                    dst: dest_offset,
+                    len: size as u64,
+                },
+                DeltaOp::WriteLiteral { dest_offset, data } => InPlaceOp::Literal { dst: dest_offset, data },
+            })
+            .collect();
+        let result = apply_in_place(path, ops, target_size)?;
+        Ok((result.bytes_copied, result.bytes_written))
+    }
+
     /// Calculate hash of a file
+    ///
+    /// Accepts any `--verify` algorithm name, so digests match the ones in
+    /// local manifests and checksum files. The exception is `xxh3` and
+    /// `xxhash3`, which keep the 64-bit XXH3 digest earlier agents
+    /// returned for them; `xxh128` asks for the 128-bit digest that local
+    /// `--verify xxhash3` records.
     fn hash_file(path: &Path, algorithm: &str) -> Result<String> {
-        let mut file = std::fs::File::open(path)
-            .map_err(|e| SmartCopyError::io(path, e))?;
-
-        let hash = match algorithm.to_lowercase().as_str() {
-            "xxhash3" | "xxh3" => {
-                let mut buffer = Vec::new();
-                file.read_to_end(&mut buffer)
-                    .map_err(|e| SmartCopyError::io(path, e))?;
-                format!("{:016x}", xxhash_rust::xxh3::xxh3_64(&buffer))
-            }
-            "blake3" => {
-                let mut hasher = blake3::Hasher::new();
-                let mut buffer = [0u8; 65536];
-                loop {
-                    let bytes_read = file.read(&mut buffer)
-                        .map_err(|e| SmartCopyError::io(path, e))?;
-                    if bytes_read == 0 {
-                        break;
-                    }
-                    hasher.update(&buffer[..bytes_read]);
-                }
-                hasher.finalize().to_hex().to_string()
-            }
-            "sha256" => {
-                use sha2::{Sha256, Digest};
-                let mut hasher = Sha256::new();
-                let mut buffer = [0u8; 65536];
-                loop {
-                    let bytes_read = file.read(&mut buffer)
-                        .map_err(|e| SmartCopyError::io(path, e))?;
-                    if bytes_read == 0 {
-                        break;
-                    }
-                    hasher.update(&buffer[..bytes_read]);
-                }
-                hex::encode(hasher.finalize())
-            }
-            _ => {
-                return Err(SmartCopyError::config(format!(
-                    "Unknown hash algorithm: {}",
-                    algorithm
-                )));
-            }
-        };
-
-        Ok(hash)
+        if algorithm.eq_ignore_ascii_case("xxh3") || algorithm.eq_ignore_ascii_case("xxhash3") {
+            return Ok(format!("{:016x}", crate::hash::quick_hash(path)?));
+        }
+        let algorithm = HashAlgorithm::from_name(algorithm).ok_or_else(|| {
+            SmartCopyError::config(format!("Unknown hash algorithm: {}", algorithm))
+        })?;
+        Ok(crate::hash::hash_file(path, algorithm)?.hash)
     }
 
     /// Set file attributes
@@ -846,6 +979,7 @@ impl AgentServer {
 pub struct AgentClient {
     writer: Box<dyn Write + Send>,
     reader: Box<dyn Read + Send>,
+    server_features: Vec<String>,
 }
 
 impl AgentClient {
@@ -861,6 +995,7 @@ impl AgentClient {
         let mut client = Self {
             writer: Box::new(stream),
             reader: Box::new(BufReader::new(reader)),
+            server_features: Vec::new(),
         };
 
         // Send magic bytes
@@ -878,6 +1013,7 @@ impl AgentClient {
         let mut client = Self {
             writer: Box::new(stdin),
             reader: Box::new(BufReader::new(stdout)),
+            server_features: Vec::new(),
         };
 
         // Send magic bytes
@@ -900,13 +1036,14 @@ impl AgentClient {
         let response = self.send_request(&request)?;
 
         match response {
-            AgentResponse::HandshakeOk { version, .. } => {
+            AgentResponse::HandshakeOk { version, server_features } => {
                 if version != PROTOCOL_VERSION {
                     return Err(SmartCopyError::connection(
                         "agent",
                         format!("Protocol version mismatch: expected {}, got {}", PROTOCOL_VERSION, version),
                     ));
                 }
+                self.server_features = server_features;
                 Ok(())
             }
             AgentResponse::Error { message, .. } => {
@@ -1034,6 +1171,154 @@ impl AgentClient {
         }
     }
 
+    /// Apply delta on remote, updating `path` in place
+    ///
+    /// Copy sources are offsets in the remote file as it is now. An
+    /// interrupted update leaves a journal next to the file, see
+    /// [`crate::sync::read_in_place_journal`].
+    pub fn apply_delta_in_place(
+        &mut self,
+        path: &Path,
+        delta_ops: Vec<DeltaOp>,
+        target_size: u64,
+    ) -> Result<(u64, u64)> {
+        if !self.supports("inplace") {
+            return Err(SmartCopyError::UnsupportedOperation(
+                "remote agent does not support in-place delta".to_string(),
+            ));
+        }
+
+        let request = AgentRequest::ApplyDeltaInPlace {
+            path: path.to_path_buf(),
+            delta_ops,
+            target_size,
+        };
+
+        match self.send_request(&request)? {
+            AgentResponse::DeltaApplied { bytes_copied, bytes_written } => {
+                Ok((bytes_copied, bytes_written))
+            }
+            AgentResponse::Error { message, .. } => {
+                Err(SmartCopyError::RemoteTransferError(message))
+            }
+            _ => Err(SmartCopyError::RemoteTransferError(
+                "Unexpected response".to_string()
+            )),
+        }
+    }
+
+    /// Move a remote file to a new path
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<()> {
+        if !self.supports("rename") {
+            return Err(SmartCopyError::UnsupportedOperation(
+                "remote agent does not support rename".to_string(),
+            ));
+        }
+
+        let request = AgentRequest::Rename {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        };
+
+        match self.send_request(&request)? {
+            AgentResponse::Renamed => Ok(()),
+            AgentResponse::Error { message, .. } => {
+                Err(SmartCopyError::RemoteTransferError(message))
+            }
+            _ => Err(SmartCopyError::RemoteTransferError(
+                "Unexpected response".to_string()
+            )),
+        }
+    }
+
+    /// Apply renames detected by [`crate::sync::IncrementalSync`] under a
+    /// remote destination root
+    ///
+    /// The remote counterpart of [`crate::sync::apply_renames`]. A failed
+    /// rename is recorded and the file copied as usual; only transport
+    /// errors are returned.
+    pub fn apply_renames(&mut self, dest_root: &Path, renames: &[SyncChange]) -> Result<RenameResult> {
+        let mut result = RenameResult::default();
+
+        for change in renames {
+            let SyncAction::Rename { from } = &change.action else {
+                continue;
+            };
+            let (old, new) = (unescape_path(from), unescape_path(&change.path));
+
+            match self.rename(&dest_root.join(old), &dest_root.join(new)) {
+                Ok(()) => {
+                    result.renamed += 1;
+                    result.bytes += change.size;
+                }
+                Err(SmartCopyError::RemoteTransferError(message)) => {
+                    result.failed.push((change.path.clone(), message));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Check whether the remote agent advertised a feature
+    pub fn supports(&self, feature: &str) -> bool {
+        self.server_features.iter().any(|f| f == feature)
+    }
+
+    /// Get a content-defined chunk signature from remote
+    ///
+    /// Turn it into a [`FileSignature`] with
+    /// [`RemoteFileSignature::into_file_signature`] and the same `params`.
+    pub fn get_cdc_signature(&mut self, path: &Path, params: CdcParams) -> Result<RemoteFileSignature> {
+        if !self.supports("cdc") {
+            return Err(SmartCopyError::UnsupportedOperation(
+                "remote agent does not support content-defined chunking".to_string(),
+            ));
+        }
+
+        let request = AgentRequest::GetCdcSignature {
+            path: path.to_path_buf(),
+            params,
+        };
+
+        match self.send_request(&request)? {
+            AgentResponse::Signature { signature } => Ok(signature),
+            AgentResponse::Error { message, .. } => {
+                Err(SmartCopyError::RemoteTransferError(message))
+            }
+            _ => Err(SmartCopyError::RemoteTransferError(
+                "Unexpected response".to_string()
+            )),
+        }
+    }
+
+    /// Set extended attributes on a remote path
+    ///
+    /// Returns the attributes the agent could not set as warnings.
+    pub fn set_xattrs(&mut self, path: &Path, xattrs: Vec<(String, Vec<u8>)>) -> Result<Vec<String>> {
+        if !self.supports("xattrs") {
+            return Err(SmartCopyError::UnsupportedOperation(
+                "remote agent does not support extended attributes".to_string(),
+            ));
+        }
+
+        let request = AgentRequest::SetXattrs {
+            path: path.to_path_buf(),
+            xattrs,
+        };
+
+        match self.send_request(&request)? {
+            AgentResponse::XattrsSet { warnings } => Ok(warnings),
+            AgentResponse::Error { message, .. } => {
+                Err(SmartCopyError::RemoteTransferError(message))
+            }
+            _ => Err(SmartCopyError::RemoteTransferError(
+                "Unexpected response".to_string()
+            )),
+        }
+    }
+
     /// Ping the remote agent
     pub fn ping(&mut self) -> Result<bool> {
         match self.send_request(&AgentRequest::Ping)? {
@@ -1071,6 +1356,190 @@ mod tests {
         }
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join(crate::fs::path_from_bytes(b"caf\xe9.dat"));
+        std::fs::write(&file, b"data").unwrap();
+
+        let request = AgentRequest::GetMetadata { path: file.clone() };
+        let serialized = bincode::serialize(&request).unwrap();
+        let path = match bincode::deserialize(&serialized).unwrap() {
+            AgentRequest::GetMetadata { path } => path,
+            other => panic!("Wrong request type: {:?}", other),
+        };
+        assert_eq!(path, file);
+
+        let server = AgentServer::new(AgentProtocol::Stdio, 0, String::new());
+        match server.handle_request(AgentRequest::GetMetadata { path }).unwrap() {
+            AgentResponse::Metadata { exists, size, .. } => {
+                assert!(exists);
+                assert_eq!(size, 4);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        let listing = server
+            .handle_request(AgentRequest::ListDirectory {
+                path: dir.path().to_path_buf(),
+                recursive: false,
+            })
+            .unwrap();
+        let serialized = bincode::serialize(&listing).unwrap();
+        match bincode::deserialize(&serialized).unwrap() {
+            AgentResponse::DirectoryListing { entries } => {
+                assert!(entries.iter().any(|e| e.path.file_name() == file.file_name()));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_xattrs_request() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("data.bin");
+        std::fs::write(&file, b"data").unwrap();
+
+        let server = AgentServer::new(AgentProtocol::Stdio, 0, String::new());
+        let request = AgentRequest::SetXattrs {
+            path: file.clone(),
+            xattrs: vec![("user.origin".to_string(), b"instrument-7".to_vec())],
+        };
+
+        match server.handle_request(request).unwrap() {
+            AgentResponse::XattrsSet { warnings } => {
+                if warnings.is_empty() {
+                    assert_eq!(
+                        xattr::get(&file, "user.origin").unwrap(),
+                        Some(b"instrument-7".to_vec())
+                    );
+                }
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        let missing = AgentRequest::SetXattrs {
+            path: dir.path().join("missing"),
+            xattrs: Vec::new(),
+        };
+        assert!(matches!(
+            server.handle_request(missing).unwrap(),
+            AgentResponse::Error { code: 11, .. }
+        ));
+    }
+
+    #[test]
+    fn test_hash_file_request() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("data.bin");
+        std::fs::write(&file, b"abc").unwrap();
+        let server = AgentServer::new(AgentProtocol::Stdio, 0, String::new());
+
+        for (name, expected) in [
+            ("md5", "900150983cd24fb0d6963f7d28e17f72".to_string()),
+            ("CRC32C", crate::hash::hash_bytes(b"abc", HashAlgorithm::Crc32c).hash),
+            ("xxh128", crate::hash::hash_bytes(b"abc", HashAlgorithm::XXHash3).hash),
+            // Older clients get the 64-bit digest they always did
+            ("xxh3", format!("{:016x}", xxhash_rust::xxh3::xxh3_64(b"abc"))),
+            ("xxhash3", format!("{:016x}", xxhash_rust::xxh3::xxh3_64(b"abc"))),
+        ] {
+            let request = AgentRequest::HashFile {
+                path: file.clone(),
+                algorithm: name.to_string(),
+            };
+            match server.handle_request(request).unwrap() {
+                AgentResponse::Hash { hash, .. } => assert_eq!(hash, expected),
+                other => panic!("Unexpected response: {:?}", other),
+            }
+        }
+
+        let unknown = AgentRequest::HashFile {
+            path: file,
+            algorithm: "md4".to_string(),
+        };
+        assert!(matches!(
+            server.handle_request(unknown).unwrap(),
+            AgentResponse::Error { code: 7, .. }
+        ));
+    }
+
+    #[test]
+    fn test_cdc_signature_request() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("data.bin");
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+        std::fs::write(&file, &data).unwrap();
+        let params = CdcParams::new(1024, 4096, 16384).unwrap();
+
+        let server = AgentServer::new(AgentProtocol::Stdio, 0, String::new());
+        let request = AgentRequest::GetCdcSignature { path: file.clone(), params };
+        let serialized = bincode::serialize(&request).unwrap();
+        let response = server.handle_request(bincode::deserialize(&serialized).unwrap()).unwrap();
+
+        match response {
+            AgentResponse::Signature { signature } => {
+                let signature = signature.into_file_signature(Some(params));
+                let local = FileSignature::generate_cdc(&file, params).unwrap();
+                assert_eq!(signature.num_chunks, local.num_chunks);
+                let delta = crate::sync::FileDelta::calculate(&signature, &file, 0).unwrap();
+                assert_eq!(delta.transfer_size, 0);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_apply_delta_in_place_request() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("image.bin");
+        std::fs::write(&file, b"AAAABBBBCCCC").unwrap();
+
+        // Swap the first two blocks, replace the third and grow the file
+        let server = AgentServer::new(AgentProtocol::Stdio, 0, String::new());
+        let request = AgentRequest::ApplyDeltaInPlace {
+            path: file.clone(),
+            delta_ops: vec![
+                DeltaOp::CopyChunk { source_offset: 4, dest_offset: 0, size: 4 },
+                DeltaOp::CopyChunk { source_offset: 0, dest_offset: 4, size: 4 },
+                DeltaOp::WriteLiteral { dest_offset: 8, data: b"xyz!!".to_vec() },
+            ],
+            target_size: 13,
+        };
+        let serialized = bincode::serialize(&request).unwrap();
+        let response = server.handle_request(bincode::deserialize(&serialized).unwrap()).unwrap();
+
+        assert!(matches!(response, AgentResponse::DeltaApplied { bytes_copied: 8, bytes_written: 5 }));
+        assert_eq!(std::fs::read(&file).unwrap(), b"BBBBAAAAxyz!!");
+    }
+
+    #[test]
+    fn test_rename_request() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let old = dir.path().join("a.txt");
+        let new = dir.path().join("moved/b.txt");
+        std::fs::write(&old, b"content").unwrap();
+
+        let server = AgentServer::new(AgentProtocol::Stdio, 0, String::new());
+        let request = AgentRequest::Rename { from: old.clone(), to: new.clone() };
+        let serialized = bincode::serialize(&request).unwrap();
+        let response = server.handle_request(bincode::deserialize(&serialized).unwrap()).unwrap();
+
+        assert!(matches!(response, AgentResponse::Renamed));
+        assert!(!old.exists());
+        assert_eq!(std::fs::read(&new).unwrap(), b"content");
+
+        // Never overwrites
+        std::fs::write(&old, b"other").unwrap();
+        let request = AgentRequest::Rename { from: old.clone(), to: new.clone() };
+        assert!(matches!(
+            server.handle_request(request).unwrap(),
+            AgentResponse::Error { code: 12, .. }
+        ));
+        assert_eq!(std::fs::read(&new).unwrap(), b"content");
+    }
+
     #[test]
     fn test_response_serialization() {
         let response = AgentResponse::Pong;
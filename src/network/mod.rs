@@ -13,7 +13,7 @@
 //! |-----------|----------|-------|----------|
 //! | SSH/SFTP | TLS via SSH | Moderate | Remote servers |
 //! | SSH+Agent | TLS via SSH | Fast | Delta sync |
-//! | TCP Direct | None | Very Fast | Trusted LAN |
+//! | TCP Direct | Optional PSK + ChaCha20-Poly1305 | Very Fast | Trusted LAN |
 //! | QUIC | TLS 1.3 | Very Fast | Modern networks |
 //!
 //! ## Quick Start
@@ -29,7 +29,7 @@
 //! smartcopy /local remote:9877:/path --quic
 //!
 //! # TCP direct (LAN)
-//! smartcopy /local remote:9876:/path --tcp-direct
+//! smartcopy /local user@remote:/path --tcp-direct --tcp-port 9876
 //! ```
 
 mod ssh;
@@ -33,7 +33,7 @@
 
 use crate::config::RemoteConfig;
 use crate::error::{Result, SmartCopyError};
-use crate::fs::FileEntry;
+use crate::fs::{FileEntry, XattrFilter};
 use crate::network::agent::{AgentClient, AgentRequest, AgentResponse};
 use rayon::prelude::*;
 use std::collections::VecDeque;
@@ -185,6 +185,8 @@ pub struct ParallelSyncConfig {
     pub retry_delay: Duration,
     /// Bandwidth limit per connection (bytes/sec, 0 = unlimited)
     pub bandwidth_limit: u64,
+    /// Extended attributes to replicate (None = don't copy xattrs)
+    pub xattr_filter: Option<XattrFilter>,
 }
 
 impl Default for ParallelSyncConfig {
@@ -197,6 +199,7 @@ impl Default for ParallelSyncConfig {
             max_retries: 3,
             retry_delay: Duration::from_millis(100),
             bandwidth_limit: 0,
+            xattr_filter: None,
         }
     }
 }
@@ -235,6 +238,8 @@ pub struct TransferResult {
     pub error: Option<String>,
     /// Retries used
     pub retries: usize,
+    /// Non-fatal problems (e.g. xattrs the remote could not set)
+    pub warnings: Vec<String>,
 }
 
 /// Parallel remote sync engine
@@ -297,8 +302,12 @@ impl ParallelRemoteSync {
         let mut success_count = 0u64;
         let mut failure_count = 0u64;
         let mut failures = Vec::new();
+        let mut warnings = Vec::new();
 
         for result in &results {
+            for warning in &result.warnings {
+                warnings.push((result.source.display().to_string(), warning.clone()));
+            }
             if result.success {
                 total_bytes += result.bytes_transferred;
                 success_count += 1;
@@ -324,6 +333,7 @@ impl ParallelRemoteSync {
             duration,
             throughput,
             failures,
+            warnings,
             pool_stats: pool.stats(),
         })
     }
@@ -394,6 +404,7 @@ impl ParallelRemoteSync {
                         success: false,
                         error: Some("Shutdown requested".to_string()),
                         retries: 0,
+                        warnings: Vec::new(),
                     };
                 }
 
@@ -402,8 +413,8 @@ impl ParallelRemoteSync {
                 let mut last_error = None;
 
                 while retries <= config.max_retries {
-                    match Self::execute_single_transfer(pool, &op, &progress) {
-                        Ok(bytes) => {
+                    match Self::execute_single_transfer(pool, &op, &progress, config.xattr_filter.as_ref()) {
+                        Ok((bytes, warnings)) => {
                             let duration = start.elapsed();
                             let throughput = if duration.as_secs_f64() > 0.0 {
                                 bytes as f64 / duration.as_secs_f64()
@@ -419,6 +430,7 @@ impl ParallelRemoteSync {
                                 success: true,
                                 error: None,
                                 retries,
+                                warnings,
                             };
                         }
                         Err(e) => {
@@ -442,6 +454,7 @@ impl ParallelRemoteSync {
                     success: false,
                     error: last_error,
                     retries,
+                    warnings: Vec::new(),
                 }
             })
             .collect();
@@ -450,11 +463,14 @@ impl ParallelRemoteSync {
     }
 
     /// Execute a single transfer operation
+    ///
+    /// Extended attributes are sent once per file, with its first chunk.
     fn execute_single_transfer(
         pool: &ConnectionPool<AgentClient>,
         op: &TransferOp,
         progress: &Arc<SyncProgress>,
-    ) -> Result<u64> {
+        xattr_filter: Option<&XattrFilter>,
+    ) -> Result<(u64, Vec<String>)> {
         let mut conn = pool.get()?;
         let client = conn.get_mut()
             .ok_or_else(|| SmartCopyError::RemoteTransferError("No connection available".to_string()))?;
@@ -474,13 +490,25 @@ impl ParallelRemoteSync {
         let create = op.offset.is_none() || op.offset == Some(0);
         client.write_chunk(&op.dest, op.offset.unwrap_or(0), data, create)?;
 
+        let mut warnings = Vec::new();
+        if let (true, Some(filter)) = (create, xattr_filter) {
+            let (xattrs, read_warnings) = crate::fs::read_xattrs(&op.source, filter);
+            warnings.extend(read_warnings);
+            if !xattrs.is_empty() {
+                match client.set_xattrs(&op.dest, xattrs) {
+                    Ok(remote_warnings) => warnings.extend(remote_warnings),
+                    Err(e) => warnings.push(e.to_string()),
+                }
+            }
+        }
+
         // Update progress
         progress.add_bytes(bytes_len);
-        if op.offset.is_none() || op.offset == Some(0) {
+        if create {
             progress.add_file();
         }
 
-        Ok(bytes_len)
+        Ok((bytes_len, warnings))
     }
 
     /// Read a chunk from a file
@@ -621,6 +649,8 @@ pub struct SyncResult {
     pub throughput: f64,
     /// List of failures (path, error)
     pub failures: Vec<(String, String)>,
+    /// Non-fatal warnings (path, message)
+    pub warnings: Vec<(String, String)>,
     /// Connection pool statistics
     pub pool_stats: PoolStats,
 }
@@ -644,6 +674,13 @@ impl SyncResult {
                 println!("  {} - {}", path, err);
             }
         }
+
+        if !self.warnings.is_empty() {
+            println!("\nWarnings:");
+            for (path, warning) in &self.warnings {
+                println!("  {} - {}", path, warning);
+            }
+        }
     }
 }
 
@@ -361,6 +361,8 @@ mod tests {
             streams: 1,
             tcp_direct: false,
             tcp_port: 9876,
+            tcp_key_file: None,
+            tcp_encrypt: false,
             quic: false,
             quic_port: 9877,
             use_agent: false,
@@ -2,15 +2,29 @@
 //!
 //! Provides high-speed file transfer over TCP without SSH overhead.
 //! Best for trusted LAN environments where encryption is not required.
+//!
+//! Clients can download files from the server's root directory or push
+//! files and directories into it. Pushed files are written to a temporary
+//! name next to their destination and renamed into place once complete.
+//!
+//! Every connection starts with a handshake that negotiates the protocol
+//! version. A server configured with a pre-shared key requires both sides
+//! to prove knowledge of it (HMAC-SHA256 over fresh nonces), and the
+//! session can then be encrypted with ChaCha20-Poly1305 (`encryption`
+//! feature). Servers can also be limited to reads or writes and to a set
+//! of client networks.
 
 use crate::error::{IoResultExt, Result, SmartCopyError};
+use crate::fs::{escape_path, path_from_bytes, path_to_bytes, ScanResult};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use std::io::{BufReader, BufWriter, Read, Write};
-use std::net::{TcpListener, TcpStream, SocketAddr};
-use std::path::Path;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::net::{IpAddr, TcpListener, TcpStream, SocketAddr};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 /// TCP stream tuning configuration.
 ///
@@ -173,12 +187,48 @@ pub fn apply_tcp_tuning(stream: &TcpStream, config: &TcpStreamConfig) -> std::io
     Ok(())
 }
 
-/// Magic bytes for protocol identification
-const PROTOCOL_MAGIC: &[u8; 8] = b"SMCOPY01";
+/// Magic bytes opening the client handshake
+const PROTOCOL_MAGIC: &[u8; 8] = b"SMCOPYHS";
+
+/// Magic bytes sent by version 1 clients, which had no handshake
+const LEGACY_MAGIC: &[u8; 8] = b"SMCOPY01";
+
+/// Protocol version spoken by this build
+pub const TCP_PROTOCOL_VERSION: u16 = 2;
+
+/// Oldest protocol version this build accepts
+const MIN_PROTOCOL_VERSION: u16 = 2;
+
+/// Time allowed for a client to complete the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Shortest accepted pre-shared key
+const MIN_KEY_LEN: usize = 16;
+
+/// Handshake flag: authenticate with the pre-shared key
+const FLAG_AUTH: u8 = 1;
+/// Handshake flag: encrypt the session
+const FLAG_ENCRYPT: u8 = 2;
+/// Handshake flag: server refuses writes
+const FLAG_READ_ONLY: u8 = 4;
+/// Handshake flag: server refuses reads
+const FLAG_WRITE_ONLY: u8 = 8;
 
 /// Default buffer size (1MB)
 const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024;
 
+/// Largest path accepted in a push message
+const MAX_PATH_LEN: usize = 64 * 1024;
+
+/// Largest data frame accepted in a push message
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Suffix of files being pushed, before they are renamed into place
+const PUSH_TMP_SUFFIX: &str = ".smartcopy-push";
+
+/// Distinguishes concurrent pushes to the same path
+static PUSH_COUNTER: AtomicU64 = AtomicU64::new(0);
+
 /// Message types for the protocol
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -195,6 +245,20 @@ pub enum MessageType {
     Ping = 5,
     /// Pong response
     Pong = 6,
+    /// Start pushing a file (path, size)
+    PushCreate = 7,
+    /// Data frame of the file being pushed
+    PushData = 8,
+    /// End of the file being pushed; the server moves it into place
+    PushComplete = 9,
+    /// Set mode and modification time of a pushed path
+    SetAttributes = 10,
+    /// Create a directory and its parents
+    CreateDirectory = 11,
+    /// Push request succeeded
+    Ack = 12,
+    /// Handshake accepted (version, flags, server nonce)
+    Hello = 13,
     /// Shutdown server
     Shutdown = 255,
 }
@@ -208,6 +272,13 @@ impl MessageType {
             4 => Some(Self::Error),
             5 => Some(Self::Ping),
             6 => Some(Self::Pong),
+            7 => Some(Self::PushCreate),
+            8 => Some(Self::PushData),
+            9 => Some(Self::PushComplete),
+            10 => Some(Self::SetAttributes),
+            11 => Some(Self::CreateDirectory),
+            12 => Some(Self::Ack),
+            13 => Some(Self::Hello),
             255 => Some(Self::Shutdown),
             _ => None,
         }
@@ -224,6 +295,8 @@ pub struct TcpServer {
     shutdown: Arc<AtomicBool>,
     /// Bytes transferred counter
     bytes_transferred: Arc<AtomicU64>,
+    /// Authentication, access and network restrictions
+    options: Arc<TcpServerOptions>,
 }
 
 impl TcpServer {
@@ -237,9 +310,16 @@ impl TcpServer {
             root: root.to_path_buf(),
             shutdown: Arc::new(AtomicBool::new(false)),
             bytes_transferred: Arc::new(AtomicU64::new(0)),
+            options: Arc::new(TcpServerOptions::default()),
         })
     }
 
+    /// Set authentication, access and network restrictions
+    pub fn with_options(mut self, options: TcpServerOptions) -> Self {
+        self.options = Arc::new(options);
+        self
+    }
+
     /// Get the bound address
     pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
         self.listener.local_addr()
@@ -257,6 +337,17 @@ impl TcpServer {
 
     /// Run the server (blocking)
     pub fn run(&self) -> Result<()> {
+        if self.options.require_encryption && !cfg!(feature = "encryption") {
+            return Err(SmartCopyError::UnsupportedOperation(
+                "encryption requires the 'encryption' feature".to_string(),
+            ));
+        }
+        if self.options.require_encryption && self.options.psk.is_none() {
+            return Err(SmartCopyError::ConfigError(
+                "encryption requires a pre-shared key".to_string(),
+            ));
+        }
+
         self.listener.set_nonblocking(true)
             .map_err(|e| SmartCopyError::connection("listener", e.to_string()))?;
 
@@ -267,9 +358,10 @@ impl TcpServer {
 
                     let root = self.root.clone();
                     let bytes_counter = Arc::clone(&self.bytes_transferred);
+                    let options = Arc::clone(&self.options);
 
                     thread::spawn(move || {
-                        if let Err(e) = Self::handle_client(stream, &root, bytes_counter) {
+                        if let Err(e) = Self::handle_client(stream, addr, &root, bytes_counter, &options) {
                             tracing::error!("Client error: {}", e);
                         }
                     });
@@ -289,21 +381,31 @@ impl TcpServer {
     /// Handle a single client connection
     fn handle_client(
         stream: TcpStream,
+        peer: SocketAddr,
         root: &Path,
         bytes_counter: Arc<AtomicU64>,
+        options: &TcpServerOptions,
     ) -> Result<()> {
-        let mut reader = BufReader::with_capacity(DEFAULT_BUFFER_SIZE, stream.try_clone()?);
-        let mut writer = BufWriter::with_capacity(DEFAULT_BUFFER_SIZE, stream);
+        // Accepted sockets may inherit the listener's non-blocking mode
+        stream.set_nonblocking(false)?;
 
-        // Read and verify magic
-        let mut magic = [0u8; 8];
-        reader.read_exact(&mut magic)
-            .map_err(|e| SmartCopyError::RemoteTransferError(e.to_string()))?;
-
-        if &magic != PROTOCOL_MAGIC {
-            return Err(SmartCopyError::RemoteTransferError("Invalid protocol magic".to_string()));
+        if !options.allows(peer.ip()) {
+            tracing::warn!("Rejected connection from {}: address not allowed", peer);
+            return write_error(&mut &stream, "connection not allowed from this address");
         }
 
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let HandshakeOutcome::Accepted(keys) = Self::handshake(&stream, peer, options)? else {
+            return Ok(());
+        };
+        stream.set_read_timeout(None)?;
+
+        let (mut reader, mut writer) = transport(&stream, keys.as_ref(), false)?;
+        let access = options.access;
+
+        // File being pushed on this connection, removed if never completed
+        let mut upload: Option<PendingUpload> = None;
+
         loop {
             // Read message type
             let mut msg_type = [0u8; 1];
@@ -318,12 +420,75 @@ impl TcpServer {
 
             match msg_type {
                 MessageType::FileRequest => {
-                    Self::handle_file_request(&mut reader, &mut writer, root, &bytes_counter)?;
+                    Self::handle_file_request(&mut reader, &mut writer, root, &bytes_counter, access)?;
                 }
                 MessageType::Ping => {
                     writer.write_all(&[MessageType::Pong as u8])?;
                     writer.flush()?;
                 }
+                MessageType::PushCreate => {
+                    let path = read_bytes(&mut reader, MAX_PATH_LEN)?;
+                    let size = read_u64(&mut reader)?;
+                    // A new push abandons an unfinished one
+                    upload = None;
+                    if access == TcpAccess::ReadOnly {
+                        write_error(&mut writer, "server is read-only")?;
+                        continue;
+                    }
+                    match PendingUpload::create(root, &path, size) {
+                        Ok(pending) => {
+                            upload = Some(pending);
+                            write_ack(&mut writer)?;
+                        }
+                        Err(e) => write_error(&mut writer, &e)?,
+                    }
+                }
+                MessageType::PushData => {
+                    let data = read_bytes(&mut reader, MAX_FRAME_SIZE)?;
+                    // Without a push in progress the data is dropped and
+                    // PushComplete reports the error
+                    if let Some(pending) = upload.as_mut() {
+                        pending.write(&data);
+                        bytes_counter.fetch_add(data.len() as u64, Ordering::Relaxed);
+                    }
+                }
+                MessageType::PushComplete => {
+                    match upload.take() {
+                        Some(pending) => match pending.finish() {
+                            Ok(()) => write_ack(&mut writer)?,
+                            Err(e) => write_error(&mut writer, &e)?,
+                        },
+                        None => write_error(&mut writer, "no push in progress")?,
+                    }
+                }
+                MessageType::SetAttributes => {
+                    let path = read_bytes(&mut reader, MAX_PATH_LEN)?;
+                    let mode = read_u32(&mut reader)?;
+                    let secs = read_u64(&mut reader)? as i64;
+                    let nanos = read_u32(&mut reader)?;
+                    if access == TcpAccess::ReadOnly {
+                        write_error(&mut writer, "server is read-only")?;
+                        continue;
+                    }
+                    match Self::set_attributes(root, &path, mode, secs, nanos) {
+                        Ok(()) => write_ack(&mut writer)?,
+                        Err(e) => write_error(&mut writer, &e)?,
+                    }
+                }
+                MessageType::CreateDirectory => {
+                    let path = read_bytes(&mut reader, MAX_PATH_LEN)?;
+                    if access == TcpAccess::ReadOnly {
+                        write_error(&mut writer, "server is read-only")?;
+                        continue;
+                    }
+                    let result = resolve_client_entry(root, &path).and_then(|dir| {
+                        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", client_display(&path), e))
+                    });
+                    match result {
+                        Ok(()) => write_ack(&mut writer)?,
+                        Err(e) => write_error(&mut writer, &e)?,
+                    }
+                }
                 MessageType::Shutdown => break,
                 _ => {
                     return Err(SmartCopyError::RemoteTransferError(
@@ -342,23 +507,29 @@ impl TcpServer {
         writer: &mut W,
         root: &Path,
         bytes_counter: &Arc<AtomicU64>,
+        access: TcpAccess,
     ) -> Result<()> {
-        // Read path length
-        let mut len_buf = [0u8; 4];
-        reader.read_exact(&mut len_buf)?;
-        let path_len = u32::from_le_bytes(len_buf) as usize;
+        let path = read_bytes(reader, MAX_PATH_LEN)?;
 
-        // Read path
-        let mut path_buf = vec![0u8; path_len];
-        reader.read_exact(&mut path_buf)?;
-        let rel_path = String::from_utf8_lossy(&path_buf);
+        if access == TcpAccess::WriteOnly {
+            return write_error(writer, "server is write-only");
+        }
 
-        let file_path = root.join(&*rel_path);
+        let file_path = match resolve_client_path(root, &path) {
+            Ok(file_path) => file_path,
+            Err(e) => return write_error(writer, &e),
+        };
 
         // Open and send file
-        match std::fs::File::open(&file_path) {
-            Ok(file) => {
-                let size = file.metadata()?.len();
+        let opened = std::fs::File::open(&file_path).and_then(|file| {
+            let meta = file.metadata()?;
+            if !meta.is_file() {
+                return Err(std::io::Error::other("not a regular file"));
+            }
+            Ok((file, meta.len()))
+        });
+        match opened {
+            Ok((file, size)) => {
 
                 // Send file size
                 writer.write_all(&[MessageType::FileData as u8])?;
@@ -385,51 +556,707 @@ impl TcpServer {
                 writer.write_all(&[MessageType::FileComplete as u8])?;
                 writer.flush()?;
             }
-            Err(e) => {
-                // Send error
-                let error_msg = e.to_string();
-                writer.write_all(&[MessageType::Error as u8])?;
-                writer.write_all(&(error_msg.len() as u32).to_le_bytes())?;
-                writer.write_all(error_msg.as_bytes())?;
-                writer.flush()?;
-            }
+            Err(e) => write_error(writer, &format!("{}: {}", client_display(&path), e))?,
         }
 
         Ok(())
     }
+
+    /// Run the server side of the handshake
+    ///
+    /// A refused client has already been sent the reason.
+    fn handshake(
+        mut stream: &TcpStream,
+        peer: SocketAddr,
+        options: &TcpServerOptions,
+    ) -> Result<HandshakeOutcome> {
+        let mut magic = [0u8; 8];
+        stream.read_exact(&mut magic)
+            .map_err(|e| SmartCopyError::RemoteTransferError(e.to_string()))?;
+
+        if &magic == LEGACY_MAGIC {
+            // Version 1 clients read this as the reply to their first request
+            write_error(&mut stream, &format!(
+                "protocol version 1 is not supported by this server (versions {}-{}); upgrade the client",
+                MIN_PROTOCOL_VERSION, TCP_PROTOCOL_VERSION
+            ))?;
+            return Ok(HandshakeOutcome::Refused);
+        }
+        if &magic != PROTOCOL_MAGIC {
+            return Err(SmartCopyError::RemoteTransferError("Invalid protocol magic".to_string()));
+        }
+
+        let min_version = read_u16(&mut stream)?;
+        let max_version = read_u16(&mut stream)?;
+        let mut flags = [0u8; 1];
+        stream.read_exact(&mut flags)?;
+        let flags = flags[0];
+        let mut client_nonce = [0u8; 32];
+        stream.read_exact(&mut client_nonce)?;
+
+        let version = max_version.min(TCP_PROTOCOL_VERSION);
+        let refusal = if version < min_version.max(MIN_PROTOCOL_VERSION) {
+            Some(format!(
+                "no common protocol version: client speaks {}-{}, server {}-{}",
+                min_version, max_version, MIN_PROTOCOL_VERSION, TCP_PROTOCOL_VERSION
+            ))
+        } else if options.psk.is_some() && flags & FLAG_AUTH == 0 {
+            Some("server requires a pre-shared key".to_string())
+        } else if options.psk.is_none() && flags & FLAG_AUTH != 0 {
+            Some("server has no pre-shared key and cannot authenticate".to_string())
+        } else if flags & FLAG_ENCRYPT != 0 && !cfg!(feature = "encryption") {
+            Some("server was built without encryption support".to_string())
+        } else if options.require_encryption && flags & FLAG_ENCRYPT == 0 {
+            Some("server requires encryption".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = refusal {
+            tracing::warn!("Rejected connection from {}: {}", peer, reason);
+            write_error(&mut stream, &reason)?;
+            return Ok(HandshakeOutcome::Refused);
+        }
+
+        let mut server_flags = flags & (FLAG_AUTH | FLAG_ENCRYPT);
+        server_flags |= match options.access {
+            TcpAccess::ReadWrite => 0,
+            TcpAccess::ReadOnly => FLAG_READ_ONLY,
+            TcpAccess::WriteOnly => FLAG_WRITE_ONLY,
+        };
+        let server_nonce = random_nonce()?;
+
+        stream.write_all(&[MessageType::Hello as u8])?;
+        stream.write_all(&version.to_le_bytes())?;
+        stream.write_all(&[server_flags])?;
+        stream.write_all(&server_nonce)?;
+        stream.flush()?;
+
+        let Some(psk) = &options.psk else {
+            return Ok(HandshakeOutcome::Accepted(None));
+        };
+
+        let transcript = handshake_transcript(version, server_flags, &client_nonce, &server_nonce);
+        let mut proof = [0u8; 32];
+        stream.read_exact(&mut proof)?;
+        if handshake_mac(psk, b"smartcopy-tcp client proof", &transcript)
+            .verify_slice(&proof)
+            .is_err()
+        {
+            tracing::warn!("Rejected connection from {}: authentication failed", peer);
+            write_error(&mut stream, "authentication failed")?;
+            return Ok(HandshakeOutcome::Refused);
+        }
+
+        let server_proof = handshake_mac(psk, b"smartcopy-tcp server proof", &transcript).finalize().into_bytes();
+        stream.write_all(&[MessageType::Ack as u8])?;
+        stream.write_all(&server_proof)?;
+        stream.flush()?;
+
+        let keys = (server_flags & FLAG_ENCRYPT != 0).then(|| SessionKeys::derive(psk, &transcript));
+        Ok(HandshakeOutcome::Accepted(keys))
+    }
+
+    /// Apply a pushed mode (0 leaves it unchanged) and modification time
+    ///
+    /// Only the permission bits are applied; setuid, setgid and sticky bits
+    /// from a client are dropped.
+    fn set_attributes(root: &Path, path: &[u8], mode: u32, secs: i64, nanos: u32) -> std::result::Result<(), String> {
+        let shown = client_display(path);
+        let path = resolve_client_entry(root, path)?;
+
+        #[cfg(unix)]
+        if mode != 0 {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o777))
+                .map_err(|e| format!("{}: {}", shown, e))?;
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+
+        filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(secs, nanos))
+            .map_err(|e| format!("{}: {}", shown, e))
+    }
+}
+
+/// A file being pushed to the server
+struct PendingUpload {
+    /// Final path
+    dest: PathBuf,
+    /// Path as the client sent it, for error messages
+    shown: String,
+    /// Temporary file next to `dest`
+    tmp: PathBuf,
+    /// Writer for `tmp`
+    file: BufWriter<std::fs::File>,
+    /// Size announced by the client
+    expected: u64,
+    /// Bytes received so far
+    written: u64,
+    /// First write error; later data is dropped
+    error: Option<String>,
+}
+
+impl PendingUpload {
+    /// Create the temporary file for a push to `path` under `root`
+    fn create(root: &Path, path: &[u8], expected: u64) -> std::result::Result<Self, String> {
+        let shown = client_display(path);
+        let dest = resolve_client_entry(root, path)?;
+        let name = dest
+            .file_name()
+            .ok_or_else(|| format!("{} is not a file path", shown))?;
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("{}: {}", shown, e))?;
+        }
+
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(format!(
+            "{}.{}.{}",
+            PUSH_TMP_SUFFIX,
+            std::process::id(),
+            PUSH_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp = dest.with_file_name(tmp_name);
+        // Never follow or reuse something already at the temporary name
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+            .map_err(|e| format!("{}: {}", shown, e))?;
+
+        Ok(Self {
+            dest,
+            shown,
+            tmp,
+            file: BufWriter::with_capacity(DEFAULT_BUFFER_SIZE, file),
+            expected,
+            written: 0,
+            error: None,
+        })
+    }
+
+    /// Append a data frame
+    fn write(&mut self, data: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+        match self.file.write_all(data) {
+            Ok(()) => self.written += data.len() as u64,
+            Err(e) => self.error = Some(format!("{}: {}", self.shown, e)),
+        }
+    }
+
+    /// Persist the data and move the file into place
+    fn finish(mut self) -> std::result::Result<(), String> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        if self.written != self.expected {
+            return Err(format!(
+                "{}: received {} of {} bytes",
+                self.shown,
+                self.written,
+                self.expected
+            ));
+        }
+        self.file
+            .flush()
+            .and_then(|_| self.file.get_ref().sync_all())
+            .and_then(|_| std::fs::rename(&self.tmp, &self.dest))
+            .map_err(|e| format!("{}: {}", self.shown, e))
+    }
+}
+
+impl Drop for PendingUpload {
+    fn drop(&mut self) {
+        // Gone already if the push completed
+        let _ = std::fs::remove_file(&self.tmp);
+    }
+}
+
+/// Resolve a client-supplied path under the server root
+///
+/// Only plain relative paths are accepted, so a client cannot read or
+/// write outside the directory the server was started in. An empty path
+/// is the root itself. Paths through an existing symlink below the root
+/// are refused as well, since the link could point anywhere.
+fn resolve_client_path(root: &Path, path: &[u8]) -> std::result::Result<PathBuf, String> {
+    let relative = path_from_bytes(path);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(format!("{} is outside the server root", relative.display()));
+    }
+
+    let mut resolved = root.to_path_buf();
+    let mut exists = true;
+    for component in relative.components() {
+        resolved.push(component);
+        if !exists {
+            continue;
+        }
+        match std::fs::symlink_metadata(&resolved) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                return Err(format!("{} goes through a symlink", relative.display()));
+            }
+            Ok(_) => {}
+            // Nothing below a missing component can be a link yet
+            Err(_) => exists = false,
+        }
+    }
+    Ok(resolved)
+}
+
+/// Remote path of a scanned entry, `remote_root` itself for the scan root
+fn remote_path(remote_root: &Path, relative: &Path) -> PathBuf {
+    if relative.as_os_str().is_empty() {
+        remote_root.to_path_buf()
+    } else {
+        remote_root.join(relative)
+    }
+}
+
+/// Resolve a client path that names an entry below the server root
+///
+/// Pushes, attribute changes and directory creation must not act on the
+/// root itself: a temporary file next to it would land in its parent.
+fn resolve_client_entry(root: &Path, path: &[u8]) -> std::result::Result<PathBuf, String> {
+    let relative = path_from_bytes(path);
+    if !relative.components().any(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("'{}' does not name an entry below the server root", client_display(path)));
+    }
+    resolve_client_path(root, path)
+}
+
+/// A client path as shown in errors sent back to it
+///
+/// Errors name the path the client sent, never the server-side path, so
+/// the layout of the server's filesystem is not revealed.
+fn client_display(path: &[u8]) -> String {
+    escape_path(&path_from_bytes(path))
+}
+
+/// Read a length-prefixed byte string of at most `max` bytes
+fn read_bytes<R: Read>(reader: &mut R, max: usize) -> Result<Vec<u8>> {
+    let len = read_u32(reader)? as usize;
+    if len > max {
+        return Err(SmartCopyError::RemoteTransferError(format!(
+            "Message of {} bytes exceeds limit of {}",
+            len, max
+        )));
+    }
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Write a length-prefixed byte string
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Send an acknowledgment
+fn write_ack<W: Write>(writer: &mut W) -> Result<()> {
+    writer.write_all(&[MessageType::Ack as u8])?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Send an error message
+fn write_error<W: Write>(writer: &mut W, message: &str) -> Result<()> {
+    writer.write_all(&[MessageType::Error as u8])?;
+    write_bytes(writer, message.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read an error message body (after the message type)
+fn read_error<R: Read>(reader: &mut R) -> SmartCopyError {
+    match read_bytes(reader, MAX_FRAME_SIZE) {
+        Ok(msg) => SmartCopyError::RemoteTransferError(String::from_utf8_lossy(&msg).to_string()),
+        Err(e) => e,
+    }
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> Result<u16> {
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+/// Which operations a server permits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TcpAccess {
+    /// Downloads and pushes
+    #[default]
+    ReadWrite,
+    /// Downloads only
+    ReadOnly,
+    /// Pushes only
+    WriteOnly,
+}
+
+/// A client network allowed to connect, e.g. `10.0.0.0/8` or `::1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllowedNetwork {
+    /// Network address
+    pub addr: IpAddr,
+    /// Prefix length in bits
+    pub prefix: u8,
+}
+
+impl AllowedNetwork {
+    /// Whether `ip` lies in this network
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr.to_canonical(), ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for AllowedNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| format!("invalid address: {}", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.parse::<u8>().ok().filter(|&p| p <= max)
+                .ok_or_else(|| format!("invalid prefix length: {}", s))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// Server-side security settings
+#[derive(Clone, Default)]
+pub struct TcpServerOptions {
+    /// Pre-shared key clients must prove knowledge of
+    pub psk: Option<Vec<u8>>,
+    /// Permitted operations
+    pub access: TcpAccess,
+    /// Client networks allowed to connect (empty allows all)
+    pub allow: Vec<AllowedNetwork>,
+    /// Refuse unencrypted sessions
+    pub require_encryption: bool,
+}
+
+impl TcpServerOptions {
+    /// Whether a client address passes the allowlist
+    fn allows(&self, ip: IpAddr) -> bool {
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(ip))
+    }
+}
+
+/// Client-side security settings
+#[derive(Clone, Default)]
+pub struct TcpClientOptions {
+    /// Pre-shared key for servers that require one
+    pub psk: Option<Vec<u8>>,
+    /// Encrypt the session (requires a key and the `encryption` feature)
+    pub encrypt: bool,
+}
+
+/// Load a pre-shared key from a file
+///
+/// The file's contents are the key, without trailing whitespace; at least
+/// 16 bytes are required. Generate one with e.g.
+/// `head -c 32 /dev/urandom | base64 > tcp.key`.
+pub fn load_tcp_key(path: &Path) -> Result<Vec<u8>> {
+    let mut key = std::fs::read(path).with_path(path)?;
+    while key.last().is_some_and(|b| b.is_ascii_whitespace()) {
+        key.pop();
+    }
+    if key.len() < MIN_KEY_LEN {
+        return Err(SmartCopyError::ConfigError(format!(
+            "{}: pre-shared key must be at least {} bytes",
+            path.display(),
+            MIN_KEY_LEN
+        )));
+    }
+    Ok(key)
+}
+
+/// Result of the server side of the handshake
+enum HandshakeOutcome {
+    /// Session established, with keys if it is encrypted
+    Accepted(Option<SessionKeys>),
+    /// Client refused
+    Refused,
+}
+
+/// Per-direction keys of an encrypted session
+#[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+struct SessionKeys {
+    /// Client to server
+    c2s: [u8; 32],
+    /// Server to client
+    s2c: [u8; 32],
+}
+
+impl SessionKeys {
+    fn derive(psk: &[u8], transcript: &[u8]) -> Self {
+        Self {
+            c2s: handshake_mac(psk, b"smartcopy-tcp c2s key", transcript).finalize().into_bytes().into(),
+            s2c: handshake_mac(psk, b"smartcopy-tcp s2c key", transcript).finalize().into_bytes().into(),
+        }
+    }
+}
+
+/// Buffered message reader for one side of a session
+type SessionReader = BufReader<Box<dyn Read + Send>>;
+
+/// Buffered message writer for one side of a session
+type SessionWriter = BufWriter<Box<dyn Write + Send>>;
+
+/// Wrap a handshaken stream, encrypting it if the session has keys
+#[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+fn transport(stream: &TcpStream, keys: Option<&SessionKeys>, client: bool) -> Result<(SessionReader, SessionWriter)> {
+    let read_half = stream.try_clone()?;
+    let write_half = stream.try_clone()?;
+
+    let (reader, writer): (Box<dyn Read + Send>, Box<dyn Write + Send>) = match keys {
+        None => (Box::new(read_half), Box::new(write_half)),
+        #[cfg(feature = "encryption")]
+        Some(keys) => {
+            use crate::crypto::{EncryptedReader, EncryptedWriter};
+            let (read_key, write_key) = if client { (&keys.s2c, &keys.c2s) } else { (&keys.c2s, &keys.s2c) };
+            (
+                Box::new(EncryptedReader::new(BufReader::new(read_half), read_key)),
+                Box::new(EncryptedWriter::new(write_half, write_key)),
+            )
+        }
+        #[cfg(not(feature = "encryption"))]
+        Some(_) => {
+            return Err(SmartCopyError::UnsupportedOperation(
+                "encryption requires the 'encryption' feature".to_string(),
+            ))
+        }
+    };
+
+    Ok((
+        BufReader::with_capacity(DEFAULT_BUFFER_SIZE, reader),
+        BufWriter::with_capacity(DEFAULT_BUFFER_SIZE, writer),
+    ))
+}
+
+/// Bytes both sides authenticate: the negotiated version and flags and
+/// both nonces
+fn handshake_transcript(version: u16, flags: u8, client_nonce: &[u8; 32], server_nonce: &[u8; 32]) -> Vec<u8> {
+    let mut transcript = Vec::with_capacity(67);
+    transcript.extend_from_slice(&version.to_le_bytes());
+    transcript.push(flags);
+    transcript.extend_from_slice(client_nonce);
+    transcript.extend_from_slice(server_nonce);
+    transcript
+}
+
+/// HMAC-SHA256 keyed with the pre-shared key over a label and transcript
+fn handshake_mac(psk: &[u8], label: &[u8], transcript: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(psk).expect("HMAC accepts any key length");
+    mac.update(label);
+    mac.update(transcript);
+    mac
+}
+
+/// Fresh random handshake nonce
+fn random_nonce() -> Result<[u8; 32]> {
+    let mut nonce = [0u8; 32];
+    getrandom::getrandom(&mut nonce)
+        .map_err(|e| SmartCopyError::RemoteTransferError(format!("no randomness for handshake: {}", e)))?;
+    Ok(nonce)
 }
 
 /// TCP transfer client
 pub struct TcpClient {
     /// Connection stream
     stream: TcpStream,
+    /// Messages from the server (decrypted if the session is encrypted)
+    reader: SessionReader,
+    /// Messages to the server
+    writer: SessionWriter,
     /// Buffer size
     buffer_size: usize,
+    /// Operations the server permits
+    access: TcpAccess,
 }
 
 impl TcpClient {
-    /// Connect to a TCP server
+    /// Connect to a TCP server without a pre-shared key
     pub fn connect(addr: &str) -> Result<Self> {
+        Self::connect_with(addr, &TcpClientOptions::default())
+    }
+
+    /// Connect to a TCP server, authenticating and encrypting as configured
+    ///
+    /// With a pre-shared key, the server must also prove it knows the key,
+    /// so data is never sent to an impostor.
+    pub fn connect_with(addr: &str, options: &TcpClientOptions) -> Result<Self> {
+        if options.encrypt && options.psk.is_none() {
+            return Err(SmartCopyError::ConfigError(
+                "encryption requires a pre-shared key".to_string(),
+            ));
+        }
+        if options.encrypt && !cfg!(feature = "encryption") {
+            return Err(SmartCopyError::UnsupportedOperation(
+                "encryption requires the 'encryption' feature".to_string(),
+            ));
+        }
+
         let stream = TcpStream::connect(addr)
             .map_err(|e| SmartCopyError::connection(addr, e.to_string()))?;
-
-        // Send protocol magic
-        let mut stream_clone = stream.try_clone()?;
-        stream_clone.write_all(PROTOCOL_MAGIC)?;
-        stream_clone.flush()?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+        let (keys, flags) = Self::handshake(&stream, options).map_err(|e| match e {
+            SmartCopyError::Io { source, .. } if source.kind() == std::io::ErrorKind::UnexpectedEof => {
+                SmartCopyError::connection(
+                    addr,
+                    "server closed the connection during the handshake (older protocol version or address not allowed)"
+                        .to_string(),
+                )
+            }
+            e => e,
+        })?;
+        stream.set_read_timeout(None)?;
+
+        let (reader, writer) = transport(&stream, keys.as_ref(), true)?;
+        let access = if flags & FLAG_READ_ONLY != 0 {
+            TcpAccess::ReadOnly
+        } else if flags & FLAG_WRITE_ONLY != 0 {
+            TcpAccess::WriteOnly
+        } else {
+            TcpAccess::ReadWrite
+        };
 
         Ok(Self {
             stream,
+            reader,
+            writer,
             buffer_size: DEFAULT_BUFFER_SIZE,
+            access,
         })
     }
 
+    /// Run the client side of the handshake, returning the session keys
+    /// and the server's flags
+    fn handshake(mut stream: &TcpStream, options: &TcpClientOptions) -> Result<(Option<SessionKeys>, u8)> {
+        let mut flags = 0;
+        if options.psk.is_some() {
+            flags |= FLAG_AUTH;
+        }
+        if options.encrypt {
+            flags |= FLAG_ENCRYPT;
+        }
+        let client_nonce = random_nonce()?;
+
+        stream.write_all(PROTOCOL_MAGIC)?;
+        stream.write_all(&MIN_PROTOCOL_VERSION.to_le_bytes())?;
+        stream.write_all(&TCP_PROTOCOL_VERSION.to_le_bytes())?;
+        stream.write_all(&[flags])?;
+        stream.write_all(&client_nonce)?;
+        stream.flush()?;
+
+        let mut msg_type = [0u8; 1];
+        stream.read_exact(&mut msg_type)?;
+        match MessageType::from_u8(msg_type[0]) {
+            Some(MessageType::Hello) => {}
+            Some(MessageType::Error) => return Err(read_error(&mut stream)),
+            _ => {
+                return Err(SmartCopyError::RemoteTransferError(
+                    "Unexpected handshake response".to_string()
+                ))
+            }
+        }
+
+        let version = read_u16(&mut stream)?;
+        let mut server_flags = [0u8; 1];
+        stream.read_exact(&mut server_flags)?;
+        let server_flags = server_flags[0];
+        let mut server_nonce = [0u8; 32];
+        stream.read_exact(&mut server_nonce)?;
+
+        if !(MIN_PROTOCOL_VERSION..=TCP_PROTOCOL_VERSION).contains(&version)
+            || server_flags & (FLAG_AUTH | FLAG_ENCRYPT) != flags
+        {
+            return Err(SmartCopyError::RemoteTransferError(format!(
+                "server negotiated unsupported protocol version {} or options",
+                version
+            )));
+        }
+
+        let Some(psk) = &options.psk else {
+            return Ok((None, server_flags));
+        };
+
+        let transcript = handshake_transcript(version, server_flags, &client_nonce, &server_nonce);
+        let proof = handshake_mac(psk, b"smartcopy-tcp client proof", &transcript).finalize().into_bytes();
+        stream.write_all(&proof)?;
+        stream.flush()?;
+
+        stream.read_exact(&mut msg_type)?;
+        match MessageType::from_u8(msg_type[0]) {
+            Some(MessageType::Ack) => {}
+            Some(MessageType::Error) => return Err(read_error(&mut stream)),
+            _ => {
+                return Err(SmartCopyError::RemoteTransferError(
+                    "Unexpected handshake response".to_string()
+                ))
+            }
+        }
+        let mut server_proof = [0u8; 32];
+        stream.read_exact(&mut server_proof)?;
+        if handshake_mac(psk, b"smartcopy-tcp server proof", &transcript)
+            .verify_slice(&server_proof)
+            .is_err()
+        {
+            return Err(SmartCopyError::RemoteTransferError(
+                "server failed authentication".to_string()
+            ));
+        }
+
+        let keys = (flags & FLAG_ENCRYPT != 0).then(|| SessionKeys::derive(psk, &transcript));
+        Ok((keys, server_flags))
+    }
+
     /// Set buffer size
     pub fn with_buffer_size(mut self, size: usize) -> Self {
         self.buffer_size = size;
         self
     }
 
+    /// Operations the server permits
+    pub fn access(&self) -> TcpAccess {
+        self.access
+    }
+
     /// Download a file from the server
     pub fn download(&mut self, remote_path: &str, local_path: &Path) -> Result<u64> {
         // Ensure local parent exists
@@ -437,8 +1264,8 @@ impl TcpClient {
             std::fs::create_dir_all(parent).with_path(parent)?;
         }
 
-        let mut writer = BufWriter::with_capacity(self.buffer_size, self.stream.try_clone()?);
-        let mut reader = BufReader::with_capacity(self.buffer_size, self.stream.try_clone()?);
+        let writer = &mut self.writer;
+        let reader = &mut self.reader;
 
         // Send file request
         writer.write_all(&[MessageType::FileRequest as u8])?;
@@ -487,18 +1314,212 @@ impl TcpClient {
 
                 Ok(total_received)
             }
-            Some(MessageType::Error) => {
-                // Read error message
-                let mut len_buf = [0u8; 4];
-                reader.read_exact(&mut len_buf)?;
-                let msg_len = u32::from_le_bytes(len_buf) as usize;
+            Some(MessageType::Error) => Err(read_error(reader)),
+            _ => Err(SmartCopyError::RemoteTransferError(
+                "Unexpected response message".to_string()
+            )),
+        }
+    }
+
+    /// Upload a local file to `remote_path` on the server
+    ///
+    /// The remote path is relative to the server root. The file is moved
+    /// into place only once all data has arrived, then its mode and
+    /// modification time are set. Returns the number of bytes sent.
+    pub fn upload(&mut self, local_path: &Path, remote_path: &Path) -> Result<u64> {
+        let file = std::fs::File::open(local_path).with_path(local_path)?;
+        let meta = file.metadata().with_path(local_path)?;
+        let size = meta.len();
+
+        self.writer.write_all(&[MessageType::PushCreate as u8])?;
+        write_bytes(&mut self.writer, &path_to_bytes(remote_path))?;
+        self.writer.write_all(&size.to_le_bytes())?;
+        self.writer.flush()?;
+        self.read_ack()?;
+
+        let mut file_reader = BufReader::with_capacity(self.buffer_size, file);
+        let mut buffer = vec![0u8; self.buffer_size.min(MAX_FRAME_SIZE)];
+        let mut total_sent = 0u64;
+
+        // A local read error still completes the push, which the server
+        // then rejects, so the connection stays usable
+        let read_result = loop {
+            match file_reader.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(n) => {
+                    self.writer.write_all(&[MessageType::PushData as u8])?;
+                    write_bytes(&mut self.writer, &buffer[..n])?;
+                    total_sent += n as u64;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(SmartCopyError::io(local_path, e)),
+            }
+        };
 
-                let mut msg_buf = vec![0u8; msg_len];
-                reader.read_exact(&mut msg_buf)?;
-                let error_msg = String::from_utf8_lossy(&msg_buf);
+        self.writer.write_all(&[MessageType::PushComplete as u8])?;
+        self.writer.flush()?;
+        let completed = self.read_ack();
+        read_result?;
+        completed?;
 
-                Err(SmartCopyError::RemoteTransferError(error_msg.to_string()))
+        let mtime = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+        self.set_attributes(remote_path, file_mode(&meta), mtime)?;
+
+        Ok(total_sent)
+    }
+
+    /// Create a directory (and its parents) on the server
+    pub fn create_dir(&mut self, remote_path: &Path) -> Result<()> {
+        self.writer.write_all(&[MessageType::CreateDirectory as u8])?;
+        write_bytes(&mut self.writer, &path_to_bytes(remote_path))?;
+        self.writer.flush()?;
+        self.read_ack()
+    }
+
+    /// Set the mode (0 leaves it unchanged) and modification time of a
+    /// path on the server
+    pub fn set_attributes(&mut self, remote_path: &Path, mode: u32, mtime: SystemTime) -> Result<()> {
+        let since_epoch = mtime.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+
+        self.writer.write_all(&[MessageType::SetAttributes as u8])?;
+        write_bytes(&mut self.writer, &path_to_bytes(remote_path))?;
+        self.writer.write_all(&mode.to_le_bytes())?;
+        self.writer.write_all(&since_epoch.as_secs().to_le_bytes())?;
+        self.writer.write_all(&since_epoch.subsec_nanos().to_le_bytes())?;
+        self.writer.flush()?;
+        self.read_ack()
+    }
+
+    /// Upload a scanned tree to `remote_root` over `connections` parallel
+    /// connections
+    ///
+    /// Directories are created first and their attributes set last, so
+    /// file writes don't disturb directory times. Files go largest first
+    /// to keep connections evenly loaded. A file the server rejects is
+    /// recorded as a failure; a connection that breaks is dropped and its
+    /// remaining work taken by the others.
+    pub fn upload_tree(
+        addr: &str,
+        options: &TcpClientOptions,
+        scan: &ScanResult,
+        remote_root: &Path,
+        connections: usize,
+    ) -> Result<UploadSummary> {
+        let mut summary = UploadSummary::default();
+        let mut control = TcpClient::connect_with(addr, options)?;
+        if control.access() == TcpAccess::ReadOnly {
+            return Err(SmartCopyError::RemoteTransferError("server is read-only".to_string()));
+        }
+
+        // A single-file source is pushed to `remote_root` itself, and the
+        // server root (an empty `remote_root`) is never created or changed
+        let mut dirs: Vec<_> = scan
+            .directories
+            .iter()
+            .map(|dir| (dir, remote_path(remote_root, &dir.relative_path)))
+            .filter(|(_, remote)| !remote.as_os_str().is_empty())
+            .collect();
+        if scan.root.is_dir() && !remote_root.as_os_str().is_empty() {
+            control.create_dir(remote_root)?;
+        }
+        for (dir, remote) in &dirs {
+            match control.create_dir(remote) {
+                Ok(()) => summary.dirs_created += 1,
+                Err(SmartCopyError::RemoteTransferError(e)) => {
+                    summary.failures.push((escape_path(&dir.relative_path), e));
+                }
+                Err(e) => return Err(e),
             }
+        }
+
+        let mut files: Vec<_> = scan
+            .files
+            .iter()
+            .filter(|f| !f.is_symlink && f.special.is_none())
+            .collect();
+        files.sort_by_key(|f| std::cmp::Reverse(f.size));
+
+        let next = AtomicUsize::new(0);
+        let done: Vec<AtomicBool> = files.iter().map(|_| AtomicBool::new(false)).collect();
+        let results = Mutex::new((0u64, 0u64, Vec::new()));
+
+        thread::scope(|scope| {
+            for _ in 0..connections.max(1).min(files.len().max(1)) {
+                scope.spawn(|| {
+                    let mut client = match TcpClient::connect_with(addr, options) {
+                        Ok(client) => client,
+                        Err(e) => {
+                            tracing::warn!("Upload connection failed: {}", e);
+                            return;
+                        }
+                    };
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        let Some(file) = files.get(i) else { break };
+                        let result = client.upload(&file.path, &remote_path(remote_root, &file.relative_path));
+                        done[i].store(true, Ordering::SeqCst);
+
+                        let mut results = results.lock().unwrap_or_else(|e| e.into_inner());
+                        match result {
+                            Ok(bytes) => {
+                                results.0 += 1;
+                                results.1 += bytes;
+                            }
+                            Err(e) => {
+                                // Server rejections and local file errors leave
+                                // the connection in step; socket errors don't
+                                let broken = match &e {
+                                    SmartCopyError::RemoteTransferError(_) => false,
+                                    SmartCopyError::Io { path, .. } => path.as_os_str().is_empty(),
+                                    _ => true,
+                                };
+                                results.2.push((escape_path(&file.relative_path), e.to_string()));
+                                if broken {
+                                    tracing::warn!("Upload connection lost: {}", e);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    let _ = client.close();
+                });
+            }
+        });
+
+        let (files_uploaded, bytes_uploaded, failures) = results.into_inner().unwrap_or_else(|e| e.into_inner());
+        summary.files_uploaded = files_uploaded;
+        summary.bytes_uploaded = bytes_uploaded;
+        summary.failures.extend(failures);
+        for (file, done) in files.iter().zip(&done) {
+            if !done.load(Ordering::SeqCst) {
+                summary.failures.push((escape_path(&file.relative_path), "no connection to upload over".to_string()));
+            }
+        }
+
+        // Deepest first, so setting a child's times doesn't touch its parent's
+        dirs.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.relative_path.components().count()));
+        for (dir, remote) in &dirs {
+            match control.set_attributes(remote, dir.permissions, dir.modified) {
+                Ok(()) => {}
+                Err(SmartCopyError::RemoteTransferError(e)) => {
+                    summary.failures.push((escape_path(&dir.relative_path), e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let _ = control.close();
+
+        Ok(summary)
+    }
+
+    /// Wait for an Ack, turning an Error reply into an error
+    fn read_ack(&mut self) -> Result<()> {
+        let mut msg_type = [0u8; 1];
+        self.reader.read_exact(&mut msg_type)?;
+
+        match MessageType::from_u8(msg_type[0]) {
+            Some(MessageType::Ack) => Ok(()),
+            Some(MessageType::Error) => Err(read_error(&mut self.reader)),
             _ => Err(SmartCopyError::RemoteTransferError(
                 "Unexpected response message".to_string()
             )),
@@ -509,12 +1530,11 @@ impl TcpClient {
     pub fn ping(&mut self) -> Result<Duration> {
         let start = std::time::Instant::now();
 
-        let mut stream = self.stream.try_clone()?;
-        stream.write_all(&[MessageType::Ping as u8])?;
-        stream.flush()?;
+        self.writer.write_all(&[MessageType::Ping as u8])?;
+        self.writer.flush()?;
 
         let mut response = [0u8; 1];
-        stream.read_exact(&mut response)?;
+        self.reader.read_exact(&mut response)?;
 
         if response[0] != MessageType::Pong as u8 {
             return Err(SmartCopyError::RemoteTransferError("Invalid ping response".to_string()));
@@ -525,12 +1545,40 @@ impl TcpClient {
 
     /// Close the connection
     pub fn close(mut self) -> Result<()> {
-        self.stream.write_all(&[MessageType::Shutdown as u8])?;
-        self.stream.flush()?;
+        self.writer.write_all(&[MessageType::Shutdown as u8])?;
+        self.writer.flush()?;
+        self.stream.shutdown(std::net::Shutdown::Write)?;
         Ok(())
     }
 }
 
+/// Result of [`TcpClient::upload_tree`]
+#[derive(Debug, Clone, Default)]
+pub struct UploadSummary {
+    /// Files uploaded
+    pub files_uploaded: u64,
+    /// Bytes uploaded
+    pub bytes_uploaded: u64,
+    /// Directories created
+    pub dirs_created: u64,
+    /// Failed paths (escaped relative path, error)
+    pub failures: Vec<(String, String)>,
+}
+
+/// Permission bits of a local file (0 where not available)
+fn file_mode(meta: &std::fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        meta.permissions().mode()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = meta;
+        0
+    }
+}
+
 /// Measure network bandwidth between two points
 pub fn measure_bandwidth(server_addr: &str, _test_size: usize) -> Result<BandwidthResult> {
     let mut client = TcpClient::connect(server_addr)?;
@@ -617,6 +1665,379 @@ mod tests {
         assert!(client.nodelay().unwrap());
     }
 
+    /// Start a server on a free local port, returning its address
+    fn start_server(root: &Path) -> (String, Arc<AtomicBool>, thread::JoinHandle<()>) {
+        start_server_with(root, TcpServerOptions::default())
+    }
+
+    fn start_server_with(root: &Path, options: TcpServerOptions) -> (String, Arc<AtomicBool>, thread::JoinHandle<()>) {
+        let server = TcpServer::bind("127.0.0.1:0", root).unwrap().with_options(options);
+        let addr = server.local_addr().unwrap().to_string();
+        let shutdown = server.shutdown_flag();
+        let handle = thread::spawn(move || {
+            let _ = server.run();
+        });
+        (addr, shutdown, handle)
+    }
+
+    #[test]
+    fn test_resolve_client_path() {
+        let root = Path::new("/srv/data");
+        assert_eq!(resolve_client_path(root, b"a/b.txt").unwrap(), root.join("a/b.txt"));
+        assert_eq!(resolve_client_path(root, b"").unwrap(), root);
+        assert!(resolve_client_path(root, b"../escape").is_err());
+        assert!(resolve_client_path(root, b"a/../../escape").is_err());
+        assert!(resolve_client_path(root, b"/etc/passwd").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_resolve_client_path_refuses_symlinks() {
+        let outside = TempDir::new().unwrap();
+        let root = TempDir::new().unwrap();
+        std::fs::write(outside.path().join("target.txt"), b"outside").unwrap();
+        std::fs::create_dir(root.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.path().join("dir_link")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("target.txt"), root.path().join("file_link")).unwrap();
+
+        assert!(resolve_client_path(root.path(), b"dir_link/new.txt").is_err());
+        assert!(resolve_client_path(root.path(), b"dir_link/target.txt").is_err());
+        assert!(resolve_client_path(root.path(), b"file_link").is_err());
+        assert_eq!(
+            resolve_client_path(root.path(), b"real/missing/new.txt").unwrap(),
+            root.path().join("real/missing/new.txt")
+        );
+
+        // Pushes through a link leave the outside directory untouched
+        let (addr, shutdown, server_thread) = start_server(root.path());
+        let local = root.path().join("real/local.txt");
+        std::fs::write(&local, b"pushed").unwrap();
+        let mut client = TcpClient::connect(&addr).unwrap();
+        assert!(client.upload(&local, Path::new("dir_link/new.txt")).is_err());
+        assert!(!outside.path().join("new.txt").exists());
+        assert!(client.download("file_link", &root.path().join("real/copy.txt")).is_err());
+
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = client.close();
+        let _ = server_thread.join();
+    }
+
+    #[test]
+    fn test_tcp_upload() {
+        let local = TempDir::new().unwrap();
+        let remote = TempDir::new().unwrap();
+        let (addr, shutdown, server_thread) = start_server(remote.path());
+
+        let file_path = local.path().join("data.bin");
+        std::fs::write(&file_path, vec![7u8; 300 * 1024]).unwrap();
+        let mtime = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&file_path, mtime).unwrap();
+
+        // Small frames to exercise several data messages
+        let mut client = TcpClient::connect(&addr).unwrap().with_buffer_size(64 * 1024);
+        let bytes = client.upload(&file_path, Path::new("nested/data.bin")).unwrap();
+        assert_eq!(bytes, 300 * 1024);
+
+        let uploaded = remote.path().join("nested/data.bin");
+        assert_eq!(std::fs::read(&uploaded).unwrap(), vec![7u8; 300 * 1024]);
+        let meta = std::fs::metadata(&uploaded).unwrap();
+        assert_eq!(filetime::FileTime::from_last_modification_time(&meta), mtime);
+        // No temporary files left behind
+        assert_eq!(std::fs::read_dir(remote.path().join("nested")).unwrap().count(), 1);
+
+        // Special mode bits from the client are dropped
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            client.set_attributes(Path::new("nested/data.bin"), 0o4755, SystemTime::now()).unwrap();
+            let mode = std::fs::metadata(&uploaded).unwrap().permissions().mode();
+            assert_eq!(mode & 0o7777, 0o755);
+        }
+
+        // Rejected paths fail without breaking the connection
+        let err = client.upload(&file_path, Path::new("../outside.bin")).unwrap_err();
+        assert!(matches!(err, SmartCopyError::RemoteTransferError(_)));
+        assert!(!remote.path().parent().unwrap().join("outside.bin").exists());
+        client.ping().unwrap();
+
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = client.close();
+        let _ = server_thread.join();
+    }
+
+    #[test]
+    fn test_tcp_download_outside_root_refused() {
+        let parent = TempDir::new().unwrap();
+        let root = parent.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("inside.txt"), b"inside").unwrap();
+        std::fs::write(parent.path().join("x"), b"secret").unwrap();
+        let (addr, shutdown, server_thread) = start_server(&root);
+
+        let local = TempDir::new().unwrap();
+        let target = local.path().join("out");
+        let mut client = TcpClient::connect(&addr).unwrap();
+        for path in ["../x", "/etc/passwd", "a/../../x"] {
+            let err = client.download(path, &target).unwrap_err();
+            assert!(matches!(err, SmartCopyError::RemoteTransferError(_)), "{}", path);
+        }
+        assert!(!target.exists());
+
+        // Errors name the requested path, not the server's
+        let err = client.download("missing.txt", &target).unwrap_err();
+        assert!(err.to_string().contains("missing.txt"), "{}", err);
+        assert!(!err.to_string().contains(&*root.to_string_lossy()), "{}", err);
+
+        // The connection stays usable after a refusal
+        assert_eq!(client.download("inside.txt", &target).unwrap(), 6);
+        assert_eq!(std::fs::read(&target).unwrap(), b"inside");
+
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = client.close();
+        let _ = server_thread.join();
+    }
+
+    #[test]
+    fn test_tcp_upload_tree() {
+        let local = TempDir::new().unwrap();
+        let remote = TempDir::new().unwrap();
+        let (addr, shutdown, server_thread) = start_server(remote.path());
+
+        std::fs::create_dir_all(local.path().join("a/b")).unwrap();
+        std::fs::create_dir_all(local.path().join("empty")).unwrap();
+        std::fs::write(local.path().join("top.txt"), b"top").unwrap();
+        std::fs::write(local.path().join("a/one.txt"), b"one").unwrap();
+        std::fs::write(local.path().join("a/b/two.bin"), vec![2u8; 100_000]).unwrap();
+
+        let scan = crate::fs::Scanner::new(crate::fs::ScanConfig::default())
+            .unwrap()
+            .scan(local.path())
+            .unwrap();
+        let summary = TcpClient::upload_tree(&addr, &TcpClientOptions::default(), &scan, Path::new("backup"), 3).unwrap();
+
+        assert!(summary.failures.is_empty(), "{:?}", summary.failures);
+        assert_eq!(summary.files_uploaded, 3);
+        assert_eq!(summary.bytes_uploaded, 100_006);
+        let dest = remote.path().join("backup");
+        assert_eq!(std::fs::read(dest.join("top.txt")).unwrap(), b"top");
+        assert_eq!(std::fs::read(dest.join("a/one.txt")).unwrap(), b"one");
+        assert_eq!(std::fs::read(dest.join("a/b/two.bin")).unwrap().len(), 100_000);
+        assert!(dest.join("empty").is_dir());
+
+        // An empty remote root pushes into the server root itself
+        let summary = TcpClient::upload_tree(&addr, &TcpClientOptions::default(), &scan, Path::new(""), 2).unwrap();
+        assert!(summary.failures.is_empty(), "{:?}", summary.failures);
+        assert_eq!(std::fs::read(remote.path().join("a/one.txt")).unwrap(), b"one");
+
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = server_thread.join();
+    }
+
+    #[test]
+    fn test_tcp_upload_single_file() {
+        let local = TempDir::new().unwrap();
+        let remote = TempDir::new().unwrap();
+        let (addr, shutdown, server_thread) = start_server(remote.path());
+
+        let source = local.path().join("f.txt");
+        std::fs::write(&source, b"single").unwrap();
+        let scan = crate::fs::Scanner::new(crate::fs::ScanConfig::default())
+            .unwrap()
+            .scan(&source)
+            .unwrap();
+        let summary =
+            TcpClient::upload_tree(&addr, &TcpClientOptions::default(), &scan, Path::new("dest/f.txt"), 2).unwrap();
+
+        assert!(summary.failures.is_empty(), "{:?}", summary.failures);
+        assert_eq!(summary.files_uploaded, 1);
+        let uploaded = remote.path().join("dest/f.txt");
+        assert!(uploaded.is_file());
+        assert_eq!(std::fs::read(&uploaded).unwrap(), b"single");
+
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = server_thread.join();
+    }
+
+    #[test]
+    fn test_tcp_push_to_root_refused() {
+        let parent = TempDir::new().unwrap();
+        let root = parent.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        let (addr, shutdown, server_thread) = start_server(&root);
+
+        let local = TempDir::new().unwrap();
+        let file_path = local.path().join("data.bin");
+        std::fs::write(&file_path, b"data").unwrap();
+
+        let mut client = TcpClient::connect(&addr).unwrap();
+        for path in ["", ".", "./."] {
+            let err = client.upload(&file_path, Path::new(path)).unwrap_err();
+            assert!(matches!(err, SmartCopyError::RemoteTransferError(_)), "{:?}", path);
+            assert!(client.set_attributes(Path::new(path), 0o700, SystemTime::now()).is_err());
+            assert!(client.create_dir(Path::new(path)).is_err());
+        }
+        // Nothing was written next to the root
+        assert_eq!(std::fs::read_dir(parent.path()).unwrap().count(), 1);
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+
+        // Errors name the client's path, not the server's
+        let err = client.upload(&file_path, Path::new("missing/../../x")).unwrap_err();
+        assert!(!err.to_string().contains(&*root.to_string_lossy()), "{}", err);
+        std::fs::write(root.join("file"), b"x").unwrap();
+        let err = client.upload(&file_path, Path::new("file/below")).unwrap_err();
+        assert!(err.to_string().contains("file/below"), "{}", err);
+        assert!(!err.to_string().contains(&*root.to_string_lossy()), "{}", err);
+        client.ping().unwrap();
+
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = client.close();
+        let _ = server_thread.join();
+    }
+
+    #[test]
+    fn test_allowed_network() {
+        let net: AllowedNetwork = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains("10.1.200.3".parse().unwrap()));
+        assert!(!net.contains("10.2.0.1".parse().unwrap()));
+        // IPv4-mapped IPv6 peers match IPv4 networks
+        assert!(net.contains("::ffff:10.1.0.9".parse().unwrap()));
+
+        let host: AllowedNetwork = "::1".parse().unwrap();
+        assert_eq!(host.prefix, 128);
+        assert!(host.contains("::1".parse().unwrap()));
+        assert!(!host.contains("127.0.0.1".parse().unwrap()));
+
+        let any: AllowedNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("192.0.2.1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<AllowedNetwork>().is_err());
+        assert!("not-an-ip".parse::<AllowedNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_tcp_pre_shared_key() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("file.txt"), b"secret data").unwrap();
+        let key = b"0123456789abcdef0123456789abcdef".to_vec();
+        let (addr, shutdown, server_thread) = start_server_with(dir.path(), TcpServerOptions {
+            psk: Some(key.clone()),
+            ..Default::default()
+        });
+
+        let err = TcpClient::connect(&addr).err().unwrap();
+        assert!(err.to_string().contains("requires a pre-shared key"), "{}", err);
+
+        let wrong = TcpClientOptions { psk: Some(b"wrong key, wrong key".to_vec()), encrypt: false };
+        let err = TcpClient::connect_with(&addr, &wrong).err().unwrap();
+        assert!(err.to_string().contains("authentication failed"), "{}", err);
+
+        let options = TcpClientOptions { psk: Some(key), encrypt: false };
+        let mut client = TcpClient::connect_with(&addr, &options).unwrap();
+        let bytes = client.download("file.txt", &dir.path().join("copy.txt")).unwrap();
+        assert_eq!(bytes, 11);
+        let _ = client.close();
+
+        // A keyed client will not talk to a server that cannot prove the key
+        let (open_addr, open_shutdown, open_thread) = start_server(dir.path());
+        assert!(TcpClient::connect_with(&open_addr, &options).is_err());
+
+        shutdown.store(true, Ordering::SeqCst);
+        open_shutdown.store(true, Ordering::SeqCst);
+        let _ = server_thread.join();
+        let _ = open_thread.join();
+    }
+
+    #[test]
+    fn test_tcp_access_and_allowlist() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, b"data").unwrap();
+
+        let (addr, shutdown, server_thread) = start_server_with(dir.path(), TcpServerOptions {
+            access: TcpAccess::ReadOnly,
+            ..Default::default()
+        });
+        let mut client = TcpClient::connect(&addr).unwrap();
+        assert_eq!(client.access(), TcpAccess::ReadOnly);
+        let err = client.upload(&file, Path::new("pushed.txt")).unwrap_err();
+        assert!(err.to_string().contains("read-only"), "{}", err);
+        assert!(!dir.path().join("pushed.txt").exists());
+        client.download("file.txt", &dir.path().join("copy.txt")).unwrap();
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = server_thread.join();
+
+        let (addr, shutdown, server_thread) = start_server_with(dir.path(), TcpServerOptions {
+            access: TcpAccess::WriteOnly,
+            ..Default::default()
+        });
+        let mut client = TcpClient::connect(&addr).unwrap();
+        let err = client.download("file.txt", &dir.path().join("copy2.txt")).unwrap_err();
+        assert!(err.to_string().contains("write-only"), "{}", err);
+        client.upload(&file, Path::new("pushed.txt")).unwrap();
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = server_thread.join();
+
+        let (addr, shutdown, server_thread) = start_server_with(dir.path(), TcpServerOptions {
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        });
+        let err = TcpClient::connect(&addr).err().unwrap();
+        assert!(err.to_string().contains("not allowed"), "{}", err);
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = server_thread.join();
+    }
+
+    #[test]
+    fn test_tcp_legacy_client_rejected() {
+        let dir = TempDir::new().unwrap();
+        let (addr, shutdown, server_thread) = start_server(dir.path());
+
+        // A version 1 client sends its magic and a request straight away
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(LEGACY_MAGIC).unwrap();
+        stream.write_all(&[MessageType::FileRequest as u8]).unwrap();
+        write_bytes(&mut stream, b"file.txt").unwrap();
+
+        let mut msg_type = [0u8; 1];
+        stream.read_exact(&mut msg_type).unwrap();
+        assert_eq!(msg_type[0], MessageType::Error as u8);
+        let err = read_error(&mut stream);
+        assert!(err.to_string().contains("protocol version 1"), "{}", err);
+
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = server_thread.join();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_tcp_encrypted_session() {
+        let local = TempDir::new().unwrap();
+        let remote = TempDir::new().unwrap();
+        let key = b"an encryption test key".to_vec();
+        let (addr, shutdown, server_thread) = start_server_with(remote.path(), TcpServerOptions {
+            psk: Some(key.clone()),
+            require_encryption: true,
+            ..Default::default()
+        });
+
+        let plain = TcpClientOptions { psk: Some(key.clone()), encrypt: false };
+        let err = TcpClient::connect_with(&addr, &plain).err().unwrap();
+        assert!(err.to_string().contains("requires encryption"), "{}", err);
+
+        let data: Vec<u8> = (0..700_000u32).map(|i| (i % 253) as u8).collect();
+        std::fs::write(local.path().join("data.bin"), &data).unwrap();
+        let options = TcpClientOptions { psk: Some(key), encrypt: true };
+        let mut client = TcpClient::connect_with(&addr, &options).unwrap();
+        client.upload(&local.path().join("data.bin"), Path::new("data.bin")).unwrap();
+        client.ping().unwrap();
+        client.download("data.bin", &local.path().join("back.bin")).unwrap();
+        assert_eq!(std::fs::read(remote.path().join("data.bin")).unwrap(), data);
+        assert_eq!(std::fs::read(local.path().join("back.bin")).unwrap(), data);
+
+        shutdown.store(true, Ordering::SeqCst);
+        let _ = client.close();
+        let _ = server_thread.join();
+    }
+
     #[test]
     #[ignore] // Requires starting a server
     fn test_tcp_transfer() {
@@ -17,8 +17,9 @@ const DEFAULT_MAX_CONCURRENT: usize = 64;
 /// Default multipart upload threshold: 100 MB.
 const MULTIPART_THRESHOLD: u64 = 100 * 1024 * 1024;
 
-/// Default part size for multipart upload: 8 MB.
-const MULTIPART_PART_SIZE: u64 = 8 * 1024 * 1024;
+/// Part size for multipart upload: 8 MB (the `--part-size` that checks
+/// this client's multipart ETags).
+pub const MULTIPART_PART_SIZE: u64 = 8 * 1024 * 1024;
 
 /// Maximum retry delay cap in seconds.
 const MAX_RETRY_DELAY_SECS: u64 = 64;
@@ -404,22 +404,13 @@ impl S3Client {
     }
 
     fn list_with_cli(&self, prefix: &Option<String>) -> io::Result<Vec<S3Object>> {
-        let s3_url = match prefix {
-            Some(p) => format!("s3://{}/{}", self.config.bucket, p),
-            None => format!("s3://{}", self.config.bucket),
-        };
-
-        let output = std::process::Command::new("aws")
-            .args(&[
-                "s3",
-                "ls",
-                &s3_url,
-                "--recursive",
-                "--output",
-                "json",
-            ])
-            .env_args(&self.config)
-            .output()?;
+        // s3api listings carry ETags and sizes; `aws s3 ls` doesn't
+        let mut cmd = std::process::Command::new("aws");
+        cmd.args(["s3api", "list-objects-v2", "--bucket", &self.config.bucket, "--output", "json"]);
+        if let Some(prefix) = prefix {
+            cmd.args(["--prefix", prefix.as_str()]);
+        }
+        let output = cmd.env_args(&self.config).output()?;
 
         if !output.status.success() {
             return Err(io::Error::new(
@@ -428,29 +419,7 @@ impl S3Client {
             ));
         }
 
-        // Parse output (simplified, would need proper JSON parsing in production)
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let mut objects = Vec::new();
-
-        for line in stdout.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 4 {
-                let size = parts[2].parse().unwrap_or(0);
-                let key = parts[3..].join(" ");
-
-                objects.push(S3Object {
-                    key,
-                    size,
-                    last_modified: 0,
-                    etag: None,
-                    storage_class: None,
-                    content_type: None,
-                    metadata: HashMap::new(),
-                });
-            }
-        }
-
-        Ok(objects)
+        parse_list_objects_json(&output.stdout)
     }
 
     fn upload_single(&self, local_path: &Path, key: &str) -> io::Result<S3Object> {
@@ -594,6 +563,58 @@ impl CommandS3Ext for std::process::Command {
     }
 }
 
+/// One object in an `aws s3api list-objects-v2` listing
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedObject {
+    key: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    last_modified: Option<String>,
+    #[serde(default, rename = "ETag")]
+    etag: Option<String>,
+    #[serde(default)]
+    storage_class: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ObjectListing {
+    #[serde(default)]
+    contents: Vec<ListedObject>,
+}
+
+/// Parse the JSON output of `aws s3api list-objects-v2`
+///
+/// ETags are kept as listed, including their quotes. An empty document
+/// (what the CLI prints for an empty prefix) is an empty listing.
+pub fn parse_list_objects_json(json: &[u8]) -> io::Result<Vec<S3Object>> {
+    if json.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Vec::new());
+    }
+    let listing: ObjectListing = serde_json::from_slice(json)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(listing
+        .contents
+        .into_iter()
+        .map(|obj| S3Object {
+            key: obj.key,
+            size: obj.size,
+            last_modified: obj
+                .last_modified
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok())
+                .map(|t| t.timestamp().max(0) as u64)
+                .unwrap_or(0),
+            etag: obj.etag,
+            storage_class: obj.storage_class,
+            content_type: None,
+            metadata: HashMap::new(),
+        })
+        .collect())
+}
+
 /// Parse S3 URL (s3://bucket/key)
 pub fn parse_s3_url(url: &str) -> Option<(String, String)> {
     if !url.starts_with("s3://") {
@@ -639,6 +660,31 @@ mod tests {
         assert!(!is_s3_url("https://bucket.s3.amazonaws.com/key"));
     }
 
+    #[test]
+    fn test_parse_list_objects_json() {
+        let json = br#"{
+            "Contents": [
+                {
+                    "Key": "backup/data.bin",
+                    "LastModified": "2024-05-01T12:00:00+00:00",
+                    "ETag": "\"9e107d9d372bb6826bd81d3542a419d6-3\"",
+                    "Size": 25165824,
+                    "StorageClass": "STANDARD"
+                },
+                { "Key": "backup/empty", "Size": 0 }
+            ]
+        }"#;
+        let objects = parse_list_objects_json(json).unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].size, 25165824);
+        assert_eq!(objects[0].last_modified, 1714564800);
+        assert_eq!(objects[0].etag.as_deref(), Some("\"9e107d9d372bb6826bd81d3542a419d6-3\""));
+        assert_eq!(objects[1].etag, None);
+
+        assert!(parse_list_objects_json(b"\n").unwrap().is_empty());
+        assert!(parse_list_objects_json(b"2024-05-01 data.bin").is_err());
+    }
+
     #[test]
     fn test_s3_config_minio() {
         let config = S3Config::minio(
@@ -0,0 +1,148 @@
+//! Content-defined chunking (FastCDC)
+//!
+//! Fixed-size chunks shift with every inserted or deleted byte, so after an
+//! edit near the start of a file no later chunk lines up with the old
+//! version. FastCDC cuts where a rolling gear hash of the content hits a
+//! mask, so cut points move with the data: an edit only changes the chunks
+//! around it, and the same content gives the same chunks in any file. Chunk
+//! hashes then identify content across files and versions.
+
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use fastcdc::v2020::{StreamCDC, AVERAGE_MAX, AVERAGE_MIN, MAXIMUM_MAX, MAXIMUM_MIN, MINIMUM_MAX, MINIMUM_MIN};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+
+/// FastCDC chunk size limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CdcParams {
+    /// Smallest chunk (except a file's last)
+    pub min_size: u32,
+    /// Target average chunk size
+    pub avg_size: u32,
+    /// Largest chunk
+    pub max_size: u32,
+}
+
+impl Default for CdcParams {
+    fn default() -> Self {
+        Self {
+            min_size: 16 * 1024,
+            avg_size: 64 * 1024,
+            max_size: 256 * 1024,
+        }
+    }
+}
+
+impl CdcParams {
+    /// Create chunking parameters, checking them against FastCDC's limits
+    pub fn new(min_size: u32, avg_size: u32, max_size: u32) -> Result<Self> {
+        let params = Self {
+            min_size,
+            avg_size,
+            max_size,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Check the sizes are ordered and within FastCDC's limits
+    pub fn validate(&self) -> Result<()> {
+        let in_range = (MINIMUM_MIN..=MINIMUM_MAX).contains(&self.min_size)
+            && (AVERAGE_MIN..=AVERAGE_MAX).contains(&self.avg_size)
+            && (MAXIMUM_MIN..=MAXIMUM_MAX).contains(&self.max_size);
+        if !in_range || self.min_size > self.avg_size || self.avg_size > self.max_size {
+            return Err(SmartCopyError::config(format!(
+                "invalid CDC sizes {}: need min <= avg <= max with min {}..={}, avg {}..={}, max {}..={}",
+                self, MINIMUM_MIN, MINIMUM_MAX, AVERAGE_MIN, AVERAGE_MAX, MAXIMUM_MIN, MAXIMUM_MAX
+            )));
+        }
+        Ok(())
+    }
+
+    /// Split a file into chunks, calling `f` with each chunk's offset and data
+    ///
+    /// Returns the file size.
+    pub fn chunk_file<F>(&self, path: &Path, mut f: F) -> Result<u64>
+    where
+        F: FnMut(u64, &[u8]) -> Result<()>,
+    {
+        self.validate()?;
+        let file = File::open(path).with_path(path)?;
+        let mut size = 0u64;
+        for chunk in StreamCDC::new(file, self.min_size, self.avg_size, self.max_size) {
+            let chunk = chunk.map_err(std::io::Error::from).with_path(path)?;
+            size = chunk.offset + chunk.length as u64;
+            f(chunk.offset, &chunk.data)?;
+        }
+        Ok(size)
+    }
+}
+
+impl std::fmt::Display for CdcParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.min_size, self.avg_size, self.max_size)
+    }
+}
+
+impl std::str::FromStr for CdcParams {
+    type Err = String;
+
+    /// Parse `MIN,AVG,MAX` sizes such as `16K,64K,256K`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let sizes = s
+            .split(',')
+            .map(|part| {
+                let size = crate::config::parse_size(part.trim())?;
+                u32::try_from(size).map_err(|_| format!("chunk size too large: {}", part))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        match sizes[..] {
+            [min, avg, max] => Self::new(min, avg, max).map_err(|e| e.to_string()),
+            _ => Err(format!("expected MIN,AVG,MAX chunk sizes, got '{}'", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cdc_params_and_chunking() {
+        let params: CdcParams = "1K,4K,16K".parse().unwrap();
+        assert_eq!(params, CdcParams::new(1024, 4096, 16384).unwrap());
+        assert!("4K,1K,16K".parse::<CdcParams>().is_err());
+        assert!("1K,4K".parse::<CdcParams>().is_err());
+        assert!(CdcParams::new(16, 4096, 16384).is_err());
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        let mut state = 9u64;
+        let data: Vec<u8> = (0..200_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 56) as u8
+            })
+            .collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let mut chunks = Vec::new();
+        let size = params
+            .chunk_file(&path, |offset, chunk| {
+                chunks.push((offset, chunk.to_vec()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(size, data.len() as u64);
+        assert!(chunks.len() > 10);
+        let mut joined = Vec::new();
+        for (offset, chunk) in &chunks {
+            assert_eq!(*offset, joined.len() as u64);
+            joined.extend_from_slice(chunk);
+        }
+        assert_eq!(joined, data);
+        assert!(chunks[..chunks.len() - 1].iter().all(|(_, c)| c.len() >= 1024 && c.len() <= 16384));
+    }
+}
@@ -4,9 +4,18 @@
 //! - Block-level change detection
 //! - Only transfer modified chunks
 //! - Parallel chunk processing
+//!
+//! Signatures use either fixed-size blocks, matched at any offset by a
+//! rolling search, or content-defined chunks ([`CdcParams`]), matched by
+//! chunking the new file the same way.
 
+use crate::config::HashAlgorithm;
 use crate::error::{IoResultExt, Result, SmartCopyError};
+use crate::fs::escape_path;
+use crate::hash::{hash_bytes, ChunkHashes};
+use crate::sync::{apply_in_place_with, CdcParams, InPlaceOp};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
@@ -15,6 +24,10 @@ use std::path::Path;
 /// Default chunk size (1 MB)
 pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
 
+/// Longest literal [`FileDelta::for_each_op`] emits for content-defined
+/// signatures
+pub const MAX_LITERAL_RUN: usize = 1024 * 1024;
+
 /// Rolling checksum for fast block comparison (Adler32-like)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RollingChecksum {
@@ -72,7 +85,7 @@ fn strong_hash(data: &[u8]) -> u64 {
 }
 
 /// A chunk/block signature
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkSignature {
     /// Block index
     pub index: usize,
@@ -87,18 +100,20 @@ pub struct ChunkSignature {
 }
 
 /// Signature for entire file (list of chunk signatures)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileSignature {
-    /// File path
+    /// File path (escaped form, see [`escape_path`])
     pub path: String,
     /// Total file size
     pub file_size: u64,
-    /// Chunk size used
+    /// Chunk size used (the average for content-defined chunks)
     pub chunk_size: usize,
     /// Number of chunks
     pub num_chunks: usize,
     /// Chunk signatures
     pub chunks: Vec<ChunkSignature>,
+    /// Content-defined chunking parameters, `None` for fixed-size blocks
+    pub cdc: Option<CdcParams>,
 }
 
 impl FileSignature {
@@ -139,11 +154,36 @@ impl FileSignature {
         }
 
         Ok(FileSignature {
-            path: path.to_string_lossy().to_string(),
+            path: escape_path(path),
             file_size,
             chunk_size,
             num_chunks: chunks.len(),
             chunks,
+            cdc: None,
+        })
+    }
+
+    /// Generate a signature of content-defined chunks
+    pub fn generate_cdc(path: &Path, params: CdcParams) -> Result<Self> {
+        let mut chunks = Vec::new();
+        let file_size = params.chunk_file(path, |offset, data| {
+            chunks.push(ChunkSignature {
+                index: chunks.len(),
+                offset,
+                size: data.len(),
+                weak_checksum: RollingChecksum::calculate(data).value(),
+                strong_hash: strong_hash(data),
+            });
+            Ok(())
+        })?;
+
+        Ok(FileSignature {
+            path: escape_path(path),
+            file_size,
+            chunk_size: params.avg_size as usize,
+            num_chunks: chunks.len(),
+            chunks,
+            cdc: Some(params),
         })
     }
 
@@ -193,15 +233,27 @@ impl FileSignature {
             .collect::<Result<Vec<_>>>()?;
 
         Ok(FileSignature {
-            path: path.to_string_lossy().to_string(),
+            path: escape_path(path),
             file_size,
             chunk_size,
             num_chunks: chunks.len(),
             chunks,
+            cdc: None,
         })
     }
 }
 
+/// Emit `literal` as an insert op, if it holds anything
+fn flush_literal<F>(literal: &mut Vec<u8>, emit: &mut F) -> Result<()>
+where
+    F: FnMut(DeltaOp) -> Result<()>,
+{
+    if literal.is_empty() {
+        return Ok(());
+    }
+    emit(DeltaOp::InsertData { data: std::mem::take(literal) })
+}
+
 /// Delta instructions
 #[derive(Debug, Clone)]
 pub enum DeltaOp {
@@ -228,14 +280,65 @@ pub struct FileDelta {
 
 impl FileDelta {
     /// Calculate delta between original signature and new file
+    ///
+    /// Like rsync, a `chunk_size` window slides over the new file one byte
+    /// at a time, so blocks of the original are found at any offset and only
+    /// bytes that match no block are sent as literals. The original's
+    /// shorter last block is only matched at the end of the new file.
+    ///
+    /// Content-defined signatures are matched chunk for chunk instead, and
+    /// `chunk_size` is ignored.
     pub fn calculate(
         original_sig: &FileSignature,
         new_file: &Path,
         chunk_size: usize,
     ) -> Result<Self> {
+        let mut ops = Vec::new();
+        let mut transfer_size = 0u64;
+        let new_size = Self::for_each_op(original_sig, new_file, chunk_size, |op| {
+            if let DeltaOp::InsertData { data } = &op {
+                transfer_size += data.len() as u64;
+            }
+            ops.push(op);
+            Ok(())
+        })?;
+
+        let savings = if new_size > 0 {
+            ((new_size - transfer_size) as f64 / new_size as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        Ok(FileDelta {
+            ops,
+            original_size: original_sig.file_size,
+            target_size: new_size,
+            transfer_size,
+            savings_percent: savings,
+        })
+    }
+
+    /// Hand the ops of [`FileDelta::calculate`] to `emit` as they are found
+    ///
+    /// Nothing is collected, and literal runs are cut at `chunk_size` (at
+    /// [`MAX_LITERAL_RUN`] for content-defined signatures), so memory stays
+    /// bounded however large the new file is. Returns its size.
+    pub fn for_each_op<F>(
+        original_sig: &FileSignature,
+        new_file: &Path,
+        chunk_size: usize,
+        mut emit: F,
+    ) -> Result<u64>
+    where
+        F: FnMut(DeltaOp) -> Result<()>,
+    {
+        if let Some(params) = original_sig.cdc {
+            return Self::for_each_cdc_op(original_sig, new_file, params, emit);
+        }
         let new_file_handle = File::open(new_file).with_path(new_file)?;
         let new_size = new_file_handle.metadata().with_path(new_file)?.len();
         let mut reader = BufReader::with_capacity(chunk_size * 2, new_file_handle);
+        let block = chunk_size.max(1);
 
         // Build hash table for quick lookup
         let mut weak_map: HashMap<u32, Vec<usize>> = HashMap::new();
@@ -244,77 +347,123 @@ impl FileDelta {
                 .or_default()
                 .push(idx);
         }
+        let find_block = |weak: u32, data: &[u8]| {
+            let indices = weak_map.get(&weak)?;
+            let strong = strong_hash(data);
+            indices.iter().copied().find(|&idx| {
+                let chunk = &original_sig.chunks[idx];
+                chunk.size == data.len() && chunk.strong_hash == strong
+            })
+        };
 
-        let mut ops = Vec::new();
-        let mut buffer = vec![0u8; chunk_size];
         let mut literal_buffer = Vec::new();
-        let mut transfer_size = 0u64;
 
-        loop {
-            let bytes_read = reader.read(&mut buffer)
-                .map_err(|e| SmartCopyError::io(new_file, e))?;
+        // `buffer[pos..pos + block]` is the window; bytes before `pos` are done
+        let mut buffer: Vec<u8> = Vec::with_capacity(block * 3);
+        let mut pos = 0usize;
+        let mut eof = false;
+        let mut rolling: Option<RollingChecksum> = None;
 
-            if bytes_read == 0 {
+        loop {
+            // Keep at least one byte beyond the window so it can roll
+            if !eof && buffer.len() - pos <= block {
+                if pos >= block {
+                    buffer.drain(..pos);
+                    pos = 0;
+                }
+                let filled = buffer.len();
+                buffer.resize(filled + block, 0);
+                let read = reader.read(&mut buffer[filled..])
+                    .map_err(|e| SmartCopyError::io(new_file, e))?;
+                buffer.truncate(filled + read);
+                eof = read == 0;
+                continue;
+            }
+            if buffer.len() - pos < block {
                 break;
             }
 
-            let data = &buffer[..bytes_read];
-            let weak = RollingChecksum::calculate(data);
-            let weak_val = weak.value();
-
-            // Check if this block matches any original block
-            let mut matched = false;
-            if let Some(indices) = weak_map.get(&weak_val) {
-                let strong = strong_hash(data);
-
-                for &idx in indices {
-                    if original_sig.chunks[idx].strong_hash == strong
-                        && original_sig.chunks[idx].size == bytes_read
-                    {
-                        // Found a match!
-                        // First, flush any pending literal data
-                        if !literal_buffer.is_empty() {
-                            transfer_size += literal_buffer.len() as u64;
-                            ops.push(DeltaOp::InsertData {
-                                data: std::mem::take(&mut literal_buffer)
-                            });
-                        }
-
-                        ops.push(DeltaOp::CopyBlock {
-                            source_index: idx,
-                            size: bytes_read
-                        });
-                        matched = true;
-                        break;
-                    }
-                }
+            let window = &buffer[pos..pos + block];
+            let checksum = rolling.unwrap_or_else(|| RollingChecksum::calculate(window));
+            if let Some(idx) = find_block(checksum.value(), window) {
+                flush_literal(&mut literal_buffer, &mut emit)?;
+                emit(DeltaOp::CopyBlock { source_index: idx, size: block })?;
+                pos += block;
+                rolling = None;
+                continue;
             }
 
-            if !matched {
-                // No match - add to literal buffer
-                literal_buffer.extend_from_slice(data);
+            // No match - the first byte of the window becomes literal
+            literal_buffer.push(buffer[pos]);
+            if literal_buffer.len() >= block {
+                flush_literal(&mut literal_buffer, &mut emit)?;
             }
+            rolling = buffer.get(pos + block).map(|&next| {
+                let mut checksum = checksum;
+                checksum.roll(buffer[pos], next);
+                checksum
+            });
+            pos += 1;
         }
 
-        // Flush remaining literal data
-        if !literal_buffer.is_empty() {
-            transfer_size += literal_buffer.len() as u64;
-            ops.push(DeltaOp::InsertData { data: literal_buffer });
+        // Less than a block is left; it may end with the original's last block
+        let tail = &buffer[pos..];
+        let last_match = original_sig
+            .chunks
+            .last()
+            .filter(|chunk| chunk.size < block && chunk.size <= tail.len())
+            .and_then(|chunk| {
+                let data = &tail[tail.len() - chunk.size..];
+                find_block(RollingChecksum::calculate(data).value(), data)
+            });
+        match last_match {
+            Some(idx) => {
+                let size = original_sig.chunks[idx].size;
+                literal_buffer.extend_from_slice(&tail[..tail.len() - size]);
+                flush_literal(&mut literal_buffer, &mut emit)?;
+                emit(DeltaOp::CopyBlock { source_index: idx, size })?;
+            }
+            None => {
+                literal_buffer.extend_from_slice(tail);
+                flush_literal(&mut literal_buffer, &mut emit)?;
+            }
         }
 
-        let savings = if new_size > 0 {
-            ((new_size - transfer_size) as f64 / new_size as f64) * 100.0
-        } else {
-            0.0
-        };
+        Ok(new_size)
+    }
 
-        Ok(FileDelta {
-            ops,
-            original_size: original_sig.file_size,
-            target_size: new_size,
-            transfer_size,
-            savings_percent: savings,
-        })
+    /// Delta ops against a content-defined signature
+    ///
+    /// The new file is cut with the same parameters; each chunk whose
+    /// contents occur anywhere in the original is copied, the rest are sent.
+    fn for_each_cdc_op<F>(original_sig: &FileSignature, new_file: &Path, params: CdcParams, mut emit: F) -> Result<u64>
+    where
+        F: FnMut(DeltaOp) -> Result<()>,
+    {
+        let mut by_hash: HashMap<(u64, usize), usize> = HashMap::new();
+        for (idx, chunk) in original_sig.chunks.iter().enumerate() {
+            by_hash.entry((chunk.strong_hash, chunk.size)).or_insert(idx);
+        }
+
+        // Unmatched chunks in a row are sent as one literal
+        let mut literal = Vec::new();
+        let new_size = params.chunk_file(new_file, |_, data| {
+            match by_hash.get(&(strong_hash(data), data.len())) {
+                Some(&idx) => {
+                    flush_literal(&mut literal, &mut emit)?;
+                    emit(DeltaOp::CopyBlock { source_index: idx, size: data.len() })
+                }
+                None => {
+                    literal.extend_from_slice(data);
+                    if literal.len() >= MAX_LITERAL_RUN {
+                        flush_literal(&mut literal, &mut emit)?;
+                    }
+                    Ok(())
+                }
+            }
+        })?;
+        flush_literal(&mut literal, &mut emit)?;
+        Ok(new_size)
     }
 }
 
@@ -324,6 +473,8 @@ pub struct ChunkedCopier {
     chunk_size: usize,
     /// Number of parallel workers
     workers: usize,
+    /// Content-defined chunking for delta copies
+    cdc: Option<CdcParams>,
 }
 
 /// Result of a chunk copy operation (for parallel hash aggregation)
@@ -337,7 +488,13 @@ struct ChunkHashResult {
 impl ChunkedCopier {
     /// Create a new chunked copier
     pub fn new(chunk_size: usize, workers: usize) -> Self {
-        Self { chunk_size, workers }
+        Self { chunk_size, workers, cdc: None }
+    }
+
+    /// Use content-defined chunks instead of fixed blocks for delta copies
+    pub fn with_cdc(mut self, params: CdcParams) -> Self {
+        self.cdc = Some(params);
+        self
     }
 
     /// Copy a file using parallel chunks
@@ -508,6 +665,75 @@ impl ChunkedCopier {
         })
     }
 
+    /// Copy a file using parallel chunks, hashing each chunk with `algorithm`
+    ///
+    /// Each chunk is hashed while it is in memory, so no second read of the
+    /// source is needed. [`ChunkHashes::composite`] turns the result into a
+    /// whole-file digest.
+    pub fn copy_parallel_with_chunk_hashes(
+        &self,
+        source: &Path,
+        dest: &Path,
+        algorithm: HashAlgorithm,
+    ) -> Result<(CopyChunksResult, ChunkHashes)> {
+        let src_file = File::open(source).with_path(source)?;
+        let file_size = src_file.metadata().with_path(source)?.len();
+
+        let dest_file = File::create(dest).with_path(dest)?;
+        dest_file.set_len(file_size).with_path(dest)?;
+        drop(dest_file);
+
+        let num_chunks = (file_size as usize).div_ceil(self.chunk_size);
+        let chunk_size = self.chunk_size;
+        let start_time = std::time::Instant::now();
+
+        // Results keep chunk order, so hashes line up with chunk indexes
+        let results: Vec<Result<(u64, String)>> = (0..num_chunks)
+            .into_par_iter()
+            .map(|index| {
+                let offset = (index * chunk_size) as u64;
+
+                let mut src = File::open(source).with_path(source)?;
+                let mut dst = std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(dest)
+                    .with_path(dest)?;
+
+                src.seek(SeekFrom::Start(offset)).with_path(source)?;
+                dst.seek(SeekFrom::Start(offset)).with_path(dest)?;
+
+                let size = chunk_size.min((file_size - offset) as usize);
+                let mut buffer = vec![0u8; size];
+                src.read_exact(&mut buffer).with_path(source)?;
+                let hash = hash_bytes(&buffer, algorithm).hash;
+                dst.write_all(&buffer).with_path(dest)?;
+
+                Ok((size as u64, hash))
+            })
+            .collect();
+
+        let mut bytes_copied = 0u64;
+        let mut hashes = Vec::with_capacity(num_chunks);
+        for result in results {
+            let (bytes, hash) = result?;
+            bytes_copied += bytes;
+            hashes.push(hash);
+        }
+
+        let duration = start_time.elapsed();
+
+        Ok((
+            CopyChunksResult {
+                bytes_copied,
+                chunks_processed: hashes.len(),
+                duration,
+                throughput: bytes_copied as f64 / duration.as_secs_f64(),
+                hash: None,
+            },
+            ChunkHashes::fixed(chunk_size as u64, algorithm, hashes),
+        ))
+    }
+
     /// Copy using delta transfer
     pub fn copy_delta(
         &self,
@@ -518,7 +744,10 @@ impl ChunkedCopier {
         let start_time = std::time::Instant::now();
 
         // Generate signature for original
-        let signature = FileSignature::generate_parallel(original, self.chunk_size)?;
+        let signature = match self.cdc {
+            Some(params) => FileSignature::generate_cdc(original, params)?,
+            None => FileSignature::generate_parallel(original, self.chunk_size)?,
+        };
 
         // Calculate delta
         let delta = FileDelta::calculate(&signature, new_file, self.chunk_size)?;
@@ -558,6 +787,85 @@ impl ChunkedCopier {
             duration: start_time.elapsed(),
         })
     }
+
+    /// Update `dest` in place to match `new_file` using delta transfer
+    ///
+    /// Unlike [`copy_delta`](Self::copy_delta) no second file is built;
+    /// see [`apply_in_place_with`] for how operations are ordered and how an
+    /// interrupted update is detected.
+    pub fn copy_delta_inplace(&self, new_file: &Path, dest: &Path) -> Result<DeltaCopyResult> {
+        let start_time = std::time::Instant::now();
+
+        let signature = match self.cdc {
+            Some(params) => FileSignature::generate_cdc(dest, params)?,
+            None => FileSignature::generate_parallel(dest, self.chunk_size)?,
+        };
+        // Positions in the new file, with runs of blocks that stay
+        // contiguous merged into one copy. Literal data is not kept: it is
+        // the new file's content at the same position, read back while
+        // applying.
+        let mut ops: Vec<InPlaceOp> = Vec::new();
+        let mut literals: Vec<(u64, u64)> = Vec::new();
+        let mut dst = 0u64;
+        let mut transfer_size = 0u64;
+        let target_size = FileDelta::for_each_op(&signature, new_file, self.chunk_size, |op| {
+            match op {
+                DeltaOp::CopyBlock { source_index, size } => {
+                    let src = signature.chunks[source_index].offset;
+                    let size = size as u64;
+                    match ops.last_mut() {
+                        Some(InPlaceOp::Copy { src: prev_src, dst: prev_dst, len })
+                            if *prev_src + *len == src && *prev_dst + *len == dst =>
+                        {
+                            *len += size;
+                        }
+                        _ => ops.push(InPlaceOp::Copy { src, dst, len: size }),
+                    }
+                    dst += size;
+                }
+                DeltaOp::InsertData { data } => {
+                    let len = data.len() as u64;
+                    match literals.last_mut() {
+                        Some((start, run)) if *start + *run == dst => *run += len,
+                        _ => literals.push((dst, len)),
+                    }
+                    dst += len;
+                    transfer_size += len;
+                }
+            }
+            Ok(())
+        })?;
+
+        let piece = self.chunk_size.clamp(64 * 1024, MAX_LITERAL_RUN);
+        apply_in_place_with(dest, ops, target_size, |writer| {
+            let mut source = File::open(new_file).with_path(new_file)?;
+            let mut buffer = vec![0u8; piece];
+            for (start, len) in literals {
+                source.seek(SeekFrom::Start(start)).with_path(new_file)?;
+                let mut done = 0;
+                while done < len {
+                    let n = (len - done).min(piece as u64) as usize;
+                    source.read_exact(&mut buffer[..n]).with_path(new_file)?;
+                    writer.write(start + done, &buffer[..n])?;
+                    done += n as u64;
+                }
+            }
+            Ok(())
+        })?;
+
+        let savings_percent = if target_size > 0 {
+            ((target_size - transfer_size) as f64 / target_size as f64) * 100.0
+        } else {
+            0.0
+        };
+        Ok(DeltaCopyResult {
+            original_size: signature.file_size,
+            target_size,
+            bytes_transferred: transfer_size,
+            savings_percent,
+            duration: start_time.elapsed(),
+        })
+    }
 }
 
 /// Result of chunked copy
@@ -648,6 +956,25 @@ mod tests {
         assert_eq!(src_content, dst_content);
     }
 
+    #[test]
+    fn test_chunked_copy_with_chunk_hashes() {
+        let dir = TempDir::new().unwrap();
+        let content: Vec<u8> = (0..2_500_000u32).map(|i| (i % 251) as u8).collect();
+        let src = create_test_file(dir.path(), "source.bin", &content);
+        let dst = dir.path().join("dest.bin");
+
+        let copier = ChunkedCopier::new(1024 * 1024, 4);
+        let (result, chunks) = copier
+            .copy_parallel_with_chunk_hashes(&src, &dst, HashAlgorithm::Sha256)
+            .unwrap();
+
+        assert_eq!(result.bytes_copied, content.len() as u64);
+        assert_eq!(std::fs::read(&dst).unwrap(), content);
+        // Same hashes as hashing the copy afterwards
+        let rehashed = crate::hash::hash_file_chunked(&dst, HashAlgorithm::Sha256, 1024 * 1024).unwrap();
+        assert_eq!(chunks, rehashed);
+    }
+
     #[test]
     fn test_delta_identical() {
         let dir = TempDir::new().unwrap();
@@ -681,4 +1008,96 @@ mod tests {
         // Should have some savings (2/3 blocks unchanged)
         assert!(delta.savings_percent > 50.0);
     }
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    /// Delta of `new` against `original`, checked by rebuilding `new`
+    fn delta_between(original: &[u8], new: &[u8]) -> FileDelta {
+        let dir = TempDir::new().unwrap();
+        let original = create_test_file(dir.path(), "original.bin", original);
+        let modified = create_test_file(dir.path(), "modified.bin", new);
+        let rebuilt = dir.path().join("rebuilt.bin");
+
+        let sig = FileSignature::generate(&original, 4096).unwrap();
+        let delta = FileDelta::calculate(&sig, &modified, 4096).unwrap();
+        ChunkedCopier::new(4096, 1).copy_delta(&original, &modified, &rebuilt).unwrap();
+        assert_eq!(std::fs::read(&rebuilt).unwrap(), new);
+
+        // The same result when updating the original in place
+        ChunkedCopier::new(4096, 1).copy_delta_inplace(&modified, &original).unwrap();
+        assert_eq!(std::fs::read(&original).unwrap(), new);
+        delta
+    }
+
+    #[test]
+    fn test_delta_insertion() {
+        let original = pseudo_random(64 * 1024 + 100, 1);
+        let mut new = original.clone();
+        new.insert(10, 0x42);
+
+        // Only the block around the insertion is sent
+        let delta = delta_between(&original, &new);
+        assert!(delta.transfer_size <= 4097, "sent {}", delta.transfer_size);
+    }
+
+    #[test]
+    fn test_delta_deletion() {
+        let original = pseudo_random(64 * 1024 + 100, 2);
+        let mut new = original.clone();
+        new.drain(5000..5003);
+
+        let delta = delta_between(&original, &new);
+        assert!(delta.transfer_size < 4096, "sent {}", delta.transfer_size);
+    }
+
+    #[test]
+    fn test_delta_shifted_content() {
+        // Two regions swapped and a literal prefix added: every block of the
+        // original still appears, at unaligned offsets
+        let original = pseudo_random(40 * 1024, 3);
+        let mut new = b"header".to_vec();
+        new.extend_from_slice(&original[20 * 1024..]);
+        new.extend_from_slice(&original[..20 * 1024]);
+
+        let delta = delta_between(&original, &new);
+        assert_eq!(delta.transfer_size, 6);
+
+        // A file unrelated to the original is sent whole
+        let unrelated = pseudo_random(10_000, 4);
+        let delta = delta_between(&original, &unrelated);
+        assert_eq!(delta.transfer_size, 10_000);
+    }
+
+    #[test]
+    fn test_delta_cdc() {
+        let dir = TempDir::new().unwrap();
+        let params = CdcParams::new(1024, 4096, 16384).unwrap();
+        let content = pseudo_random(1024 * 1024, 5);
+        let mut edited = content[..300_000].to_vec();
+        edited.extend_from_slice(&[0x55; 100]);
+        edited.extend_from_slice(&content[300_000..]);
+        let original = create_test_file(dir.path(), "original.bin", &content);
+        let modified = create_test_file(dir.path(), "modified.bin", &edited);
+
+        // Only the chunks around the insertion change
+        let sig = FileSignature::generate_cdc(&original, params).unwrap();
+        assert_eq!(sig.chunks.iter().map(|c| c.size as u64).sum::<u64>(), sig.file_size);
+        let delta = FileDelta::calculate(&sig, &modified, 0).unwrap();
+        assert!(delta.transfer_size <= 2 * 16384 + 100, "sent {}", delta.transfer_size);
+
+        let rebuilt = dir.path().join("rebuilt.bin");
+        ChunkedCopier::new(4096, 1)
+            .with_cdc(params)
+            .copy_delta(&original, &modified, &rebuilt)
+            .unwrap();
+        assert_eq!(std::fs::read(&rebuilt).unwrap(), edited);
+    }
 }
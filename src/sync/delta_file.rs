@@ -0,0 +1,1021 @@
+//! Offline signature, delta and patch files
+//!
+//! rdiff-style files that split a delta transfer in time, so the basis and
+//! the new version never have to be reachable together:
+//!
+//! - [`write_signature`]: block signatures of a basis file or tree
+//! - [`write_delta`]: a new version expressed against a signature
+//! - [`apply_delta`]: the basis plus a delta rebuild the new version
+//!
+//! Both file kinds share one container: an 8-byte magic, a little-endian
+//! `u32` format version, then records, each a `u64` length, a bincode
+//! payload and the payload's XXH3-64. The last record carries the entry
+//! count, so a truncated file fails instead of yielding a short tree.
+//! A patched file is a start record, one record per copy or literal of at
+//! most 1 MiB, and an end record with the op count, so neither side ever
+//! holds a whole file in memory.
+//!
+//! Basis and target files are identified by their BLAKE3 hash; patching
+//! refuses a basis that differs from the one the signature was made from
+//! and checks every rebuilt file before moving it into place. Literal data
+//! can be LZ4 or, with the `zstd` feature, zstd compressed.
+//!
+//! Given a directory, all three work over the whole tree: regular files
+//! are matched by relative path, new files are sent as literals and
+//! deleted files are recorded. Symbolic links and special files are
+//! skipped.
+
+use crate::config::HashAlgorithm;
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use crate::fs::{escape_path, unescape_path, ScanConfig, Scanner};
+use crate::hash::{hash_file, StreamingHasher};
+use crate::sync::{CdcParams, DeltaOp, FileDelta, FileSignature};
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Magic bytes of a signature file
+pub const SIGNATURE_MAGIC: &[u8; 8] = b"SCPYSIG\0";
+
+/// Magic bytes of a delta file
+pub const DELTA_MAGIC: &[u8; 8] = b"SCPYDLT\0";
+
+/// Format version written to signature and delta files
+pub const DELTA_FORMAT_VERSION: u32 = 1;
+
+/// Largest record accepted in a delta file
+const MAX_DELTA_RECORD: u64 = 4 * 1024 * 1024;
+
+/// Hash identifying basis and target files
+const FILE_HASH: HashAlgorithm = HashAlgorithm::Blake3;
+
+/// Longest literal written to a delta file
+const LITERAL_BLOCK: usize = 1024 * 1024;
+
+/// How a signature splits basis files into blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignatureChunking {
+    /// Fixed-size blocks, matched at any offset
+    Fixed(usize),
+    /// Content-defined chunks
+    Cdc(CdcParams),
+}
+
+impl SignatureChunking {
+    fn signature(&self, path: &Path) -> Result<FileSignature> {
+        match *self {
+            Self::Fixed(size) => FileSignature::generate_parallel(path, size),
+            Self::Cdc(params) => FileSignature::generate_cdc(path, params),
+        }
+    }
+}
+
+/// Compression of literal data in delta files
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LiteralCompression {
+    /// Store literals as-is
+    #[default]
+    None,
+    /// LZ4 - fast, moderate ratio
+    Lz4,
+    /// Zstandard - slower, better ratio (requires the `zstd` feature)
+    Zstd,
+}
+
+impl LiteralCompression {
+    /// Compress a literal, falling back to storing it raw when that is
+    /// not smaller
+    fn compress(self, data: &[u8]) -> Result<(Self, Vec<u8>)> {
+        let packed = match self {
+            Self::None => return Ok((Self::None, data.to_vec())),
+            Self::Lz4 => lz4_flex::compress(data),
+            Self::Zstd => zstd_compress(data)?,
+        };
+        if packed.len() < data.len() {
+            Ok((self, packed))
+        } else {
+            Ok((Self::None, data.to_vec()))
+        }
+    }
+
+    fn decompress(self, data: Vec<u8>, len: usize) -> Result<Vec<u8>> {
+        let data = match self {
+            Self::None => data,
+            Self::Lz4 => lz4_flex::decompress(&data, len)
+                .map_err(|e| SmartCopyError::CompressionError(e.to_string()))?,
+            Self::Zstd => zstd_decompress(&data, len)?,
+        };
+        if data.len() != len {
+            return Err(SmartCopyError::DeltaSyncError(format!(
+                "literal expanded to {} bytes, expected {}",
+                data.len(),
+                len
+            )));
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_compress(data: &[u8]) -> Result<Vec<u8>> {
+    zstd::bulk::compress(data, 3).map_err(|e| SmartCopyError::CompressionError(e.to_string()))
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decompress(data: &[u8], len: usize) -> Result<Vec<u8>> {
+    zstd::bulk::decompress(data, len).map_err(|e| SmartCopyError::CompressionError(e.to_string()))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_compress(_data: &[u8]) -> Result<Vec<u8>> {
+    Err(zstd_unavailable())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decompress(_data: &[u8], _len: usize) -> Result<Vec<u8>> {
+    Err(zstd_unavailable())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_unavailable() -> SmartCopyError {
+    SmartCopyError::UnsupportedOperation(
+        "zstd literals need smartcopy built with the 'zstd' feature".to_string(),
+    )
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignatureHeader {
+    tree: bool,
+    chunking: SignatureChunking,
+}
+
+/// One basis file; `signature.path` holds the escaped relative path,
+/// empty for a single file
+#[derive(Serialize, Deserialize)]
+struct SignatureEntry {
+    hash: String,
+    signature: FileSignature,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SignatureRecord {
+    Header(SignatureHeader),
+    File(SignatureEntry),
+    End { entries: u64 },
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeltaHeader {
+    tree: bool,
+}
+
+/// Modification time and Unix mode of a target file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct FileMeta {
+    mtime: i64,
+    mtime_nanos: u32,
+    mode: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+enum PatchOp {
+    /// Copy a byte range of the basis file
+    Copy { offset: u64, len: u64 },
+    /// Literal data
+    Literal { compression: LiteralCompression, len: u64, data: Vec<u8> },
+}
+
+#[derive(Serialize, Deserialize)]
+enum FileChange {
+    /// Content identical to the basis
+    Unchanged { hash: String, size: u64, meta: FileMeta },
+    /// In the basis tree, gone from the new one
+    Deleted,
+    /// Rebuilt from the basis (`basis_hash` set) or from literals alone,
+    /// with the ops in the `Op` records that follow, up to a `FileEnd`
+    Streamed {
+        basis_hash: Option<String>,
+        target_hash: String,
+        target_size: u64,
+        meta: FileMeta,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeltaEntry {
+    path: String,
+    change: FileChange,
+}
+
+#[derive(Serialize, Deserialize)]
+enum DeltaRecord {
+    Header(DeltaHeader),
+    File(DeltaEntry),
+    End { entries: u64 },
+    Op(PatchOp),
+    FileEnd { ops: u64 },
+}
+
+/// Writes the length-prefixed, checksummed records of a container
+///
+/// Records go to a temporary file that [`RecordWriter::finish`] renames
+/// into place, so a failed run leaves no half-written file behind.
+struct RecordWriter {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    tmp: PathBuf,
+    written: u64,
+    finished: bool,
+}
+
+impl RecordWriter {
+    fn create(path: &Path, magic: &[u8; 8]) -> Result<Self> {
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let file = File::create(&tmp).with_path(&tmp)?;
+        let mut writer = Self {
+            writer: BufWriter::with_capacity(1024 * 1024, file),
+            path: path.to_path_buf(),
+            tmp,
+            written: 0,
+            finished: false,
+        };
+        writer.write_bytes(magic)?;
+        writer.write_bytes(&DELTA_FORMAT_VERSION.to_le_bytes())?;
+        Ok(writer)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes).with_path(&self.tmp)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        let payload = bincode::serialize(record)
+            .map_err(|e| SmartCopyError::DeltaSyncError(e.to_string()))?;
+        self.write_bytes(&(payload.len() as u64).to_le_bytes())?;
+        self.write_bytes(&payload)?;
+        self.write_bytes(&xxhash_rust::xxh3::xxh3_64(&payload).to_le_bytes())
+    }
+
+    /// Flush, sync and move into place, returning the file size
+    fn finish(mut self) -> Result<u64> {
+        self.writer.flush().with_path(&self.tmp)?;
+        self.writer.get_ref().sync_all().with_path(&self.tmp)?;
+        std::fs::rename(&self.tmp, &self.path).with_path(&self.path)?;
+        self.finished = true;
+        Ok(self.written)
+    }
+}
+
+impl Drop for RecordWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = std::fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Reads and checks the records of a container
+struct RecordReader {
+    reader: BufReader<File>,
+    path: PathBuf,
+    remaining: u64,
+    /// Largest record accepted
+    max_record: u64,
+}
+
+impl RecordReader {
+    fn open(path: &Path, magic: &[u8; 8], kind: &str) -> Result<Self> {
+        let file = File::open(path).with_path(path)?;
+        let remaining = file.metadata().with_path(path)?.len();
+        let mut reader = Self {
+            reader: BufReader::with_capacity(1024 * 1024, file),
+            path: path.to_path_buf(),
+            remaining,
+            max_record: u64::MAX,
+        };
+
+        let mut header = [0u8; 12];
+        if reader.read_bytes(&mut header).is_err() || &header[..8] != magic {
+            return Err(SmartCopyError::DeltaSyncError(format!(
+                "'{}' is not a smartcopy {} file",
+                path.display(),
+                kind
+            )));
+        }
+        let version = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        if version > DELTA_FORMAT_VERSION {
+            return Err(SmartCopyError::DeltaSyncError(format!(
+                "'{}' uses {} format version {}, this build reads up to {}",
+                path.display(),
+                kind,
+                version,
+                DELTA_FORMAT_VERSION
+            )));
+        }
+        Ok(reader)
+    }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<()> {
+        match self.reader.read_exact(buf) {
+            Ok(()) => {
+                self.remaining -= buf.len() as u64;
+                Ok(())
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Err(self.corrupt("truncated")),
+            Err(e) => Err(SmartCopyError::io(&self.path, e)),
+        }
+    }
+
+    fn next<T: DeserializeOwned>(&mut self) -> Result<T> {
+        let mut word = [0u8; 8];
+        self.read_bytes(&mut word)?;
+        let len = u64::from_le_bytes(word);
+        // Checked against the file size so a corrupt length can't force a
+        // huge allocation
+        if len.saturating_add(8) > self.remaining {
+            return Err(self.corrupt("truncated"));
+        }
+        if len > self.max_record {
+            return Err(self.corrupt(&format!("record of {} bytes exceeds limit of {}", len, self.max_record)));
+        }
+        let mut payload = vec![0u8; len as usize];
+        self.read_bytes(&mut payload)?;
+        self.read_bytes(&mut word)?;
+        if u64::from_le_bytes(word) != xxhash_rust::xxh3::xxh3_64(&payload) {
+            return Err(self.corrupt("record checksum mismatch"));
+        }
+        bincode::deserialize(&payload).map_err(|e| self.corrupt(&e.to_string()))
+    }
+
+    fn corrupt(&self, reason: &str) -> SmartCopyError {
+        SmartCopyError::DeltaSyncError(format!("'{}' is corrupt: {}", self.path.display(), reason))
+    }
+}
+
+/// Result of [`write_signature`]
+#[derive(Debug, Clone, Default)]
+pub struct SignatureSummary {
+    /// Basis files signed
+    pub files: u64,
+    /// Total basis bytes
+    pub bytes: u64,
+    /// Blocks in all signatures
+    pub blocks: u64,
+    /// Size of the signature file
+    pub signature_size: u64,
+}
+
+/// Result of [`write_delta`]
+#[derive(Debug, Clone, Default)]
+pub struct DeltaSummary {
+    /// Files identical to the basis
+    pub unchanged: u64,
+    /// Files rebuilt from the basis
+    pub patched: u64,
+    /// Files with no basis
+    pub added: u64,
+    /// Basis files missing from the new tree
+    pub deleted: u64,
+    /// Bytes copied from the basis
+    pub copied_bytes: u64,
+    /// Literal bytes before compression
+    pub literal_bytes: u64,
+    /// Size of the delta file
+    pub delta_size: u64,
+}
+
+/// Result of [`apply_delta`]
+#[derive(Debug, Clone, Default)]
+pub struct PatchSummary {
+    /// Files written to the output
+    pub files: u64,
+    /// Bytes written to the output
+    pub bytes: u64,
+    /// Basis files left out as deleted
+    pub deleted: u64,
+}
+
+/// Regular files under `root` as (path, escaped relative path), sorted
+fn tree_files(root: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let scanner = Scanner::new(ScanConfig {
+        include_hidden: true,
+        ..Default::default()
+    })?;
+    let mut files: Vec<_> = scanner.scan(root)?.files.into_iter()
+        .filter(|e| !e.is_symlink && !e.is_dir && e.special.is_none())
+        .map(|e| (e.path, escape_path(&e.relative_path)))
+        .collect();
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(files)
+}
+
+/// Files to work on: the tree under a directory, or a single file under
+/// the empty relative path
+fn input_files(path: &Path) -> Result<(bool, Vec<(PathBuf, String)>)> {
+    let metadata = std::fs::metadata(path).with_path(path)?;
+    if metadata.is_dir() {
+        Ok((true, tree_files(path)?))
+    } else {
+        Ok((false, vec![(path.to_path_buf(), String::new())]))
+    }
+}
+
+fn relative_path(root: &Path, escaped: &str) -> Result<PathBuf> {
+    if escaped.is_empty() {
+        return Ok(root.to_path_buf());
+    }
+    let rel = unescape_path(escaped);
+    if rel.is_relative() && rel.components().all(|c| matches!(c, std::path::Component::Normal(_))) {
+        Ok(root.join(rel))
+    } else {
+        Err(SmartCopyError::InvalidPath(escaped.to_string()))
+    }
+}
+
+/// Write a signature of `basis`, a file or a directory tree, to `output`
+pub fn write_signature(basis: &Path, output: &Path, chunking: SignatureChunking) -> Result<SignatureSummary> {
+    match chunking {
+        SignatureChunking::Fixed(0) => {
+            return Err(SmartCopyError::ConfigError("block size must be non-zero".to_string()));
+        }
+        SignatureChunking::Cdc(params) => params.validate()?,
+        SignatureChunking::Fixed(_) => {}
+    }
+
+    let (tree, files) = input_files(basis)?;
+    let entries = files
+        .par_iter()
+        .map(|(path, rel)| {
+            let mut signature = chunking.signature(path)?;
+            signature.path = rel.clone();
+            let hash = hash_file(path, FILE_HASH)?.hash;
+            Ok(SignatureEntry { hash, signature })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut summary = SignatureSummary::default();
+    let mut writer = RecordWriter::create(output, SIGNATURE_MAGIC)?;
+    writer.write(&SignatureRecord::Header(SignatureHeader { tree, chunking }))?;
+    for entry in entries {
+        summary.files += 1;
+        summary.bytes += entry.signature.file_size;
+        summary.blocks += entry.signature.chunks.len() as u64;
+        writer.write(&SignatureRecord::File(entry))?;
+    }
+    writer.write(&SignatureRecord::End { entries: summary.files })?;
+    summary.signature_size = writer.finish()?;
+    Ok(summary)
+}
+
+fn read_signature(path: &Path) -> Result<(SignatureHeader, Vec<SignatureEntry>)> {
+    let mut reader = RecordReader::open(path, SIGNATURE_MAGIC, "signature")?;
+    let header = match reader.next()? {
+        SignatureRecord::Header(header) => header,
+        _ => return Err(reader.corrupt("missing header")),
+    };
+    let mut entries = Vec::new();
+    loop {
+        match reader.next()? {
+            SignatureRecord::File(entry) => entries.push(entry),
+            SignatureRecord::End { entries: count } if count == entries.len() as u64 => break,
+            _ => return Err(reader.corrupt("unexpected record")),
+        }
+    }
+    Ok((header, entries))
+}
+
+fn file_meta(path: &Path) -> Result<FileMeta> {
+    let metadata = std::fs::metadata(path).with_path(path)?;
+    let mtime = filetime::FileTime::from_last_modification_time(&metadata);
+    #[cfg(unix)]
+    let mode = std::os::unix::fs::PermissionsExt::mode(&metadata.permissions());
+    #[cfg(not(unix))]
+    let mode = 0;
+    Ok(FileMeta {
+        mtime: mtime.unix_seconds(),
+        mtime_nanos: mtime.nanoseconds(),
+        mode,
+    })
+}
+
+fn apply_meta(path: &Path, meta: &FileMeta) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(meta.mode & 0o7777))
+            .with_path(path)?;
+    }
+    let mtime = filetime::FileTime::from_unix_time(meta.mtime, meta.mtime_nanos);
+    filetime::set_file_mtime(path, mtime).with_path(path)
+}
+
+/// Literal op for `data`, compressed when that helps
+fn literal_op(data: &[u8], compression: LiteralCompression) -> Result<PatchOp> {
+    let (compression, data_out) = compression.compress(data)?;
+    Ok(PatchOp::Literal {
+        compression,
+        len: data.len() as u64,
+        data: data_out,
+    })
+}
+
+/// Streams one patched file's ops into a delta file, merging adjacent
+/// copies and cutting literals at [`LITERAL_BLOCK`]
+struct PatchWriter<'a> {
+    writer: &'a mut RecordWriter,
+    compression: LiteralCompression,
+    /// Copy not written yet, as (offset, len)
+    copy: Option<(u64, u64)>,
+    ops: u64,
+    copied_bytes: u64,
+    literal_bytes: u64,
+}
+
+impl<'a> PatchWriter<'a> {
+    fn new(writer: &'a mut RecordWriter, compression: LiteralCompression) -> Self {
+        Self { writer, compression, copy: None, ops: 0, copied_bytes: 0, literal_bytes: 0 }
+    }
+
+    fn copy(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.copied_bytes += len;
+        if let Some((prev, prev_len)) = self.copy.as_mut() {
+            if *prev + *prev_len == offset {
+                *prev_len += len;
+                return Ok(());
+            }
+        }
+        self.flush_copy()?;
+        self.copy = Some((offset, len));
+        Ok(())
+    }
+
+    fn literal(&mut self, data: &[u8]) -> Result<()> {
+        self.flush_copy()?;
+        for piece in data.chunks(LITERAL_BLOCK) {
+            self.literal_bytes += piece.len() as u64;
+            self.write_op(literal_op(piece, self.compression)?)?;
+        }
+        Ok(())
+    }
+
+    fn flush_copy(&mut self) -> Result<()> {
+        match self.copy.take() {
+            Some((offset, len)) => self.write_op(PatchOp::Copy { offset, len }),
+            None => Ok(()),
+        }
+    }
+
+    fn write_op(&mut self, op: PatchOp) -> Result<()> {
+        self.ops += 1;
+        self.writer.write(&DeltaRecord::Op(op))
+    }
+
+    /// Write the end record, returning (copied, literal) bytes
+    fn finish(mut self) -> Result<(u64, u64)> {
+        self.flush_copy()?;
+        self.writer.write(&DeltaRecord::FileEnd { ops: self.ops })?;
+        Ok((self.copied_bytes, self.literal_bytes))
+    }
+}
+
+/// Stream the ops of a file without a basis
+fn write_literal_ops(path: &Path, patch: &mut PatchWriter) -> Result<()> {
+    let mut file = File::open(path).with_path(path)?;
+    let mut buffer = vec![0u8; LITERAL_BLOCK];
+    loop {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match file.read(&mut buffer[filled..]).with_path(path)? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled == 0 {
+            return Ok(());
+        }
+        patch.literal(&buffer[..filled])?;
+    }
+}
+
+/// Stream the ops of a file against its basis signature, with block
+/// copies resolved to basis offsets
+fn write_delta_ops(signature: &FileSignature, path: &Path, patch: &mut PatchWriter) -> Result<()> {
+    FileDelta::for_each_op(signature, path, signature.chunk_size, |op| match op {
+        DeltaOp::CopyBlock { source_index, size } => {
+            patch.copy(signature.chunks[source_index].offset, size as u64)
+        }
+        DeltaOp::InsertData { data } => patch.literal(&data),
+    })?;
+    Ok(())
+}
+
+/// Write the delta from the basis described by `signature` to `new`, a
+/// file or a directory tree matching the signature's kind
+pub fn write_delta(
+    signature: &Path,
+    new: &Path,
+    output: &Path,
+    compression: LiteralCompression,
+) -> Result<DeltaSummary> {
+    let (header, entries) = read_signature(signature)?;
+    let (tree, targets) = input_files(new)?;
+    if tree != header.tree {
+        return Err(SmartCopyError::DeltaSyncError(format!(
+            "signature '{}' was made from a {}, but '{}' is not one",
+            signature.display(),
+            if header.tree { "directory" } else { "file" },
+            new.display()
+        )));
+    }
+    let mut basis: BTreeMap<String, SignatureEntry> = entries
+        .into_iter()
+        .map(|entry| (entry.signature.path.clone(), entry))
+        .collect();
+
+    let mut summary = DeltaSummary::default();
+    let mut writer = RecordWriter::create(output, DELTA_MAGIC)?;
+    writer.write(&DeltaRecord::Header(DeltaHeader { tree }))?;
+    let mut count = 0u64;
+
+    for (path, rel) in targets {
+        let meta = file_meta(&path)?;
+        let target = hash_file(&path, FILE_HASH)?;
+        count += 1;
+        let entry = basis.remove(&rel);
+        if entry.as_ref().is_some_and(|entry| entry.hash == target.hash) {
+            summary.unchanged += 1;
+            let change = FileChange::Unchanged { hash: target.hash, size: target.size, meta };
+            writer.write(&DeltaRecord::File(DeltaEntry { path: rel, change }))?;
+            continue;
+        }
+
+        let change = FileChange::Streamed {
+            basis_hash: entry.as_ref().map(|entry| entry.hash.clone()),
+            target_hash: target.hash,
+            target_size: target.size,
+            meta,
+        };
+        writer.write(&DeltaRecord::File(DeltaEntry { path: rel, change }))?;
+        let mut patch = PatchWriter::new(&mut writer, compression);
+        match &entry {
+            Some(entry) => {
+                summary.patched += 1;
+                write_delta_ops(&entry.signature, &path, &mut patch)?;
+            }
+            None => {
+                summary.added += 1;
+                write_literal_ops(&path, &mut patch)?;
+            }
+        }
+        let (copied, literal) = patch.finish()?;
+        summary.copied_bytes += copied;
+        summary.literal_bytes += literal;
+    }
+
+    for rel in basis.into_keys() {
+        summary.deleted += 1;
+        writer.write(&DeltaRecord::File(DeltaEntry { path: rel, change: FileChange::Deleted }))?;
+        count += 1;
+    }
+
+    writer.write(&DeltaRecord::End { entries: count })?;
+    summary.delta_size = writer.finish()?;
+    Ok(summary)
+}
+
+/// Rebuilds one file into `dest` via a temporary file, checking its hash
+/// before renaming it into place
+///
+/// The temporary file is removed unless [`FileRebuild::finish`] succeeds.
+struct FileRebuild<'a> {
+    source: Option<(File, &'a Path)>,
+    writer: BufWriter<File>,
+    hasher: StreamingHasher,
+    buffer: Vec<u8>,
+    tmp: PathBuf,
+    dest: &'a Path,
+    finished: bool,
+}
+
+impl<'a> FileRebuild<'a> {
+    fn create(basis: Option<&'a Path>, dest: &'a Path) -> Result<Self> {
+        let mut tmp = dest.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let source = match basis {
+            Some(path) => Some((File::open(path).with_path(path)?, path)),
+            None => None,
+        };
+        let file = File::create(&tmp).with_path(&tmp)?;
+        Ok(Self {
+            source,
+            writer: BufWriter::with_capacity(1024 * 1024, file),
+            hasher: StreamingHasher::new(FILE_HASH),
+            buffer: vec![0u8; LITERAL_BLOCK],
+            tmp,
+            dest,
+            finished: false,
+        })
+    }
+
+    fn apply(&mut self, op: PatchOp) -> Result<()> {
+        match op {
+            PatchOp::Copy { offset, len } => {
+                let (file, path) = self.source.as_mut().ok_or_else(|| {
+                    SmartCopyError::DeltaSyncError("block copy in a file without a basis".to_string())
+                })?;
+                file.seek(SeekFrom::Start(offset)).with_path(*path)?;
+                let mut remaining = len;
+                while remaining > 0 {
+                    let n = remaining.min(self.buffer.len() as u64) as usize;
+                    file.read_exact(&mut self.buffer[..n]).with_path(*path)?;
+                    self.hasher.process(&self.buffer[..n]);
+                    self.writer.write_all(&self.buffer[..n]).with_path(&self.tmp)?;
+                    remaining -= n as u64;
+                }
+            }
+            PatchOp::Literal { compression, len, data } => {
+                let data = compression.decompress(data, len as usize)?;
+                self.hasher.process(&data);
+                self.writer.write_all(&data).with_path(&self.tmp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, target_hash: &str, target_size: u64, meta: &FileMeta) -> Result<()> {
+        self.writer.flush().with_path(&self.tmp)?;
+        self.writer.get_ref().sync_all().with_path(&self.tmp)?;
+        let actual = std::mem::replace(&mut self.hasher, StreamingHasher::new(FILE_HASH)).finalize();
+        if actual.hash != target_hash || actual.size != target_size {
+            return Err(SmartCopyError::IntegrityMismatch {
+                path: self.dest.to_path_buf(),
+                expected: target_hash.to_string(),
+                actual: actual.hash,
+            });
+        }
+        apply_meta(&self.tmp, meta)?;
+        std::fs::rename(&self.tmp, self.dest).with_path(self.dest)?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl Drop for FileRebuild<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = std::fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Check a patched file's basis against the hash recorded for it
+fn checked_basis(source: &Path, basis_hash: Option<String>) -> Result<Option<&Path>> {
+    let Some(expected) = basis_hash else {
+        return Ok(None);
+    };
+    let actual = hash_file(source, FILE_HASH)?.hash;
+    if actual != expected {
+        return Err(SmartCopyError::IntegrityMismatch {
+            path: source.to_path_buf(),
+            expected,
+            actual,
+        });
+    }
+    Ok(Some(source))
+}
+
+/// Rebuild the new version from `basis` and `delta` into `output`
+///
+/// For a tree delta `output` is a directory, created if needed, and must
+/// differ from `basis`. Each patched file's basis is checked against the
+/// hash recorded in the signature before use.
+pub fn apply_delta(basis: &Path, delta: &Path, output: &Path) -> Result<PatchSummary> {
+    let mut reader = RecordReader::open(delta, DELTA_MAGIC, "delta")?;
+    reader.max_record = MAX_DELTA_RECORD;
+    let header = match reader.next()? {
+        DeltaRecord::Header(header) => header,
+        _ => return Err(reader.corrupt("missing header")),
+    };
+
+    if let (Ok(a), Ok(b)) = (basis.canonicalize(), output.canonicalize()) {
+        if a == b {
+            return Err(SmartCopyError::SameSourceAndDestination(output.to_path_buf()));
+        }
+    }
+    if header.tree {
+        if !basis.is_dir() {
+            return Err(SmartCopyError::DeltaSyncError(format!(
+                "'{}' is a tree delta, but basis '{}' is not a directory",
+                delta.display(),
+                basis.display()
+            )));
+        }
+        std::fs::create_dir_all(output).with_path(output)?;
+    }
+
+    let mut summary = PatchSummary::default();
+    let mut count = 0u64;
+    loop {
+        let entry = match reader.next()? {
+            DeltaRecord::File(entry) => entry,
+            DeltaRecord::End { entries } if entries == count => break,
+            _ => return Err(reader.corrupt("unexpected record")),
+        };
+        count += 1;
+
+        let source = relative_path(basis, &entry.path)?;
+        let dest = relative_path(output, &entry.path)?;
+        if let Some(parent) = dest.parent().filter(|_| header.tree) {
+            std::fs::create_dir_all(parent).with_path(parent)?;
+        }
+
+        match entry.change {
+            FileChange::Deleted => {
+                summary.deleted += 1;
+                continue;
+            }
+            FileChange::Unchanged { hash, size, meta } => {
+                // Checked through the rebuilt copy's hash
+                let mut rebuild = FileRebuild::create(Some(&source), &dest)?;
+                rebuild.apply(PatchOp::Copy { offset: 0, len: size })?;
+                rebuild.finish(&hash, size, &meta)?;
+                summary.bytes += size;
+            }
+            FileChange::Streamed { basis_hash, target_hash, target_size, meta } => {
+                let mut rebuild = FileRebuild::create(checked_basis(&source, basis_hash)?, &dest)?;
+                let mut ops = 0u64;
+                loop {
+                    match reader.next()? {
+                        DeltaRecord::Op(PatchOp::Literal { len, .. }) if len > LITERAL_BLOCK as u64 => {
+                            return Err(reader.corrupt("literal too long"));
+                        }
+                        DeltaRecord::Op(op) => rebuild.apply(op)?,
+                        DeltaRecord::FileEnd { ops: expected } if expected == ops => break,
+                        _ => return Err(reader.corrupt("unexpected record")),
+                    }
+                    ops += 1;
+                }
+                rebuild.finish(&target_hash, target_size, &meta)?;
+                summary.bytes += target_size;
+            }
+        }
+        summary.files += 1;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 33) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let old = temp.path().join("old.bin");
+        let new = temp.path().join("new.bin");
+        let sig = temp.path().join("old.sig");
+        let delta = temp.path().join("changes.delta");
+        let out = temp.path().join("out.bin");
+
+        let original = pseudo_random(200_000, 7);
+        let mut modified = original.clone();
+        modified.splice(50_000..50_000, b"inserted bytes".iter().copied());
+        modified.truncate(180_000);
+        std::fs::write(&old, &original).unwrap();
+        std::fs::write(&new, &modified).unwrap();
+
+        write_signature(&old, &sig, SignatureChunking::Fixed(4096)).unwrap();
+        let summary = write_delta(&sig, &new, &delta, LiteralCompression::Lz4).unwrap();
+        assert_eq!(summary.patched, 1);
+        assert!(summary.literal_bytes < 10_000);
+
+        apply_delta(&old, &delta, &out).unwrap();
+        assert_eq!(std::fs::read(&out).unwrap(), modified);
+
+        // A basis other than the signed one is refused
+        std::fs::write(&old, &modified).unwrap();
+        assert!(apply_delta(&old, &delta, &temp.path().join("other.bin")).is_err());
+    }
+
+    #[test]
+    fn test_tree_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let old = temp.path().join("old");
+        let new = temp.path().join("new");
+        let out = temp.path().join("out");
+        let sig = temp.path().join("tree.sig");
+        let delta = temp.path().join("tree.delta");
+        for dir in [&old, &new] {
+            std::fs::create_dir_all(dir.join("sub")).unwrap();
+            std::fs::write(dir.join("same.txt"), b"unchanged").unwrap();
+        }
+        let data = pseudo_random(100_000, 3);
+        std::fs::write(old.join("sub/data.bin"), &data).unwrap();
+        let mut changed = data.clone();
+        changed[60_000..60_010].copy_from_slice(b"0123456789");
+        std::fs::write(new.join("sub/data.bin"), &changed).unwrap();
+        std::fs::write(old.join("gone.txt"), b"deleted").unwrap();
+        std::fs::write(new.join("added.txt"), b"new file").unwrap();
+
+        write_signature(&old, &sig, SignatureChunking::Cdc(CdcParams::new(1024, 4096, 16384).unwrap())).unwrap();
+        let summary = write_delta(&sig, &new, &delta, LiteralCompression::None).unwrap();
+        assert_eq!((summary.unchanged, summary.patched, summary.added, summary.deleted), (1, 1, 1, 1));
+
+        let patched = apply_delta(&old, &delta, &out).unwrap();
+        assert_eq!((patched.files, patched.deleted), (3, 1));
+        assert_eq!(std::fs::read(out.join("sub/data.bin")).unwrap(), changed);
+        assert_eq!(std::fs::read(out.join("added.txt")).unwrap(), b"new file");
+        assert_eq!(std::fs::read(out.join("same.txt")).unwrap(), b"unchanged");
+        assert!(!out.join("gone.txt").exists());
+    }
+
+    #[test]
+    fn test_large_file_streams_as_many_records() {
+        let temp = TempDir::new().unwrap();
+        let old = temp.path().join("old.bin");
+        let new = temp.path().join("new.bin");
+        let sig = temp.path().join("old.sig");
+        let delta = temp.path().join("changes.delta");
+        let out = temp.path().join("out.bin");
+
+        // Mostly rewritten, with literal runs longer than one record
+        let original = pseudo_random(3 * LITERAL_BLOCK, 11);
+        let mut modified = pseudo_random(3 * LITERAL_BLOCK, 12);
+        modified[LITERAL_BLOCK..LITERAL_BLOCK + 65536]
+            .copy_from_slice(&original[LITERAL_BLOCK..LITERAL_BLOCK + 65536]);
+        std::fs::write(&old, &original).unwrap();
+        std::fs::write(&new, &modified).unwrap();
+
+        write_signature(&old, &sig, SignatureChunking::Fixed(4 * LITERAL_BLOCK)).unwrap();
+        let summary = write_delta(&sig, &new, &delta, LiteralCompression::None).unwrap();
+        assert_eq!(summary.literal_bytes, modified.len() as u64);
+
+        let mut reader = RecordReader::open(&delta, DELTA_MAGIC, "delta").unwrap();
+        reader.max_record = MAX_DELTA_RECORD;
+        let mut ops = 0;
+        loop {
+            match reader.next::<DeltaRecord>().unwrap() {
+                DeltaRecord::Op(PatchOp::Literal { len, .. }) => {
+                    assert!(len <= LITERAL_BLOCK as u64);
+                    ops += 1;
+                }
+                DeltaRecord::End { .. } => break,
+                _ => {}
+            }
+        }
+        assert!(ops >= 3);
+
+        apply_delta(&old, &delta, &out).unwrap();
+        assert_eq!(std::fs::read(&out).unwrap(), modified);
+
+        // Oversized records are refused before being read
+        let big = temp.path().join("big.delta");
+        let mut writer = RecordWriter::create(&big, DELTA_MAGIC).unwrap();
+        writer.write(&DeltaRecord::Header(DeltaHeader { tree: false })).unwrap();
+        writer.write(&DeltaRecord::Op(literal_op(&vec![1u8; 5 * LITERAL_BLOCK], LiteralCompression::None).unwrap())).unwrap();
+        writer.finish().unwrap();
+        let err = apply_delta(&old, &big, &temp.path().join("big.bin")).unwrap_err();
+        assert!(err.to_string().contains("exceeds limit"), "{}", err);
+    }
+
+    #[test]
+    fn test_corrupt_delta_rejected() {
+        let temp = TempDir::new().unwrap();
+        let old = temp.path().join("old.bin");
+        let sig = temp.path().join("old.sig");
+        let delta = temp.path().join("changes.delta");
+        std::fs::write(&old, pseudo_random(10_000, 1)).unwrap();
+        write_signature(&old, &sig, SignatureChunking::Fixed(1024)).unwrap();
+        write_delta(&sig, &old, &delta, LiteralCompression::None).unwrap();
+
+        let mut bytes = std::fs::read(&delta).unwrap();
+        let mid = bytes.len() / 2;
+        bytes[mid] ^= 0xff;
+        std::fs::write(&delta, &bytes).unwrap();
+        let err = apply_delta(&old, &delta, &temp.path().join("out.bin")).unwrap_err();
+        assert!(err.to_string().contains("corrupt"));
+
+        // A signature is not a delta
+        assert!(apply_delta(&old, &sig, &temp.path().join("out.bin")).is_err());
+    }
+}
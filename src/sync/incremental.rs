@@ -4,13 +4,16 @@
 //! - Comparing metadata (size, mtime) for change detection
 //! - Only copying new or modified files
 //! - Optionally deleting extra files in destination
+//! - Optionally turning a new file plus a deletion of the same content
+//!   into a rename, so reorganised trees are not transferred again
 
-use crate::error::Result;
-use crate::fs::{compare_entries, FileComparison, FileEntry, Scanner, ScanConfig, ScanResult};
+use crate::error::{IoResultExt, Result};
+use crate::fs::{compare_entries, escape_path, unescape_path, FileComparison, FileEntry, Scanner, ScanConfig, ScanResult};
+use crate::hash::quick_hash;
 use crate::sync::SyncManifest;
 use rayon::prelude::*;
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Synchronization action to perform
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -25,12 +28,17 @@ pub enum SyncAction {
     Skip,
     /// Conflict (destination is newer)
     Conflict,
+    /// Move a destination file that would be deleted to this new path
+    Rename {
+        /// Relative path of the existing destination file (escaped form)
+        from: String,
+    },
 }
 
 /// A file change detected during sync analysis
 #[derive(Debug, Clone)]
 pub struct SyncChange {
-    /// Relative path
+    /// Relative path (escaped form, see [`escape_path`])
     pub path: String,
     /// Action to perform
     pub action: SyncAction,
@@ -51,12 +59,16 @@ pub struct SyncAnalysis {
     pub to_skip: Vec<SyncChange>,
     /// Files to delete (extra in destination)
     pub to_delete: Vec<SyncChange>,
+    /// New files that are existing destination files under a new path
+    pub to_rename: Vec<SyncChange>,
     /// Conflicts (destination newer than source)
     pub conflicts: Vec<SyncChange>,
     /// Total bytes to copy
     pub bytes_to_copy: u64,
     /// Total bytes to delete
     pub bytes_to_delete: u64,
+    /// Total bytes moved by renames instead of copied
+    pub bytes_to_rename: u64,
     /// Total files in source
     pub source_count: usize,
     /// Total files in destination
@@ -70,9 +82,11 @@ impl SyncAnalysis {
             to_copy: Vec::new(),
             to_skip: Vec::new(),
             to_delete: Vec::new(),
+            to_rename: Vec::new(),
             conflicts: Vec::new(),
             bytes_to_copy: 0,
             bytes_to_delete: 0,
+            bytes_to_rename: 0,
             source_count: 0,
             dest_count: 0,
         }
@@ -80,12 +94,12 @@ impl SyncAnalysis {
 
     /// Check if there are any changes
     pub fn has_changes(&self) -> bool {
-        !self.to_copy.is_empty() || !self.to_delete.is_empty()
+        !self.to_copy.is_empty() || !self.to_delete.is_empty() || !self.to_rename.is_empty()
     }
 
     /// Get total number of actions
     pub fn action_count(&self) -> usize {
-        self.to_copy.len() + self.to_delete.len()
+        self.to_copy.len() + self.to_delete.len() + self.to_rename.len()
     }
 
     /// Print summary
@@ -98,6 +112,12 @@ impl SyncAnalysis {
             self.to_copy.len(),
             humansize::format_size(self.bytes_to_copy, humansize::BINARY)
         );
+        if !self.to_rename.is_empty() {
+            println!("To rename: {} files ({} not copied)",
+                self.to_rename.len(),
+                humansize::format_size(self.bytes_to_rename, humansize::BINARY)
+            );
+        }
         println!("To skip:   {} files", self.to_skip.len());
         println!("To delete: {} files ({})",
             self.to_delete.len(),
@@ -123,6 +143,8 @@ pub struct IncrementalSync {
     content_compare: bool,
     /// Ignore file time differences
     ignore_times: bool,
+    /// Turn new files matching files about to be deleted into renames
+    detect_renames: bool,
 }
 
 impl IncrementalSync {
@@ -132,6 +154,7 @@ impl IncrementalSync {
             delete_extra: false,
             content_compare: false,
             ignore_times: false,
+            detect_renames: false,
         }
     }
 
@@ -153,6 +176,17 @@ impl IncrementalSync {
         self
     }
 
+    /// Detect renamed and moved files
+    ///
+    /// A new file with the same size and [`quick_hash`] as a destination
+    /// file about to be deleted becomes a [`SyncAction::Rename`] of that
+    /// file. With a manifest, an unchanged source inode identifies the
+    /// file without hashing. Only applies with [`delete_extra`](Self::delete_extra).
+    pub fn detect_renames(mut self, enable: bool) -> Self {
+        self.detect_renames = enable;
+        self
+    }
+
     /// Analyze source and destination for sync
     pub fn analyze(&self, source: &Path, dest: &Path) -> Result<SyncAnalysis> {
         // Scan both directories
@@ -167,6 +201,7 @@ impl IncrementalSync {
                 root: dest.to_path_buf(),
                 files: Vec::new(),
                 directories: Vec::new(),
+                specials: Vec::new(),
                 total_size: 0,
                 file_count: 0,
                 dir_count: 0,
@@ -199,9 +234,9 @@ impl IncrementalSync {
             .collect();
 
         for source_entry in &source_scan.files {
-            let rel_path = source_entry.relative_path.to_string_lossy();
+            let rel_path = escape_path(&source_entry.relative_path);
 
-            if let Some(manifest_entry) = manifest_entries.get(rel_path.as_ref()) {
+            if let Some(manifest_entry) = manifest_entries.get(rel_path.as_str()) {
                 // Compare with manifest
                 let mtime = source_entry.modified
                     .duration_since(std::time::UNIX_EPOCH)
@@ -247,7 +282,7 @@ impl IncrementalSync {
         if self.delete_extra {
             let source_paths: HashSet<String> = source_scan.files
                 .iter()
-                .map(|e| e.relative_path.to_string_lossy().to_string())
+                .map(|e| escape_path(&e.relative_path))
                 .collect();
 
             for entry in &manifest.entries {
@@ -262,13 +297,17 @@ impl IncrementalSync {
                     analysis.bytes_to_delete += entry.size;
                 }
             }
+
+            if self.detect_renames {
+                self.match_manifest_renames(&mut analysis, manifest);
+            }
         }
 
         Ok(analysis)
     }
 
     /// Compare two scan results
-    fn compare_scans(
+    pub fn compare_scans(
         &self,
         source: &ScanResult,
         dest: &ScanResult,
@@ -280,17 +319,17 @@ impl IncrementalSync {
         // Build destination file lookup
         let dest_files: HashMap<String, &FileEntry> = dest.files
             .iter()
-            .map(|e| (e.relative_path.to_string_lossy().to_string(), e))
+            .map(|e| (escape_path(&e.relative_path), e))
             .collect();
 
         let source_paths: HashSet<String> = source.files
             .iter()
-            .map(|e| e.relative_path.to_string_lossy().to_string())
+            .map(|e| escape_path(&e.relative_path))
             .collect();
 
         // Compare source files to destination
         for source_entry in &source.files {
-            let rel_path = source_entry.relative_path.to_string_lossy().to_string();
+            let rel_path = escape_path(&source_entry.relative_path);
 
             if let Some(dest_entry) = dest_files.get(&rel_path) {
                 // File exists in both - compare
@@ -350,7 +389,7 @@ impl IncrementalSync {
         // Find extra files in destination
         if self.delete_extra {
             for dest_entry in &dest.files {
-                let rel_path = dest_entry.relative_path.to_string_lossy().to_string();
+                let rel_path = escape_path(&dest_entry.relative_path);
                 if !source_paths.contains(&rel_path) {
                     analysis.to_delete.push(SyncChange {
                         path: rel_path,
@@ -362,10 +401,205 @@ impl IncrementalSync {
                     analysis.bytes_to_delete += dest_entry.size;
                 }
             }
+
+            if self.detect_renames {
+                let deleted: Vec<_> = analysis.to_delete.iter()
+                    .map(|c| c.dest.as_ref().map(|d| d.path.clone()))
+                    .collect();
+                let pairs = match_by_content(&analysis, &deleted);
+                apply_matches(&mut analysis, pairs);
+            }
         }
 
         Ok(analysis)
     }
+
+    /// Rename detection against a manifest: first by source inode, then by
+    /// content for files still present under the destination root
+    fn match_manifest_renames(&self, analysis: &mut SyncAnalysis, manifest: &SyncManifest) {
+        let entries = manifest.as_map();
+        let mut by_inode: HashMap<(u64, u64), usize> = HashMap::new();
+        for (i, change) in analysis.to_delete.iter().enumerate() {
+            if let Some(inode) = entries.get(change.path.as_str()).and_then(|e| e.inode) {
+                by_inode.insert((inode, change.size), i);
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for (i, change) in analysis.to_copy.iter().enumerate() {
+            let Some(source) = change.source.as_ref().filter(|_| change.action == SyncAction::CopyNew) else {
+                continue;
+            };
+            if let Some(d) = by_inode.remove(&(source.inode, source.size)) {
+                // Same inode and size but new content is an edit, not a rename
+                let mtime = source.modified
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                if entries.get(analysis.to_delete[d].path.as_str()).is_some_and(|e| e.mtime == mtime) {
+                    pairs.push((i, d));
+                }
+            }
+        }
+
+        let matched: HashSet<usize> = pairs.iter().map(|&(i, _)| i).collect();
+        let taken: HashSet<usize> = pairs.iter().map(|&(_, d)| d).collect();
+        let dest_root = Path::new(&manifest.dest_root);
+        let deleted: Vec<_> = analysis.to_delete.iter().enumerate()
+            .map(|(d, c)| {
+                (!taken.contains(&d))
+                    .then(|| unescape_path(&c.path))
+                    .map(|rel| dest_root.join(rel))
+                    .filter(|p| p.is_file())
+            })
+            .collect();
+        let mut rest = match_by_content(analysis, &deleted);
+        rest.retain(|(i, _)| !matched.contains(i));
+        pairs.extend(rest);
+        apply_matches(analysis, pairs);
+    }
+}
+
+/// Pair new files in `to_copy` with deletions by size and [`quick_hash`]
+///
+/// `deleted[d]` is the destination path of `to_delete[d]`, `None` to leave
+/// it out. Each deletion is used at most once, so two new copies of one
+/// file still cost one transfer, not a shared inode.
+fn match_by_content(analysis: &SyncAnalysis, deleted: &[Option<PathBuf>]) -> Vec<(usize, usize)> {
+    let mut sizes: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (d, path) in deleted.iter().enumerate() {
+        let size = analysis.to_delete[d].size;
+        // Empty files cost nothing to create
+        if path.is_some() && size > 0 {
+            sizes.entry(size).or_default().push(d);
+        }
+    }
+
+    let new_files: Vec<(usize, &FileEntry)> = analysis.to_copy.iter().enumerate()
+        .filter(|(_, c)| c.action == SyncAction::CopyNew)
+        .filter_map(|(i, c)| c.source.as_ref().map(|s| (i, s)))
+        .filter(|(_, s)| sizes.contains_key(&s.size))
+        .collect();
+    let new_sizes: HashSet<u64> = new_files.iter().map(|(_, s)| s.size).collect();
+
+    // Hash only what could pair up; unreadable files just don't match
+    let old_hashes: HashMap<(u64, u64), Vec<usize>> = sizes.iter()
+        .filter(|(size, _)| new_sizes.contains(size))
+        .flat_map(|(&size, ds)| ds.iter().map(move |&d| (size, d)))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .filter_map(|(size, d)| {
+            let path = deleted[d].as_ref()?;
+            quick_hash(path).ok().map(|hash| ((size, hash), d))
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .fold(HashMap::new(), |mut map, (key, d)| {
+            map.entry(key).or_insert_with(Vec::new).push(d);
+            map
+        });
+    let new_hashes: Vec<(usize, (u64, u64))> = new_files
+        .par_iter()
+        .filter_map(|&(i, s)| quick_hash(&s.path).ok().map(|hash| (i, (s.size, hash))))
+        .collect();
+
+    let mut old_hashes = old_hashes;
+    let mut pairs = Vec::new();
+    for (i, key) in new_hashes {
+        if let Some(d) = old_hashes.get_mut(&key).and_then(|ds| ds.pop()) {
+            pairs.push((i, d));
+        }
+    }
+    pairs
+}
+
+/// Move matched (new file, deletion) pairs into `to_rename`
+fn apply_matches(analysis: &mut SyncAnalysis, pairs: Vec<(usize, usize)>) {
+    if pairs.is_empty() {
+        return;
+    }
+    let sources: HashMap<usize, usize> = pairs.iter().copied().collect();
+    let taken: HashSet<usize> = pairs.iter().map(|&(_, d)| d).collect();
+
+    let to_delete = std::mem::take(&mut analysis.to_delete);
+    let mut old: HashMap<usize, SyncChange> = HashMap::new();
+    for (d, change) in to_delete.into_iter().enumerate() {
+        if taken.contains(&d) {
+            analysis.bytes_to_delete -= change.size;
+            old.insert(d, change);
+        } else {
+            analysis.to_delete.push(change);
+        }
+    }
+
+    let to_copy = std::mem::take(&mut analysis.to_copy);
+    for (i, mut change) in to_copy.into_iter().enumerate() {
+        match sources.get(&i).and_then(|d| old.remove(d)) {
+            Some(from) => {
+                analysis.bytes_to_copy -= change.size;
+                analysis.bytes_to_rename += change.size;
+                change.action = SyncAction::Rename { from: from.path };
+                change.dest = from.dest;
+                analysis.to_rename.push(change);
+            }
+            None => analysis.to_copy.push(change),
+        }
+    }
+}
+
+/// Result of [`apply_renames`]
+#[derive(Debug, Clone, Default)]
+pub struct RenameResult {
+    /// Files moved to their new path
+    pub renamed: u64,
+    /// Bytes not copied thanks to renames
+    pub bytes: u64,
+    /// Renames that failed (path, error); these files are copied instead
+    pub failed: Vec<(String, String)>,
+}
+
+/// Apply detected renames under `dest_root`
+///
+/// Each new path is hard-linked to the old file, and the old paths are
+/// removed once every link exists, so an interruption leaves both names
+/// rather than neither. Where hard links are not supported the file is
+/// renamed instead.
+pub fn apply_renames(dest_root: &Path, renames: &[SyncChange]) -> RenameResult {
+    let mut result = RenameResult::default();
+    let mut linked = Vec::new();
+
+    for change in renames {
+        let SyncAction::Rename { from } = &change.action else {
+            continue;
+        };
+        let outcome = (|| {
+            let (old, new) = (dest_root.join(unescape_path(from)), dest_root.join(unescape_path(&change.path)));
+            if let Some(parent) = new.parent() {
+                std::fs::create_dir_all(parent).with_path(parent)?;
+            }
+            match std::fs::hard_link(&old, &new) {
+                Ok(()) => Ok(Some(old)),
+                // Never replace a file that appeared in the meantime
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(e).with_path(&new),
+                Err(_) => std::fs::rename(&old, &new).with_path(&new).map(|_| None),
+            }
+        })();
+        match outcome {
+            Ok(old) => {
+                linked.extend(old);
+                result.renamed += 1;
+                result.bytes += change.size;
+            }
+            Err(e) => result.failed.push((change.path.clone(), e.to_string())),
+        }
+    }
+
+    for old in linked {
+        if let Err(e) = std::fs::remove_file(&old) {
+            result.failed.push((escape_path(&old), e.to_string()));
+        }
+    }
+    result
 }
 
 impl Default for IncrementalSync {
@@ -456,6 +690,60 @@ mod tests {
         assert_eq!(analysis.to_delete[0].path, "extra.txt");
     }
 
+    #[test]
+    fn test_detect_renames() {
+        let src = TempDir::new().unwrap();
+        let dst = TempDir::new().unwrap();
+
+        create_test_file(src.path(), "new/a.txt", b"moved content");
+        create_test_file(src.path(), "b.txt", b"fresh content");
+        create_test_file(dst.path(), "old/a.txt", b"moved content");
+        create_test_file(dst.path(), "gone.txt", b"other content");
+
+        let sync = IncrementalSync::new().delete_extra(true).detect_renames(true);
+        let analysis = sync.analyze(src.path(), dst.path()).unwrap();
+
+        assert_eq!(analysis.to_rename.len(), 1);
+        assert_eq!(analysis.to_rename[0].path, "new/a.txt");
+        assert_eq!(analysis.to_rename[0].action, SyncAction::Rename { from: "old/a.txt".to_string() });
+        assert_eq!(analysis.bytes_to_rename, 13);
+        assert_eq!(analysis.to_copy.len(), 1);
+        assert_eq!(analysis.to_delete.len(), 1);
+        assert_eq!(analysis.to_delete[0].path, "gone.txt");
+
+        let result = apply_renames(dst.path(), &analysis.to_rename);
+        assert_eq!(result.renamed, 1);
+        assert!(result.failed.is_empty());
+        assert!(!dst.path().join("old/a.txt").exists());
+        assert_eq!(std::fs::read(dst.path().join("new/a.txt")).unwrap(), b"moved content");
+
+        // Without the option, the same tree is a delete plus a copy
+        let sync = IncrementalSync::new().delete_extra(true);
+        let analysis = sync.analyze(src.path(), dst.path()).unwrap();
+        assert!(analysis.to_rename.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_detect_renames_with_manifest() {
+        let src = TempDir::new().unwrap();
+        create_test_file(src.path(), "a.txt", b"tracked");
+
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(src.path()).unwrap();
+        // The destination is remote, so only the inode can match
+        let manifest = SyncManifest::from_scan(&scan, "/nonexistent/dest");
+        std::fs::rename(src.path().join("a.txt"), src.path().join("b.txt")).unwrap();
+
+        let sync = IncrementalSync::new().delete_extra(true).detect_renames(true);
+        let analysis = sync.analyze_with_manifest(src.path(), &manifest).unwrap();
+
+        assert_eq!(analysis.to_rename.len(), 1);
+        assert_eq!(analysis.to_rename[0].path, "b.txt");
+        assert_eq!(analysis.to_rename[0].action, SyncAction::Rename { from: "a.txt".to_string() });
+        assert!(analysis.to_copy.is_empty());
+        assert!(analysis.to_delete.is_empty());
+    }
+
     #[test]
     fn test_needs_sync() {
         let src = TempDir::new().unwrap();
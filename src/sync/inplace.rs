@@ -0,0 +1,662 @@
+//! In-place delta application
+//!
+//! Applies copy and literal operations directly to the existing file
+//! instead of rebuilding into a second one, so updating a 1 TB image needs
+//! no extra 1 TB of space.
+//!
+//! Copies read the file they write to, so they are ordered: a copy runs
+//! before anything overwrites its source. Copies whose source and
+//! destination overlap are done as a directional memmove; copies caught
+//! in a cycle (two blocks that swap places) have their source saved to the
+//! journal first and are written at the end. Copies that leave data where
+//! it already is are skipped.
+//!
+//! Literals are written as they arrive, before any copy runs, unless they
+//! land on the source of a copy; those are saved to the journal too and
+//! written at the end. No literal is held in memory for the whole apply.
+//!
+//! While applying, a journal next to the file ([`in_place_journal_path`])
+//! records the sizes, buffered copies and literals and periodic
+//! checkpoints taken after syncing the file.
+//! A journal left behind means an apply was interrupted and the file is a
+//! mix of old and new content. The journal is not replayed: it only lets
+//! [`read_in_place_journal`] detect the interruption, after which the
+//! update is re-run with a delta computed afresh against the file as it
+//! is. Blocks already written then match in place, and data lost from the
+//! file comes across as literals.
+
+use crate::error::{IoResultExt, Result, SmartCopyError};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Data moved per read/write while copying within the file
+const MOVE_BUFFER: u64 = 4 * 1024 * 1024;
+
+/// Bytes moved or buffered between checkpoints
+const CHECKPOINT_BYTES: u64 = 256 * 1024 * 1024;
+
+/// A write to the file being updated in place
+#[derive(Debug, Clone)]
+pub enum InPlaceOp {
+    /// Copy `len` bytes at `src` in the current file to `dst`
+    Copy {
+        /// Offset in the file before the update
+        src: u64,
+        /// Offset in the updated file
+        dst: u64,
+        /// Bytes to copy
+        len: u64,
+    },
+    /// Write literal data at `dst`
+    Literal {
+        /// Offset in the updated file
+        dst: u64,
+        /// Bytes to write
+        data: Vec<u8>,
+    },
+}
+
+/// Result of [`apply_in_place`]
+#[derive(Debug, Clone, Default)]
+pub struct InPlaceResult {
+    /// Bytes moved within the file
+    pub bytes_copied: u64,
+    /// Literal bytes written
+    pub bytes_written: u64,
+    /// Bytes already in place, not touched
+    pub bytes_unchanged: u64,
+    /// Bytes saved to the journal to break copy cycles
+    pub bytes_buffered: u64,
+    /// Checkpoints taken
+    pub checkpoints: u64,
+}
+
+/// State of an interrupted in-place apply, from its journal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InPlaceJournal {
+    /// File size before the apply
+    pub original_size: u64,
+    /// File size the apply was producing
+    pub target_size: u64,
+    /// Copy steps planned
+    pub steps: u64,
+    /// Copy steps completed and synced at the last checkpoint
+    pub steps_done: u64,
+    /// Bytes moved within the file or buffered by those steps
+    pub bytes_copied: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+enum JournalRecord {
+    Header { original_size: u64, target_size: u64, steps: u64 },
+    Buffered { dst: u64, data: Vec<u8> },
+    Progress { steps_done: u64, bytes_copied: u64 },
+}
+
+/// Journal kept next to a file while it is updated in place
+pub fn in_place_journal_path(path: &Path) -> PathBuf {
+    let mut journal = path.as_os_str().to_os_string();
+    journal.push(".smartcopy-journal");
+    PathBuf::from(journal)
+}
+
+/// Read the journal of an interrupted in-place apply, `None` if the file
+/// is not mid-update
+pub fn read_in_place_journal(path: &Path) -> Result<Option<InPlaceJournal>> {
+    let journal_path = in_place_journal_path(path);
+    let file = match File::open(&journal_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(SmartCopyError::io(&journal_path, e)),
+    };
+    let mut reader = BufReader::new(file);
+
+    let mut state = match read_record(&mut reader) {
+        Some(JournalRecord::Header { original_size, target_size, steps }) => InPlaceJournal {
+            original_size,
+            target_size,
+            steps,
+            steps_done: 0,
+            bytes_copied: 0,
+        },
+        _ => {
+            return Err(SmartCopyError::DeltaSyncError(format!(
+                "'{}' is corrupt: missing header",
+                journal_path.display()
+            )))
+        }
+    };
+    // Records after a torn write at the end are ignored
+    while let Some(record) = read_record(&mut reader) {
+        if let JournalRecord::Progress { steps_done, bytes_copied } = record {
+            state.steps_done = steps_done;
+            state.bytes_copied = bytes_copied;
+        }
+    }
+    Ok(Some(state))
+}
+
+fn read_record<R: Read>(reader: &mut R) -> Option<JournalRecord> {
+    let mut word = [0u8; 8];
+    reader.read_exact(&mut word).ok()?;
+    let mut payload = vec![0u8; usize::try_from(u64::from_le_bytes(word)).ok()?];
+    reader.read_exact(&mut payload).ok()?;
+    reader.read_exact(&mut word).ok()?;
+    if u64::from_le_bytes(word) != xxhash_rust::xxh3::xxh3_64(&payload) {
+        return None;
+    }
+    bincode::deserialize(&payload).ok()
+}
+
+/// Append-only journal file
+struct Journal {
+    file: File,
+    path: PathBuf,
+    len: u64,
+}
+
+impl Journal {
+    fn create(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .with_path(path)?;
+        Ok(Self { file, path: path.to_path_buf(), len: 0 })
+    }
+
+    /// Append a record, returning its offset
+    fn append(&mut self, record: &JournalRecord) -> Result<u64> {
+        let payload = bincode::serialize(record)
+            .map_err(|e| SmartCopyError::DeltaSyncError(e.to_string()))?;
+        let mut framed = Vec::with_capacity(payload.len() + 16);
+        framed.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        framed.extend_from_slice(&payload);
+        framed.extend_from_slice(&xxhash_rust::xxh3::xxh3_64(&payload).to_le_bytes());
+
+        let offset = self.len;
+        self.file.seek(SeekFrom::Start(offset)).with_path(&self.path)?;
+        self.file.write_all(&framed).with_path(&self.path)?;
+        self.len += framed.len() as u64;
+        Ok(offset)
+    }
+
+    fn read_at(&mut self, offset: u64) -> Result<JournalRecord> {
+        self.file.seek(SeekFrom::Start(offset)).with_path(&self.path)?;
+        read_record(&mut self.file).ok_or_else(|| {
+            SmartCopyError::DeltaSyncError(format!("'{}' is corrupt", self.path.display()))
+        })
+    }
+
+    fn sync(&self) -> Result<()> {
+        self.file.sync_data().with_path(&self.path)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Move {
+    src: u64,
+    dst: u64,
+    len: u64,
+}
+
+impl Move {
+    fn src_end(&self) -> u64 {
+        self.src + self.len
+    }
+
+    fn dst_end(&self) -> u64 {
+        self.dst + self.len
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// Copy within the file now
+    Copy(usize),
+    /// Save the source to the journal and write it at the end
+    Buffer(usize),
+}
+
+/// Order copies so that none overwrites the source of a copy still to
+/// run; a copy that can't be placed because of a cycle is buffered
+///
+/// `moves` must be sorted by destination, with destinations disjoint.
+fn plan(moves: &[Move]) -> Vec<Step> {
+    let n = moves.len();
+
+    // a -> b when b writes over a's source, so a has to run first
+    let mut successors: Vec<Vec<u32>> = vec![Vec::new(); n];
+    let mut predecessors: Vec<Vec<u32>> = vec![Vec::new(); n];
+    let mut indegree = vec![0u32; n];
+    for (a, m) in moves.iter().enumerate() {
+        let first = moves.partition_point(|b| b.dst_end() <= m.src);
+        for (b, other) in moves.iter().enumerate().skip(first) {
+            if other.dst >= m.src_end() {
+                break;
+            }
+            // Overlap with its own destination is handled while moving
+            if b != a {
+                successors[a].push(b as u32);
+                predecessors[b].push(a as u32);
+                indegree[b] += 1;
+            }
+        }
+    }
+
+    let mut steps = Vec::with_capacity(n);
+    let mut done = vec![false; n];
+    let mut seen = vec![0usize; n];
+    let mut ready: VecDeque<usize> = (0..n).filter(|&i| indegree[i] == 0).collect();
+    let mut cursor = 0;
+    while steps.len() < n {
+        let (next, step) = match ready.pop_front() {
+            Some(i) => (i, Step::Copy(i)),
+            None => {
+                // Everything left waits on a cycle. Walking back along
+                // pending predecessors must revisit a copy, which lies on
+                // a cycle; buffering it breaks that cycle.
+                while done[cursor] {
+                    cursor += 1;
+                }
+                let mut at = cursor;
+                while seen[at] != steps.len() + 1 {
+                    seen[at] = steps.len() + 1;
+                    at = predecessors[at]
+                        .iter()
+                        .map(|&p| p as usize)
+                        .find(|&p| !done[p])
+                        .expect("a waiting copy has a pending predecessor");
+                }
+                (at, Step::Buffer(at))
+            }
+        };
+        done[next] = true;
+        steps.push(step);
+        for &b in &successors[next] {
+            let b = b as usize;
+            indegree[b] -= 1;
+            if indegree[b] == 0 {
+                ready.push_back(b);
+            }
+        }
+    }
+    steps
+}
+
+fn read_range(file: &mut File, path: &Path, offset: u64, buf: &mut [u8]) -> Result<()> {
+    file.seek(SeekFrom::Start(offset)).with_path(path)?;
+    file.read_exact(buf).with_path(path)
+}
+
+fn write_range(file: &mut File, path: &Path, offset: u64, buf: &[u8]) -> Result<()> {
+    file.seek(SeekFrom::Start(offset)).with_path(path)?;
+    file.write_all(buf).with_path(path)
+}
+
+/// Copy within the file, front to back or back to front depending on the
+/// direction, so an overlapping source is read before it is overwritten
+fn move_range(file: &mut File, path: &Path, m: Move, buffer: &mut [u8]) -> Result<()> {
+    let piece = buffer.len() as u64;
+    let mut done = 0;
+    while done < m.len {
+        let n = piece.min(m.len - done);
+        let at = if m.dst > m.src { m.len - done - n } else { done };
+        let buf = &mut buffer[..n as usize];
+        read_range(file, path, m.src + at, buf)?;
+        write_range(file, path, m.dst + at, buf)?;
+        done += n;
+    }
+    Ok(())
+}
+
+/// Apply `ops` to `path` in place, producing a file of `target_size`
+///
+/// Copy sources refer to the file as it is before the apply. Copy and
+/// literal destinations must not overlap.
+pub fn apply_in_place(path: &Path, ops: Vec<InPlaceOp>, target_size: u64) -> Result<InPlaceResult> {
+    apply_in_place_with(path, ops, target_size, |_| Ok(()))
+}
+
+/// Like [`apply_in_place`], with further literals handed to a
+/// [`LiteralWriter`] by `literals` rather than kept in `ops`
+///
+/// `literals` runs before any copy, so literal data can be produced a
+/// piece at a time, for instance read from the new version of the file.
+pub fn apply_in_place_with<F>(
+    path: &Path,
+    ops: Vec<InPlaceOp>,
+    target_size: u64,
+    literals: F,
+) -> Result<InPlaceResult>
+where
+    F: FnOnce(&mut LiteralWriter<'_>) -> Result<()>,
+{
+    let mut file = OpenOptions::new().read(true).write(true).open(path).with_path(path)?;
+    let original_size = file.metadata().with_path(path)?.len();
+
+    let mut result = InPlaceResult::default();
+    let mut moves = Vec::new();
+    let mut given = Vec::new();
+    for op in ops {
+        match op {
+            InPlaceOp::Copy { len: 0, .. } => {}
+            InPlaceOp::Copy { src, dst, len } => {
+                if src.checked_add(len).is_none_or(|end| end > original_size) {
+                    return Err(SmartCopyError::DeltaSyncError(format!(
+                        "copy of {} bytes at {} is past the end of '{}' ({} bytes)",
+                        len,
+                        src,
+                        path.display(),
+                        original_size
+                    )));
+                }
+                if src == dst {
+                    result.bytes_unchanged += len;
+                } else {
+                    moves.push(Move { src, dst, len });
+                }
+            }
+            InPlaceOp::Literal { dst, data } => given.push((dst, data)),
+        }
+    }
+
+    let mut taken: Vec<(u64, u64)> = moves.iter().map(|m| (m.dst, m.dst_end()))
+        .chain(given.iter().map(|(dst, data)| (*dst, *dst + data.len() as u64)))
+        .collect();
+    taken.sort_unstable();
+    let mut end = 0;
+    for &(dst, dst_end) in &taken {
+        if dst < end || dst_end < dst || dst_end > target_size {
+            return Err(SmartCopyError::DeltaSyncError(format!(
+                "overlapping or out-of-range write at {} in '{}'",
+                dst,
+                path.display()
+            )));
+        }
+        end = dst_end;
+    }
+
+    moves.sort_unstable_by_key(|m| m.dst);
+    let steps = plan(&moves);
+
+    // Copy sources, merged, which literals must not overwrite yet
+    let mut sources: Vec<(u64, u64)> = moves.iter().map(|m| (m.src, m.src_end())).collect();
+    sources.sort_unstable();
+    sources.dedup_by(|next, prev| {
+        if next.0 <= prev.1 {
+            prev.1 = prev.1.max(next.1);
+            true
+        } else {
+            false
+        }
+    });
+
+    let mut journal = Journal::create(&in_place_journal_path(path))?;
+    journal.append(&JournalRecord::Header {
+        original_size,
+        target_size,
+        steps: steps.len() as u64,
+    })?;
+    journal.sync()?;
+
+    // Growing first keeps every source intact; shrinking waits until the
+    // tail has been read
+    if target_size > original_size {
+        file.set_len(target_size).with_path(path)?;
+    }
+
+    let mut writer = LiteralWriter {
+        file: &mut file,
+        path,
+        journal: &mut journal,
+        sources,
+        taken,
+        target_size,
+        end: 0,
+        spooled: Vec::new(),
+        bytes_written: 0,
+    };
+    for (dst, data) in given {
+        writer.put(dst, &data)?;
+    }
+    literals(&mut writer)?;
+    let mut buffered = std::mem::take(&mut writer.spooled);
+    result.bytes_written = writer.bytes_written;
+    journal.sync()?;
+
+    let mut buffer = vec![0u8; MOVE_BUFFER as usize];
+    let mut since_checkpoint = 0u64;
+    for (index, step) in steps.iter().enumerate() {
+        match *step {
+            Step::Copy(i) => {
+                let m = moves[i];
+                move_range(&mut file, path, m, &mut buffer)?;
+                result.bytes_copied += m.len;
+                since_checkpoint += m.len;
+            }
+            Step::Buffer(i) => {
+                let m = moves[i];
+                let mut offset = 0;
+                while offset < m.len {
+                    let n = MOVE_BUFFER.min(m.len - offset);
+                    let mut data = vec![0u8; n as usize];
+                    read_range(&mut file, path, m.src + offset, &mut data)?;
+                    let record = JournalRecord::Buffered { dst: m.dst + offset, data };
+                    buffered.push(journal.append(&record)?);
+                    offset += n;
+                }
+                journal.sync()?;
+                result.bytes_buffered += m.len;
+                result.bytes_copied += m.len;
+                since_checkpoint += m.len;
+            }
+        }
+        if since_checkpoint >= CHECKPOINT_BYTES {
+            checkpoint(&file, path, &mut journal, index as u64 + 1, &mut result)?;
+            since_checkpoint = 0;
+        }
+    }
+
+    for offset in buffered {
+        if let JournalRecord::Buffered { dst, data } = journal.read_at(offset)? {
+            write_range(&mut file, path, dst, &data)?;
+        }
+    }
+    if target_size < original_size {
+        file.set_len(target_size).with_path(path)?;
+    }
+
+    file.sync_all().with_path(path)?;
+    drop(journal);
+    std::fs::remove_file(in_place_journal_path(path)).with_path(path)?;
+    Ok(result)
+}
+
+/// Whether `start..end` overlaps one of the sorted, disjoint `ranges`
+fn overlaps(ranges: &[(u64, u64)], start: u64, end: u64) -> bool {
+    let i = ranges.partition_point(|r| r.1 <= start);
+    ranges.get(i).is_some_and(|r| r.0 < end)
+}
+
+/// Writes the literals of [`apply_in_place_with`]
+pub struct LiteralWriter<'a> {
+    file: &'a mut File,
+    path: &'a Path,
+    journal: &'a mut Journal,
+    /// Sources of the copies still to run
+    sources: Vec<(u64, u64)>,
+    /// Copy destinations and literals given in the ops
+    taken: Vec<(u64, u64)>,
+    target_size: u64,
+    /// End of the last literal passed to [`LiteralWriter::write`]
+    end: u64,
+    /// Journal offsets of literals written at the end
+    spooled: Vec<u64>,
+    bytes_written: u64,
+}
+
+impl LiteralWriter<'_> {
+    /// Write `data` at `dst` in the updated file
+    ///
+    /// Literals must come in order of destination and must not overlap
+    /// each other, a copy destination or a literal given in the ops.
+    pub fn write(&mut self, dst: u64, data: &[u8]) -> Result<()> {
+        let end = dst.checked_add(data.len() as u64);
+        match end {
+            Some(end) if dst >= self.end && end <= self.target_size && !overlaps(&self.taken, dst, end) => {
+                self.end = end;
+                self.put(dst, data)
+            }
+            _ => Err(SmartCopyError::DeltaSyncError(format!(
+                "overlapping or out-of-range write at {} in '{}'",
+                dst,
+                self.path.display()
+            ))),
+        }
+    }
+
+    /// Write now, or save to the journal when a copy still has to read
+    /// the range
+    fn put(&mut self, dst: u64, data: &[u8]) -> Result<()> {
+        if overlaps(&self.sources, dst, dst + data.len() as u64) {
+            for (i, piece) in data.chunks(MOVE_BUFFER as usize).enumerate() {
+                let record = JournalRecord::Buffered {
+                    dst: dst + i as u64 * MOVE_BUFFER,
+                    data: piece.to_vec(),
+                };
+                self.spooled.push(self.journal.append(&record)?);
+            }
+        } else {
+            write_range(self.file, self.path, dst, data)?;
+        }
+        self.bytes_written += data.len() as u64;
+        Ok(())
+    }
+}
+
+fn checkpoint(
+    file: &File,
+    path: &Path,
+    journal: &mut Journal,
+    steps_done: u64,
+    result: &mut InPlaceResult,
+) -> Result<()> {
+    file.sync_data().with_path(path)?;
+    journal.append(&JournalRecord::Progress {
+        steps_done,
+        bytes_copied: result.bytes_copied,
+    })?;
+    journal.sync()?;
+    result.checkpoints += 1;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// What the ops produce when applied to a separate output
+    fn expected(original: &[u8], ops: &[InPlaceOp], target_size: usize) -> Vec<u8> {
+        let mut out = vec![0u8; target_size];
+        for op in ops {
+            match op {
+                InPlaceOp::Copy { src, dst, len } => {
+                    let (src, dst, len) = (*src as usize, *dst as usize, *len as usize);
+                    out[dst..dst + len].copy_from_slice(&original[src..src + len]);
+                }
+                InPlaceOp::Literal { dst, data } => {
+                    out[*dst as usize..*dst as usize + data.len()].copy_from_slice(data);
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_in_place_shift_swap_and_shrink() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("image.bin");
+        let original: Vec<u8> = (0..40_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &original).unwrap();
+
+        let ops = vec![
+            // Inserted header shifts the first block right over itself
+            InPlaceOp::Literal { dst: 0, data: b"header".to_vec() },
+            InPlaceOp::Copy { src: 0, dst: 6, len: 10_000 },
+            // Two blocks swap places: a cycle
+            InPlaceOp::Copy { src: 20_000, dst: 10_006, len: 10_000 },
+            InPlaceOp::Copy { src: 10_000, dst: 20_006, len: 10_000 },
+            // Unchanged block
+            InPlaceOp::Copy { src: 30_006, dst: 30_006, len: 5_000 },
+        ];
+        let want = expected(&original, &ops, 35_006);
+
+        let result = apply_in_place(&path, ops, 35_006).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), want);
+        assert_eq!(result.bytes_unchanged, 5_000);
+        assert_eq!(result.bytes_buffered, 10_000);
+        assert!(read_in_place_journal(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_streamed_literals_wait_for_copy_sources() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("image.bin");
+        let original: Vec<u8> = (0..30_000u32).map(|i| (i % 241) as u8).collect();
+        std::fs::write(&path, &original).unwrap();
+
+        // The first block moves to the end and a literal takes its place;
+        // the literal after it overwrites nothing still to be read
+        let copies = vec![InPlaceOp::Copy { src: 0, dst: 20_000, len: 10_000 }];
+        let mut all = copies.clone();
+        all.push(InPlaceOp::Literal { dst: 0, data: vec![1; 10_000] });
+        all.push(InPlaceOp::Literal { dst: 10_000, data: vec![2; 10_000] });
+        let want = expected(&original, &all, 30_000);
+
+        let result = apply_in_place_with(&path, copies, 30_000, |writer| {
+            writer.write(0, &[1; 10_000])?;
+            writer.write(10_000, &[2; 10_000])?;
+            // Out of order, and over a copy destination
+            assert!(writer.write(5_000, &[3; 10]).is_err());
+            assert!(writer.write(25_000, &[3; 10]).is_err());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), want);
+        assert_eq!(result.bytes_written, 20_000);
+        assert_eq!(result.bytes_buffered, 0);
+    }
+
+    #[test]
+    fn test_chain_is_ordered_without_buffering() {
+        // Each block moves into the previous one's place
+        let moves: Vec<Move> = (0..5u64)
+            .map(|i| Move { src: (i + 1) * 100, dst: i * 100, len: 100 })
+            .collect();
+        let steps = plan(&moves);
+        assert_eq!(steps, (0..5).map(Step::Copy).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_interrupted_apply_is_detected() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("image.bin");
+        std::fs::write(&path, b"data").unwrap();
+
+        let mut journal = Journal::create(&in_place_journal_path(&path)).unwrap();
+        journal.append(&JournalRecord::Header { original_size: 4, target_size: 8, steps: 3 }).unwrap();
+        journal.append(&JournalRecord::Progress { steps_done: 2, bytes_copied: 4 }).unwrap();
+        drop(journal);
+
+        let state = read_in_place_journal(&path).unwrap().unwrap();
+        assert_eq!((state.steps, state.steps_done, state.target_size), (3, 2, 8));
+        assert_eq!(state.bytes_copied, 4);
+    }
+}
@@ -3,25 +3,36 @@
 //! Provides persistent tracking of synchronized files
 //! for efficient incremental updates.
 //!
-//! Note: Paths are stored as UTF-8 strings with lossy conversion.
-//! For paths with non-UTF8 characters, use byte-level operations
-//! or ensure your filesystem uses UTF-8 encoding.
+//! Paths are stored in the reversible escaped form from
+//! [`crate::fs::path_encoding`], so non-UTF-8 names keep distinct keys,
+//! plus their raw bytes (hex) when they are not valid UTF-8.
 
 use crate::config::HashAlgorithm;
 use crate::error::{IoResultExt, Result, SmartCopyError};
-use crate::fs::{FileEntry, ScanResult};
+use crate::fs::{escape_path, path_from_bytes, path_to_bytes, unescape_path, FileEntry, ScanResult, SpecialKind};
+use crate::hash::{binary_manifest_payload, encode_binary_manifest, ManifestSignature};
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Magic bytes of a binary sync manifest
+pub const SYNC_MANIFEST_MAGIC: &[u8; 8] = b"SCPYSMF\0";
+
+/// Layout version written to binary sync manifests
+///
+/// Bump it whenever a field is added to [`SyncManifest`] or
+/// [`ManifestEntry`]; bincode ignores `#[serde(default)]`.
+pub const SYNC_MANIFEST_BINARY_VERSION: u32 = 1;
+
 /// A single file entry in the manifest
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManifestEntry {
-    /// Relative path from root (UTF-8, may be lossy for non-UTF8 paths)
+    /// Relative path from root (escaped form, see [`escape_path`])
     pub path: String,
-    /// Raw path bytes for non-UTF8 paths (base64 encoded)
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Raw path bytes for non-UTF8 paths (hex encoded)
+    #[serde(default)]
     pub path_bytes: Option<String>,
     /// File size in bytes
     pub size: u64,
@@ -33,6 +44,15 @@ pub struct ManifestEntry {
     pub hash_algorithm: Option<HashAlgorithm>,
     /// File permissions (Unix mode)
     pub permissions: u32,
+    /// Special file type, for FIFOs, sockets and device nodes
+    #[serde(default)]
+    pub special: Option<SpecialKind>,
+    /// Device number for device nodes
+    #[serde(default)]
+    pub rdev: Option<u64>,
+    /// Source inode at the last sync, for rename detection
+    #[serde(default)]
+    pub inode: Option<u64>,
 }
 
 impl ManifestEntry {
@@ -44,51 +64,34 @@ impl ManifestEntry {
             .map(|d| d.as_secs())
             .unwrap_or(0);
 
-        let path_str = entry.relative_path.to_string_lossy().to_string();
-
         // Store raw bytes if path contains non-UTF8 characters
-        #[cfg(unix)]
-        let path_bytes = {
-            use std::os::unix::ffi::OsStrExt;
-            let bytes = entry.relative_path.as_os_str().as_bytes();
-            // Check if lossy conversion lost data
-            if path_str.contains('\u{FFFD}') || path_str.as_bytes() != bytes {
-                Some(hex::encode(bytes))
-            } else {
-                None
-            }
+        let path_bytes = match entry.relative_path.to_str() {
+            Some(_) => None,
+            None => Some(hex::encode(path_to_bytes(&entry.relative_path))),
         };
 
-        #[cfg(not(unix))]
-        let path_bytes = None;
-
         Self {
-            path: path_str,
+            path: escape_path(&entry.relative_path),
             path_bytes,
             size: entry.size,
             mtime,
             hash: None,
             hash_algorithm: None,
             permissions: entry.permissions,
+            special: entry.special,
+            rdev: entry.special.filter(|k| k.is_device()).map(|_| entry.rdev),
+            inode: (entry.inode != 0).then_some(entry.inode),
         }
     }
 
     /// Get the path as PathBuf, preferring raw bytes if available
-    #[cfg(unix)]
     pub fn to_path(&self) -> PathBuf {
         if let Some(ref hex_bytes) = self.path_bytes {
             if let Ok(bytes) = hex::decode(hex_bytes) {
-                use std::os::unix::ffi::OsStrExt;
-                return PathBuf::from(std::ffi::OsStr::from_bytes(&bytes));
+                return path_from_bytes(&bytes);
             }
         }
-        PathBuf::from(&self.path)
-    }
-
-    /// Get the path as PathBuf
-    #[cfg(not(unix))]
-    pub fn to_path(&self) -> PathBuf {
-        PathBuf::from(&self.path)
+        unescape_path(&self.path)
     }
 
     /// Create with hash
@@ -99,6 +102,8 @@ impl ManifestEntry {
     }
 
     /// Check if file matches this entry (by metadata)
+    ///
+    /// Special files also match on type and device number.
     pub fn matches_metadata(&self, entry: &FileEntry) -> bool {
         let entry_mtime = entry
             .modified
@@ -106,10 +111,28 @@ impl ManifestEntry {
             .map(|d| d.as_secs())
             .unwrap_or(0);
 
-        self.size == entry.size && self.mtime == entry_mtime
+        self.size == entry.size
+            && self.mtime == entry_mtime
+            && self.special == entry.special
+            && self.rdev.unwrap_or(0) == entry.rdev
     }
 }
 
+/// Signed fields of a [`ManifestEntry`] (everything but `inode`, which
+/// only describes the source filesystem)
+#[derive(Serialize)]
+struct SignedManifestEntry<'a> {
+    path: &'a str,
+    path_bytes: &'a Option<String>,
+    size: u64,
+    mtime: u64,
+    hash: &'a Option<String>,
+    hash_algorithm: Option<HashAlgorithm>,
+    permissions: u32,
+    special: Option<SpecialKind>,
+    rdev: Option<u64>,
+}
+
 /// Sync manifest containing state of all synchronized files
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncManifest {
@@ -129,12 +152,18 @@ pub struct SyncManifest {
     pub total_size: u64,
     /// File entries
     pub entries: Vec<ManifestEntry>,
+    /// Ed25519 signature over roots and entries
+    #[serde(default)]
+    pub signature: Option<ManifestSignature>,
 }
 
 impl SyncManifest {
     /// Current manifest version
     pub const VERSION: u32 = 1;
 
+    /// Domain tag of sync manifest signatures
+    const SIGNATURE_DOMAIN: &'static str = "smartcopy-sync-manifest-v1";
+
     /// Create a new empty manifest
     pub fn new(source_root: &str, dest_root: &str) -> Self {
         let now = SystemTime::now()
@@ -151,6 +180,7 @@ impl SyncManifest {
             total_files: 0,
             total_size: 0,
             entries: Vec::new(),
+            signature: None,
         }
     }
 
@@ -158,7 +188,7 @@ impl SyncManifest {
     pub fn from_scan(scan: &ScanResult, dest_root: &str) -> Self {
         let mut manifest = Self::new(&scan.root.to_string_lossy(), dest_root);
 
-        for entry in &scan.files {
+        for entry in scan.files.iter().chain(&scan.specials) {
             manifest.add_entry(ManifestEntry::from_file_entry(entry));
         }
 
@@ -186,6 +216,11 @@ impl SyncManifest {
         self.entries.iter().find(|e| e.path == path)
     }
 
+    /// Find entry by filesystem path (exact, including non-UTF-8 names)
+    pub fn find_path(&self, path: &Path) -> Option<&ManifestEntry> {
+        self.find(&escape_path(path))
+    }
+
     /// Find entry by path (mutable)
     pub fn find_mut(&mut self, path: &str) -> Option<&mut ManifestEntry> {
         self.entries.iter_mut().find(|e| e.path == path)
@@ -234,8 +269,7 @@ impl SyncManifest {
 
     /// Save manifest to file (binary/bincode - more compact)
     pub fn save_binary(&self, path: &Path) -> Result<()> {
-        let data = bincode::serialize(self)
-            .map_err(|e| SmartCopyError::ManifestError(e.to_string()))?;
+        let data = encode_binary_manifest(SYNC_MANIFEST_MAGIC, SYNC_MANIFEST_BINARY_VERSION, self)?;
         std::fs::write(path, data).with_path(path)?;
         Ok(())
     }
@@ -250,8 +284,54 @@ impl SyncManifest {
     /// Load manifest from binary file
     pub fn load_binary(path: &Path) -> Result<Self> {
         let data = std::fs::read(path).with_path(path)?;
-        bincode::deserialize(&data)
-            .map_err(|e| SmartCopyError::ManifestError(e.to_string()))
+        match binary_manifest_payload(&data, SYNC_MANIFEST_MAGIC, SYNC_MANIFEST_BINARY_VERSION)? {
+            Some(payload) => bincode::deserialize(payload)
+                .map_err(|e| SmartCopyError::ManifestError(e.to_string())),
+            None => Err(SmartCopyError::ManifestError(format!(
+                "{}: not a binary sync manifest",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Load a JSON or binary manifest, detected from its contents
+    pub fn load_any(path: &Path) -> Result<Self> {
+        match crate::hash::HashManifestFormat::detect(path)? {
+            crate::hash::HashManifestFormat::Json => Self::load(path),
+            _ => Self::load_binary(path),
+        }
+    }
+
+    fn signed_payload(&self) -> Result<Vec<u8>> {
+        let entries: Vec<SignedManifestEntry> = self
+            .entries
+            .iter()
+            .map(|e| SignedManifestEntry {
+                path: &e.path,
+                path_bytes: &e.path_bytes,
+                size: e.size,
+                mtime: e.mtime,
+                hash: &e.hash,
+                hash_algorithm: e.hash_algorithm,
+                permissions: e.permissions,
+                special: e.special,
+                rdev: e.rdev,
+            })
+            .collect();
+        crate::hash::canonical_json(&(&self.source_root, &self.dest_root, entries))
+    }
+
+    /// Sign roots and entries, replacing any previous signature
+    pub fn sign(&mut self, key: &SigningKey) -> Result<()> {
+        let payload = self.signed_payload()?;
+        self.signature = Some(ManifestSignature::sign(key, Self::SIGNATURE_DOMAIN, &payload));
+        Ok(())
+    }
+
+    /// Check that the manifest is signed by `key` and unchanged since
+    pub fn verify_signature(&self, key: &VerifyingKey) -> Result<()> {
+        let signature = self.signature.as_ref().ok_or_else(crate::hash::unsigned_manifest)?;
+        signature.verify(key, Self::SIGNATURE_DOMAIN, &self.signed_payload()?)
     }
 
     /// Get paths of all entries
@@ -274,6 +354,9 @@ impl SyncManifest {
         println!("Size:    {}", humansize::format_size(self.total_size, humansize::BINARY));
         println!("Created: {}", self.format_timestamp(self.created));
         println!("Updated: {}", self.format_timestamp(self.updated));
+        if let Some(signature) = &self.signature {
+            println!("Signed:  {} key {}", signature.algorithm, signature.public_key);
+        }
     }
 
     fn format_timestamp(&self, ts: u64) -> String {
@@ -306,8 +389,8 @@ impl ManifestDiff {
         let manifest_map = manifest.as_map();
         let mut seen_paths = std::collections::HashSet::new();
 
-        for entry in &scan.files {
-            let path = entry.relative_path.to_string_lossy().to_string();
+        for entry in scan.files.iter().chain(&scan.specials) {
+            let path = escape_path(&entry.relative_path);
             seen_paths.insert(path.clone());
 
             if let Some(manifest_entry) = manifest_map.get(path.as_str()) {
@@ -382,6 +465,9 @@ mod tests {
             hash: None,
             hash_algorithm: None,
             permissions: 0o644,
+            special: None,
+            rdev: None,
+            inode: None,
         });
 
         assert_eq!(manifest.total_files, 1);
@@ -402,6 +488,9 @@ mod tests {
             hash: Some("abc123".to_string()),
             hash_algorithm: Some(HashAlgorithm::XXHash3),
             permissions: 0o644,
+            special: None,
+            rdev: None,
+            inode: None,
         });
 
         // Test JSON save/load
@@ -414,7 +503,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // Binary format has compatibility issues with clap derive macros on enums
     fn test_manifest_save_load_binary() {
         let dir = TempDir::new().unwrap();
         let mut manifest = SyncManifest::new("/source", "/dest");
@@ -428,6 +516,9 @@ mod tests {
             hash: Some("abc123".to_string()),
             hash_algorithm: None,
             permissions: 0o644,
+            special: None,
+            rdev: None,
+            inode: None,
         });
 
         // Test binary save/load
@@ -438,6 +529,95 @@ mod tests {
         assert_eq!(loaded.entries[0].path, "test.txt");
     }
 
+    #[test]
+    fn test_binary_manifest_header() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("manifest.bin");
+
+        let mut manifest = SyncManifest::new("/source", "/dest");
+        manifest.add_entry(ManifestEntry {
+            path: "dev".to_string(),
+            path_bytes: None,
+            size: 0,
+            mtime: 7,
+            hash: None,
+            hash_algorithm: None,
+            permissions: 0o644,
+            special: Some(SpecialKind::CharDevice),
+            rdev: Some(0x0103),
+            inode: Some(77),
+        });
+        manifest.save_binary(&path).unwrap();
+        assert!(std::fs::read(&path).unwrap().starts_with(SYNC_MANIFEST_MAGIC));
+        let loaded = SyncManifest::load_binary(&path).unwrap();
+        assert_eq!(loaded.entries[0].special, Some(SpecialKind::CharDevice));
+        assert_eq!(loaded.entries[0].rdev, Some(0x0103));
+        assert_eq!(loaded.entries[0].inode, Some(77));
+
+        // bincode without the header is refused rather than misread
+        std::fs::write(&path, bincode::serialize(&manifest).unwrap()).unwrap();
+        assert!(SyncManifest::load_binary(&path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_manifest_records_specials() {
+        use crate::fs::{ScanConfig, Scanner};
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new().unwrap();
+        File::create(dir.path().join("data.txt")).unwrap().write_all(b"data").unwrap();
+        let fifo = dir.path().join("pipe");
+        let c_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+
+        let config = ScanConfig {
+            include_specials: true,
+            ..Default::default()
+        };
+        let scan = Scanner::new(config).unwrap().scan(dir.path()).unwrap();
+        let manifest = SyncManifest::from_scan(&scan, "/dest");
+
+        let json_path = dir.path().join("manifest.json");
+        manifest.save(&json_path).unwrap();
+        let loaded = SyncManifest::load(&json_path).unwrap();
+
+        assert_eq!(loaded.total_files, 2);
+        assert_eq!(loaded.find("pipe").unwrap().special, Some(SpecialKind::Fifo));
+        assert_eq!(loaded.find("data.txt").unwrap().special, None);
+        assert!(!ManifestDiff::calculate(&scan, &loaded).has_changes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_manifest_non_utf8_paths() {
+        use crate::fs::{ScanConfig, Scanner};
+
+        let dir = TempDir::new().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir(&src).unwrap();
+        // Two Latin-1 names that collapse to the same lossy string
+        let names = [path_from_bytes(b"caf\xe9.dat"), path_from_bytes(b"caf\xe8.dat")];
+        for (i, name) in names.iter().enumerate() {
+            std::fs::write(src.join(name), vec![b'x'; i + 1]).unwrap();
+        }
+
+        let scan = Scanner::new(ScanConfig::default()).unwrap().scan(&src).unwrap();
+        let manifest = SyncManifest::from_scan(&scan, "/dest");
+        assert_ne!(manifest.entries[0].path, manifest.entries[1].path);
+
+        for path in [dir.path().join("m.json"), dir.path().join("m.bin")] {
+            manifest.save(&path).unwrap();
+            let loaded = SyncManifest::load(&path).unwrap();
+            for (i, name) in names.iter().enumerate() {
+                let entry = loaded.find_path(name).unwrap();
+                assert_eq!(&entry.to_path(), name);
+                assert_eq!(entry.size, i as u64 + 1);
+            }
+            assert!(!ManifestDiff::calculate(&scan, &loaded).has_changes());
+        }
+    }
+
     #[test]
     fn test_manifest_upsert() {
         let mut manifest = SyncManifest::new("/source", "/dest");
@@ -450,6 +630,9 @@ mod tests {
             hash: None,
             hash_algorithm: None,
             permissions: 0o644,
+            special: None,
+            rdev: None,
+            inode: None,
         });
 
         assert_eq!(manifest.total_size, 100);
@@ -463,6 +646,9 @@ mod tests {
             hash: None,
             hash_algorithm: None,
             permissions: 0o644,
+            special: None,
+            rdev: None,
+            inode: None,
         });
 
         assert_eq!(manifest.total_files, 1);
@@ -481,6 +667,9 @@ mod tests {
             hash: None,
             hash_algorithm: None,
             permissions: 0o644,
+            special: None,
+            rdev: None,
+            inode: None,
         });
 
         let removed = manifest.remove("file.txt");
@@ -488,4 +677,33 @@ mod tests {
         assert_eq!(manifest.total_files, 0);
         assert_eq!(manifest.total_size, 0);
     }
+
+    #[test]
+    fn test_signed_manifest() {
+        let mut manifest = SyncManifest::new("/source", "/dest");
+        manifest.add_entry(ManifestEntry {
+            path: "file.txt".to_string(),
+            path_bytes: None,
+            size: 100,
+            mtime: 0,
+            hash: Some("abc".to_string()),
+            hash_algorithm: Some(HashAlgorithm::Blake3),
+            permissions: 0o644,
+            special: None,
+            rdev: None,
+            inode: None,
+        });
+        let key = SigningKey::from_bytes(&[2u8; 32]);
+        manifest.sign(&key).unwrap();
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("manifest.bin");
+        manifest.save_binary(&path).unwrap();
+        let loaded = SyncManifest::load_binary(&path).unwrap();
+        loaded.verify_signature(&key.verifying_key()).unwrap();
+
+        let mut tampered = loaded;
+        tampered.entries[0].size = 101;
+        assert!(tampered.verify_signature(&key.verifying_key()).is_err());
+    }
 }
@@ -4,10 +4,16 @@
 //! - Metadata-based change detection
 //! - Manifest tracking for efficient re-sync
 //! - Delta/chunked transfer for large files
+//! - In-place delta application for huge files
+//! - Content-defined chunking for delta and deduplication
+//! - Offline signature, delta and patch files
 //! - Resume interrupted transfers
 
 mod incremental;
 mod delta;
+mod cdc;
+mod delta_file;
+mod inplace;
 mod manifest;
 mod resume;
 
@@ -16,5 +22,8 @@ pub mod parquet_manifest;
 
 pub use incremental::*;
 pub use delta::*;
+pub use cdc::*;
+pub use delta_file::*;
+pub use inplace::*;
 pub use manifest::*;
 pub use resume::{ResumeManager, TransferState, FileTransferState, TransferStatus, FileStatus, ResumeResult, ResumableWriter};
@@ -3,13 +3,19 @@
 //! Uses Apache Arrow columnar format with Parquet file storage for efficient
 //! manifest operations on large file sets (millions of files).
 //! Provides ZSTD compression for optimal storage density.
+//!
+//! Paths are stored in the escaped form from [`crate::fs::path_encoding`];
+//! names that are not valid UTF-8 also keep their raw bytes in the
+//! nullable `path_bytes` column.
 
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use arrow::array::{
-    Array, ArrayRef, BooleanBuilder, Int64Builder, StringBuilder, UInt32Builder, UInt64Builder,
+    Array, ArrayRef, BinaryBuilder, BooleanBuilder, Int64Builder, StringBuilder, UInt32Builder,
+    UInt64Builder,
 };
 use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
@@ -19,6 +25,10 @@ use parquet::basic::Compression;
 use parquet::file::properties::WriterProperties;
 use parquet::file::reader::FileReader;
 
+use crate::config::HashAlgorithm;
+use crate::fs::{escape_path, path_from_bytes, path_to_bytes, unescape_path};
+use crate::hash::{FileHashEntry, HashManifest};
+
 /// Maximum records per Arrow RecordBatch chunk.
 /// Prevents StringBuilder overflow on very long file paths.
 const CHUNK_SIZE: usize = 500_000;
@@ -40,6 +50,8 @@ fn manifest_schema() -> Arc<Schema> {
         Field::new("symlink_target", DataType::Utf8, true),
         Field::new("xxhash3", DataType::Utf8, true),
         Field::new("blake3", DataType::Utf8, true),
+        Field::new("path_bytes", DataType::Binary, true),
+        Field::new("last_verified", DataType::Int64, true),
     ]))
 }
 
@@ -60,6 +72,29 @@ pub struct ManifestEntry {
     pub symlink_target: Option<String>,
     pub xxhash3: Option<String>,
     pub blake3: Option<String>,
+    /// Raw path bytes, only for paths that are not valid UTF-8
+    pub path_bytes: Option<Vec<u8>>,
+    /// Unix time the contents last matched their hash on re-read (scrub)
+    pub last_verified: Option<i64>,
+}
+
+impl ManifestEntry {
+    /// Escaped path and raw bytes columns for a filesystem path
+    pub fn encode_path(path: &Path) -> (String, Option<Vec<u8>>) {
+        let bytes = match path.to_str() {
+            Some(_) => None,
+            None => Some(path_to_bytes(path).into_owned()),
+        };
+        (escape_path(path), bytes)
+    }
+
+    /// Get the path as PathBuf, preferring raw bytes if available
+    pub fn to_path(&self) -> PathBuf {
+        match self.path_bytes {
+            Some(ref bytes) => path_from_bytes(bytes),
+            None => unescape_path(&self.path),
+        }
+    }
 }
 
 /// Writes manifest entries to a Parquet file with ZSTD compression.
@@ -119,6 +154,8 @@ impl ParquetManifestWriter {
         let mut symlink_builder = StringBuilder::with_capacity(len, len * 32);
         let mut xxhash3_builder = StringBuilder::with_capacity(len, len * 32);
         let mut blake3_builder = StringBuilder::with_capacity(len, len * 64);
+        let mut path_bytes_builder = BinaryBuilder::new();
+        let mut last_verified_builder = Int64Builder::with_capacity(len);
 
         for entry in self.buffer.drain(..) {
             path_builder.append_value(&entry.path);
@@ -168,6 +205,14 @@ impl ParquetManifestWriter {
                 Some(ref v) => blake3_builder.append_value(v),
                 None => blake3_builder.append_null(),
             }
+            match entry.path_bytes {
+                Some(ref v) => path_bytes_builder.append_value(v),
+                None => path_bytes_builder.append_null(),
+            }
+            match entry.last_verified {
+                Some(v) => last_verified_builder.append_value(v),
+                None => last_verified_builder.append_null(),
+            }
         }
 
         let columns: Vec<ArrayRef> = vec![
@@ -185,6 +230,8 @@ impl ParquetManifestWriter {
             Arc::new(symlink_builder.finish()),
             Arc::new(xxhash3_builder.finish()),
             Arc::new(blake3_builder.finish()),
+            Arc::new(path_bytes_builder.finish()),
+            Arc::new(last_verified_builder.finish()),
         ];
 
         let batch = RecordBatch::try_new(manifest_schema(), columns)?;
@@ -289,6 +336,22 @@ impl ParquetManifestReader {
                 .as_any()
                 .downcast_ref::<arrow::array::StringArray>()
                 .expect("blake3 column");
+            // Absent in manifests written before path_bytes was added
+            let path_bytes_col = (batch.num_columns() > 14).then(|| {
+                batch
+                    .column(14)
+                    .as_any()
+                    .downcast_ref::<arrow::array::BinaryArray>()
+                    .expect("path_bytes column")
+            });
+            // Absent in manifests written before scrubbing was added
+            let last_verified_col = (batch.num_columns() > 15).then(|| {
+                batch
+                    .column(15)
+                    .as_any()
+                    .downcast_ref::<arrow::array::Int64Array>()
+                    .expect("last_verified column")
+            });
 
             for i in 0..num_rows {
                 entries.push(ManifestEntry {
@@ -350,6 +413,14 @@ impl ParquetManifestReader {
                     } else {
                         Some(blake3_col.value(i).to_string())
                     },
+                    path_bytes: match path_bytes_col {
+                        Some(col) if !col.is_null(i) => Some(col.value(i).to_vec()),
+                        _ => None,
+                    },
+                    last_verified: match last_verified_col {
+                        Some(col) if !col.is_null(i) => Some(col.value(i)),
+                        _ => None,
+                    },
                 });
             }
         }
@@ -357,6 +428,67 @@ impl ParquetManifestReader {
         Ok(entries)
     }
 
+    /// Read a Parquet manifest as a [`HashManifest`] for verification.
+    ///
+    /// Uses the BLAKE3 column when every entry has it, otherwise XXHash3.
+    pub fn read_hash_manifest(path: &Path) -> Result<HashManifest, Box<dyn std::error::Error>> {
+        let entries = Self::read_all(path)?;
+
+        let algorithm = if entries.iter().all(|e| e.blake3.is_some()) {
+            HashAlgorithm::Blake3
+        } else if entries.iter().all(|e| e.xxhash3.is_some()) {
+            HashAlgorithm::XXHash3
+        } else {
+            return Err("manifest has no hash column filled for every entry".into());
+        };
+
+        let mut manifest = HashManifest::new(algorithm, &path.display().to_string());
+        for entry in entries {
+            let hash = match algorithm {
+                HashAlgorithm::Blake3 => entry.blake3.clone(),
+                _ => entry.xxhash3.clone(),
+            };
+            manifest.add_entry(FileHashEntry {
+                path: escape_path(&entry.to_path()),
+                size: entry.size,
+                hash: hash.unwrap_or_default(),
+                algorithm,
+                mtime: entry.mtime_secs.max(0) as u64,
+                last_verified: entry.last_verified.map(|t| t.max(0) as u64),
+                chunks: None,
+            });
+        }
+
+        Ok(manifest)
+    }
+
+    /// Record scrub times, keyed by escaped path, and rewrite the file.
+    ///
+    /// Every other column is kept. The new file replaces the old one by
+    /// rename, so an interrupted run leaves the previous manifest intact.
+    pub fn update_last_verified(
+        path: &Path,
+        verified: &HashMap<String, u64>,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let entries = Self::read_all(path)?;
+
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let mut writer = ParquetManifestWriter::new(&tmp)?;
+        for mut entry in entries {
+            if let Some(&time) = verified.get(&escape_path(&entry.to_path())) {
+                entry.last_verified = Some(time as i64);
+            }
+            writer.add_entry(entry)?;
+        }
+        let written = writer.finish()?;
+        std::fs::rename(&tmp, path)?;
+
+        Ok(written)
+    }
+
     /// Get the number of entries without loading all data.
     pub fn count_entries(path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
         let file = File::open(path)?;
@@ -404,6 +536,8 @@ mod tests {
                     symlink_target: None,
                     xxhash3: Some(format!("{:032x}", i)),
                     blake3: None,
+                    path_bytes: None,
+                    last_verified: None,
                 })
                 .unwrap();
         }
@@ -421,4 +555,84 @@ mod tests {
         let count = ParquetManifestReader::count_entries(&manifest_path).unwrap();
         assert_eq!(count, 100);
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("test-manifest.parquet");
+        let names = [path_from_bytes(b"caf\xe9.dat"), path_from_bytes(b"caf\xe8.dat")];
+
+        let mut writer = ParquetManifestWriter::new(&manifest_path).unwrap();
+        for name in &names {
+            let (path, path_bytes) = ManifestEntry::encode_path(name);
+            writer
+                .add_entry(ManifestEntry {
+                    path,
+                    size: 1,
+                    mtime_secs: 1700000000,
+                    mtime_nsecs: None,
+                    permissions: None,
+                    uid: None,
+                    gid: None,
+                    setuid: None,
+                    setgid: None,
+                    sticky: None,
+                    file_type: Some("file".to_string()),
+                    symlink_target: None,
+                    xxhash3: None,
+                    blake3: None,
+                    path_bytes,
+                    last_verified: None,
+                })
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let entries = ParquetManifestReader::read_all(&manifest_path).unwrap();
+        assert_ne!(entries[0].path, entries[1].path);
+        assert_eq!(entries[0].to_path(), names[0]);
+        assert_eq!(entries[1].to_path(), names[1]);
+    }
+
+    #[test]
+    fn test_update_last_verified() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("test-manifest.parquet");
+
+        let mut writer = ParquetManifestWriter::new(&manifest_path).unwrap();
+        for name in ["a.dat", "b.dat"] {
+            writer
+                .add_entry(ManifestEntry {
+                    path: name.to_string(),
+                    size: 1,
+                    mtime_secs: 1700000000,
+                    mtime_nsecs: None,
+                    permissions: Some(0o644),
+                    uid: None,
+                    gid: None,
+                    setuid: None,
+                    setgid: None,
+                    sticky: None,
+                    file_type: Some("file".to_string()),
+                    symlink_target: None,
+                    xxhash3: None,
+                    blake3: Some("00".repeat(32)),
+                    path_bytes: None,
+                    last_verified: None,
+                })
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let verified = HashMap::from([("a.dat".to_string(), 1700000500)]);
+        ParquetManifestReader::update_last_verified(&manifest_path, &verified).unwrap();
+
+        let manifest = ParquetManifestReader::read_hash_manifest(&manifest_path).unwrap();
+        assert_eq!(manifest.find_entry("a.dat").unwrap().last_verified, Some(1700000500));
+        assert_eq!(manifest.find_entry("b.dat").unwrap().last_verified, None);
+        // Sync metadata survives the rewrite
+        let entries = ParquetManifestReader::read_all(&manifest_path).unwrap();
+        assert_eq!(entries[1].permissions, Some(0o644));
+    }
 }
@@ -3,7 +3,7 @@
 //! Analyzes system configuration and provides recommendations
 //! for optimal file copy performance in HPC environments.
 
-use super::SystemInfo;
+use super::{StorageType, SystemInfo};
 use crate::config::WorkloadType;
 use serde::{Deserialize, Serialize};
 
@@ -98,7 +98,7 @@ impl TuningAnalyzer {
 
     /// Network tuning recommendations
     fn analyze_network(&self) -> Vec<TuningRecommendation> {
-        let recs = Vec::new();
+        let mut recs = Vec::new();
 
         #[cfg(target_os = "linux")]
         {
@@ -301,7 +301,7 @@ impl TuningAnalyzer {
 
     /// Memory tuning recommendations
     fn analyze_memory(&self) -> Vec<TuningRecommendation> {
-        let recs = Vec::new();
+        let mut recs = Vec::new();
 
         #[cfg(target_os = "linux")]
         {